@@ -0,0 +1,76 @@
+//! Compares the ways of getting a dump into SQLite, on a synthetic fixture of a few thousand
+//! crates. Run with `cargo bench --features bench`.
+
+use std::path::Path;
+
+use cratesio_dbdump_csvtab::{
+    cached_path::Cache, rusqlite::Connection, CratesIODumpLoader, FixtureBuilder, MockDump,
+    PreloadEngine,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const CRATES: usize = 2_000;
+const VERSIONS_PER_CRATE: usize = 5;
+
+fn loader(
+    target: &Path,
+    preload: bool,
+    engine: PreloadEngine,
+    threads: usize,
+) -> CratesIODumpLoader {
+    let mut loader = CratesIODumpLoader::builder()
+        .resource(&target.join("fixture.tar.gz").to_string_lossy())
+        .target_path(&target.join("data"))
+        .preload_engine(engine)
+        .preload(preload)
+        .threads(threads)
+        .cache(Cache::builder().progress_bar(None))
+        .unwrap()
+        .build()
+        .unwrap();
+    loader.update().unwrap();
+    loader
+}
+
+fn load(c: &mut Criterion) {
+    let target = Path::new("target/bench-fixture");
+    std::fs::create_dir_all(target).unwrap();
+    let fixture = FixtureBuilder::synthetic(CRATES, VERSIONS_PER_CRATE);
+    fixture.write(&target.join("fixture.tar.gz")).unwrap();
+
+    let count_dependents = |db: &Connection| {
+        db.query_row(
+            "SELECT COUNT(DISTINCT version_id) FROM dependencies WHERE CAST(crate_id AS INTEGER) = 1",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+    };
+
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    for (name, preload, engine, threads) in [
+        ("virtual_tables", false, PreloadEngine::Csvtab, 1),
+        ("preload", true, PreloadEngine::Csvtab, 1),
+        ("preload_direct", true, PreloadEngine::Direct, 1),
+        ("preload_parallel", true, PreloadEngine::Csvtab, 4),
+    ] {
+        let mut loader = loader(target, preload, engine, threads);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let db = Connection::open_in_memory().unwrap();
+                cratesio_dbdump_csvtab::rusqlite::vtab::csvtab::load_module(&db).unwrap();
+                loader.load_dump_into(&db).unwrap();
+                count_dependents(&db)
+            })
+        });
+    }
+    let mock = MockDump::from(fixture);
+    group.bench_function("direct_insert", |b| {
+        b.iter(|| count_dependents(&mock.build().unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
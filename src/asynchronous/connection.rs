@@ -0,0 +1,90 @@
+use std::{
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use rusqlite::Connection;
+use tokio::sync::oneshot;
+
+use crate::{CratesIODumpLoader, Error};
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// A connection from [`CratesIODumpLoader::open_db_async`] for use from async code. It lives
+/// on a thread of its own that runs the closures passed to `call()` one at a time, so queries
+/// never block the runtime. Clones share the connection.
+#[derive(Clone, Debug)]
+pub struct AsyncConnection {
+    jobs: Sender<Job>,
+}
+
+impl AsyncConnection {
+    /// Runs `f` on the connection's thread and resolves to what it returns.
+    pub async fn call<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |db| {
+                let _ = sender.send(f(db));
+            }))
+            .map_err(|_| Error::ConnectionClosed)?;
+        receiver.await.map_err(|_| Error::ConnectionClosed)?
+    }
+}
+
+impl CratesIODumpLoader {
+    /// `open_db()` on a connection thread, for async code. Loading the dump when needed
+    /// happens on that thread too, from a clone of this loader, so its `update_report()` and
+    /// cache aren't updated.
+    pub async fn open_db_async(&self) -> Result<AsyncConnection, Error> {
+        let mut loader = self.clone();
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (opened, open_result) = oneshot::channel();
+        thread::spawn(move || {
+            let mut db = match loader.open_db() {
+                Ok(db) => db,
+                Err(e) => {
+                    let _ = opened.send(Err(e));
+                    return;
+                }
+            };
+            let _ = opened.send(Ok(()));
+            for job in queue {
+                job(&mut db);
+            }
+        });
+        open_result.await.map_err(|_| Error::ConnectionClosed)??;
+        Ok(AsyncConnection { jobs })
+    }
+}
+
+#[test]
+fn test_open_db_async() -> Result<(), Error> {
+    use std::path::Path;
+
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/async"))
+        .minimal()
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+    loader.update()?;
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        let db = loader.open_db_async().await?;
+        let crates: i64 = db
+            .call(|db| Ok(db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?))
+            .await?;
+        assert_eq!(6, crates);
+        let journal_mode: String = db
+            .clone()
+            .call(|db| Ok(db.query_row("PRAGMA journal_mode", [], |row| row.get(0))?))
+            .await?;
+        assert_eq!("wal", journal_mode);
+        Ok(())
+    })
+}
@@ -0,0 +1,8 @@
+//! The `async` feature: thin wrappers running the blocking loader and connections on threads
+//! of their own, so the SQL and schema handling stay shared with the sync API.
+
+mod connection;
+mod stream;
+
+pub use connection::AsyncConnection;
+pub use stream::RowStream;
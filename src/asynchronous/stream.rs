@@ -0,0 +1,103 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::{rows::DumpRow, CratesIODumpLoader, Error};
+
+/// Rows buffered ahead of the consumer before the reading thread waits for it.
+const BUFFER: usize = 256;
+
+/// The rows of a table, from [`CratesIODumpLoader::stream_table`]. Dropping the stream stops
+/// the query.
+#[derive(Debug)]
+pub struct RowStream<T> {
+    rows: mpsc::Receiver<Result<T, Error>>,
+}
+
+impl<T> RowStream<T> {
+    /// The next row, or `None` once the table has been read.
+    pub async fn next(&mut self) -> Option<Result<T, Error>> {
+        self.rows.recv().await
+    }
+}
+
+impl<T> Stream for RowStream<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rows.poll_recv(cx)
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Streams the rows of `T`'s table, read on a thread of their own from an `open_db()`
+    /// connection. The thread stays at most a few hundred rows ahead of the consumer, and
+    /// errors, including failing to open the database, end the stream.
+    pub fn stream_table<T: DumpRow>(&self) -> RowStream<T> {
+        let mut loader = self.clone();
+        let (sender, rows) = mpsc::channel(BUFFER);
+        thread::spawn(move || {
+            let mut read = || -> Result<(), Error> {
+                let db = loader.open_db()?;
+                let mut stmt = db.prepare(&T::query(&db)?)?;
+                let mut query = stmt.query([])?;
+                while let Some(row) = query.next()? {
+                    if sender.blocking_send(Ok(T::from_row(row)?)).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = read() {
+                let _ = sender.blocking_send(Err(e));
+            }
+        });
+        RowStream { rows }
+    }
+}
+
+#[test]
+fn test_stream_table() -> Result<(), Error> {
+    use crate::rows::{Crate, Version};
+    use std::path::Path;
+
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/row_stream"))
+        .minimal()
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+    loader.update()?;
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        let mut versions = loader.stream_table::<Version>();
+        let mut count = 0;
+        while let Some(version) =
+            std::future::poll_fn(|cx| Pin::new(&mut versions).poll_next(cx)).await
+        {
+            assert!(version?.crate_id > 0);
+            count += 1;
+        }
+        assert!(count > 0);
+
+        let mut crates = loader.stream_table::<Crate>();
+        let first = crates.next().await.transpose()?;
+        assert!(first.is_some());
+        // Dropping a stream part way is fine.
+        drop(crates);
+
+        let missing = CratesIODumpLoader::builder()
+            .target_path(Path::new("testdata/extracted/row_stream/missing"))
+            .tables(&["crates"])
+            .build()?;
+        let mut failed = missing.stream_table::<Crate>();
+        assert!(matches!(failed.next().await, Some(Err(_))));
+        assert!(failed.next().await.is_none());
+        Ok(())
+    })
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use clap::Subcommand;
+use cratesio_dbdump_csvtab::{CratesIODumpLoader, Error, METADATA_FILE};
+use flate2::read::GzDecoder;
+use serde_json::Value;
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// List downloaded archives, extractions and databases.
+    Ls,
+    /// Delete superseded archives and all but the newest snapshots.
+    Prune {
+        /// How many diff snapshots to keep.
+        #[arg(long, default_value_t = 1)]
+        keep: usize,
+
+        /// Only print what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete every archive, extraction and database.
+    Clear {
+        /// Only print what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Archive,
+    Extraction,
+    Database,
+}
+
+#[derive(Clone)]
+struct Entry {
+    kind: Kind,
+    path: PathBuf,
+    size: u64,
+    resource: Option<String>,
+    dump_date: Option<String>,
+    /// Seconds since the epoch the entry was created at, for ordering.
+    created: f64,
+}
+
+fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|entries| entries.flatten().map(|e| size_of(&e.path())).sum())
+            .unwrap_or_default(),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+fn created(path: &Path) -> f64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+// Dump archives keep everything under a `YYYY-MM-DD-HHMMSS` directory.
+fn archive_dump_date(path: &Path) -> Option<String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path).ok()?));
+    let entry = archive.entries().ok()?.next()?.ok()?;
+    let top = entry
+        .path()
+        .ok()?
+        .components()
+        .next()?
+        .as_os_str()
+        .to_str()?
+        .to_string();
+    let date = top.get(..10)?;
+    cratesio_dbdump_csvtab::time::parse_date(date).map(|_| date.to_string())
+}
+
+/// The archives cached-path downloaded for `resource` into the shared cache `dir`.
+fn archives(dir: &Path, resource: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let meta_path = file.path();
+        if meta_path.extension() != Some("meta".as_ref()) {
+            continue;
+        }
+        let meta: Value = match fs::read(&meta_path).map(|b| serde_json::from_slice(&b)) {
+            Ok(Ok(meta)) => meta,
+            _ => continue,
+        };
+        // Other tools and resources share the cache directory.
+        if meta["resource"].as_str() != Some(resource) {
+            continue;
+        }
+        let path = meta_path.with_extension("");
+        if !path.exists() {
+            continue;
+        }
+        entries.push(Entry {
+            kind: Kind::Archive,
+            size: size_of(&path),
+            resource: Some(resource.to_string()),
+            dump_date: archive_dump_date(&path),
+            created: meta["creation_time"]
+                .as_f64()
+                .unwrap_or_else(|| created(&path)),
+            path,
+        });
+    }
+    entries
+}
+
+fn extraction(dir: &Path) -> Vec<Entry> {
+    if !dir.is_dir() {
+        return Vec::new();
+    }
+    let loader = match CratesIODumpLoader::builder().target_path(dir).build() {
+        Ok(loader) => loader,
+        Err(_) => return Vec::new(),
+    };
+    let dump_date = loader
+        .metadata()
+        .ok()
+        .flatten()
+        .map(|m| m.date().to_string());
+
+    let db = loader.sqlite_path();
+    let snapshots = dir.join("snapshots");
+    let mut entries = vec![Entry {
+        kind: Kind::Extraction,
+        path: dir.to_path_buf(),
+        size: size_of(dir).saturating_sub(size_of(&db) + size_of(&snapshots)),
+        resource: None,
+        dump_date: dump_date.clone(),
+        created: created(dir),
+    }];
+    if db.exists() {
+        entries.push(Entry {
+            kind: Kind::Database,
+            size: size_of(&db),
+            resource: None,
+            dump_date,
+            created: created(&db),
+            path: db,
+        });
+    }
+    entries
+}
+
+fn snapshots(target: &Path) -> Vec<Vec<Entry>> {
+    let mut snapshots: Vec<Vec<Entry>> = fs::read_dir(target.join("snapshots"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| extraction(&e.path()))
+        .filter(|entries| !entries.is_empty())
+        .collect();
+    // Newest first, by dump date when known.
+    snapshots.sort_by(|a, b| {
+        (&b[0].dump_date, b[0].created)
+            .partial_cmp(&(&a[0].dump_date, a[0].created))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    snapshots
+}
+
+fn human(size: u64) -> String {
+    let mut size = size as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} TiB", size)
+}
+
+fn print(entries: &[Entry]) {
+    for entry in entries {
+        let kind = match entry.kind {
+            Kind::Archive => "archive",
+            Kind::Extraction => "extracted",
+            Kind::Database => "database",
+        };
+        println!(
+            "{:<10} {:>10}  {:<10}  {}{}",
+            kind,
+            human(entry.size),
+            entry.dump_date.as_deref().unwrap_or("-"),
+            entry.path.display(),
+            entry
+                .resource
+                .as_deref()
+                .map(|r| format!(" ({})", r))
+                .unwrap_or_default()
+        );
+    }
+    let total: u64 = entries.iter().map(|e| e.size).sum();
+    println!("{} in {} entries", human(total), entries.len());
+}
+
+/// Whether the loader writes files called like `path` into its target directory: the dump's
+/// tables and metadata, the database, its lock and the staging files next to them.
+fn written_by_loader(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    name.ends_with(".csv")
+        || name.ends_with(".csv.filtered")
+        || name.ends_with(".new")
+        || name == METADATA_FILE
+        || name == ".dbdump.lock"
+        || name.starts_with("db.sqlite")
+        || name.starts_with("db.duckdb")
+}
+
+/// The files the loader wrote into the extraction `dir` and its snapshots, leaving whatever
+/// else shares the directory alone.
+fn loader_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() && path.file_name() == Some("snapshots".as_ref()) => {
+                for snapshot in fs::read_dir(&path).into_iter().flatten().flatten() {
+                    files.extend(loader_files(&snapshot.path()));
+                }
+            }
+            Ok(kind) if kind.is_dir() => {}
+            Ok(_) if written_by_loader(&path) => files.push(path),
+            _ => {}
+        }
+    }
+    files
+}
+
+/// Deletes the loader's files in `dir`, then the directories they leave empty.
+fn remove_extraction(dir: &Path) -> Result<(), Error> {
+    for file in loader_files(dir) {
+        fs::remove_file(&file)?;
+        // Stops at the first directory that still has something else in it.
+        for parent in file.ancestors().skip(1).take_while(|p| p.starts_with(dir)) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn remove(mut entries: Vec<Entry>, dry_run: bool) -> Result<(), Error> {
+    for entry in &mut entries {
+        // Extractions go with the loader's files inside them.
+        if entry.kind == Kind::Extraction {
+            entry.size = loader_files(&entry.path).iter().map(|f| size_of(f)).sum();
+        }
+    }
+    if !dry_run {
+        for entry in &entries {
+            match entry.kind {
+                Kind::Archive => {
+                    fs::remove_file(&entry.path)?;
+                    let mut meta = entry.path.clone().into_os_string();
+                    meta.push(".meta");
+                    let _ = fs::remove_file(meta);
+                    let mut extracted = entry.path.clone().into_os_string();
+                    extracted.push("-extracted");
+                    let _ = fs::remove_dir_all(extracted);
+                }
+                Kind::Extraction => remove_extraction(&entry.path)?,
+                Kind::Database => fs::remove_file(&entry.path)?,
+            }
+        }
+    }
+    print(&entries);
+    Ok(())
+}
+
+pub fn run(loader: &CratesIODumpLoader, command: CacheCommand) -> Result<(), Error> {
+    let archives = archives(&loader.cache_handle()?.dir, &loader.effective_resource());
+    match command {
+        CacheCommand::Ls => {
+            let mut entries = archives;
+            entries.extend(extraction(&loader.target_path));
+            entries.extend(snapshots(&loader.target_path).into_iter().flatten());
+            print(&entries);
+        }
+        CacheCommand::Prune { keep, dry_run } => {
+            // cached-path keeps one file per ETag, so only the newest of each resource is live.
+            let mut newest: HashMap<Option<&str>, f64> = HashMap::new();
+            for archive in &archives {
+                let created = newest.entry(archive.resource.as_deref()).or_default();
+                *created = created.max(archive.created);
+            }
+            let mut stale: Vec<Entry> = archives
+                .iter()
+                .filter(|a| a.created < newest[&a.resource.as_deref()])
+                .cloned()
+                .collect();
+            for snapshot in snapshots(&loader.target_path).into_iter().skip(keep) {
+                stale.extend(snapshot.into_iter().take(1));
+            }
+            remove(stale, dry_run)?;
+        }
+        CacheCommand::Clear { dry_run } => {
+            let mut entries = archives;
+            entries.extend(extraction(&loader.target_path).into_iter().take(1));
+            remove(entries, dry_run)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_clear_keeps_foreign_files() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/cache-clear");
+    let _ = fs::remove_dir_all(target);
+    fs::create_dir_all(target.join("snapshots/2021-05-15"))?;
+    fs::create_dir_all(target.join("notes"))?;
+    fs::create_dir_all(target.join("cache"))?;
+    let ours = "https://static.crates.io/db-dump.tar.gz";
+    for (name, resource) in [
+        ("ours", ours),
+        ("theirs", "https://example.com/other.tar.gz"),
+    ] {
+        fs::write(target.join("cache").join(name), "x")?;
+        fs::write(
+            target.join("cache").join(format!("{}.meta", name)),
+            format!(r#"{{"resource":"{}","creation_time":0.0}}"#, resource),
+        )?;
+    }
+    for file in [
+        "crates.csv",
+        "metadata.json",
+        "db.sqlite",
+        "db.sqlite-wal",
+        "db.sqlite.new",
+        "snapshots/2021-05-15/crates.csv",
+        "snapshots/2021-05-15/db.sqlite",
+        "notes.txt",
+        "notes/crates.csv",
+    ] {
+        fs::write(target.join(file), "x")?;
+    }
+
+    let mut entries = archives(&target.join("cache"), ours);
+    entries.extend(extraction(target).into_iter().take(1));
+    remove(entries, false)?;
+    let mut left: Vec<PathBuf> = Vec::new();
+    let mut dirs = vec![target.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)?.flatten() {
+            match entry.path().is_dir() {
+                true => dirs.push(entry.path()),
+                false => left.push(entry.path()),
+            }
+        }
+    }
+    left.sort();
+    assert_eq!(
+        vec![
+            target.join("cache/theirs"),
+            target.join("cache/theirs.meta"),
+            target.join("notes/crates.csv"),
+            target.join("notes.txt"),
+        ],
+        left
+    );
+    assert!(!target.join("snapshots").exists());
+    Ok(())
+}
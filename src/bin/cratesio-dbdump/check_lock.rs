@@ -0,0 +1,114 @@
+use std::{fs, path::Path};
+
+use cratesio_dbdump_csvtab::{
+    lockfile::{parse_lockfile, LockedPackage},
+    rusqlite::Connection,
+    Error,
+};
+use semver::Version;
+
+enum Finding {
+    MissingCrate,
+    MissingVersion,
+    Yanked,
+    Update { latest: String, breaking: bool },
+}
+
+/// `Some(breaking)` when `latest` is newer than `pinned`, where `breaking` means cargo would
+/// not pick it up for a `^pinned` requirement.
+fn newer(pinned: &str, latest: &str) -> Option<bool> {
+    let pinned = Version::parse(pinned).ok()?;
+    let latest = Version::parse(latest).ok()?;
+    if latest <= pinned {
+        return None;
+    }
+    let compatible = match (pinned.major, pinned.minor) {
+        (0, 0) => latest.minor == 0 && latest.patch == pinned.patch,
+        (0, minor) => latest.major == 0 && latest.minor == minor,
+        (major, _) => latest.major == major,
+    };
+    Some(!compatible)
+}
+
+fn audit(db: &Connection, package: &LockedPackage) -> Result<Vec<Finding>, Error> {
+    let mut stmt = db.prepare_cached(
+        r#"
+            SELECT v.num, v.yanked FROM main.versions v
+            JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ?
+        "#,
+    )?;
+    let versions = stmt
+        .query_map([&package.name], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)? == "t"))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if versions.is_empty() {
+        return Ok(vec![Finding::MissingCrate]);
+    }
+
+    let mut findings = Vec::new();
+    match versions.iter().find(|(num, _)| *num == package.version) {
+        Some((_, true)) => findings.push(Finding::Yanked),
+        Some((_, false)) => {}
+        None => findings.push(Finding::MissingVersion),
+    }
+    let latest = versions
+        .iter()
+        .filter(|(_, yanked)| !yanked)
+        .filter_map(|(num, _)| Version::parse(num).ok())
+        .filter(|v| v.pre.is_empty())
+        .max();
+    if let Some(latest) = latest {
+        let latest = latest.to_string();
+        if let Some(breaking) = newer(&package.version, &latest) {
+            findings.push(Finding::Update { latest, breaking });
+        }
+    }
+    Ok(findings)
+}
+
+/// How many crates.io packages `run` checked, and how many of them are yanked or unknown to
+/// the dump.
+pub struct Summary {
+    pub checked: usize,
+    pub problems: usize,
+}
+
+/// Prints every finding for the crates.io packages in `lockfile`.
+pub fn run(db: &Connection, lockfile: &Path) -> Result<Summary, Error> {
+    let packages = parse_lockfile(&fs::read_to_string(lockfile)?)?;
+    let mut failed = 0;
+    let mut checked = 0;
+    for package in packages.iter().filter(|p| p.is_crates_io()) {
+        checked += 1;
+        for finding in audit(db, package)? {
+            let pin = format!("{} {}", package.name, package.version);
+            match finding {
+                Finding::MissingCrate => {
+                    failed += 1;
+                    println!("missing crate    {}", pin);
+                }
+                Finding::MissingVersion => {
+                    failed += 1;
+                    println!("missing version  {}", pin);
+                }
+                Finding::Yanked => {
+                    failed += 1;
+                    println!("yanked           {}", pin);
+                }
+                Finding::Update { latest, breaking } => {
+                    let note = match breaking {
+                        true => " (breaking)",
+                        false => "",
+                    };
+                    println!("update           {} -> {}{}", pin, latest, note);
+                }
+            }
+        }
+    }
+    Ok(Summary {
+        checked,
+        problems: failed,
+    })
+}
@@ -0,0 +1,133 @@
+use clap::ValueEnum;
+use cratesio_dbdump_csvtab::{
+    changelog::ChangelogBuilder,
+    diff::{self, CategoryRef, DumpDiff, OwnerKind, OwnerRef, VersionRef},
+    time, CratesIODumpLoader, Error,
+};
+
+use crate::LoaderArgs;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ChangelogFormat {
+    Markdown,
+    Json,
+}
+
+/// Resolves a snapshot argument, which is either a dump date or a resource, into a loader with
+/// its own extraction directory.
+fn snapshot_loader(
+    args: &LoaderArgs,
+    snapshot: &str,
+    snapshot_url: Option<&str>,
+) -> Result<CratesIODumpLoader, Error> {
+    let is_date = snapshot.len() == 10 && time::parse_date(snapshot).is_some();
+    let resource = match (is_date, snapshot_url) {
+        (true, Some(template)) => template.replace("{date}", snapshot),
+        (true, None) => {
+            return Err(Error::InvalidOption {
+                option: "snapshot-url",
+                reason: format!(
+                "dump dates like {} need one, e.g. https://mirror.example/{{date}}/db-dump.tar.gz",
+                snapshot
+            ),
+            })
+        }
+        (false, _) => snapshot.to_string(),
+    };
+    let key: String = snapshot
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+            true => c,
+            false => '_',
+        })
+        .collect();
+
+    let mut loader = args.loader()?;
+    let target = loader.target_path.join("snapshots").join(key);
+    loader.base_path = None;
+    loader
+        .into_builder()
+        .resource(&resource)
+        .target_path(&target)
+        .build()
+}
+
+fn print_versions(title: &str, versions: &[VersionRef]) {
+    println!("{} ({}):", title, versions.len());
+    for v in versions {
+        println!("  {} {}", v.crate_name, v.num);
+    }
+}
+
+fn print_owners(title: &str, owners: &[OwnerRef]) {
+    println!("{} ({}):", title, owners.len());
+    for o in owners {
+        let kind = match o.kind {
+            OwnerKind::User => "user",
+            OwnerKind::Team => "team",
+        };
+        println!("  {} {} {}", o.crate_name, kind, o.login);
+    }
+}
+
+fn print_categories(title: &str, categories: &[CategoryRef]) {
+    println!("{} ({}):", title, categories.len());
+    for c in categories {
+        println!("  {} {}", c.crate_name, c.slug);
+    }
+}
+
+fn print(diff: &DumpDiff) {
+    println!("new crates ({}):", diff.new_crates.len());
+    for name in &diff.new_crates {
+        println!("  {}", name);
+    }
+    println!("removed crates ({}):", diff.removed_crates.len());
+    for name in &diff.removed_crates {
+        println!("  {}", name);
+    }
+    print_versions("new versions", &diff.new_versions);
+    print_versions("yanked", &diff.yanked);
+    print_versions("unyanked", &diff.unyanked);
+    print_owners("owners added", &diff.owners_added);
+    print_owners("owners removed", &diff.owners_removed);
+    print_categories("categories added", &diff.categories_added);
+    print_categories("categories removed", &diff.categories_removed);
+}
+
+pub fn run(
+    args: &LoaderArgs,
+    older: &str,
+    newer: &str,
+    snapshot_url: Option<&str>,
+) -> Result<(), Error> {
+    let mut older = snapshot_loader(args, older, snapshot_url)?;
+    let mut newer = snapshot_loader(args, newer, snapshot_url)?;
+    print(&diff::diff(&mut older, &mut newer)?);
+    Ok(())
+}
+
+pub fn changelog(
+    args: &LoaderArgs,
+    older: &str,
+    newer: &str,
+    snapshot_url: Option<&str>,
+    format: ChangelogFormat,
+) -> Result<(), Error> {
+    let mut older = snapshot_loader(args, older, snapshot_url)?;
+    let mut newer = snapshot_loader(args, newer, snapshot_url)?;
+    let diff = diff::diff(&mut older, &mut newer)?;
+
+    let mut builder = ChangelogBuilder::default();
+    if let (Some(from), Some(to)) = (older.metadata()?, newer.metadata()?) {
+        builder.period(from.date(), to.date());
+    }
+    let changelog = builder.build(&diff, &newer.open_db()?)?;
+    match format {
+        ChangelogFormat::Markdown => print!("{}", changelog.to_markdown()),
+        ChangelogFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&changelog.to_json())?)
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use cratesio_dbdump_csvtab::{
+    export_csv, export_ndjson, export_ndjson_where, rusqlite::Connection, sql, Error,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+fn output(path: Option<&Path>) -> Result<Box<dyn Write>, Error> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+pub fn run(
+    db: &Connection,
+    table: &str,
+    format: ExportFormat,
+    condition: Option<&str>,
+    path: Option<&Path>,
+) -> Result<(), Error> {
+    let query = match condition {
+        Some(condition) => format!(
+            "SELECT * FROM {} WHERE {}",
+            sql::identifier(table),
+            condition
+        ),
+        None => format!("SELECT * FROM {}", sql::identifier(table)),
+    };
+
+    let rows = match format {
+        ExportFormat::Csv => export_csv(db, &query, output(path)?)?,
+        ExportFormat::Ndjson => match condition {
+            Some(condition) => export_ndjson_where(db, table, condition, output(path)?)?,
+            None => export_ndjson(db, table, output(path)?)?,
+        },
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => {
+            let path = path.map_or_else(
+                || std::path::PathBuf::from(format!("{}.parquet", table)),
+                Path::to_path_buf,
+            );
+            cratesio_dbdump_csvtab::ParquetExporter::default().export_query(db, &query, &path)?;
+            eprintln!("wrote {}", path.display());
+            return Ok(());
+        }
+    };
+    if let Some(path) = path {
+        eprintln!("wrote {} rows to {}", rows, path.display());
+    }
+    Ok(())
+}
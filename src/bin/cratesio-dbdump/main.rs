@@ -0,0 +1,306 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand};
+use cratesio_dbdump_csvtab::{
+    export_sample, stats::registry_stats, CrateSelection, CratesIODumpLoader, Error,
+};
+
+mod cache;
+mod check_lock;
+mod diff;
+mod export;
+mod output;
+mod rdeps;
+mod shell;
+
+use export::ExportFormat;
+use output::{Format, Rows};
+
+#[derive(Parser)]
+#[command(
+    name = "cratesio-dbdump",
+    version,
+    about = "Work with the crates.io database dump"
+)]
+struct Cli {
+    #[command(flatten)]
+    loader: LoaderArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct LoaderArgs {
+    /// TOML file to configure the loader from, see `CratesIODumpLoader::from_config`.
+    /// `CRATESIO_DBDUMP_*` environment variables override it, and the other options override
+    /// both.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Dump archive to use, either a URL or a local path.
+    #[arg(long, global = true)]
+    resource: Option<String>,
+
+    /// Directory the dump is extracted to and the database is built in.
+    #[arg(long, global = true)]
+    target_path: Option<PathBuf>,
+
+    /// Comma-separated list of tables to extract and load.
+    #[arg(long, global = true, value_delimiter = ',')]
+    tables: Option<Vec<String>>,
+
+    /// Only use the crates, dependencies and versions tables.
+    #[arg(long, global = true, conflicts_with = "tables")]
+    minimal: bool,
+
+    /// Like --minimal, preloading only the columns dependency analysis needs.
+    #[arg(long, global = true, conflicts_with_all = ["tables", "minimal"])]
+    lean: bool,
+}
+
+impl LoaderArgs {
+    fn loader(&self) -> Result<CratesIODumpLoader, Error> {
+        let mut loader = match &self.config {
+            Some(path) => CratesIODumpLoader::from_config(path)?,
+            None => CratesIODumpLoader::default(),
+        };
+        loader.apply_env()?;
+        if self.resource.is_some() {
+            // Relative to the working directory rather than the config file.
+            loader.base_path = None;
+        }
+        let mut builder = loader.into_builder();
+        if let Some(resource) = &self.resource {
+            builder = builder.resource(resource);
+        }
+        if let Some(path) = &self.target_path {
+            builder = builder.target_path(path);
+        }
+        if let Some(tables) = &self.tables {
+            builder = builder.tables(tables);
+        }
+        if self.minimal {
+            builder = builder.minimal();
+        }
+        if self.lean {
+            builder = builder.lean();
+        }
+        builder.build()
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the dump if it changed and extract the selected tables.
+    Update,
+    /// Rebuild the SQLite database from the extracted tables.
+    Load {
+        /// Copy the tables into the database instead of using csv virtual tables.
+        #[arg(long)]
+        preload: bool,
+    },
+    /// Inspect or reclaim the space used by archives and extractions.
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommand,
+    },
+    /// Summarize notable new crates, releases and yanks between two snapshots.
+    Changelog {
+        /// Older snapshot, as a dump date or a resource.
+        older: String,
+        /// Newer snapshot, as a dump date or a resource.
+        newer: String,
+
+        /// URL template for dated snapshots, with `{date}` replaced by the date.
+        #[arg(long)]
+        snapshot_url: Option<String>,
+
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: diff::ChangelogFormat,
+    },
+    /// Report yanked pins, unknown crates and available updates in a `Cargo.lock`.
+    CheckLock {
+        #[arg(default_value = "Cargo.lock")]
+        lockfile: PathBuf,
+    },
+    /// Print the path of the SQLite database.
+    Path,
+    /// Run a SQL query against the database, building it first if needed.
+    Query {
+        sql: String,
+
+        #[arg(long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Report what changed between two snapshots of the dump.
+    Diff {
+        /// Older snapshot, as a dump date or a resource.
+        older: String,
+        /// Newer snapshot, as a dump date or a resource.
+        newer: String,
+
+        /// URL template for dated snapshots, with `{date}` replaced by the date.
+        #[arg(long)]
+        snapshot_url: Option<String>,
+    },
+    /// Export a table, optionally filtered, to a file or stdout.
+    Export {
+        #[arg(long)]
+        table: String,
+
+        /// Output format; parquet needs a build with the `cli-parquet` feature.
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
+        /// SQL condition rows must match, e.g. "yanked = 't'".
+        #[arg(long = "where")]
+        condition: Option<String>,
+
+        /// File to write to; defaults to stdout, or `<table>.parquet` for parquet.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List the crates depending on a crate, most downloaded first.
+    Rdeps {
+        name: String,
+
+        /// Only count dependencies of this kind.
+        #[arg(long, value_enum)]
+        kind: Option<rdeps::Kind>,
+
+        /// Only look at the newest non-yanked version of each dependent.
+        #[arg(long)]
+        latest_only: bool,
+    },
+    /// Write a small dump archive holding only some crates, for use as a test fixture.
+    Sample {
+        /// Crates to keep.
+        #[arg(long, value_delimiter = ',', required_unless_present = "top")]
+        crates: Vec<String>,
+
+        /// Keep the most downloaded crates instead.
+        #[arg(long, conflicts_with = "crates")]
+        top: Option<usize>,
+
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Open an interactive SQL prompt, with `semver_matches` and `normalize_name` available.
+    Shell,
+    /// Print headline statistics about the registry.
+    Stats {
+        /// How many of the largest categories to list.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            // Like clap, 2 for bad arguments.
+            match e {
+                Error::InvalidOption { .. } => ExitCode::from(2),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, Error> {
+    let mut loader = cli.loader.loader()?;
+
+    match cli.command {
+        Command::Update => {
+            loader.update()?;
+        }
+        Command::Load { preload } => {
+            if preload {
+                loader = loader.into_builder().preload(true).build()?;
+            }
+            loader.update()?.reload()?;
+            println!("{}", loader.sqlite_path().display());
+        }
+        Command::Cache { command } => cache::run(&loader, command)?,
+        Command::Changelog {
+            older,
+            newer,
+            snapshot_url,
+            format,
+        } => diff::changelog(&cli.loader, &older, &newer, snapshot_url.as_deref(), format)?,
+        Command::CheckLock { lockfile } => {
+            let db = loader.update()?.open_db()?;
+            let summary = check_lock::run(&db, &lockfile)?;
+            eprintln!(
+                "checked {} crates.io packages, {} problems",
+                summary.checked, summary.problems
+            );
+            // Yanked or unknown pins fail the check.
+            if summary.problems > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Path => println!("{}", loader.sqlite_path().display()),
+        Command::Query { sql, format } => {
+            let db = loader.update()?.open_db()?;
+            Rows::query(&db, &sql, &[])?.print(format)?;
+        }
+        Command::Diff {
+            older,
+            newer,
+            snapshot_url,
+        } => diff::run(&cli.loader, &older, &newer, snapshot_url.as_deref())?,
+        Command::Export {
+            table,
+            format,
+            condition,
+            output,
+        } => {
+            let db = loader.update()?.open_db()?;
+            export::run(&db, &table, format, condition.as_deref(), output.as_deref())?;
+        }
+        Command::Rdeps {
+            name,
+            kind,
+            latest_only,
+        } => {
+            let db = loader.update()?.open_db()?;
+            rdeps::run(&db, &name, kind, latest_only)?;
+        }
+        Command::Sample { crates, top, out } => {
+            let selection = match top {
+                Some(n) => CrateSelection::TopByDownloads(n),
+                None => CrateSelection::Names(crates),
+            };
+            let db = loader.update()?.open_db()?;
+            export_sample(&db, &selection, loader.metadata()?.as_ref(), &out)?;
+            println!("{}", out.display());
+        }
+        Command::Shell => {
+            let db = loader.update()?.open_db()?;
+            shell::run(&db, &loader.target_path.join(".shell_history"))?;
+        }
+        Command::Stats { top } => {
+            let db = loader.update()?.open_db()?;
+            let stats = registry_stats(&db, top)?;
+            if let Some(metadata) = loader.metadata()? {
+                println!("dump date:  {}", metadata.date());
+            }
+            println!("crates:     {}", stats.crates);
+            if let Some(versions) = stats.versions {
+                println!("versions:   {}", versions);
+            }
+            println!("downloads:  {}", stats.total_downloads);
+            if !stats.top_categories.is_empty() {
+                println!("top categories:");
+                for (category, crates) in &stats.top_categories {
+                    println!("  {:<30} {}", category, crates);
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
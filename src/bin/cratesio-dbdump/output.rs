@@ -0,0 +1,135 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use cratesio_dbdump_csvtab::{
+    rusqlite::{types::Value, Connection, ToSql},
+    Error,
+};
+use serde_json::{Map, Value as Json};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+pub struct Rows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Rows {
+    pub fn query(db: &Connection, sql: &str, params: &[&dyn ToSql]) -> Result<Self, Error> {
+        let mut stmt = db.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = Vec::new();
+        let mut result = stmt.query(params)?;
+        while let Some(row) = result.next()? {
+            rows.push(
+                (0..columns.len())
+                    .map(|i| row.get(i))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(Self { columns, rows })
+    }
+
+    pub fn print(&self, format: Format) -> Result<(), Error> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        match format {
+            Format::Table => self.write_table(&mut out)?,
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(out);
+                writer.write_record(&self.columns)?;
+                for row in &self.rows {
+                    writer.write_record(row.iter().map(text))?;
+                }
+                writer.flush()?;
+            }
+            Format::Json => {
+                let rows: Vec<Json> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let object: Map<String, Json> = self
+                            .columns
+                            .iter()
+                            .cloned()
+                            .zip(row.iter().map(json))
+                            .collect();
+                        Json::Object(object)
+                    })
+                    .collect();
+                serde_json::to_writer_pretty(&mut out, &rows)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_table<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(text).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(c.chars().count()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let separator: String = widths
+            .iter()
+            .map(|w| format!("+{}", "-".repeat(w + 2)))
+            .collect::<String>()
+            + "+";
+        let line = |values: &[String]| -> String {
+            values
+                .iter()
+                .zip(&widths)
+                .map(|(v, w)| format!("| {:<width$} ", v, width = w))
+                .collect::<String>()
+                + "|"
+        };
+
+        writeln!(out, "{}", separator)?;
+        writeln!(out, "{}", line(&self.columns))?;
+        writeln!(out, "{}", separator)?;
+        for row in &cells {
+            writeln!(out, "{}", line(row))?;
+        }
+        writeln!(out, "{}", separator)?;
+        writeln!(out, "({} rows)", cells.len())
+    }
+}
+
+pub fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => f.to_string(),
+        Value::Text(t) => t.replace('\n', " "),
+        Value::Blob(b) => String::from_utf8_lossy(b).into_owned(),
+    }
+}
+
+fn json(value: &Value) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Integer(i) => Json::from(*i),
+        Value::Real(f) => Json::from(*f),
+        Value::Text(t) => Json::from(t.as_str()),
+        Value::Blob(b) => Json::from(String::from_utf8_lossy(b).into_owned()),
+    }
+}
@@ -0,0 +1,55 @@
+use clap::ValueEnum;
+use cratesio_dbdump_csvtab::{
+    rows::DependencyKind, rusqlite::Connection, Error, ReverseDependencies,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Kind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl From<Kind> for DependencyKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Normal => Self::Normal,
+            Kind::Build => Self::Build,
+            Kind::Dev => Self::Dev,
+        }
+    }
+}
+
+pub fn run(
+    db: &Connection,
+    name: &str,
+    kind: Option<Kind>,
+    latest_only: bool,
+) -> Result<(), Error> {
+    let rdeps = ReverseDependencies::default()
+        .kind(kind.map(DependencyKind::from))
+        .latest_only(latest_only)
+        .query(db, name)?;
+
+    let width = rdeps
+        .iter()
+        .map(|d| d.crate_name.len() + d.version.len() + 1)
+        .max();
+    for dep in &rdeps {
+        let optional = match dep.optional {
+            true => " optional",
+            false => "",
+        };
+        println!(
+            "{:<width$}  {:>12}  {} {}{}",
+            format!("{} {}", dep.crate_name, dep.version),
+            dep.downloads,
+            dep.kind,
+            dep.req,
+            optional,
+            width = width.unwrap_or_default()
+        );
+    }
+    eprintln!("{} reverse dependencies of {}", rdeps.len(), name);
+    Ok(())
+}
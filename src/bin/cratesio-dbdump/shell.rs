@@ -0,0 +1,109 @@
+use std::io;
+
+use clap::ValueEnum;
+use cratesio_dbdump_csvtab::{register_functions, rusqlite::Connection, Error};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::output::{Format, Rows};
+
+const HELP: &str = "\
+statements run once terminated with `;`
+.tables               list tables and views
+.format table|csv|json  change the output format
+.help                 show this message
+.quit                 exit (as does ctrl-d)";
+
+fn readline_error(err: ReadlineError) -> Error {
+    match err {
+        ReadlineError::Io(err) => err.into(),
+        err => io::Error::other(err).into(),
+    }
+}
+
+fn run_statement(db: &Connection, sql: &str, format: Format) -> Result<(), Error> {
+    let rows = Rows::query(db, sql, &[])?;
+    if !rows.columns.is_empty() {
+        rows.print(format)?;
+    }
+    Ok(())
+}
+
+/// Reads SQL from the terminal and runs it against `db` until EOF, keeping history in
+/// `history`. Errors from statements are printed rather than ending the session.
+pub fn run(db: &Connection, history: &std::path::Path) -> Result<(), Error> {
+    register_functions(db)?;
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    let _ = editor.load_history(history);
+
+    let mut format = Format::Table;
+    let mut buffer = String::new();
+    loop {
+        let prompt = match buffer.is_empty() {
+            true => "dbdump> ",
+            false => "   ...> ",
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(readline_error(err)),
+        };
+
+        let trimmed = line.trim();
+        if buffer.is_empty() && trimmed.starts_with('.') {
+            let _ = editor.add_history_entry(trimmed);
+            let mut words = trimmed.split_whitespace();
+            let result = match (words.next(), words.next()) {
+                (Some(".quit") | Some(".exit"), _) => break,
+                (Some(".help"), _) => {
+                    println!("{}", HELP);
+                    Ok(())
+                }
+                (Some(".tables"), _) => run_statement(
+                    db,
+                    "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') ORDER BY name",
+                    format,
+                ),
+                (Some(".format"), Some(name)) => {
+                    match Format::from_str(name, true) {
+                        Ok(f) => format = f,
+                        Err(err) => eprintln!("{}", err),
+                    }
+                    Ok(())
+                }
+                (Some(command), _) => {
+                    eprintln!("unknown command {}, see .help", command);
+                    Ok(())
+                }
+                (None, _) => Ok(()),
+            };
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+            }
+            continue;
+        }
+
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+        if !trimmed.ends_with(';') {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(buffer.as_str());
+        if let Err(err) = run_statement(db, buffer.trim_end().trim_end_matches(';'), format) {
+            match std::error::Error::source(&err) {
+                Some(cause) => eprintln!("error: {}", cause),
+                None => eprintln!("error: {}", err),
+            }
+        }
+        buffer.clear();
+    }
+
+    editor.save_history(history).map_err(readline_error)?;
+    Ok(())
+}
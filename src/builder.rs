@@ -0,0 +1,275 @@
+#![allow(deprecated)]
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use cached_path::CacheBuilder;
+
+use crate::{
+    CratesIODumpLoader, EpochTimestamps, Error, Phase, PreloadEngine, Preset, Profile, Table,
+    TableWriter,
+};
+
+/// Configures a [`CratesIODumpLoader`] in one expression, e.g.
+/// `CratesIODumpLoader::builder().minimal().preload(true).build()?`. Builders are cheap to
+/// clone, so one can be kept around as a template for several loaders.
+///
+/// The options are documented on the `CratesIODumpLoader` methods of the same name.
+#[derive(Clone, Debug, Default)]
+pub struct CratesIODumpLoaderBuilder {
+    loader: CratesIODumpLoader,
+}
+
+impl CratesIODumpLoader {
+    pub fn builder() -> CratesIODumpLoaderBuilder {
+        CratesIODumpLoaderBuilder::default()
+    }
+
+    /// A builder starting from this loader's configuration.
+    pub fn into_builder(self) -> CratesIODumpLoaderBuilder {
+        CratesIODumpLoaderBuilder { loader: self }
+    }
+}
+
+impl CratesIODumpLoaderBuilder {
+    /// The configured loader, checked like [`CratesIODumpLoader::validate`] does except for
+    /// `target_path`, which isn't touched until the loader is used.
+    pub fn build(self) -> Result<CratesIODumpLoader, Error> {
+        self.loader.check_options()?;
+        Ok(self.loader)
+    }
+
+    pub fn resource(mut self, path: &str) -> Self {
+        self.loader.resource(path);
+        self
+    }
+
+    pub fn files(mut self, files: Vec<PathBuf>) -> Self {
+        self.loader.files(files);
+        self
+    }
+
+    pub fn tables<T: Into<Table> + Clone>(mut self, tables: &[T]) -> Self {
+        self.loader.tables(tables);
+        self
+    }
+
+    pub fn table_schema(mut self, table: &str, schema: &str) -> Self {
+        self.loader.table_schema(table, schema);
+        self
+    }
+
+    pub fn columns(mut self, table: &str, columns: &[&str]) -> Self {
+        self.loader.columns(table, columns);
+        self
+    }
+
+    pub fn custom_table(mut self, table: TableWriter) -> Self {
+        self.loader.custom_table(table);
+        self
+    }
+
+    /// Take every table in the archive, including ones crates.io added after this release,
+    /// instead of the tables picked with `tables()`, `files()` or a profile. See
+    /// [`CratesIODumpLoader::tables_from_archive`].
+    pub fn all_tables(mut self) -> Self {
+        self.loader.discover_tables = true;
+        self
+    }
+
+    pub fn minimal(mut self) -> Self {
+        self.loader.minimal();
+        self
+    }
+
+    pub fn lean(mut self) -> Self {
+        self.loader.lean();
+        self
+    }
+
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.loader.profile(profile);
+        self
+    }
+
+    pub fn base_path(mut self, path: &Path) -> Self {
+        self.loader.base_path(path);
+        self
+    }
+
+    pub fn target_path(mut self, path: &Path) -> Self {
+        self.loader.target_path(path);
+        self
+    }
+
+    pub fn cache(mut self, builder: CacheBuilder) -> Result<Self, Error> {
+        self.loader.cache(builder)?;
+        Ok(self)
+    }
+
+    pub fn sha256(mut self, hash: Option<&str>) -> Self {
+        self.loader.sha256(hash);
+        self
+    }
+
+    pub fn downloads_since(mut self, date: &str) -> Result<Self, Error> {
+        self.loader.downloads_since(date)?;
+        Ok(self)
+    }
+
+    pub fn preload(mut self, should: bool) -> Self {
+        self.loader.preload(should);
+        self
+    }
+
+    pub fn preload_engine(mut self, engine: PreloadEngine) -> Self {
+        self.loader.preload_engine(engine);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.loader.threads(threads);
+        self
+    }
+
+    pub fn lazy(mut self, should: bool) -> Self {
+        self.loader.lazy(should);
+        self
+    }
+
+    pub fn continue_on_error(mut self, should: bool) -> Self {
+        self.loader.continue_on_error(should);
+        self
+    }
+
+    pub fn typed_schemas(mut self, should: bool) -> Self {
+        self.loader.typed_schemas(should);
+        self
+    }
+
+    pub fn index_preset(mut self, preset: Preset) -> Self {
+        self.loader.index_preset(preset);
+        self
+    }
+
+    pub fn cadence_views(mut self, should: bool) -> Self {
+        self.loader.cadence_views(should);
+        self
+    }
+
+    /// Store the timestamps of preloaded dump tables as seconds since the unix epoch, see
+    /// [`EpochTimestamps`].
+    pub fn epoch_timestamps(mut self, mode: EpochTimestamps) -> Self {
+        self.loader.epoch_timestamps = Some(mode);
+        self
+    }
+
+    /// Load with the tables and types of the [`crate::formats::DumpFormat`] called `name`
+    /// rather than the one of the dump's date, so later releases of this crate that know
+    /// newer formats load the same way.
+    pub fn schema_version(mut self, name: &str) -> Self {
+        self.loader.schema_version = Some(name.to_string());
+        self
+    }
+
+    /// Create the `keyword_crates` and `category_crates` views of [`crate::tags`] after
+    /// loading, when their tables are loaded.
+    pub fn tag_views(mut self, should: bool) -> Self {
+        self.loader.tag_views = should;
+        self
+    }
+
+    pub fn growth_stats(mut self, should: bool) -> Self {
+        self.loader.growth_stats(should);
+        self
+    }
+
+    /// Materialize the `category_stats` table of [`crate::stats::materialize_category_stats`]
+    /// when loading.
+    pub fn category_stats(mut self, should: bool) -> Self {
+        self.loader.category_stats = should;
+        self
+    }
+
+    pub fn sample_rows(mut self, rows: usize) -> Self {
+        self.loader.sample_rows(rows);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.loader.seed(seed);
+        self
+    }
+
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.loader.memory_limit(bytes);
+        self
+    }
+
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.loader.busy_timeout(timeout);
+        self
+    }
+
+    pub fn page_size(mut self, bytes: u32) -> Self {
+        self.loader.page_size(bytes);
+        self
+    }
+
+    pub fn mmap_size(mut self, bytes: u64) -> Self {
+        self.loader.mmap_size(bytes);
+        self
+    }
+
+    /// Fail with [`Error::Timeout`] when `phase` takes longer than `limit`.
+    pub fn phase_timeout(mut self, phase: Phase, limit: Duration) -> Self {
+        let timeout = match phase {
+            Phase::Download => &mut self.loader.download_timeout,
+            Phase::Extract => &mut self.loader.extract_timeout,
+            Phase::Load => &mut self.loader.load_timeout,
+        };
+        *timeout = Some(limit);
+        self
+    }
+
+    #[cfg(feature = "db-cache")]
+    pub fn db_cache(mut self, should: bool) -> Self {
+        self.loader.db_cache(should);
+        self
+    }
+}
+
+#[test]
+fn test_builder() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let template = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .minimal()
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?;
+    let mut loader = template
+        .clone()
+        .target_path(Path::new("testdata/extracted/builder"))
+        .build()?;
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.update()?.load_dump_into(&db)?;
+    let crates: i64 = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?;
+    assert_eq!(6, crates);
+
+    // Clones share the cache but not the configuration.
+    let copy = loader.clone().into_builder().preload(false).build()?;
+    assert!(loader.preload && !copy.preload);
+    assert_eq!(loader.cache_handle()?.dir, copy.cache_handle()?.dir);
+
+    assert!(matches!(
+        template
+            .table_schema("users", "CREATE TABLE x(id INT);")
+            .build(),
+        Err(Error::InvalidOption { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! How often crates release, for judging whether they are maintained.
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::Error;
+
+/// Every version of the crates matching `filter`, see [`create_cadence_views`].
+fn intervals(filter: &str) -> String {
+    format!(
+        r#"
+            SELECT c.name AS crate_name, v.num AS num, v.created_at AS created_at,
+                julianday(v.created_at) - julianday(LAG(v.created_at) OVER (
+                    PARTITION BY v.crate_id ORDER BY v.created_at
+                )) AS days_since_previous,
+                CAST(v.num AS INTEGER) AS major,
+                CAST(substr(v.num, instr(v.num, '.') + 1) AS INTEGER) AS minor,
+                instr(v.num, '-') = 0 AS stable
+            FROM versions v
+            JOIN crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE {}
+        "#,
+        filter
+    )
+}
+
+/// One row per crate of `release_intervals`, see [`create_cadence_views`].
+const CADENCE: &str = r#"
+    majors AS (
+        SELECT crate_name, MIN(julianday(created_at)) AS at FROM release_intervals
+        WHERE stable GROUP BY crate_name, major
+    ), minors AS (
+        SELECT crate_name, MIN(julianday(created_at)) AS at FROM release_intervals
+        WHERE stable GROUP BY crate_name, major, minor
+    ), spans AS (
+        SELECT crate_name, (MAX(at) - MIN(at)) / NULLIF(COUNT(*) - 1, 0) AS days
+        FROM majors GROUP BY crate_name
+    ), minor_spans AS (
+        SELECT crate_name, (MAX(at) - MIN(at)) / NULLIF(COUNT(*) - 1, 0) AS days
+        FROM minors GROUP BY crate_name
+    )
+    SELECT r.crate_name AS crate_name, COUNT(*) AS releases,
+        AVG(r.days_since_previous) AS avg_days_between_releases,
+        spans.days AS avg_days_between_majors,
+        minor_spans.days AS avg_days_between_minors,
+        (SELECT julianday(MAX(created_at)) FROM versions) - julianday(MAX(r.created_at))
+            AS last_release_age_days
+    FROM release_intervals r
+    LEFT JOIN spans ON spans.crate_name = r.crate_name
+    LEFT JOIN minor_spans ON minor_spans.crate_name = r.crate_name
+    GROUP BY r.crate_name
+"#;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReleaseInterval {
+    pub num: String,
+    pub created_at: String,
+    /// `None` for the first release.
+    pub days_since_previous: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReleaseCadence {
+    pub crate_name: String,
+    pub releases: i64,
+    pub avg_days_between_releases: Option<f64>,
+    /// Between the first releases of consecutive stable majors; `None` with a single major.
+    pub avg_days_between_majors: Option<f64>,
+    pub avg_days_between_minors: Option<f64>,
+    /// Days between the crate's newest release and the newest release in the dump.
+    pub last_release_age_days: f64,
+}
+
+/// (Re)creates the `release_intervals` and `release_cadence` views.
+///
+/// `release_intervals` has every version with the days since the crate's previous release, and
+/// its major and minor number. `release_cadence` has one row per crate, with the fields of
+/// [`ReleaseCadence`]. Ages are measured against the newest release in the dump rather than the
+/// current time, so they don't drift as the dump gets older.
+pub fn create_cadence_views(db: &Connection) -> Result<(), Error> {
+    db.execute_batch(&format!(
+        r#"
+            DROP VIEW IF EXISTS release_cadence;
+            DROP VIEW IF EXISTS release_intervals;
+            CREATE VIEW release_intervals AS {};
+            CREATE VIEW release_cadence AS WITH {};
+        "#,
+        intervals("1"),
+        CADENCE
+    ))?;
+    Ok(())
+}
+
+/// Every release of `name`, oldest first. Doesn't need the views.
+pub fn release_intervals(db: &Connection, name: &str) -> Result<Vec<ReleaseInterval>, Error> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT num, created_at, days_since_previous FROM ({}) ORDER BY created_at",
+        intervals("c.name = ?")
+    ))?;
+    let intervals = stmt.query_map([name], |row| {
+        Ok(ReleaseInterval {
+            num: row.get(0)?,
+            created_at: row.get(1)?,
+            days_since_previous: row.get(2)?,
+        })
+    })?;
+    Ok(intervals.collect::<Result<_, _>>()?)
+}
+
+/// Release cadence of `name`, or `None` if it has no versions. Doesn't need the views.
+pub fn release_cadence(db: &Connection, name: &str) -> Result<Option<ReleaseCadence>, Error> {
+    Ok(db
+        .query_row(
+            &format!(
+                r#"
+                    WITH release_intervals AS ({}), {}
+                "#,
+                intervals("c.name = ?"),
+                CADENCE
+            ),
+            [name],
+            |row| {
+                Ok(ReleaseCadence {
+                    crate_name: row.get(0)?,
+                    releases: row.get(1)?,
+                    avg_days_between_releases: row.get(2)?,
+                    avg_days_between_majors: row.get(3)?,
+                    avg_days_between_minors: row.get(4)?,
+                    last_release_age_days: row.get(5)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+#[test]
+fn test_release_cadence() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/cadence"))
+        .minimal()
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let intervals = release_intervals(&db, "tokio")?;
+    assert_eq!(2, intervals.len());
+    assert_eq!(None, intervals[0].days_since_previous);
+    let between = intervals[1].days_since_previous.unwrap();
+
+    let tokio = release_cadence(&db, "tokio")?.unwrap();
+    assert_eq!(2, tokio.releases);
+    assert_eq!(Some(between), tokio.avg_days_between_releases);
+    assert_eq!(None, tokio.avg_days_between_majors);
+    assert_eq!(Some(between), tokio.avg_days_between_minors);
+    assert!(tokio.last_release_age_days >= 0.0);
+    assert_eq!(None, release_cadence(&db, "nope")?);
+    assert!(!crate::table_exists(&db, "release_cadence")?);
+
+    create_cadence_views(&db)?;
+    let releases: i64 = db.query_row(
+        "SELECT releases FROM release_cadence WHERE crate_name = 'tokio'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(tokio.releases, releases);
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! "What happened in the registry" reports built from a [`DumpDiff`].
+
+use std::fmt::Write;
+
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use crate::{
+    diff::{DumpDiff, VersionRef},
+    stats::crate_downloads,
+    table_exists, Error,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewCrate {
+    pub name: String,
+    pub description: String,
+    pub downloads: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Release {
+    pub crate_name: String,
+    pub num: String,
+    /// Crates with a version depending on this one, in the newer snapshot.
+    pub dependents: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// Dates of the older and newer snapshot, when known.
+    pub period: Option<(String, String)>,
+    pub new_crates: Vec<NewCrate>,
+    pub releases: Vec<Release>,
+    pub yanked: Vec<VersionRef>,
+    pub unyanked: Vec<VersionRef>,
+}
+
+pub struct ChangelogBuilder {
+    pub top_new_crates: usize,
+    pub top_releases: usize,
+    pub min_dependents: i64,
+    pub period: Option<(String, String)>,
+}
+
+impl Default for ChangelogBuilder {
+    fn default() -> Self {
+        Self {
+            top_new_crates: 10,
+            top_releases: 10,
+            min_dependents: 1,
+            period: None,
+        }
+    }
+}
+
+impl ChangelogBuilder {
+    /// How many new crates to list, most downloaded first.
+    pub fn top_new_crates(&mut self, n: usize) -> &mut Self {
+        self.top_new_crates = n;
+        self
+    }
+
+    /// How many releases to list, most depended upon first.
+    pub fn top_releases(&mut self, n: usize) -> &mut Self {
+        self.top_releases = n;
+        self
+    }
+
+    /// Leave out releases of crates with fewer dependents than this.
+    pub fn min_dependents(&mut self, n: i64) -> &mut Self {
+        self.min_dependents = n;
+        self
+    }
+
+    pub fn period(&mut self, from: &str, to: &str) -> &mut Self {
+        self.period = Some((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Picks the notable entries of `diff`, looking up downloads and dependents in `newer`, the
+    /// database of the newer snapshot.
+    pub fn build(&self, diff: &DumpDiff, newer: &Connection) -> Result<Changelog, Error> {
+        let mut changelog = Changelog {
+            period: self.period.clone(),
+            yanked: diff.yanked.clone(),
+            unyanked: diff.unyanked.clone(),
+            ..Changelog::default()
+        };
+
+        let mut stmt = newer.prepare(&format!(
+            r#"
+                SELECT COALESCE(c.description, ''), COALESCE(d.downloads, 0) FROM crates c
+                LEFT JOIN {} d ON d.crate_id = CAST(c.id AS INTEGER)
+                WHERE c.name = ?
+            "#,
+            crate_downloads(newer, "main")?
+        ))?;
+        for name in &diff.new_crates {
+            let (description, downloads) =
+                stmt.query_row([name], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            changelog.new_crates.push(NewCrate {
+                name: name.clone(),
+                description,
+                downloads,
+            });
+        }
+        changelog
+            .new_crates
+            .sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.name.cmp(&b.name)));
+        changelog.new_crates.truncate(self.top_new_crates);
+
+        if table_exists(newer, "dependencies")? {
+            let mut stmt = newer.prepare(
+                r#"
+                    SELECT COUNT(DISTINCT CAST(v.crate_id AS INTEGER)) FROM crates c
+                    JOIN dependencies d ON CAST(d.crate_id AS INTEGER) = CAST(c.id AS INTEGER)
+                    JOIN versions v ON CAST(v.id AS INTEGER) = CAST(d.version_id AS INTEGER)
+                    WHERE c.name = ?
+                "#,
+            )?;
+            for version in &diff.new_versions {
+                let dependents = stmt.query_row([&version.crate_name], |row| row.get(0))?;
+                if dependents >= self.min_dependents {
+                    changelog.releases.push(Release {
+                        crate_name: version.crate_name.clone(),
+                        num: version.num.clone(),
+                        dependents,
+                    });
+                }
+            }
+        }
+        changelog.releases.sort_by(|a, b| {
+            b.dependents
+                .cmp(&a.dependents)
+                .then(a.crate_name.cmp(&b.crate_name))
+        });
+        changelog.releases.truncate(self.top_releases);
+        Ok(changelog)
+    }
+}
+
+impl Changelog {
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        match &self.period {
+            Some((from, to)) => writeln!(out, "# Registry changes {} to {}", from, to),
+            None => writeln!(out, "# Registry changes"),
+        }
+        .unwrap();
+
+        if !self.new_crates.is_empty() {
+            out.push_str("\n## New crates\n\n");
+            for c in &self.new_crates {
+                match c.description.is_empty() {
+                    true => writeln!(out, "- **{}** ({} downloads)", c.name, c.downloads),
+                    false => writeln!(
+                        out,
+                        "- **{}**: {} ({} downloads)",
+                        c.name,
+                        c.description.trim(),
+                        c.downloads
+                    ),
+                }
+                .unwrap();
+            }
+        }
+        if !self.releases.is_empty() {
+            out.push_str("\n## Releases\n\n");
+            for r in &self.releases {
+                writeln!(
+                    out,
+                    "- **{}** {} ({} dependents)",
+                    r.crate_name, r.num, r.dependents
+                )
+                .unwrap();
+            }
+        }
+        for (title, versions) in [("Yanked", &self.yanked), ("Unyanked", &self.unyanked)] {
+            if versions.is_empty() {
+                continue;
+            }
+            write!(out, "\n## {}\n\n", title).unwrap();
+            for v in versions {
+                writeln!(out, "- {} {}", v.crate_name, v.num).unwrap();
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let versions = |versions: &[VersionRef]| -> Vec<Value> {
+            versions
+                .iter()
+                .map(|v| json!({ "crate": v.crate_name, "version": v.num }))
+                .collect()
+        };
+        json!({
+            "from": self.period.as_ref().map(|p| &p.0),
+            "to": self.period.as_ref().map(|p| &p.1),
+            "new_crates": self.new_crates.iter().map(|c| json!({
+                "name": c.name,
+                "description": c.description,
+                "downloads": c.downloads,
+            })).collect::<Vec<_>>(),
+            "releases": self.releases.iter().map(|r| json!({
+                "crate": r.crate_name,
+                "version": r.num,
+                "dependents": r.dependents,
+            })).collect::<Vec<_>>(),
+            "yanked": versions(&self.yanked),
+            "unyanked": versions(&self.unyanked),
+        })
+    }
+}
+
+#[test]
+fn test_changelog() -> Result<(), Error> {
+    use crate::{diff::diff, mock::archive_loader};
+
+    let mut older = archive_loader(
+        "testdata/fixture.tar.gz",
+        "testdata/extracted/changelog-old",
+    )?;
+    let mut newer = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/changelog-new",
+    )?;
+    let diff = diff(&mut older, &mut newer)?;
+
+    let changelog = ChangelogBuilder::default()
+        .min_dependents(0)
+        .period("2021-05-15", "2021-05-22")
+        .build(&diff, &newer.open_db()?)?;
+    assert_eq!("eyre", changelog.new_crates[0].name);
+    assert_eq!(100, changelog.new_crates[0].downloads);
+    assert_eq!(2, changelog.releases.len());
+    assert_eq!(
+        "# Registry changes 2021-05-15 to 2021-05-22\n\n## New crates\n\n- **eyre**: Flexible error reports (100 downloads)\n\n## Releases\n\n- **eyre** 0.6.5 (0 dependents)\n- **tokio** 1.6.0 (0 dependents)\n\n## Yanked\n\n- mio 0.7.11\n",
+        changelog.to_markdown()
+    );
+    assert_eq!("mio", changelog.to_json()["yanked"][0]["crate"]);
+
+    let default = ChangelogBuilder::default().build(&diff, &newer.open_db()?)?;
+    assert!(default.releases.is_empty());
+    Ok(())
+}
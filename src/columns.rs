@@ -0,0 +1,154 @@
+//! Names of the dump's tables and columns as constants, e.g. `columns::versions::CRATE_ID`,
+//! so SQL strings and row accessors name them in one place that follows [`schema::TABLES`].
+//!
+//! [`schema::TABLES`]: crate::schema::TABLES
+
+macro_rules! tables {
+    ($($table:ident { $($column:ident = $name:literal,)* })*) => {
+        $(
+            #[doc = concat!("Columns of `", stringify!($table), "`.")]
+            pub mod $table {
+                /// Name of the table.
+                pub const TABLE: &str = stringify!($table);
+                $(
+                    #[doc = concat!("`", $name, "`")]
+                    pub const $column: &str = $name;
+                )*
+                /// Every column, in the order of [`crate::schema::TABLES`].
+                pub const ALL: &[&str] = &[$($column),*];
+            }
+        )*
+
+        /// Every table's [`TABLE`](crates::TABLE) and [`ALL`](crates::ALL) columns.
+        pub const TABLES: &[(&str, &[&str])] = &[$(($table::TABLE, $table::ALL)),*];
+    };
+}
+
+tables! {
+    badges {
+        ATTRIBUTES = "attributes",
+        BADGE_TYPE = "badge_type",
+        CRATE_ID = "crate_id",
+    }
+    categories {
+        CATEGORY = "category",
+        CRATES_CNT = "crates_cnt",
+        CREATED_AT = "created_at",
+        DESCRIPTION = "description",
+        ID = "id",
+        PATH = "path",
+        SLUG = "slug",
+    }
+    crate_downloads {
+        CRATE_ID = "crate_id",
+        DOWNLOADS = "downloads",
+    }
+    crate_owners {
+        CRATE_ID = "crate_id",
+        CREATED_AT = "created_at",
+        CREATED_BY = "created_by",
+        OWNER_ID = "owner_id",
+        OWNER_KIND = "owner_kind",
+    }
+    crates {
+        CREATED_AT = "created_at",
+        DESCRIPTION = "description",
+        DOCUMENTATION = "documentation",
+        DOWNLOADS = "downloads",
+        HOMEPAGE = "homepage",
+        ID = "id",
+        MAX_UPLOAD_SIZE = "max_upload_size",
+        NAME = "name",
+        README = "readme",
+        REPOSITORY = "repository",
+        UPDATED_AT = "updated_at",
+    }
+    crates_categories {
+        CATEGORY_ID = "category_id",
+        CRATE_ID = "crate_id",
+    }
+    crates_keywords {
+        CRATE_ID = "crate_id",
+        KEYWORD_ID = "keyword_id",
+    }
+    default_versions {
+        CRATE_ID = "crate_id",
+        NUM_VERSIONS = "num_versions",
+        VERSION_ID = "version_id",
+    }
+    dependencies {
+        CRATE_ID = "crate_id",
+        DEFAULT_FEATURES = "default_features",
+        EXPLICIT_NAME = "explicit_name",
+        FEATURES = "features",
+        ID = "id",
+        KIND = "kind",
+        OPTIONAL = "optional",
+        REQ = "req",
+        TARGET = "target",
+        VERSION_ID = "version_id",
+    }
+    keywords {
+        CRATES_CNT = "crates_cnt",
+        CREATED_AT = "created_at",
+        ID = "id",
+        KEYWORD = "keyword",
+    }
+    metadata {
+        TOTAL_DOWNLOADS = "total_downloads",
+    }
+    reserved_crate_names {
+        NAME = "name",
+    }
+    teams {
+        AVATAR = "avatar",
+        GITHUB_ID = "github_id",
+        ID = "id",
+        LOGIN = "login",
+        NAME = "name",
+    }
+    users {
+        GH_AVATAR = "gh_avatar",
+        GH_ID = "gh_id",
+        GH_LOGIN = "gh_login",
+        ID = "id",
+        NAME = "name",
+    }
+    version_authors {
+        NAME = "name",
+        VERSION_ID = "version_id",
+    }
+    version_downloads {
+        DATE = "date",
+        DOWNLOADS = "downloads",
+        VERSION_ID = "version_id",
+    }
+    versions {
+        CHECKSUM = "checksum",
+        CRATE_ID = "crate_id",
+        CRATE_SIZE = "crate_size",
+        CREATED_AT = "created_at",
+        DOWNLOADS = "downloads",
+        FEATURES = "features",
+        ID = "id",
+        LICENSE = "license",
+        LINKS = "links",
+        NUM = "num",
+        PUBLISHED_BY = "published_by",
+        UPDATED_AT = "updated_at",
+        YANKED = "yanked",
+    }
+}
+
+#[test]
+fn test_columns() {
+    use crate::schema;
+
+    assert_eq!("crate_id", versions::CRATE_ID);
+    assert_eq!(schema::TABLES.len(), TABLES.len());
+    for (table, (name, columns)) in schema::TABLES.iter().zip(TABLES) {
+        assert_eq!(table.name, *name);
+        let expected: Vec<&str> = table.columns.iter().map(|c| c.name).collect();
+        assert_eq!(expected, *columns);
+    }
+}
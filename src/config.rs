@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use cached_path::Cache;
+use serde::Deserialize;
+
+use crate::{file_error, CratesIODumpLoader, Error, PreloadEngine, Profile};
+
+/// A `from_config()` file. Every key is optional, leaving the default in place.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    resource: Option<String>,
+    target_path: Option<PathBuf>,
+    profile: Option<ProfileName>,
+    tables: Option<Vec<String>>,
+    #[serde(default)]
+    schemas: BTreeMap<String, String>,
+    preload: Option<bool>,
+    preload_engine: Option<Engine>,
+    threads: Option<usize>,
+    lazy: Option<bool>,
+    sha256: Option<String>,
+    cache: Option<CacheConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProfileName {
+    Minimal,
+    Lean,
+    Analytics,
+    Search,
+    Full,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Engine {
+    Csvtab,
+    Direct,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CacheConfig {
+    dir: Option<PathBuf>,
+    /// Seconds before a cached archive is checked for a newer one.
+    freshness_lifetime: Option<u64>,
+    offline: Option<bool>,
+    /// Seconds before a download is given up on.
+    timeout: Option<u64>,
+    max_retries: Option<u32>,
+    progress_bar: Option<bool>,
+}
+
+impl CratesIODumpLoader {
+    /// A loader configured by the TOML file at `path`, so deployments can change what is loaded
+    /// and where without recompiling. Relative paths in it are resolved against its directory.
+    ///
+    /// ```toml
+    /// resource = "https://static.crates.io/db-dump.tar.gz"
+    /// target_path = "data"
+    /// tables = ["crates", "versions"]
+    /// # or profile = "minimal", "analytics", "search", "full" or "lean"
+    /// preload = true
+    /// preload_engine = "direct"
+    ///
+    /// [schemas]
+    /// crates = "CREATE TABLE x(id INTEGER, name TEXT, ...)"
+    ///
+    /// [cache]
+    /// dir = "cache"
+    /// freshness_lifetime = 86400
+    /// ```
+    pub fn from_config(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(file_error(path))?;
+        let config: Config = toml::from_str(&text).map_err(|source| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut loader = Self::default();
+        loader.configure(config, path.parent().unwrap_or_else(|| Path::new("")))?;
+        Ok(loader)
+    }
+
+    #[allow(deprecated)]
+    fn configure(&mut self, config: Config, base: &Path) -> Result<(), Error> {
+        self.base_path(base);
+        if let Some(resource) = &config.resource {
+            self.resource(resource);
+        }
+        if let Some(path) = &config.target_path {
+            self.target_path(&base.join(path));
+        }
+        match config.profile {
+            Some(ProfileName::Minimal) => {
+                self.profile(Profile::Minimal);
+            }
+            Some(ProfileName::Lean) => {
+                self.lean();
+            }
+            Some(ProfileName::Analytics) => {
+                self.profile(Profile::Analytics);
+            }
+            Some(ProfileName::Search) => {
+                self.profile(Profile::Search);
+            }
+            Some(ProfileName::Full) => {
+                self.profile(Profile::Full);
+            }
+            None => {}
+        }
+        if let Some(tables) = &config.tables {
+            let tables: Vec<&str> = tables.iter().map(String::as_str).collect();
+            self.tables(&tables);
+        }
+        for (table, schema) in &config.schemas {
+            self.table_schema(table, schema);
+        }
+        if let Some(preload) = config.preload {
+            self.preload(preload);
+        }
+        match config.preload_engine {
+            Some(Engine::Csvtab) => {
+                self.preload_engine(PreloadEngine::Csvtab);
+            }
+            Some(Engine::Direct) => {
+                self.preload_engine(PreloadEngine::Direct);
+            }
+            None => {}
+        }
+        if let Some(threads) = config.threads {
+            self.threads(threads);
+        }
+        if let Some(lazy) = config.lazy {
+            self.lazy(lazy);
+        }
+        if config.sha256.is_some() {
+            self.sha256(config.sha256.as_deref());
+        }
+        if let Some(cache) = config.cache {
+            let mut builder = Cache::builder();
+            if let Some(dir) = cache.dir {
+                builder = builder.dir(base.join(dir));
+            }
+            if let Some(seconds) = cache.freshness_lifetime {
+                builder = builder.freshness_lifetime(seconds);
+            }
+            if let Some(offline) = cache.offline {
+                builder = builder.offline(offline);
+            }
+            if let Some(seconds) = cache.timeout {
+                builder = builder.timeout(Duration::from_secs(seconds));
+            }
+            if let Some(retries) = cache.max_retries {
+                builder = builder.max_retries(retries);
+            }
+            if cache.progress_bar == Some(false) {
+                builder = builder.progress_bar(None);
+            }
+            self.cache(builder)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_from_config() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let dir = Path::new("testdata/extracted/config");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)?;
+    fs::copy("testdata/test.tar.gz", dir.join("test.tar.gz"))?;
+    let config = dir.join("loader.toml");
+    fs::write(
+        &config,
+        r#"
+resource = "test.tar.gz"
+target_path = "data"
+tables = ["test"]
+preload = true
+
+[schemas]
+test = "CREATE TABLE x(id INTEGER, name TEXT)"
+
+[cache]
+dir = "cache"
+progress_bar = false
+"#,
+    )?;
+
+    let mut loader = CratesIODumpLoader::from_config(&config)?;
+    assert_eq!(dir.join("data"), loader.target_path);
+    assert_eq!(dir.join("cache"), loader.cache_handle()?.dir);
+    assert!(loader.preload);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.update()?.load_dump_into(&db)?;
+    let id: String = db.query_row("SELECT typeof(id) FROM test LIMIT 1", [], |row| row.get(0))?;
+    assert_eq!("integer", id);
+
+    fs::write(&config, "tables = [\"crates\"]\nprelod = true\n")?;
+    assert!(matches!(
+        CratesIODumpLoader::from_config(&config),
+        Err(Error::InvalidConfig { .. })
+    ));
+    Ok(())
+}
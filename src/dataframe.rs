@@ -0,0 +1,149 @@
+use polars::prelude::{Column, DataFrame, DataType, IntoColumn, NamedFrom, Series, TimeUnit};
+use rusqlite::{types::Value, Connection};
+
+use crate::{
+    schema::{self, ColumnType},
+    sql, time, Error,
+};
+
+/// Loads a whole dump table, typing its columns from the canonical schema.
+pub fn to_dataframe(db: &Connection, table: &str) -> Result<DataFrame, Error> {
+    let canonical = schema::table(table);
+    collect(
+        db,
+        &format!("SELECT * FROM {}", sql::identifier(table)),
+        |name| canonical.and_then(|t| t.column(name)).map(|c| c.ty),
+    )
+}
+
+/// Runs `sql` and loads the result, inferring column types from the returned values.
+pub fn query_to_dataframe(db: &Connection, sql: &str) -> Result<DataFrame, Error> {
+    collect(db, sql, |_| None)
+}
+
+fn collect(
+    db: &Connection,
+    sql: &str,
+    column_type: impl Fn(&str) -> Option<ColumnType>,
+) -> Result<DataFrame, Error> {
+    let mut stmt = db.prepare(sql)?;
+    let names: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+
+    let mut values: Vec<Vec<Value>> = vec![Vec::new(); names.len()];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        for (i, column) in values.iter_mut().enumerate() {
+            column.push(row.get(i)?);
+        }
+    }
+
+    let height = values.first().map_or(0, Vec::len);
+    let columns = names
+        .iter()
+        .zip(values)
+        .map(|(name, values)| to_column(name, &values, column_type(name)))
+        .collect::<Result<Vec<Column>, Error>>()?;
+    Ok(DataFrame::new(height, columns)?)
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Real(f) => Some(f.to_string()),
+        Value::Text(t) => Some(t.clone()),
+        Value::Blob(b) => Some(String::from_utf8_lossy(b).into_owned()),
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(i) => Some(*i),
+        Value::Real(f) => Some(*f as i64),
+        Value::Text(t) => t.parse().ok(),
+        _ => None,
+    }
+}
+
+fn real(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Real(f) => Some(*f),
+        Value::Text(t) => t.parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_column(name: &str, values: &[Value], ty: Option<ColumnType>) -> Result<Column, Error> {
+    let name = name.into();
+    let series = match ty {
+        Some(ColumnType::Integer) | Some(ColumnType::BigInt) => {
+            Series::new(name, values.iter().map(integer).collect::<Vec<_>>())
+        }
+        Some(ColumnType::Boolean) => Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| match text(v).as_deref() {
+                    Some("t") | Some("true") => Some(true),
+                    Some("f") | Some("false") => Some(false),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Some(ColumnType::Timestamp) => Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| text(v).and_then(|t| time::parse_timestamp_micros(&t)))
+                .collect::<Vec<_>>(),
+        )
+        .cast(&DataType::Datetime(TimeUnit::Microseconds, None))?,
+        Some(ColumnType::Date) => Series::new(
+            name,
+            values
+                .iter()
+                .map(|v| text(v).and_then(|t| time::parse_date(&t)))
+                .collect::<Vec<_>>(),
+        )
+        .cast(&DataType::Date)?,
+        Some(_) => Series::new(name, values.iter().map(text).collect::<Vec<_>>()),
+        None => {
+            let mut non_null = values.iter().filter(|v| **v != Value::Null);
+            if non_null.clone().all(|v| matches!(v, Value::Integer(_))) {
+                Series::new(name, values.iter().map(integer).collect::<Vec<_>>())
+            } else if non_null.all(|v| matches!(v, Value::Integer(_) | Value::Real(_))) {
+                Series::new(name, values.iter().map(real).collect::<Vec<_>>())
+            } else {
+                Series::new(name, values.iter().map(text).collect::<Vec<_>>())
+            }
+        }
+    };
+    Ok(series.into_column())
+}
+
+#[test]
+fn test_to_dataframe() -> Result<(), Error> {
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(
+        r#"
+            CREATE TABLE versions(id TEXT, num TEXT, yanked TEXT, created_at TEXT);
+            INSERT INTO versions VALUES ('1', '1.0.0', 'f', '2021-05-15 12:00:00.5');
+            INSERT INTO versions VALUES ('2', '1.0.1', 't', '2021-05-16 12:00:00');
+        "#,
+    )?;
+
+    let df = to_dataframe(&db, "versions")?;
+    assert_eq!(2, df.height());
+    assert_eq!(&DataType::Int64, df.column("id")?.dtype());
+    assert_eq!(&DataType::Boolean, df.column("yanked")?.dtype());
+    assert_eq!(
+        &DataType::Datetime(TimeUnit::Microseconds, None),
+        df.column("created_at")?.dtype()
+    );
+
+    let df = query_to_dataframe(&db, "SELECT COUNT(*) AS n, 0.5 AS half FROM versions")?;
+    assert_eq!(&DataType::Int64, df.column("n")?.dtype());
+    assert_eq!(&DataType::Float64, df.column("half")?.dtype());
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+use datafusion::{
+    arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    datasource::{
+        file_format::csv::CsvFormat,
+        listing::{ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl},
+        TableProvider,
+    },
+    prelude::SessionContext,
+};
+
+use crate::{
+    schema::{self, ColumnType},
+    CratesIODumpLoader, Error,
+};
+
+fn canonical_type(ty: ColumnType) -> DataType {
+    match ty {
+        ColumnType::Integer | ColumnType::BigInt => DataType::Int64,
+        ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+        ColumnType::Date => DataType::Date32,
+        // Booleans are `t`/`f`, which the arrow csv reader doesn't understand.
+        _ => DataType::Utf8,
+    }
+}
+
+fn override_type(ty: &str) -> DataType {
+    let ty = ty.to_ascii_uppercase();
+    if ty.contains("INT") {
+        DataType::Int64
+    } else if ty.contains("REAL") || ty.contains("FLOA") || ty.contains("DOUB") {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    }
+}
+
+impl CratesIODumpLoader {
+    fn datafusion_schema(&self, table: &str, file: &Path) -> Result<SchemaRef, Error> {
+        if let Some(schema) = self.table_schema.get(table) {
+            let fields: Vec<Field> = schema::parse_columns(schema)
+                .iter()
+                .map(|(name, ty)| Field::new(name, override_type(ty), true))
+                .collect();
+            return Ok(Arc::new(Schema::new(fields)));
+        }
+
+        let mut header = String::new();
+        BufReader::new(File::open(file)?).read_line(&mut header)?;
+        let canonical = schema::table(table);
+        let fields: Vec<Field> = schema::parse_header(&header)?
+            .iter()
+            .map(|name| {
+                let ty = canonical
+                    .and_then(|t| t.column(name))
+                    .map_or(DataType::Utf8, |c| canonical_type(c.ty));
+                Field::new(name, ty, true)
+            })
+            .collect();
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    /// A DataFusion table over the extracted CSV of `table`, typed from the canonical schema.
+    pub fn table_provider(&self, table: &str) -> Result<Arc<dyn TableProvider>, Error> {
+        let file = self.target_path.join(format!("{}.csv", table));
+        let schema = self.datafusion_schema(table, &file)?;
+
+        let url = ListingTableUrl::parse(file.to_string_lossy())?;
+        let options = ListingOptions::new(Arc::new(CsvFormat::default().with_has_header(true)))
+            .with_file_extension(".csv");
+        let config = ListingTableConfig::new(url)
+            .with_listing_options(options)
+            .with_schema(schema);
+        Ok(Arc::new(ListingTable::try_new(config)?))
+    }
+
+    /// Registers every extracted table in `ctx` under its dump name.
+    pub fn register_datafusion(&self, ctx: &SessionContext) -> Result<(), Error> {
+        for path in &self.files {
+            let table = path.file_stem().unwrap_or_default().to_string_lossy();
+            ctx.register_table(table.as_ref(), self.table_provider(&table)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_register_datafusion() -> Result<(), Error> {
+    use datafusion::arrow::array::{Array, Int64Array};
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/datafusion"))
+        .minimal()
+        .cache(cache)?
+        .update()?;
+
+    let ctx = SessionContext::new();
+    loader.register_datafusion(&ctx)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let batches = runtime.block_on(async {
+        ctx.sql("SELECT SUM(downloads) FROM versions WHERE crate_id = 3")
+            .await?
+            .collect()
+            .await
+    })?;
+    let sum = batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(12_000_000, sum.value(0));
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! zstd-compressed copies of preloaded databases, so later runs can skip the csv load.
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{file_error, pin, CratesIODumpLoader, Error};
+
+const LEVEL: i32 = 3;
+
+impl CratesIODumpLoader {
+    /// Keep a zstd-compressed copy of preloaded `open_db()` databases in the cache, keyed by
+    /// the archive's SHA-256 and the loading options, and restore it instead of loading the
+    /// csvs again when both match.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn db_cache(&mut self, should: bool) -> &mut Self {
+        self.db_cache = should;
+        self
+    }
+
+    /// Where the compressed database for the current archive and options goes, if cached.
+    pub(crate) fn cached_db(&self) -> Result<Option<PathBuf>, Error> {
+        if !self.db_cache || !self.preload || self.lazy {
+            return Ok(None);
+        }
+        let mut table_schema: Vec<_> = self.table_schema.iter().collect();
+        table_schema.sort();
+        let mut table_columns: Vec<_> = self.table_columns.iter().collect();
+        table_columns.sort();
+        let options = format!(
+            "{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}\n{}\n{:?}\n{}\n{}\n{}\n{:?}\n{:?}\n{}",
+            env!("CARGO_PKG_VERSION"),
+            pin::sha256_file(&self.cached_resource()?)?,
+            self.files,
+            table_schema,
+            self.custom_tables,
+            self.sample_rows,
+            self.seed,
+            self.growth_stats,
+            self.downloads_since,
+            self.index_presets,
+            self.page_size,
+            table_columns,
+            self.typed_schemas,
+            self.cadence_views,
+            self.tag_views,
+            self.epoch_timestamps,
+            self.schema_version,
+            self.category_stats,
+        );
+        let key: String = Sha256::digest(options.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(Some(
+            self.cache_handle()?
+                .dir
+                .join("db")
+                .join(format!("{}.sqlite.zst", key)),
+        ))
+    }
+}
+
+/// Decompresses `cached` into `db`, returning whether there was anything to restore.
+pub(crate) fn restore(cached: Option<&Path>, db: &Path) -> Result<bool, Error> {
+    let cached = match cached {
+        Some(cached) if cached.exists() => cached,
+        _ => return Ok(false),
+    };
+    let input = File::open(cached).map_err(file_error(cached))?;
+    let output = File::create(db).map_err(file_error(db))?;
+    zstd::stream::copy_decode(input, output).map_err(file_error(cached))?;
+    Ok(true)
+}
+
+/// Compresses `db` into `cached`, through a temporary file so readers never see half of it.
+pub(crate) fn store(db: &Path, cached: &Path) -> Result<(), Error> {
+    if let Some(dir) = cached.parent() {
+        fs::create_dir_all(dir).map_err(file_error(dir))?;
+    }
+    let partial = cached.with_extension("zst.partial");
+    let input = File::open(db).map_err(file_error(db))?;
+    let output = File::create(&partial).map_err(file_error(&partial))?;
+    zstd::stream::copy_encode(input, output, LEVEL).map_err(file_error(&partial))?;
+    fs::rename(&partial, cached).map_err(file_error(cached))
+}
+
+#[test]
+fn test_db_cache() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/db-cache");
+    let _ = fs::remove_dir_all(target);
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(&target.join("data"))
+        .minimal()
+        .preload(true)
+        .db_cache(true)
+        .cache(
+            cached_path::Cache::builder()
+                .dir(target.join("cache"))
+                .progress_bar(None),
+        )?
+        .update()?;
+    let count = |loader: &mut CratesIODumpLoader| -> Result<i64, Error> {
+        let db = loader.open_db()?;
+        let count = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?;
+        Ok(count)
+    };
+    assert_eq!(6, count(&mut loader)?);
+    let cached = loader.cached_db()?.unwrap();
+    assert!(cached.exists());
+
+    // A restored database doesn't look at the csvs at all.
+    fs::remove_file(loader.sqlite_path())?;
+    fs::write(target.join("data/crates.csv"), "id,name\n")?;
+    assert_eq!(6, count(&mut loader)?);
+
+    loader.table_schema("crates", "CREATE TABLE x(id INT, name TEXT);");
+    assert_ne!(Some(cached), loader.cached_db()?);
+    Ok(())
+}
@@ -0,0 +1,580 @@
+//! Compact archives of the rows that changed between two snapshots.
+//!
+//! A delta is a `.tar.gz` holding `delta.json`, which lists the tables and the snapshots the
+//! delta goes between, followed by `<table>.removed.csv` and `<table>.added.csv` for every
+//! table. Tables whose columns changed are shipped whole and recreated when applied.
+//!
+//! The same changes can also be written as a plain SQL script, for databases this crate
+//! doesn't manage.
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use crate::{export_csv, sql, CratesIODumpLoader, Error};
+
+const MANIFEST: &str = "delta.json";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableDelta {
+    pub table: String,
+    pub removed: u64,
+    pub added: u64,
+    /// The table's columns changed, so all of its rows are in `added`.
+    pub replaced: bool,
+}
+
+fn columns(db: &Connection, schema: &str, table: &str) -> Result<Vec<String>, Error> {
+    let mut stmt = db.prepare(&format!(
+        "PRAGMA {}.table_info({})",
+        schema,
+        sql::identifier(table)
+    ))?;
+    let names = stmt.query_map([], |row| row.get(1))?;
+    Ok(names.collect::<Result<_, _>>()?)
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| sql::identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Updates both loaders and attaches their databases to a fresh connection as `old` and `new`.
+fn attach_snapshots(
+    older: &mut CratesIODumpLoader,
+    newer: &mut CratesIODumpLoader,
+) -> Result<Connection, Error> {
+    if older.target_path == newer.target_path {
+        return Err(Error::SharedTargetPath(older.target_path.clone()));
+    }
+    let older_db = older.update()?.sqlite_path();
+    drop(older.open_db()?);
+    let newer_db = newer.update()?.sqlite_path();
+    drop(newer.open_db()?);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    db.execute("ATTACH DATABASE ? AS old", [older_db.to_string_lossy()])?;
+    db.execute("ATTACH DATABASE ? AS new", [newer_db.to_string_lossy()])?;
+    Ok(db)
+}
+
+/// Updates both loaders and writes the rows that differ between their snapshots to `path`.
+///
+/// Only tables loaded by `newer` are included. Like [`crate::diff::diff`], the two loaders need
+/// their own target paths.
+pub fn write_delta(
+    older: &mut CratesIODumpLoader,
+    newer: &mut CratesIODumpLoader,
+    path: &Path,
+) -> Result<Vec<TableDelta>, Error> {
+    let db = attach_snapshots(older, newer)?;
+
+    let mut archive = tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::best()));
+    let mut append = |name: String, data: Vec<u8>| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, name, data.as_slice())
+    };
+
+    let mut deltas = Vec::new();
+    let mut csvs = Vec::new();
+    for file in &newer.files {
+        let table = file.file_stem().unwrap_or_default().to_string_lossy();
+        let new_columns = columns(&db, "new", &table)?;
+        let old_columns = columns(&db, "old", &table)?;
+        let replaced = new_columns != old_columns;
+        let quoted = sql::identifier(&table);
+
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        let (removed_rows, added_rows) = match replaced {
+            true => (
+                export_csv(
+                    &db,
+                    &format!("SELECT * FROM new.{} WHERE 0", quoted),
+                    &mut removed,
+                )?,
+                export_csv(&db, &format!("SELECT * FROM new.{}", quoted), &mut added)?,
+            ),
+            false => (
+                export_csv(
+                    &db,
+                    &format!("SELECT * FROM old.{0} EXCEPT SELECT * FROM new.{0}", quoted),
+                    &mut removed,
+                )?,
+                export_csv(
+                    &db,
+                    &format!("SELECT * FROM new.{0} EXCEPT SELECT * FROM old.{0}", quoted),
+                    &mut added,
+                )?,
+            ),
+        };
+        deltas.push(TableDelta {
+            table: table.to_string(),
+            removed: removed_rows,
+            added: added_rows,
+            replaced,
+        });
+        csvs.push((format!("{}.removed.csv", table), removed));
+        csvs.push((format!("{}.added.csv", table), added));
+    }
+
+    let timestamp = |loader: &CratesIODumpLoader| -> Result<Option<String>, Error> {
+        Ok(loader.metadata()?.map(|m| m.timestamp))
+    };
+    let manifest = json!({
+        "from": timestamp(older)?,
+        "to": timestamp(newer)?,
+        "tables": deltas.iter().map(|d| json!({
+            "table": d.table,
+            "replaced": d.replaced,
+        })).collect::<Vec<_>>(),
+    });
+    append(MANIFEST.to_string(), serde_json::to_vec_pretty(&manifest)?)?;
+    for (name, csv) in csvs {
+        append(name, csv)?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(deltas)
+}
+
+// Columns identifying a row of each table, so changed rows become UPDATEs. Tables not listed
+// here, or missing a key column, are keyed by all of their columns.
+const TABLE_KEYS: &[(&str, &[&str])] = &[
+    ("badges", &["crate_id", "badge_type"]),
+    ("categories", &["id"]),
+    ("crate_downloads", &["crate_id"]),
+    ("crate_owners", &["crate_id", "owner_id", "owner_kind"]),
+    ("crates", &["id"]),
+    ("crates_categories", &["crate_id", "category_id"]),
+    ("crates_keywords", &["crate_id", "keyword_id"]),
+    ("default_versions", &["crate_id"]),
+    ("dependencies", &["id"]),
+    ("keywords", &["id"]),
+    ("reserved_crate_names", &["name"]),
+    ("teams", &["id"]),
+    ("users", &["id"]),
+    ("version_authors", &["id"]),
+    ("version_downloads", &["version_id", "date"]),
+    ("versions", &["id"]),
+];
+
+/// Rows of `query` with every value rendered as an SQL literal.
+fn literal_rows(
+    db: &Connection,
+    columns: &[String],
+    query: &str,
+) -> Result<Vec<Vec<String>>, Error> {
+    let quoted = columns
+        .iter()
+        .map(|c| format!("quote({})", sql::identifier(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = db.prepare(&format!("SELECT {} FROM ({})", quoted, query))?;
+    let rows = stmt.query_map([], |row| (0..columns.len()).map(|i| row.get(i)).collect())?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn condition(columns: &[String], values: &[String]) -> String {
+    columns
+        .iter()
+        .zip(values)
+        .map(|(c, v)| format!("{} = {}", sql::identifier(c), v))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Updates both loaders and writes an SQL script to `out` that turns a database materialized
+/// from `older`, e.g. with `preload(true)`, into one of `newer`.
+///
+/// Every statement is guarded so that running the script twice is harmless: rows are deleted
+/// and updated by key, and only inserted when their key is absent. Tables whose columns
+/// changed are recreated. The script runs in one transaction.
+pub fn write_sql_script<W: Write>(
+    older: &mut CratesIODumpLoader,
+    newer: &mut CratesIODumpLoader,
+    mut out: W,
+) -> Result<Vec<TableDelta>, Error> {
+    let db = attach_snapshots(older, newer)?;
+    let mut deltas = Vec::new();
+    writeln!(out, "BEGIN;")?;
+    for file in &newer.files {
+        let table = file.file_stem().unwrap_or_default().to_string_lossy();
+        let quoted = sql::identifier(&table);
+        let new_columns = columns(&db, "new", &table)?;
+        let old_columns = columns(&db, "old", &table)?;
+        let list = column_list(&new_columns);
+
+        if new_columns != old_columns {
+            writeln!(
+                out,
+                "DROP TABLE IF EXISTS {0};\nCREATE TABLE {0}({1});",
+                quoted, list
+            )?;
+            let rows = literal_rows(&db, &new_columns, &format!("SELECT * FROM new.{}", quoted))?;
+            for row in &rows {
+                writeln!(
+                    out,
+                    "INSERT INTO {}({}) VALUES ({});",
+                    quoted,
+                    list,
+                    row.join(", ")
+                )?;
+            }
+            deltas.push(TableDelta {
+                table: table.to_string(),
+                removed: 0,
+                added: rows.len() as u64,
+                replaced: true,
+            });
+            continue;
+        }
+
+        let key: Vec<String> = TABLE_KEYS
+            .iter()
+            .find(|(t, _)| *t == table)
+            .map(|(_, key)| key.iter().map(|c| c.to_string()).collect())
+            .filter(|key: &Vec<String>| key.iter().all(|c| new_columns.contains(c)))
+            .unwrap_or_else(|| new_columns.clone());
+        let key_list = column_list(&key);
+        let key_index: Vec<usize> = key
+            .iter()
+            .map(|k| new_columns.iter().position(|c| c == k).unwrap())
+            .collect();
+
+        // Keys only in the older snapshot.
+        let removed = literal_rows(
+            &db,
+            &key,
+            &format!(
+                "SELECT {1} FROM old.{0} EXCEPT SELECT {1} FROM new.{0}",
+                quoted, key_list
+            ),
+        )?;
+        for values in &removed {
+            writeln!(
+                out,
+                "DELETE FROM {} WHERE {};",
+                quoted,
+                condition(&key, values)
+            )?;
+        }
+
+        // New and changed rows.
+        let added = literal_rows(
+            &db,
+            &new_columns,
+            &format!("SELECT * FROM new.{0} EXCEPT SELECT * FROM old.{0}", quoted),
+        )?;
+        for row in &added {
+            let key_values: Vec<String> = key_index.iter().map(|&i| row[i].clone()).collect();
+            let key_condition = condition(&key, &key_values);
+            if key.len() < new_columns.len() {
+                let assignments = new_columns
+                    .iter()
+                    .zip(row)
+                    .map(|(c, v)| format!("{} = {}", sql::identifier(c), v))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    out,
+                    "UPDATE {} SET {} WHERE {};",
+                    quoted, assignments, key_condition
+                )?;
+            }
+            writeln!(
+                out,
+                "INSERT INTO {0}({1}) SELECT {2} WHERE NOT EXISTS (SELECT 1 FROM {0} WHERE {3});",
+                quoted,
+                list,
+                row.join(", "),
+                key_condition
+            )?;
+        }
+        deltas.push(TableDelta {
+            table: table.to_string(),
+            removed: removed.len() as u64,
+            added: added.len() as u64,
+            replaced: false,
+        });
+    }
+    writeln!(out, "COMMIT;")?;
+    Ok(deltas)
+}
+
+/// Applies the delta at `path` to `db`, whose tables must be materialized, e.g. with
+/// `preload(true)`, and hold the snapshot the delta was written from.
+///
+/// The whole delta is applied in one transaction. Deltas with tables whose names the dump
+/// wouldn't use, or with rows of tables their manifest doesn't list, are rejected.
+pub fn apply_delta(db: &Connection, path: &Path) -> Result<Vec<TableDelta>, Error> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let tx = db.unchecked_transaction()?;
+    let mut deltas: Vec<TableDelta> = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = PathBuf::from(entry.path()?.file_name().unwrap_or_default());
+        let name = name.to_string_lossy();
+        if name == MANIFEST {
+            let mut manifest = String::new();
+            entry.read_to_string(&mut manifest)?;
+            let manifest: Value = serde_json::from_str(&manifest)?;
+            for table in manifest["tables"].as_array().into_iter().flatten() {
+                let name = table["table"].as_str().unwrap_or_default();
+                if !sql::valid_table_name(name) {
+                    return Err(Error::InvalidTableName(name.to_string()));
+                }
+                deltas.push(TableDelta {
+                    table: name.to_string(),
+                    replaced: table["replaced"].as_bool().unwrap_or_default(),
+                    ..TableDelta::default()
+                });
+            }
+            continue;
+        }
+
+        let (table, added) = match (
+            name.strip_suffix(".removed.csv"),
+            name.strip_suffix(".added.csv"),
+        ) {
+            (Some(table), _) => (table, false),
+            (_, Some(table)) => (table, true),
+            _ => continue,
+        };
+        let delta = match deltas.iter_mut().find(|d| d.table == table) {
+            Some(delta) => delta,
+            None => {
+                return Err(Error::InvalidDelta(format!(
+                    "{} has rows of {:?}, which its manifest doesn't list",
+                    path.display(),
+                    table
+                )))
+            }
+        };
+        // Replaced tables are recreated from their added rows, which may bring new columns or
+        // a table the database doesn't have yet.
+        if !added && delta.replaced {
+            continue;
+        }
+        let quoted = sql::identifier(table);
+        let mut reader = csv::Reader::from_reader(entry);
+        let header: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+        let columns = column_list(&header);
+        let placeholders = vec!["?"; header.len()].join(", ");
+
+        if added && delta.replaced {
+            tx.execute_batch(&format!(
+                "DROP TABLE IF EXISTS main.{0}; CREATE TABLE main.{0}({1});",
+                quoted, columns
+            ))?;
+        }
+        let target = match added {
+            true => format!("main.{}", quoted),
+            false => {
+                tx.execute_batch(&format!(
+                    "DROP TABLE IF EXISTS temp.delta_removed; CREATE TEMP TABLE delta_removed({});",
+                    columns
+                ))?;
+                "temp.delta_removed".to_string()
+            }
+        };
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {}({}) VALUES ({})",
+            target, columns, placeholders
+        ))?;
+        let mut count = 0;
+        for record in reader.records() {
+            insert.execute(rusqlite::params_from_iter(record?.iter()))?;
+            count += 1;
+        }
+        drop(insert);
+
+        match added {
+            true => delta.added = count,
+            false => {
+                delta.removed = count;
+                tx.execute_batch(&format!(
+                    "DELETE FROM main.{0} WHERE ({1}) IN (SELECT {1} FROM temp.delta_removed); DROP TABLE temp.delta_removed;",
+                    quoted, columns
+                ))?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(deltas)
+}
+
+/// Asserts that the tables of `db` have the same rows as `snapshot` preloaded.
+#[cfg(test)]
+fn assert_matches_snapshot(
+    db: &Connection,
+    snapshot: &mut CratesIODumpLoader,
+) -> Result<(), Error> {
+    let expected = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&expected)?;
+    snapshot.preload(true).load_dump_into(&expected)?;
+    for file in &snapshot.files {
+        let table = file.file_stem().unwrap_or_default().to_string_lossy();
+        let mut out = (Vec::new(), Vec::new());
+        let order = column_list(&columns(db, "main", &table)?);
+        let query = format!(
+            "SELECT * FROM {} ORDER BY {}",
+            sql::identifier(&table),
+            order
+        );
+        export_csv(db, &query, &mut out.0)?;
+        export_csv(&expected, &query, &mut out.1)?;
+        assert_eq!(out.1, out.0, "{}", table);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_delta() -> Result<(), Error> {
+    use crate::mock::archive_loader;
+
+    let mut older = archive_loader("testdata/fixture.tar.gz", "testdata/extracted/delta-old")?;
+    let mut newer = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/delta-new",
+    )?;
+
+    let path = Path::new("testdata/extracted/delta.tar.gz");
+    let written = write_delta(&mut older, &mut newer, path)?;
+    let crates = written.iter().find(|d| d.table == "crates").unwrap();
+    assert_eq!(
+        (0, 1, false),
+        (crates.removed, crates.added, crates.replaced)
+    );
+
+    // Apply onto a materialized copy of the older snapshot and compare with the newer one.
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    older.preload(true).load_dump_into(&db)?;
+    let applied = apply_delta(&db, path)?;
+    assert_eq!(written, applied);
+
+    assert_matches_snapshot(&db, &mut newer)
+}
+
+#[test]
+fn test_delta_new_table() -> Result<(), Error> {
+    use crate::mock::archive_loader;
+
+    let mut older = archive_loader("testdata/fixture.tar.gz", "testdata/extracted/grown-old")?;
+    older.tables(&["crates"]);
+    let mut newer = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/grown-new",
+    )?;
+    newer.tables(&["crates", "versions"]);
+
+    let path = Path::new("testdata/extracted/grown.tar.gz");
+    let written = write_delta(&mut older, &mut newer, path)?;
+    let versions = written.iter().find(|d| d.table == "versions").unwrap();
+    assert!(versions.replaced);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    older.preload(true).load_dump_into(&db)?;
+    apply_delta(&db, path)?;
+
+    assert_matches_snapshot(&db, &mut newer)
+}
+
+#[test]
+fn test_sql_script() -> Result<(), Error> {
+    use crate::mock::archive_loader;
+
+    let mut older = archive_loader("testdata/fixture.tar.gz", "testdata/extracted/script-old")?;
+    let mut newer = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/script-new",
+    )?;
+
+    let mut script = Vec::new();
+    let written = write_sql_script(&mut older, &mut newer, &mut script)?;
+    let versions = written.iter().find(|d| d.table == "versions").unwrap();
+    // mio 0.7.11 got yanked, and two versions were published.
+    assert_eq!((0, 3), (versions.removed, versions.added));
+    let script = String::from_utf8_lossy(&script);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    older.preload(true).load_dump_into(&db)?;
+    db.execute_batch(&script)?;
+    db.execute_batch(&script)?;
+
+    assert_matches_snapshot(&db, &mut newer)
+}
+
+#[test]
+fn test_apply_crafted_delta() -> Result<(), Error> {
+    let write = |path: &Path, manifest: Value, csvs: &[(&str, &str)]| -> Result<(), Error> {
+        let mut archive =
+            tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::fast()));
+        let mut append = |name: &str, data: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive.append_data(&mut header, name, data)
+        };
+        append(MANIFEST, &serde_json::to_vec(&manifest)?)?;
+        for (name, csv) in csvs {
+            append(name, csv.as_bytes())?;
+        }
+        archive.into_inner()?.finish()?;
+        Ok(())
+    };
+    let db = Connection::open_in_memory()?;
+    db.execute_batch("CREATE TABLE crates(id TEXT, name TEXT);")?;
+    std::fs::create_dir_all("testdata/extracted")?;
+    let path = Path::new("testdata/extracted/crafted-delta.tar.gz");
+
+    let table = "crates; DROP TABLE crates; --";
+    write(
+        path,
+        json!({ "tables": [{ "table": table, "replaced": true }] }),
+        &[(&format!("{}.added.csv", table), "id\n1\n")],
+    )?;
+    assert!(matches!(
+        apply_delta(&db, path),
+        Err(Error::InvalidTableName(_))
+    ));
+
+    write(
+        path,
+        json!({ "tables": [] }),
+        &[("crates.added.csv", "id,name\n1,serde\n")],
+    )?;
+    assert!(matches!(
+        apply_delta(&db, path),
+        Err(Error::InvalidDelta(_))
+    ));
+
+    // Column names are quoted, not spliced into the statements.
+    write(
+        path,
+        json!({ "tables": [{ "table": "crates", "replaced": true }] }),
+        &[(
+            "crates.added.csv",
+            "\"id\"\"); DROP TABLE crates; --\"\n1\n",
+        )],
+    )?;
+    apply_delta(&db, path)?;
+    let columns = columns(&db, "main", "crates")?;
+    assert_eq!(vec![r#"id"); DROP TABLE crates; --"#.to_string()], columns);
+    Ok(())
+}
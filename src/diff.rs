@@ -0,0 +1,275 @@
+//! Comparison of two loaded dump snapshots.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rusqlite::Connection;
+
+use crate::{table_exists, CratesIODumpLoader, Error};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionRef {
+    pub crate_name: String,
+    pub num: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OwnerKind {
+    User,
+    Team,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OwnerRef {
+    pub crate_name: String,
+    pub kind: OwnerKind,
+    pub login: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CategoryRef {
+    pub crate_name: String,
+    pub slug: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DumpDiff {
+    pub new_crates: Vec<String>,
+    pub removed_crates: Vec<String>,
+    pub new_versions: Vec<VersionRef>,
+    pub yanked: Vec<VersionRef>,
+    pub unyanked: Vec<VersionRef>,
+    pub owners_added: Vec<OwnerRef>,
+    pub owners_removed: Vec<OwnerRef>,
+    pub categories_added: Vec<CategoryRef>,
+    pub categories_removed: Vec<CategoryRef>,
+}
+
+impl DumpDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn crate_names(db: &Connection) -> Result<BTreeSet<String>, Error> {
+    let mut stmt = db.prepare("SELECT name FROM crates")?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+    Ok(names.collect::<Result<_, _>>()?)
+}
+
+fn versions(db: &Connection) -> Result<BTreeMap<VersionRef, bool>, Error> {
+    let mut stmt = db.prepare(
+        r#"
+            SELECT c.name, v.num, v.yanked FROM versions v
+            JOIN crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+        "#,
+    )?;
+    let versions = stmt.query_map([], |row| {
+        let version = VersionRef {
+            crate_name: row.get(0)?,
+            num: row.get(1)?,
+        };
+        Ok((version, row.get::<_, String>(2)? == "t"))
+    })?;
+    Ok(versions.collect::<Result<_, _>>()?)
+}
+
+fn owners(db: &Connection) -> Result<BTreeSet<OwnerRef>, Error> {
+    // Fall back to the raw owner ids when the user/team tables weren't loaded.
+    let user = match table_exists(db, "users")? {
+        true => {
+            "(SELECT gh_login FROM users WHERE CAST(id AS INTEGER) = CAST(o.owner_id AS INTEGER))"
+        }
+        false => "NULL",
+    };
+    let team = match table_exists(db, "teams")? {
+        true => "(SELECT login FROM teams WHERE CAST(id AS INTEGER) = CAST(o.owner_id AS INTEGER))",
+        false => "NULL",
+    };
+    let sql = format!(
+        r#"
+            SELECT c.name, CAST(o.owner_kind AS INTEGER),
+                COALESCE(CASE CAST(o.owner_kind AS INTEGER) WHEN 0 THEN {} ELSE {} END, o.owner_id)
+            FROM crate_owners o
+            JOIN crates c ON CAST(c.id AS INTEGER) = CAST(o.crate_id AS INTEGER)
+        "#,
+        user, team
+    );
+    let mut stmt = db.prepare(&sql)?;
+    let owners = stmt.query_map([], |row| {
+        Ok(OwnerRef {
+            crate_name: row.get(0)?,
+            kind: match row.get::<_, i64>(1)? {
+                1 => OwnerKind::Team,
+                _ => OwnerKind::User,
+            },
+            login: row.get::<_, rusqlite::types::Value>(2).map(|v| match v {
+                rusqlite::types::Value::Text(t) => t,
+                rusqlite::types::Value::Integer(i) => i.to_string(),
+                _ => String::new(),
+            })?,
+        })
+    })?;
+    Ok(owners.collect::<Result<_, _>>()?)
+}
+
+fn categories(db: &Connection) -> Result<BTreeSet<CategoryRef>, Error> {
+    let mut stmt = db.prepare(
+        r#"
+            SELECT c.name, cat.slug FROM crates_categories cc
+            JOIN crates c ON CAST(c.id AS INTEGER) = CAST(cc.crate_id AS INTEGER)
+            JOIN categories cat ON CAST(cat.id AS INTEGER) = CAST(cc.category_id AS INTEGER)
+        "#,
+    )?;
+    let categories = stmt.query_map([], |row| {
+        Ok(CategoryRef {
+            crate_name: row.get(0)?,
+            slug: row.get(1)?,
+        })
+    })?;
+    Ok(categories.collect::<Result<_, _>>()?)
+}
+
+/// Compares two databases built from different snapshots of the dump.
+///
+/// Ownership changes are only reported when both sides have `crate_owners` loaded, and category
+/// changes when both have `crates_categories` and `categories`.
+pub fn diff_databases(older: &Connection, newer: &Connection) -> Result<DumpDiff, Error> {
+    let mut diff = DumpDiff::default();
+
+    let (old_crates, new_crates) = (crate_names(older)?, crate_names(newer)?);
+    diff.new_crates = new_crates.difference(&old_crates).cloned().collect();
+    diff.removed_crates = old_crates.difference(&new_crates).cloned().collect();
+
+    if table_exists(older, "versions")? && table_exists(newer, "versions")? {
+        let old_versions = versions(older)?;
+        for (version, yanked) in versions(newer)? {
+            match old_versions.get(&version) {
+                None => diff.new_versions.push(version),
+                Some(false) if yanked => diff.yanked.push(version),
+                Some(true) if !yanked => diff.unyanked.push(version),
+                _ => {}
+            }
+        }
+    }
+
+    if table_exists(older, "crate_owners")? && table_exists(newer, "crate_owners")? {
+        let (old_owners, new_owners) = (owners(older)?, owners(newer)?);
+        diff.owners_added = new_owners.difference(&old_owners).cloned().collect();
+        diff.owners_removed = old_owners.difference(&new_owners).cloned().collect();
+    }
+
+    let mut have_categories = true;
+    for db in [older, newer] {
+        have_categories &=
+            table_exists(db, "crates_categories")? && table_exists(db, "categories")?;
+    }
+    if have_categories {
+        let (old_categories, new_categories) = (categories(older)?, categories(newer)?);
+        diff.categories_added = new_categories
+            .difference(&old_categories)
+            .cloned()
+            .collect();
+        diff.categories_removed = old_categories
+            .difference(&new_categories)
+            .cloned()
+            .collect();
+    }
+    Ok(diff)
+}
+
+/// Updates both loaders and compares the snapshots they point at.
+///
+/// Each snapshot is built into the database of its own loader, so the two must have different
+/// target paths.
+pub fn diff(
+    older: &mut CratesIODumpLoader,
+    newer: &mut CratesIODumpLoader,
+) -> Result<DumpDiff, Error> {
+    if older.target_path == newer.target_path {
+        return Err(Error::SharedTargetPath(older.target_path.clone()));
+    }
+    let older = older.update()?.open_db()?;
+    let newer = newer.update()?.open_db()?;
+    diff_databases(&older, &newer)
+}
+
+#[test]
+fn test_diff_databases() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let open = |resource: &str, target: &str| -> Result<Connection, Error> {
+        let cache = cached_path::Cache::builder().progress_bar(None);
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::default()
+            .resource(resource)
+            .target_path(Path::new(target))
+            .cache(cache)?
+            .update()?
+            .load_dump_into(&db)?;
+        Ok(db)
+    };
+    let older = open("testdata/fixture.tar.gz", "testdata/extracted/diff-old")?;
+    let newer = open(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/diff-new",
+    )?;
+
+    let diff = diff_databases(&older, &newer)?;
+    let version = |crate_name: &str, num: &str| VersionRef {
+        crate_name: crate_name.to_string(),
+        num: num.to_string(),
+    };
+    assert_eq!(vec!["eyre".to_string()], diff.new_crates);
+    assert_eq!(
+        vec![version("eyre", "0.6.5"), version("tokio", "1.6.0")],
+        diff.new_versions
+    );
+    assert_eq!(vec![version("mio", "0.7.11")], diff.yanked);
+    assert_eq!(
+        vec![OwnerRef {
+            crate_name: "tokio".to_string(),
+            kind: OwnerKind::Team,
+            login: "github:tokio-rs:core".to_string(),
+        }],
+        diff.owners_removed
+    );
+    assert_eq!("someone", diff.owners_added[0].login);
+    assert_eq!(
+        vec![CategoryRef {
+            crate_name: "anyhow".to_string(),
+            slug: "encoding".to_string(),
+        }],
+        diff.categories_added
+    );
+    assert!(diff_databases(&newer, &newer)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_diff_loaders() -> Result<(), Error> {
+    use crate::mock::archive_loader;
+
+    let mut older = archive_loader(
+        "testdata/fixture.tar.gz",
+        "testdata/extracted/diff-loader-old",
+    )?;
+    let mut newer = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/diff-loader-new",
+    )?;
+
+    let diff = diff(&mut older, &mut newer)?;
+    assert_eq!(vec!["eyre".to_string()], diff.new_crates);
+    assert_eq!(1, diff.yanked.len());
+    let mut shared = archive_loader(
+        "testdata/fixture-next.tar.gz",
+        "testdata/extracted/diff-loader-old",
+    )?;
+    assert!(matches!(
+        self::diff(&mut older, &mut shared),
+        Err(Error::SharedTargetPath(_))
+    ));
+    Ok(())
+}
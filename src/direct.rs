@@ -0,0 +1,214 @@
+//! Preloading by reading the csvs with the `csv` crate and inserting the rows directly,
+//! instead of copying them out of a csvtab virtual table.
+
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use rusqlite::Connection;
+
+use crate::{file_error, schema, sql, CratesIODumpLoader, Error};
+
+/// How `preload()` gets the csvs into SQLite.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreloadEngine {
+    /// `CREATE TABLE AS SELECT` from a csvtab virtual table.
+    #[default]
+    Csvtab,
+    /// Reads the csvs in Rust and inserts the rows with a prepared statement, all in one
+    /// transaction. `sample_rows()` always goes through csvtab, since its filters are SQL.
+    Direct,
+}
+
+/// The column type `CREATE TABLE AS SELECT` declares for a column of type `declared`, which
+/// is just its affinity.
+fn affinity(declared: &str) -> &'static str {
+    let declared = declared.to_ascii_uppercase();
+    if declared.contains("INT") {
+        "INT"
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| declared.contains(t))
+    {
+        "TEXT"
+    } else if declared.is_empty() || declared.contains("BLOB") {
+        ""
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|t| declared.contains(t))
+    {
+        "REAL"
+    } else {
+        "NUM"
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Preload with `engine`, see [`PreloadEngine`]. Implies `preload(true)`.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn preload_engine(&mut self, engine: PreloadEngine) -> &mut Self {
+        self.preload_engine = engine;
+        self.preload = true;
+        self
+    }
+
+    pub(crate) fn direct_preload(&self) -> bool {
+        self.preload && self.preload_engine == PreloadEngine::Direct && self.sample_rows.is_none()
+    }
+
+    /// Loads `file` into `table` with the [`PreloadEngine::Direct`] engine.
+    pub(crate) fn load_direct(
+        &self,
+        db: &Connection,
+        file: &PathBuf,
+        table: &str,
+    ) -> Result<(), Error> {
+        let path = self.target_path.join(file);
+        let input = File::open(&path).map_err(file_error(&path))?;
+        let mut reader = csv::Reader::from_reader(BufReader::new(input));
+        // Same columns as the csvtab engine creates: TEXT, or the affinities of the schema.
+        let all: Vec<(String, &str)> = match self.schema_for(table)? {
+            Some(override_schema) => schema::parse_columns(&override_schema)
+                .into_iter()
+                .map(|(name, ty)| (name, affinity(&ty)))
+                .collect(),
+            None => reader
+                .headers()?
+                .iter()
+                .map(|h| (h.to_string(), "TEXT"))
+                .collect(),
+        };
+        // Positions in the csv of the columns to load.
+        let positions: Vec<usize> = match self.table_columns.get(table) {
+            Some(wanted) => wanted
+                .iter()
+                .map(|column| {
+                    all.iter()
+                        .position(|(name, _)| name == column)
+                        .ok_or_else(|| Error::UnknownColumn {
+                            table: table.to_string(),
+                            column: column.clone(),
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+            None => (0..all.len()).collect(),
+        };
+        let columns: Vec<&(String, &str)> = positions.iter().map(|&i| &all[i]).collect();
+
+        let create = format!(
+            "DROP TABLE IF EXISTS {0}; CREATE TABLE {0}({1});",
+            sql::identifier(table),
+            columns
+                .iter()
+                .map(|(name, ty)| format!("{} {}", sql::identifier(name), ty)
+                    .trim_end()
+                    .to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let insert = format!(
+            "INSERT INTO {} VALUES ({})",
+            sql::identifier(table),
+            vec!["?"; columns.len()].join(", ")
+        );
+        let table_error = |sql: &str| {
+            let sql = sql.to_string();
+            move |source| Error::TableError {
+                table: table.to_string(),
+                sql,
+                source,
+            }
+        };
+
+        db.execute_batch("SAVEPOINT direct_load")?;
+        let loaded = (|| -> Result<(), Error> {
+            db.execute_batch(&create).map_err(table_error(&create))?;
+            let mut stmt = db.prepare(&insert).map_err(table_error(&insert))?;
+            let mut record = csv::StringRecord::new();
+            while reader.read_record(&mut record)? {
+                for (i, &position) in positions.iter().enumerate() {
+                    stmt.raw_bind_parameter(i + 1, record.get(position).unwrap_or_default())
+                        .map_err(table_error(&insert))?;
+                }
+                stmt.raw_execute().map_err(table_error(&insert))?;
+            }
+            Ok(())
+        })();
+        match loaded {
+            Ok(()) => db.execute_batch("RELEASE direct_load")?,
+            Err(_) => db.execute_batch("ROLLBACK TO direct_load; RELEASE direct_load")?,
+        }
+        loaded
+    }
+}
+
+#[test]
+fn test_direct_preload() -> Result<(), Error> {
+    use std::path::Path;
+
+    let load = |engine: PreloadEngine| -> Result<Connection, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::default()
+            .resource("testdata/fixture.tar.gz")
+            .target_path(Path::new("testdata/extracted/direct"))
+            .preload_engine(engine)
+            .table_schema(
+                "crates",
+                "CREATE TABLE x(created_at TEXT, description TEXT, documentation TEXT, downloads INTEGER, homepage TEXT, id INTEGER, max_upload_size INTEGER, name TEXT, readme TEXT, repository TEXT, updated_at TEXT);",
+            )
+            .cache(cached_path::Cache::builder().progress_bar(None))?
+            .update()?
+            .load_dump_into(&db)?;
+        Ok(db)
+    };
+    let dump = |db: &Connection, sql: &str| -> Result<Vec<Vec<String>>, Error> {
+        let mut stmt = db.prepare(sql)?;
+        let columns = stmt.column_count();
+        let rows = stmt.query_map([], |row| {
+            (0..columns)
+                .map(|i| Ok(format!("{:?}", row.get::<_, rusqlite::types::Value>(i)?)))
+                .collect()
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    };
+
+    let (csvtab, direct) = (load(PreloadEngine::Csvtab)?, load(PreloadEngine::Direct)?);
+    for sql in [
+        "SELECT m.name, p.name, p.type FROM sqlite_master m, pragma_table_info(m.name) p ORDER BY m.name, p.cid",
+        "SELECT * FROM crates ORDER BY id",
+        "SELECT * FROM version_downloads ORDER BY rowid",
+    ] {
+        assert_eq!(dump(&csvtab, sql)?, dump(&direct, sql)?);
+    }
+
+    let lean = |engine: PreloadEngine| -> Result<Vec<Vec<String>>, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::default()
+            .resource("testdata/fixture.tar.gz")
+            .target_path(Path::new("testdata/extracted/direct"))
+            .lean()
+            .preload_engine(engine)
+            .cache(cached_path::Cache::builder().progress_bar(None))?
+            .load_dump_into(&db)?;
+        assert_eq!(4, crate::reverse_dependencies(&db, "serde")?.len());
+        dump(
+            &db,
+            "SELECT m.name, p.name, p.type FROM sqlite_master m, pragma_table_info(m.name) p ORDER BY m.name, p.cid",
+        )
+    };
+    let columns = lean(PreloadEngine::Csvtab)?;
+    assert_eq!(6 + 8 + 7, columns.len());
+    assert_eq!(columns, lean(PreloadEngine::Direct)?);
+
+    let db = Connection::open_in_memory()?;
+    let result = CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/direct"))
+        .minimal()
+        .preload_engine(PreloadEngine::Direct)
+        .columns("crates", &["id", "nope"])
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .load_dump_into(&db);
+    assert!(matches!(result, Err(Error::UnknownColumn { .. })));
+    Ok(())
+}
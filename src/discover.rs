@@ -0,0 +1,129 @@
+//! Taking whatever tables the archive has, for loaders that haven't picked any, so tables
+//! crates.io adds are loaded without a new release of this crate.
+
+use std::{
+    fs::{self, File},
+    path::Path,
+    path::PathBuf,
+};
+
+use flate2::read::GzDecoder;
+
+use crate::{file_error, CratesIODumpLoader, Error, UpdateReport};
+
+/// Whether the archive entry at `entry`, named `name`, is a table's csv.
+fn is_table_entry(entry: &Path, name: &Path) -> bool {
+    name.extension() == Some("csv".as_ref())
+        && entry.parent().and_then(Path::file_name) == Some("data".as_ref())
+}
+
+impl CratesIODumpLoader {
+    /// Whether to extract the archive entry at `entry`, named `name`.
+    pub(crate) fn wants_entry(&self, entry: &Path, name: &Path) -> bool {
+        self.files.iter().any(|f| f == name)
+            || (self.discover_tables && is_table_entry(entry, name))
+    }
+
+    /// Names of the tables in the archive, fetching it unless it is cached, without extracting
+    /// anything. These are the tables `all_tables()` loads, besides custom ones. A pg_dump
+    /// `.sql` resource only ever has the tables of `files` extracted, so those are returned.
+    pub fn tables_from_archive(&self) -> Result<Vec<String>, Error> {
+        let stem = |name: &Path| {
+            name.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        let path = self.fetch_archive(&mut UpdateReport::default())?;
+        if path.extension() == Some("sql".as_ref()) {
+            return Ok(self.files.iter().map(|f| stem(f)).collect());
+        }
+        let file = File::open(&path).map_err(file_error(&path))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        let mut tables = Vec::new();
+        for entry in archive.entries().map_err(Error::BadArchiveEntry)? {
+            let entry = entry.map_err(Error::BadArchiveEntry)?;
+            let entry = entry.path().map_err(Error::BadArchiveEntry)?;
+            if let Some(name) = entry.file_name() {
+                if is_table_entry(&entry, Path::new(name)) {
+                    tables.push(stem(Path::new(name)));
+                }
+            }
+        }
+        tables.sort();
+        Ok(tables)
+    }
+
+    /// The csvs extracted into `target_path`: every one but the custom tables' when
+    /// discovering tables, otherwise `files`.
+    pub(crate) fn table_files(&self) -> Result<Vec<PathBuf>, Error> {
+        if !self.discover_tables {
+            return Ok(self.files.clone());
+        }
+        let custom: Vec<PathBuf> = self.custom_tables.iter().map(|t| t.file_name()).collect();
+        let entries = fs::read_dir(&self.target_path).map_err(file_error(&self.target_path))?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let name = PathBuf::from(entry.map_err(file_error(&self.target_path))?.file_name());
+            if name.extension() == Some("csv".as_ref()) && !custom.contains(&name) {
+                files.push(name);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[test]
+fn test_discover_tables() -> Result<(), Error> {
+    use crate::{FixtureBuilder, Table};
+
+    let target = Path::new("testdata/extracted/discover");
+    let _ = fs::remove_dir_all(target);
+    fs::create_dir_all(target)?;
+    let archive = target.join("dump.tar.gz");
+    let mut fixture = FixtureBuilder::default();
+    fixture
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .row("trustpub_configs", &[("id", "1")]);
+    fixture.write(&archive)?;
+
+    let mut loader = CratesIODumpLoader::builder()
+        .resource(&archive.to_string_lossy())
+        .target_path(target)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+    assert!(loader.discover_tables);
+    loader.update()?;
+    assert!(loader
+        .files
+        .contains(&PathBuf::from("default_versions.csv")));
+    assert!(loader
+        .files
+        .contains(&PathBuf::from("trustpub_configs.csv")));
+
+    let db = rusqlite::Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.load_dump_into(&db)?;
+    let (version, count): (String, i64) = db.query_row(
+        "SELECT v.num, CAST(d.num_versions AS INTEGER)
+            FROM default_versions d JOIN versions v ON v.id = d.version_id",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(("1.0.1".to_string(), 2), (version, count));
+
+    let tables = loader.tables_from_archive()?;
+    assert!(tables.iter().any(|t| t == "trustpub_configs"));
+    assert!(tables.windows(2).all(|w| w[0] < w[1]));
+
+    // Picking tables turns it off, and all_tables() back on.
+    let picked = loader.into_builder().tables(&[Table::Crates]).build()?;
+    assert!(!picked.discover_tables);
+    assert_eq!(vec![PathBuf::from("crates.csv")], picked.table_files()?);
+    assert_eq!(tables, picked.tables_from_archive()?);
+    let all = picked.into_builder().all_tables().build()?;
+    assert!(all.discover_tables);
+    assert_eq!(tables.len(), all.table_files()?.len());
+    Ok(())
+}
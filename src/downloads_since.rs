@@ -0,0 +1,116 @@
+//! Dropping old `version_downloads` rows while extracting, before they ever reach SQLite.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{file_error, time, CratesIODumpLoader, Error};
+
+pub(crate) const DOWNLOADS_FILE: &str = "version_downloads.csv";
+
+impl CratesIODumpLoader {
+    /// Only extract `version_downloads` rows dated `date` (`YYYY-MM-DD`) or later. Applied
+    /// when `update()` extracts the archive, so it doesn't affect csvs that are already there.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn downloads_since(&mut self, date: &str) -> Result<&mut Self, Error> {
+        if time::parse_date(date).is_none() {
+            return Err(Error::InvalidDate(date.to_string()));
+        }
+        self.downloads_since = Some(date.to_string());
+        Ok(self)
+    }
+
+    /// `downloads_since()` as days since the epoch, if set.
+    pub(crate) fn downloads_since_day(&self) -> Option<i32> {
+        self.downloads_since.as_deref().and_then(time::parse_date)
+    }
+
+    /// Filters an already written `version_downloads.csv` among `written`, for the dumps that
+    /// can't be filtered while extracting.
+    pub(crate) fn filter_written_downloads(&self, written: &[PathBuf]) -> Result<(), Error> {
+        let since = match self.downloads_since_day() {
+            Some(since) => since,
+            None => return Ok(()),
+        };
+        let path = self.target_path.join(DOWNLOADS_FILE);
+        if !written.iter().any(|w| w.file_name() == path.file_name()) {
+            return Ok(());
+        }
+        let filtered = path.with_extension("csv.filtered");
+        let input = File::open(&path).map_err(file_error(&path))?;
+        copy_downloads_since(input, &filtered, since).map_err(file_error(&path))?;
+        fs::rename(&filtered, &path).map_err(file_error(&path))
+    }
+}
+
+fn csv_io(e: csv::Error) -> io::Error {
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => e,
+        kind => io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", kind)),
+    }
+}
+
+/// Copies the `version_downloads` csv in `reader` to `path`, keeping the header and the rows
+/// dated `since` (days since the epoch) or later. IO errors keep their kind.
+pub(crate) fn copy_downloads_since(reader: impl Read, path: &Path, since: i32) -> io::Result<()> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut writer = csv::Writer::from_path(path).map_err(csv_io)?;
+    let headers = reader.byte_headers().map_err(csv_io)?.clone();
+    let date = headers.iter().position(|h| h == b"date").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "version_downloads has no date column",
+        )
+    })?;
+    writer.write_byte_record(&headers).map_err(csv_io)?;
+
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).map_err(csv_io)? {
+        let day = record
+            .get(date)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(time::parse_date);
+        if day.is_some_and(|day| day >= since) {
+            writer.write_byte_record(&record).map_err(csv_io)?;
+        }
+    }
+    writer.flush()
+}
+
+#[test]
+fn test_downloads_since() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let target = Path::new("testdata/extracted/downloads-since");
+    let _ = fs::remove_dir_all(target);
+    let count = |db: &Connection, sql: &str| -> Result<i64, Error> {
+        Ok(db.query_row(sql, [], |row| row.get(0))?)
+    };
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .tables(&["version_downloads"])
+        .downloads_since("2021-05-14")?
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+    assert!(count(&db, "SELECT COUNT(*) FROM version_downloads")? > 0);
+    assert_eq!(
+        0,
+        count(
+            &db,
+            "SELECT COUNT(*) FROM version_downloads WHERE date < '2021-05-14'"
+        )?
+    );
+
+    assert!(matches!(
+        CratesIODumpLoader::default().downloads_since("last week"),
+        Err(Error::InvalidDate(_))
+    ));
+    Ok(())
+}
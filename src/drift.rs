@@ -0,0 +1,106 @@
+//! Differences between the extracted csvs and [`schema::TABLES`], so queries against a column
+//! crates.io dropped don't come as a surprise. Typed schemas follow the csv header rather than
+//! the canonical column order, so drift doesn't misalign data, and new columns load as TEXT.
+
+use crate::{CratesIODumpLoader, Error};
+
+/// How the csv of a dump table differs from its canonical schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaDrift {
+    pub table: String,
+    /// Columns the csv has that the schema doesn't, in csv order. They load as TEXT.
+    pub added: Vec<String>,
+    /// Columns of the schema the csv doesn't have.
+    pub removed: Vec<String>,
+}
+
+impl CratesIODumpLoader {
+    /// Drift of every extracted dump table, as `load_dump_into()` reports it in
+    /// [`crate::LoadReport::drift`]. Custom tables and tables without a csv are skipped.
+    pub fn schema_drift(&self) -> Result<Vec<SchemaDrift>, Error> {
+        let mut drift = Vec::new();
+        for file in self.table_files()? {
+            let table = file.file_stem().unwrap_or_default().to_string_lossy();
+            drift.extend(self.table_drift(&table)?);
+        }
+        Ok(drift)
+    }
+
+    /// Drift of `table`'s extracted csv, if it is a dump table that drifted.
+    pub(crate) fn table_drift(&self, table: &str) -> Result<Option<SchemaDrift>, Error> {
+        let format = self.dump_format()?;
+        let canonical = match format.columns(table) {
+            Some(canonical) => canonical,
+            None => return Ok(None),
+        };
+        let path = self.target_path.join(format!("{}.csv", table));
+        if !path.exists() {
+            return Ok(None);
+        }
+        let header: Vec<String> = csv::Reader::from_path(&path)?
+            .headers()?
+            .iter()
+            .map(|name| format.canonical_column(table, name).to_string())
+            .collect();
+        let drift = SchemaDrift {
+            table: table.to_string(),
+            added: header
+                .iter()
+                .filter(|name| !canonical.iter().any(|c| c.name == name.as_str()))
+                .cloned()
+                .collect(),
+            removed: canonical
+                .iter()
+                .filter(|c| !header.iter().any(|name| name == c.name))
+                .map(|c| c.name.to_string())
+                .collect(),
+        };
+        Ok(match drift.added.is_empty() && drift.removed.is_empty() {
+            true => None,
+            false => Some(drift),
+        })
+    }
+}
+
+#[test]
+fn test_schema_drift() -> Result<(), Error> {
+    use rusqlite::Connection;
+    use std::{fs, path::Path, path::PathBuf};
+
+    let target = Path::new("testdata/extracted/drift");
+    let _ = fs::remove_dir_all(target);
+    fs::create_dir_all(target)?;
+    // A dump without homepage but with trustpub_only. Lacking downloads is expected of the
+    // latest format.
+    fs::write(
+        target.join("crates.csv"),
+        "id,name,trustpub_only,created_at,updated_at,description,documentation,\
+         max_upload_size,readme,repository\n\
+         1,serde,t,2021-05-15 12:00:00,2021-05-15 12:00:00,,,,,\n",
+    )?;
+    let mut loader = CratesIODumpLoader::builder()
+        .target_path(target)
+        .files(vec![PathBuf::from("crates.csv")])
+        .typed_schemas(true)
+        .preload(true)
+        .build()?;
+    let expected = SchemaDrift {
+        table: "crates".into(),
+        added: vec!["trustpub_only".into()],
+        removed: vec!["homepage".into()],
+    };
+    assert_eq!(vec![expected.clone()], loader.schema_drift()?);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let report = loader.load_dump_into(&db)?;
+    assert_eq!(vec![expected], report.drift);
+    // Loaded by name, not position.
+    let (id, trustpub_only): (i64, String) = db.query_row(
+        "SELECT id, trustpub_only FROM crates WHERE name = 'serde'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!((1, "t".to_string()), (id, trustpub_only));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use duckdb::Connection;
+
+use crate::{schema, sql, CratesIODumpLoader, Error};
+
+impl CratesIODumpLoader {
+    pub fn duckdb_path(&self) -> PathBuf {
+        self.target_path.join(Path::new("db.duckdb"))
+    }
+
+    /// DuckDB counterpart of [`CratesIODumpLoader::open_db`].
+    pub fn open_duckdb(&mut self) -> Result<Connection, Error> {
+        let path = self.duckdb_path();
+        let should_load = !path.exists();
+
+        let db = Connection::open(&path)?;
+        if should_load {
+            self.load_dump_into_duckdb(&db)?;
+        }
+        Ok(db)
+    }
+
+    /// Creates the dump tables in `db` straight from the extracted CSVs.
+    ///
+    /// Without `preload` the tables are views over `read_csv`, mirroring the csvtab virtual tables.
+    pub fn load_dump_into_duckdb(&mut self, db: &Connection) -> Result<(), Error> {
+        let schema = self
+            .files
+            .iter()
+            .map(|f| self.file_to_duckdb_query(f))
+            .fold(String::new(), |a, b| a + b.as_str() + "\n");
+        db.execute_batch(schema.as_str())?;
+        Ok(())
+    }
+
+    fn file_to_duckdb_query(&self, path: &Path) -> String {
+        let actual_file = self.target_path.join(path);
+        let table = path.file_stem().unwrap_or_default().to_string_lossy();
+        let file = sql::literal(&actual_file.to_string_lossy());
+
+        let source = match self.table_schema.get(&table.to_string()) {
+            Some(schema) => format!(
+                "read_csv({}, header=true, columns={{{}}})",
+                file,
+                schema_to_duckdb_columns(schema),
+            ),
+            None => format!("read_csv({}, header=true, auto_detect=true)", file),
+        };
+        let table = sql::identifier(&table);
+
+        match self.preload {
+            true => format!(
+                r#"
+                    DROP VIEW IF EXISTS {0};
+                    CREATE OR REPLACE TABLE {0} AS SELECT * FROM {1};
+                "#,
+                table, source,
+            ),
+            false => format!(
+                r#"
+                    DROP TABLE IF EXISTS {0};
+                    CREATE OR REPLACE VIEW {0} AS SELECT * FROM {1};
+                "#,
+                table, source,
+            ),
+        }
+    }
+}
+
+/// Turns a csvtab-style `CREATE TABLE x(a INT, b TEXT)` schema into a DuckDB `columns` struct.
+fn schema_to_duckdb_columns(schema: &str) -> String {
+    schema::parse_columns(schema)
+        .iter()
+        .map(|(name, ty)| match ty.is_empty() {
+            true => format!("{}: 'VARCHAR'", sql::literal(name)),
+            false => format!("{}: {}", sql::literal(name), sql::literal(ty)),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[test]
+fn test_schema_to_duckdb_columns() {
+    assert_eq!(
+        "'renamed_id': 'INT', 'name': 'TEXT'",
+        schema_to_duckdb_columns("CREATE TABLE x(renamed_id INT, name TEXT);")
+    );
+}
+
+#[test]
+fn test_basic_duckdb() -> Result<(), Error> {
+    let cache = cached_path::Cache::builder().progress_bar(None);
+
+    let db = Connection::open_in_memory()?;
+    CratesIODumpLoader::default()
+        .preload(true)
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/duckdb"))
+        .tables(&["test"])
+        .table_schema("test", "CREATE TABLE x(renamed_id INT, name TEXT);")
+        .cache(cache)?
+        .update()?
+        .load_dump_into_duckdb(&db)?;
+
+    let dummy: i64 = db.query_row(
+        "SELECT renamed_id FROM test WHERE name = ?",
+        ["awooo"],
+        |row| row.get(0),
+    )?;
+    assert_eq!(3, dummy);
+    Ok(())
+}
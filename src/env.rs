@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use cached_path::Cache;
+
+use crate::{CratesIODumpLoader, Error, Profile};
+
+/// Prefix of the environment variables read by [`CratesIODumpLoader::apply_env`].
+pub const ENV_PREFIX: &str = "CRATESIO_DBDUMP_";
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, Error> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(invalid(name, value)),
+    }
+}
+
+fn invalid(name: &str, value: &str) -> Error {
+    Error::InvalidEnvVar {
+        name: format!("{}{}", ENV_PREFIX, name),
+        value: value.to_string(),
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Overrides settings from `CRATESIO_DBDUMP_*` environment variables, for deployments that
+    /// are configured through their environment:
+    ///
+    /// - `RESOURCE`, `TARGET_PATH`, `SHA256`
+    /// - `TABLES`, comma-separated, or `PROFILE` as a [`Profile`] in lowercase or `lean`
+    /// - `PRELOAD`, `LAZY` and `THREADS`
+    /// - `CACHE_DIR` and `OFFLINE`, which replace a cache set with `cache()`
+    ///
+    /// Booleans are `1`/`true`/`yes`/`on` or `0`/`false`/`no`/`off`. Unset variables leave
+    /// the setting as it is.
+    pub fn apply_env(&mut self) -> Result<&mut Self, Error> {
+        self.apply_vars(|name| std::env::var(format!("{}{}", ENV_PREFIX, name)).ok())
+    }
+
+    #[allow(deprecated)]
+    fn apply_vars(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<&mut Self, Error> {
+        if let Some(resource) = var("RESOURCE") {
+            self.resource(&resource);
+        }
+        if let Some(path) = var("TARGET_PATH") {
+            self.target_path(&PathBuf::from(path));
+        }
+        if let Some(sha256) = var("SHA256") {
+            // Empty to unpin.
+            self.sha256(Some(sha256.as_str()).filter(|s| !s.is_empty()));
+        }
+        match var("PROFILE").as_deref() {
+            Some("minimal") => {
+                self.profile(Profile::Minimal);
+            }
+            Some("analytics") => {
+                self.profile(Profile::Analytics);
+            }
+            Some("search") => {
+                self.profile(Profile::Search);
+            }
+            Some("full") => {
+                self.profile(Profile::Full);
+            }
+            Some("lean") => {
+                self.lean();
+            }
+            Some(other) => return Err(invalid("PROFILE", other)),
+            None => {}
+        }
+        if let Some(tables) = var("TABLES") {
+            let tables: Vec<&str> = tables.split(',').map(str::trim).collect();
+            self.tables(&tables);
+        }
+        if let Some(preload) = var("PRELOAD") {
+            self.preload(parse_bool("PRELOAD", &preload)?);
+        }
+        if let Some(lazy) = var("LAZY") {
+            self.lazy(parse_bool("LAZY", &lazy)?);
+        }
+        if let Some(threads) = var("THREADS") {
+            self.threads(threads.parse().map_err(|_| invalid("THREADS", &threads))?);
+        }
+
+        let dir = var("CACHE_DIR");
+        let offline = var("OFFLINE")
+            .map(|offline| parse_bool("OFFLINE", &offline))
+            .transpose()?;
+        if dir.is_some() || offline.is_some() {
+            let mut builder = Cache::builder();
+            if let Some(dir) = dir {
+                builder = builder.dir(PathBuf::from(dir));
+            }
+            if let Some(offline) = offline {
+                builder = builder.offline(offline);
+            }
+            self.cache(builder)?;
+        }
+        Ok(self)
+    }
+}
+
+#[test]
+fn test_env_overrides() -> Result<(), Error> {
+    use std::{collections::HashMap, path::Path};
+
+    let vars = |pairs: &[(&str, &str)]| {
+        let vars: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name: &str| vars.get(name).cloned()
+    };
+    let target = Path::new("testdata/extracted/env");
+    let mut loader = CratesIODumpLoader::default();
+    loader.preload(true).apply_vars(vars(&[
+        ("RESOURCE", "testdata/test.tar.gz"),
+        ("TARGET_PATH", "testdata/extracted/env"),
+        ("TABLES", "test, crates"),
+        ("PRELOAD", "off"),
+        ("THREADS", "4"),
+        ("CACHE_DIR", "testdata/extracted/env/cache"),
+        ("OFFLINE", "1"),
+    ]))?;
+    assert_eq!("testdata/test.tar.gz", loader.resource);
+    assert_eq!(target, loader.target_path);
+    assert_eq!(
+        vec![PathBuf::from("test.csv"), PathBuf::from("crates.csv")],
+        loader.files
+    );
+    assert!(!loader.preload);
+    assert_eq!(4, loader.threads);
+    assert_eq!(target.join("cache"), loader.cache_handle()?.dir);
+
+    // Unset variables leave settings alone.
+    loader.apply_vars(vars(&[]))?;
+    assert_eq!("testdata/test.tar.gz", loader.resource);
+
+    match loader.apply_vars(vars(&[("LAZY", "maybe")])) {
+        Err(Error::InvalidEnvVar { name, value }) => {
+            assert_eq!("CRATESIO_DBDUMP_LAZY", name);
+            assert_eq!("maybe", value);
+        }
+        other => panic!("expected an invalid variable, got {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
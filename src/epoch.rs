@@ -0,0 +1,168 @@
+//! Timestamps as seconds since the unix epoch, which compare and bucket as plain integers
+//! rather than the Postgres timestamp text of the dump.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schema::{self, ColumnType},
+    sql, CratesIODumpLoader, Error,
+};
+
+/// How `epoch_timestamps()` stores the timestamp columns of preloaded tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EpochTimestamps {
+    /// Next to every timestamp column, e.g. `created_at`, add an INTEGER `created_at_ts`.
+    Alongside,
+    /// Replace the text of every timestamp column with the INTEGER. The helpers of this crate
+    /// that read timestamps, e.g. the [`crate::cadence`] views, expect the text, so this is
+    /// for databases queried with SQL of your own.
+    InPlace,
+}
+
+/// `column` as seconds since the unix epoch, NULL when empty.
+fn seconds(column: &str) -> String {
+    format!(
+        "CAST(strftime('%s', {}) AS INTEGER)",
+        sql::identifier(column)
+    )
+}
+
+/// Whether `table` was preloaded into `db`, rather than left a virtual table or a view.
+fn preloaded(db: &Connection, table: &str) -> Result<bool, Error> {
+    let count: i64 = db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ? AND sql NOT LIKE 'CREATE VIRTUAL%'",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+impl CratesIODumpLoader {
+    /// Converts the timestamp columns of the dump tables in `tables` according to
+    /// `epoch_timestamps`.
+    pub(crate) fn convert_timestamps(
+        &self,
+        db: &Connection,
+        tables: &[String],
+    ) -> Result<(), Error> {
+        let mode = match self.epoch_timestamps {
+            Some(mode) => mode,
+            None => return Ok(()),
+        };
+        for table in tables {
+            let canonical = match schema::table(table) {
+                Some(canonical) if preloaded(db, table)? => canonical,
+                _ => continue,
+            };
+            let mut stmt = db.prepare("SELECT name FROM pragma_table_info(?)")?;
+            let columns = stmt
+                .query_map([table], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            let is_timestamp = |column: &str| {
+                canonical
+                    .column(column)
+                    .is_some_and(|c| c.ty == ColumnType::Timestamp)
+            };
+            if !columns.iter().any(|c| is_timestamp(c)) {
+                continue;
+            }
+
+            let name = sql::identifier(table);
+            let sql = match mode {
+                EpochTimestamps::Alongside => {
+                    let mut sql = String::new();
+                    let mut updates = Vec::new();
+                    for column in columns.iter().filter(|c| is_timestamp(c)) {
+                        let ts = sql::identifier(&format!("{}_ts", column));
+                        sql += &format!("ALTER TABLE {} ADD COLUMN {} INTEGER;\n", name, ts);
+                        updates.push(format!("{} = {}", ts, seconds(column)));
+                    }
+                    sql + &format!("UPDATE {} SET {};", name, updates.join(", "))
+                }
+                EpochTimestamps::InPlace => {
+                    let converted = sql::identifier(&format!("epoch_{}", table));
+                    let select: Vec<String> = columns
+                        .iter()
+                        .map(|column| match is_timestamp(column) {
+                            true => format!("{} AS {}", seconds(column), sql::identifier(column)),
+                            false => sql::identifier(column),
+                        })
+                        .collect();
+                    format!(
+                        r#"
+                            DROP TABLE IF EXISTS {0};
+                            CREATE TABLE {0} AS SELECT {2} FROM {1};
+                            DROP TABLE {1};
+                            ALTER TABLE {0} RENAME TO {1};
+                        "#,
+                        converted,
+                        name,
+                        select.join(", ")
+                    )
+                }
+            };
+            let tx = db.unchecked_transaction()?;
+            tx.execute_batch(&sql).map_err(|source| Error::TableError {
+                table: table.clone(),
+                sql: sql.trim().to_string(),
+                source,
+            })?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_epoch_timestamps() -> Result<(), Error> {
+    use std::path::Path;
+
+    let load = |mode: EpochTimestamps, target: &str| -> Result<Connection, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::builder()
+            .resource("testdata/fixture.tar.gz")
+            .target_path(Path::new(target))
+            .minimal()
+            .preload(true)
+            .epoch_timestamps(mode)
+            .cache(cached_path::Cache::builder().progress_bar(None))?
+            .build()?
+            .update()?
+            .load_dump_into(&db)?;
+        Ok(db)
+    };
+
+    let db = load(
+        EpochTimestamps::Alongside,
+        "testdata/extracted/epoch-alongside",
+    )?;
+    let (created_at, ts): (String, i64) = db.query_row(
+        "SELECT created_at, created_at_ts FROM crates WHERE name = 'serde'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let micros = crate::time::parse_timestamp_micros(&created_at).unwrap();
+    assert_eq!(micros / 1_000_000, ts);
+    let typed: String = db.query_row(
+        "SELECT typeof(updated_at_ts) FROM versions LIMIT 1",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!("integer", typed);
+
+    let db = load(
+        EpochTimestamps::InPlace,
+        "testdata/extracted/epoch-in-place",
+    )?;
+    let (created_at, crates): (i64, i64) = db.query_row(
+        "SELECT created_at, (SELECT COUNT(*) FROM crates) FROM crates WHERE name = 'serde'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(ts, created_at);
+    assert_eq!(6, crates);
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Typed progress events for everything `update()` and `load_dump_into()` do, see
+//! [`CratesIODumpLoader::events`].
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{CratesIODumpLoader, SchemaDrift};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadEvent {
+    /// `update()` started downloading `resource`.
+    DownloadStarted { resource: String },
+    /// Bytes downloaded so far. cached-path doesn't say how large the download is.
+    DownloadProgress { bytes: u64 },
+    /// The archive is downloaded, or was already cached, at `path`.
+    DownloadFinished { path: PathBuf },
+    /// Another process is extracting or loading into `target_path`, wait for it to finish.
+    WaitingForLock { path: PathBuf },
+    /// `update()` is extracting `table`.
+    Extracting { table: String },
+    /// The csv of a dump table has columns added or removed since this release, see
+    /// [`CratesIODumpLoader::schema_drift`].
+    SchemaDrift(SchemaDrift),
+    /// `load_dump_into()` loaded `table`. `rows` is only counted for preloaded tables, since
+    /// counting a virtual table reads its whole csv.
+    TableLoaded {
+        table: String,
+        rows: Option<u64>,
+        duration: Duration,
+    },
+}
+
+impl CratesIODumpLoader {
+    /// Receiver of [`LoadEvent`]s from now on, e.g. to render progress from another thread.
+    /// Replaces the receiver of earlier calls.
+    pub fn events(&mut self) -> Receiver<LoadEvent> {
+        let (sender, receiver) = channel();
+        self.events = Some(sender);
+        receiver
+    }
+
+    pub(crate) fn emit(&self, event: LoadEvent) {
+        if let Some(events) = &self.events {
+            // Nobody listening anymore is fine.
+            let _ = events.send(event);
+        }
+    }
+}
+
+/// Reports the size of the temporary files cached-path downloads into while it is alive.
+pub(crate) struct DownloadWatch {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+const POLL: Duration = Duration::from_millis(200);
+
+fn downloading_bytes(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(".tmp"))
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+impl DownloadWatch {
+    pub(crate) fn start(dir: &Path, events: Sender<LoadEvent>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (dir, stopped) = (dir.to_path_buf(), stop.clone());
+        let handle = thread::spawn(move || {
+            let mut last = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                let bytes = downloading_bytes(&dir);
+                if bytes != last {
+                    last = bytes;
+                    if events.send(LoadEvent::DownloadProgress { bytes }).is_err() {
+                        break;
+                    }
+                }
+                thread::sleep(POLL);
+            }
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for DownloadWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[test]
+fn test_events() -> Result<(), crate::Error> {
+    use rusqlite::Connection;
+
+    let target = Path::new("testdata/extracted/events");
+    let _ = fs::remove_dir_all(target);
+    fs::create_dir_all(target)?;
+
+    let (sender, receiver) = channel();
+    let watch = DownloadWatch::start(target, sender);
+    fs::write(target.join(".tmpAbC123"), [0; 1000])?;
+    assert_eq!(
+        LoadEvent::DownloadProgress { bytes: 1000 },
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap()
+    );
+    drop(watch);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    let events = loader.events();
+    loader
+        .resource("testdata/test.tar.gz")
+        .target_path(&target.join("data"))
+        .tables(&["test"])
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+    drop(loader);
+
+    let events: Vec<LoadEvent> = events.iter().collect();
+    assert!(matches!(&events[0], LoadEvent::DownloadFinished { .. }));
+    assert_eq!(
+        LoadEvent::Extracting {
+            table: "test".into()
+        },
+        events[1]
+    );
+    match &events[2] {
+        LoadEvent::TableLoaded { table, rows, .. } => {
+            assert_eq!("test", table);
+            assert!(rows.unwrap_or_default() > 0);
+        }
+        other => panic!("expected a loaded table, got {:?}", other),
+    }
+    assert_eq!(3, events.len());
+    Ok(())
+}
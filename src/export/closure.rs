@@ -0,0 +1,256 @@
+//! Dependency closures of a crate version or a lockfile, resolved against the dump.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    path::Path,
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use semver::{Version, VersionReq};
+
+use crate::{file_error, lockfile::parse_lockfile, Error};
+
+/// A set of packages to look up in the dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DependencyClosure {
+    /// A crate version and what it transitively depends on, resolved against the dump: the
+    /// newest non-yanked version matching each normal, non-optional dependency.
+    Crate { name: String, version: String },
+    /// The crates.io packages pinned in a `Cargo.lock`.
+    Lockfile(String),
+}
+
+impl DependencyClosure {
+    pub fn krate(name: &str, version: &str) -> Self {
+        DependencyClosure::Crate {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    pub fn lockfile(path: &Path) -> Result<Self, Error> {
+        Ok(DependencyClosure::Lockfile(
+            fs::read_to_string(path).map_err(file_error(path))?,
+        ))
+    }
+}
+
+/// A package of a closure, with what the dump knows about it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Package {
+    pub name: String,
+    pub version: String,
+    pub license: String,
+    pub checksum: String,
+    /// Indexes of the packages it depends on.
+    pub dependencies: BTreeSet<usize>,
+}
+
+/// A dependency requirement that no version of the dump satisfies, so it is missing from the
+/// closure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnresolvedDependency {
+    /// Name and version of the package that requires it.
+    pub dependent: (String, String),
+    pub name: String,
+    pub req: String,
+}
+
+/// The packages of a closure and what couldn't be resolved.
+#[derive(Debug, Default)]
+pub(crate) struct Resolved {
+    /// Only SBOMs describe the root separately.
+    #[cfg_attr(not(feature = "sbom"), allow(dead_code))]
+    pub root: Option<usize>,
+    pub packages: Vec<Package>,
+    pub unresolved: Vec<UnresolvedDependency>,
+}
+
+/// `(id, license, checksum)` of `name` `version`.
+fn lookup(
+    db: &Connection,
+    name: &str,
+    version: &str,
+) -> Result<Option<(i64, String, String)>, Error> {
+    let mut stmt = db.prepare_cached(
+        r#"
+            SELECT CAST(v.id AS INTEGER), COALESCE(v.license, ''), COALESCE(v.checksum, '')
+            FROM main.versions v JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ? AND v.num = ?
+        "#,
+    )?;
+    Ok(stmt
+        .query_row([name, version], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .optional()?)
+}
+
+/// The newest non-yanked version of `name` matching `req`. As with Cargo, prereleases only
+/// match requirements that name a prerelease of the same version.
+fn resolve(db: &Connection, name: &str, req: &str) -> Result<Option<String>, Error> {
+    let req = match VersionReq::parse(req) {
+        Ok(req) => req,
+        Err(_) => return Ok(None),
+    };
+    let mut stmt = db.prepare_cached(
+        r#"
+            SELECT v.num FROM main.versions v
+            JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ? AND v.yanked <> 't'
+        "#,
+    )?;
+    let versions = stmt
+        .query_map([name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(versions
+        .iter()
+        .filter_map(|num| Version::parse(num).ok())
+        .filter(|v| req.matches(v))
+        .max()
+        .map(|v| v.to_string()))
+}
+
+/// The root, if any, every package of `source` and the requirements left unresolved.
+pub(crate) fn resolve_closure(
+    db: &Connection,
+    source: &DependencyClosure,
+) -> Result<Resolved, Error> {
+    let mut packages: Vec<Package> = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut index: BTreeMap<(String, String), usize> = BTreeMap::new();
+    let mut add =
+        |packages: &mut Vec<Package>, name: &str, version: &str| -> Result<usize, Error> {
+            let key = (name.to_string(), version.to_string());
+            if let Some(i) = index.get(&key) {
+                return Ok(*i);
+            }
+            let (license, checksum) = match lookup(db, name, version)? {
+                Some((_, license, checksum)) => (license, checksum),
+                None => Default::default(),
+            };
+            packages.push(Package {
+                name: name.to_string(),
+                version: version.to_string(),
+                license,
+                checksum,
+                dependencies: BTreeSet::new(),
+            });
+            index.insert(key, packages.len() - 1);
+            Ok(packages.len() - 1)
+        };
+
+    match source {
+        DependencyClosure::Lockfile(text) => {
+            for package in parse_lockfile(text)?.iter().filter(|p| p.is_crates_io()) {
+                add(&mut packages, &package.name, &package.version)?;
+            }
+            Ok(Resolved {
+                root: None,
+                packages,
+                unresolved,
+            })
+        }
+        DependencyClosure::Crate { name, version } => {
+            if lookup(db, name, version)?.is_none() {
+                return Err(Error::UnknownVersion {
+                    name: name.clone(),
+                    version: version.clone(),
+                });
+            }
+            let root = add(&mut packages, name, version)?;
+            let mut queue = VecDeque::from(vec![root]);
+            let mut dependencies = db.prepare(
+                r#"
+                    SELECT dc.name, d.req FROM main.dependencies d
+                    JOIN main.crates dc ON CAST(dc.id AS INTEGER) = CAST(d.crate_id AS INTEGER)
+                    WHERE CAST(d.version_id AS INTEGER) = ? AND CAST(d.kind AS INTEGER) = 0
+                        AND d.optional <> 't'
+                    ORDER BY dc.name
+                "#,
+            )?;
+            while let Some(i) = queue.pop_front() {
+                let id = match lookup(db, &packages[i].name, &packages[i].version)? {
+                    Some((id, _, _)) => id,
+                    None => continue,
+                };
+                let requirements = dependencies
+                    .query_map([id], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (name, req) in requirements {
+                    let version = match resolve(db, &name, &req)? {
+                        Some(version) => version,
+                        None => {
+                            unresolved.push(UnresolvedDependency {
+                                dependent: (packages[i].name.clone(), packages[i].version.clone()),
+                                name,
+                                req,
+                            });
+                            continue;
+                        }
+                    };
+                    let known = packages.len();
+                    let j = add(&mut packages, &name, &version)?;
+                    if j == known {
+                        queue.push_back(j);
+                    }
+                    packages[i].dependencies.insert(j);
+                }
+            }
+            Ok(Resolved {
+                root: Some(root),
+                packages,
+                unresolved,
+            })
+        }
+    }
+}
+
+/// The dependency requirements of `source` that no version in the dump satisfies, which
+/// [`crate::vendor_manifest`] and SBOMs leave out. Always empty for lockfiles, whose versions
+/// are already resolved.
+pub fn unresolved_dependencies(
+    db: &Connection,
+    source: &DependencyClosure,
+) -> Result<Vec<UnresolvedDependency>, Error> {
+    Ok(resolve_closure(db, source)?.unresolved)
+}
+
+#[test]
+fn test_resolve_closure() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("app", &["0.1.0"])
+        .add_crate("serde", &["1.0.0", "1.1.0-rc.1"])
+        .add_crate("tokio", &["1.0.0", "2.0.0-beta.1"])
+        .dependency("app", "0.1.0", "serde", "^1.0")
+        .dependency("app", "0.1.0", "tokio", "=2.0.0-beta.1")
+        .dependency("app", "0.1.0", "missing", "^1")
+        .dependency("serde", "1.0.0", "tokio", "^3")
+        .build()?;
+
+    let resolved = resolve_closure(&db, &DependencyClosure::krate("app", "0.1.0"))?;
+    let versions: Vec<(&str, &str)> = resolved
+        .packages
+        .iter()
+        .map(|p| (p.name.as_str(), p.version.as_str()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("app", "0.1.0"),
+            ("serde", "1.0.0"),
+            ("tokio", "2.0.0-beta.1")
+        ],
+        versions
+    );
+    assert_eq!(
+        vec![("app", "missing", "^1"), ("serde", "tokio", "^3"),],
+        unresolved_dependencies(&db, &DependencyClosure::krate("app", "0.1.0"))?
+            .iter()
+            .map(|u| (u.dependent.0.as_str(), u.name.as_str(), u.req.as_str()))
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::io::Write;
+
+use rusqlite::{types::ValueRef, Connection};
+
+use crate::Error;
+
+/// Writes the rows of `query_or_table` as CSV with a header line, returning the row count.
+pub fn export_csv<W: Write>(
+    db: &Connection,
+    query_or_table: &str,
+    writer: W,
+) -> Result<u64, Error> {
+    let mut stmt = db.prepare(&super::table_or_query(query_or_table))?;
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(stmt.column_names())?;
+
+    let columns = stmt.column_count();
+    let mut count = 0;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        for i in 0..columns {
+            match row.get_ref(i)? {
+                ValueRef::Null => writer.write_field("")?,
+                ValueRef::Integer(n) => writer.write_field(n.to_string())?,
+                ValueRef::Real(f) => writer.write_field(f.to_string())?,
+                ValueRef::Text(t) | ValueRef::Blob(t) => writer.write_field(t)?,
+            }
+        }
+        writer.write_record(None::<&[u8]>)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+#[test]
+fn test_export_csv() -> Result<(), Error> {
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(
+        "CREATE TABLE t(id INT, name TEXT); INSERT INTO t VALUES (1, 'a,b'), (2, NULL);",
+    )?;
+
+    let mut out = Vec::new();
+    assert_eq!(2, export_csv(&db, "t", &mut out)?);
+    assert_eq!("id,name\n1,\"a,b\"\n2,\n", String::from_utf8_lossy(&out));
+    Ok(())
+}
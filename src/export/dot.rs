@@ -0,0 +1,225 @@
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    io::Write,
+};
+
+use rusqlite::Connection;
+
+use crate::{rows::DependencyKind, Error};
+
+pub struct DotExporter {
+    pub depth: usize,
+    pub reverse: bool,
+    pub include_dev: bool,
+}
+
+impl Default for DotExporter {
+    fn default() -> Self {
+        Self {
+            depth: 1,
+            reverse: false,
+            include_dev: true,
+        }
+    }
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    kind: DependencyKind,
+    optional: bool,
+    req: String,
+}
+
+impl DotExporter {
+    pub fn depth(&mut self, depth: usize) -> &mut Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Follow dependents instead of dependencies.
+    pub fn reverse(&mut self, reverse: bool) -> &mut Self {
+        self.reverse = reverse;
+        self
+    }
+
+    pub fn include_dev(&mut self, include: bool) -> &mut Self {
+        self.include_dev = include;
+        self
+    }
+
+    /// Walks the dependency graph of the newest non-yanked versions, starting at `root`.
+    pub fn export<W: Write>(
+        &self,
+        db: &Connection,
+        root: &str,
+        mut writer: W,
+    ) -> Result<(), Error> {
+        db.execute_batch(
+            r#"
+                DROP TABLE IF EXISTS temp.dot_latest;
+                CREATE TEMP TABLE dot_latest AS
+                    SELECT id, crate_id FROM (
+                        SELECT CAST(id AS INTEGER) AS id, CAST(crate_id AS INTEGER) AS crate_id,
+                            ROW_NUMBER() OVER (PARTITION BY crate_id ORDER BY created_at DESC) AS n
+                        FROM main.versions WHERE yanked <> 't'
+                    ) WHERE n = 1;
+            "#,
+        )?;
+        let edges = self.walk(db, root);
+        db.execute_batch("DROP TABLE IF EXISTS temp.dot_latest;")?;
+        let edges = edges?;
+
+        let mut nodes = BTreeSet::new();
+        nodes.insert(root.to_string());
+        for edge in &edges {
+            nodes.insert(edge.from.clone());
+            nodes.insert(edge.to.clone());
+        }
+
+        writeln!(writer, "digraph \"{}\" {{", escape(root))?;
+        writeln!(writer, "    rankdir=LR;")?;
+        writeln!(writer, "    node [shape=box, fontname=\"monospace\"];")?;
+        for node in &nodes {
+            match node == root {
+                true => writeln!(writer, "    \"{}\" [style=bold];", escape(node))?,
+                false => writeln!(writer, "    \"{}\";", escape(node))?,
+            }
+        }
+        for edge in &edges {
+            let style = match edge.kind {
+                DependencyKind::Normal => "solid",
+                DependencyKind::Build => "dashed",
+                DependencyKind::Dev => "dotted",
+            };
+            let color = match edge.optional {
+                true => "gray",
+                false => "black",
+            };
+            writeln!(
+                writer,
+                "    \"{}\" -> \"{}\" [label=\"{}\", style={}, color={}];",
+                escape(&edge.from),
+                escape(&edge.to),
+                escape(&edge.req),
+                style,
+                color
+            )?;
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    fn walk(&self, db: &Connection, root: &str) -> Result<Vec<Edge>, Error> {
+        let sql = match self.reverse {
+            false => {
+                r#"
+                    SELECT dep.name, CAST(d.kind AS INTEGER), d.optional, d.req
+                    FROM main.crates c
+                    JOIN dot_latest l ON l.crate_id = CAST(c.id AS INTEGER)
+                    JOIN main.dependencies d ON CAST(d.version_id AS INTEGER) = l.id
+                    JOIN main.crates dep ON CAST(dep.id AS INTEGER) = CAST(d.crate_id AS INTEGER)
+                    WHERE c.name = ?
+                "#
+            }
+            true => {
+                r#"
+                    SELECT dependent.name, CAST(d.kind AS INTEGER), d.optional, d.req
+                    FROM main.crates c
+                    JOIN main.dependencies d ON CAST(d.crate_id AS INTEGER) = CAST(c.id AS INTEGER)
+                    JOIN dot_latest l ON l.id = CAST(d.version_id AS INTEGER)
+                    JOIN main.crates dependent ON CAST(dependent.id AS INTEGER) = l.crate_id
+                    WHERE c.name = ?
+                "#
+            }
+        };
+        let mut stmt = db.prepare(sql)?;
+
+        let mut edges = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(root.to_string());
+        queue.push_back((root.to_string(), 0));
+        while let Some((name, depth)) = queue.pop_front() {
+            if depth >= self.depth {
+                continue;
+            }
+            let neighbours = stmt.query_map([&name], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, DependencyKind>(1)?,
+                    row.get::<_, String>(2)? == "t",
+                    row.get::<_, String>(3)?,
+                ))
+            })?;
+            for neighbour in neighbours {
+                let (other, kind, optional, req) = neighbour?;
+                if kind == DependencyKind::Dev && !self.include_dev {
+                    continue;
+                }
+                if seen.insert(other.clone()) {
+                    queue.push_back((other.clone(), depth + 1));
+                }
+                let (from, to) = match self.reverse {
+                    false => (name.clone(), other),
+                    true => (other, name.clone()),
+                };
+                edges.push(Edge {
+                    from,
+                    to,
+                    kind,
+                    optional,
+                    req,
+                });
+            }
+        }
+        Ok(edges)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes the dependency graph of `root`, `depth` levels deep, as GraphViz DOT.
+pub fn export_dot<W: Write>(
+    db: &Connection,
+    root: &str,
+    depth: usize,
+    writer: W,
+) -> Result<(), Error> {
+    DotExporter::default().depth(depth).export(db, root, writer)
+}
+
+#[test]
+fn test_export_dot() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/dot"))
+        .minimal()
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let mut out = Vec::new();
+    export_dot(&db, "tokio", 2, &mut out)?;
+    let dot = String::from_utf8_lossy(&out);
+    assert!(dot.contains("\"tokio\" -> \"mio\" [label=\"^0.7.6\", style=solid, color=gray];"));
+    assert!(dot.contains("\"tokio\" -> \"serde\" [label=\"^1.0\", style=dotted, color=black];"));
+    assert!(dot.contains("\"serde\" -> \"serde_derive\""));
+
+    out.clear();
+    DotExporter::default()
+        .reverse(true)
+        .export(&db, "bytes", &mut out)?;
+    let dot = String::from_utf8_lossy(&out);
+    assert!(dot.contains("\"tokio\" -> \"bytes\""));
+    assert!(!dot.contains("serde"));
+    Ok(())
+}
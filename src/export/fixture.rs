@@ -0,0 +1,361 @@
+//! Dump archives written from scratch, for tests that don't want the real dump.
+
+use std::{collections::BTreeMap, path::Path};
+
+use super::sample::write_dump_archive;
+use crate::{formats, schema, DumpMetadata, Error};
+
+type Row = BTreeMap<String, String>;
+
+/// Builds a miniature dump that [`crate::CratesIODumpLoader`] can load like the real one.
+///
+/// Every canonical table is written, with the canonical columns, so any table selection loads;
+/// apart from those the [`crate::formats::DumpFormat`] of the timestamp doesn't have.
+/// Crates, versions and dependencies get ids, timestamps and the other required columns filled
+/// in; rows added with [`FixtureBuilder::row`] are written as given, with missing canonical
+/// columns left empty.
+pub struct FixtureBuilder {
+    pub timestamp: String,
+    crates: Vec<(String, Vec<String>)>,
+    dependencies: Vec<(String, String, String, String)>,
+    rows: BTreeMap<String, Vec<Row>>,
+}
+
+impl Default for FixtureBuilder {
+    fn default() -> Self {
+        Self {
+            timestamp: "2021-05-15T02:00:00Z".to_string(),
+            crates: Vec::new(),
+            dependencies: Vec::new(),
+            rows: BTreeMap::new(),
+        }
+    }
+}
+
+impl FixtureBuilder {
+    /// A deterministic dump of `crates` crates named `crate-0`, `crate-1`, ... with
+    /// `versions_per_crate` versions each. Every version depends on up to three older crates
+    /// and the newest version of every crate has a week of downloads.
+    pub fn synthetic(crates: usize, versions_per_crate: usize) -> Self {
+        let mut fixture = Self::default();
+        let versions: Vec<String> = (0..versions_per_crate)
+            .map(|v| format!("0.{}.0", v))
+            .collect();
+        let versions: Vec<&str> = versions.iter().map(String::as_str).collect();
+        for i in 0..crates {
+            let name = format!("crate-{}", i);
+            fixture.add_crate(&name, &versions);
+            let mut dependencies: Vec<usize> = [i / 3, i / 2, i.saturating_sub(1)]
+                .iter()
+                .copied()
+                .filter(|d| *d < i)
+                .collect();
+            dependencies.dedup();
+            for version in &versions {
+                for dependency in &dependencies {
+                    fixture.dependency(&name, version, &format!("crate-{}", dependency), "^0.1");
+                }
+            }
+            if let Some(newest) = (i + 1).checked_mul(versions.len()).filter(|v| *v > 0) {
+                for day in 1..=7 {
+                    fixture.row(
+                        "version_downloads",
+                        &[
+                            ("date", &format!("2021-05-{:02}", 7 + day)),
+                            ("downloads", &((i % 100 + 1) * day).to_string()),
+                            ("version_id", &newest.to_string()),
+                        ],
+                    );
+                }
+            }
+        }
+        fixture
+    }
+
+    /// When the dump claims to have been taken, e.g. `2021-05-15T02:00:00Z`.
+    pub fn timestamp(&mut self, timestamp: &str) -> &mut Self {
+        self.timestamp = timestamp.to_string();
+        self
+    }
+
+    /// Adds a crate with the given versions, oldest first. Crates get the ids 1, 2, ... in the
+    /// order they are first added, for use in [`FixtureBuilder::row`].
+    pub fn add_crate(&mut self, name: &str, versions: &[&str]) -> &mut Self {
+        let versions = versions.iter().map(|v| v.to_string());
+        match self.crates.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => existing.extend(versions),
+            None => self.crates.push((name.to_string(), versions.collect())),
+        }
+        self
+    }
+
+    /// Makes `version` of `name` depend on `dependency` with the requirement `req`. Crates not
+    /// added yet are added without versions.
+    pub fn dependency(
+        &mut self,
+        name: &str,
+        version: &str,
+        dependency: &str,
+        req: &str,
+    ) -> &mut Self {
+        self.add_crate(name, &[]).add_crate(dependency, &[]);
+        self.dependencies.push((
+            name.to_string(),
+            version.to_string(),
+            dependency.to_string(),
+            req.to_string(),
+        ));
+        self
+    }
+
+    /// Adds a raw row to `table`, as `(column, value)` pairs.
+    pub fn row(&mut self, table: &str, values: &[(&str, &str)]) -> &mut Self {
+        let row = values
+            .iter()
+            .map(|(c, v)| (c.to_string(), v.to_string()))
+            .collect();
+        self.rows.entry(table.to_string()).or_default().push(row);
+        self
+    }
+
+    fn generated_rows(&self) -> BTreeMap<String, Vec<Row>> {
+        let created_at = format!(
+            "{} {}.000000",
+            self.timestamp.get(..10).unwrap_or_default(),
+            self.timestamp.get(11..19).unwrap_or("00:00:00")
+        );
+        let row = |values: &[(&str, String)]| -> Row {
+            values
+                .iter()
+                .map(|(c, v)| (c.to_string(), v.clone()))
+                .collect()
+        };
+
+        let mut tables: BTreeMap<String, Vec<Row>> = BTreeMap::new();
+        let mut version_ids = BTreeMap::new();
+        for (i, (name, versions)) in self.crates.iter().enumerate() {
+            let crate_id = (i + 1).to_string();
+            tables.entry("crates".into()).or_default().push(row(&[
+                ("id", crate_id.clone()),
+                ("name", name.clone()),
+                ("created_at", created_at.clone()),
+                ("updated_at", created_at.clone()),
+                ("downloads", "0".into()),
+                ("description", String::new()),
+            ]));
+            for num in versions {
+                let id = (version_ids.len() + 1).to_string();
+                version_ids.insert((name.clone(), num.clone()), id.clone());
+                tables.entry("versions".into()).or_default().push(row(&[
+                    ("id", id),
+                    ("crate_id", crate_id.clone()),
+                    ("num", num.clone()),
+                    ("created_at", created_at.clone()),
+                    ("updated_at", created_at.clone()),
+                    ("downloads", "0".into()),
+                    ("features", "{}".into()),
+                    ("yanked", "f".into()),
+                    ("crate_size", "0".into()),
+                    ("checksum", "0".repeat(64)),
+                ]));
+            }
+            if let Some(newest) = versions.last() {
+                tables
+                    .entry("default_versions".into())
+                    .or_default()
+                    .push(row(&[
+                        ("crate_id", crate_id.clone()),
+                        (
+                            "version_id",
+                            version_ids[&(name.clone(), newest.clone())].clone(),
+                        ),
+                        ("num_versions", versions.len().to_string()),
+                    ]));
+            }
+        }
+
+        let crate_ids: BTreeMap<&str, String> = self
+            .crates
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.as_str(), (i + 1).to_string()))
+            .collect();
+        for (id, (name, version, dependency, req)) in self.dependencies.iter().enumerate() {
+            let version_id = match version_ids.get(&(name.clone(), version.clone())) {
+                Some(id) => id.clone(),
+                None => continue,
+            };
+            tables.entry("dependencies".into()).or_default().push(row(&[
+                ("id", (id + 1).to_string()),
+                ("version_id", version_id),
+                (
+                    "crate_id",
+                    crate_ids
+                        .get(dependency.as_str())
+                        .cloned()
+                        .unwrap_or_default(),
+                ),
+                ("req", req.clone()),
+                ("kind", "0".into()),
+                ("optional", "f".into()),
+                ("default_features", "t".into()),
+                ("features", "{}".into()),
+            ]));
+        }
+        if !self.format().lacks_table("crate_downloads") {
+            for crate_id in crate_ids.values() {
+                tables
+                    .entry("crate_downloads".into())
+                    .or_default()
+                    .push(row(&[
+                        ("crate_id", crate_id.clone()),
+                        ("downloads", "0".into()),
+                    ]));
+            }
+        }
+        tables
+            .entry("metadata".into())
+            .or_default()
+            .push(row(&[("total_downloads", "0".into())]));
+        tables
+    }
+
+    /// The dump format of `timestamp`, deciding which canonical tables and columns are written.
+    fn format(&self) -> &'static formats::DumpFormat {
+        formats::for_date(self.timestamp.get(..10).unwrap_or(&self.timestamp))
+    }
+
+    /// Every table with its columns and rows, canonical tables first in the order of
+    /// [`schema::TABLES`].
+    pub(crate) fn tables(&self) -> Vec<(String, Vec<String>, Vec<Vec<String>>)> {
+        let mut rows = self.generated_rows();
+        for (table, extra) in &self.rows {
+            rows.entry(table.clone())
+                .or_default()
+                .extend(extra.iter().cloned());
+        }
+
+        let format = self.format();
+        let mut tables = Vec::new();
+        for table in schema::TABLES {
+            let table_rows = rows.remove(table.name).unwrap_or_default();
+            if format.lacks_table(table.name) && table_rows.is_empty() {
+                continue;
+            }
+            let columns = table
+                .columns
+                .iter()
+                .filter(|c| !format.lacks(table.name, c.name))
+                .map(|c| c.name.to_string())
+                .collect();
+            tables.push((table.name.to_string(), columns, table_rows));
+        }
+        for (table, rows) in rows {
+            let mut columns: Vec<String> = Vec::new();
+            for column in rows.iter().flat_map(|r| r.keys()) {
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+            }
+            tables.push((table, columns, rows));
+        }
+        tables
+            .into_iter()
+            .map(|(table, columns, rows)| {
+                let values = rows
+                    .iter()
+                    .map(|row| {
+                        columns
+                            .iter()
+                            .map(|c| row.get(c).cloned().unwrap_or_default())
+                            .collect()
+                    })
+                    .collect();
+                (table, columns, values)
+            })
+            .collect()
+    }
+
+    /// Writes the fixture as a `.tar.gz` at `path`, including `metadata.json`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut csvs = Vec::new();
+        for (table, columns, rows) in self.tables() {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(&columns)?;
+            for row in &rows {
+                writer.write_record(row)?;
+            }
+            let csv = writer
+                .into_inner()
+                .map_err(|e| Error::IOError(e.into_error()))?;
+            csvs.push((table, csv));
+        }
+
+        let metadata = DumpMetadata {
+            timestamp: self.timestamp.clone(),
+            crates_io_commit: None,
+        };
+        write_dump_archive(path, Some(&metadata), csvs)
+    }
+}
+
+#[test]
+fn test_fixture_builder() -> Result<(), Error> {
+    use crate::{reverse_dependencies, CratesIODumpLoader};
+    use rusqlite::Connection;
+
+    let target = Path::new("testdata/extracted/fixture-builder");
+    std::fs::create_dir_all(target)?;
+    let path = target.join("fixture.tar.gz");
+    FixtureBuilder::default()
+        .timestamp("2022-01-02T03:04:05Z")
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("tokio", &["1.0.0"])
+        .dependency("tokio", "1.0.0", "serde", "^1.0")
+        .row("categories", &[("id", "1"), ("slug", "asynchronous")])
+        .row(
+            "crates_categories",
+            &[("crate_id", "2"), ("category_id", "1")],
+        )
+        .write(&path)?;
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource(&path.to_string_lossy())
+        .target_path(&target.join("loaded"))
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let count = |sql: &str| db.query_row(sql, [], |row| row.get::<_, i64>(0));
+    assert_eq!(2, count("SELECT COUNT(*) FROM crates")?);
+    assert_eq!(3, count("SELECT COUNT(*) FROM versions")?);
+    assert_eq!(0, count("SELECT COUNT(*) FROM badges")?);
+    let rdeps = reverse_dependencies(&db, "serde")?;
+    assert_eq!(
+        vec![("tokio", "^1.0")],
+        rdeps
+            .iter()
+            .map(|d| (d.crate_name.as_str(), d.req.as_str()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!("2022-01-02", loader.metadata()?.unwrap().date());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_fixture() -> Result<(), Error> {
+    let db = crate::MockDump::from(FixtureBuilder::synthetic(10, 3)).build()?;
+    let count = |sql: &str| db.query_row(sql, [], |row| row.get::<_, i64>(0));
+    assert_eq!(10, count("SELECT COUNT(*) FROM crates")?);
+    assert_eq!(30, count("SELECT COUNT(*) FROM versions")?);
+    assert_eq!(70, count("SELECT COUNT(*) FROM version_downloads")?);
+    assert_eq!(
+        0,
+        count("SELECT COUNT(*) FROM dependencies d JOIN versions v ON v.id = d.version_id WHERE CAST(d.crate_id AS INTEGER) >= CAST(v.crate_id AS INTEGER)")?
+    );
+    assert!(count("SELECT COUNT(*) FROM dependencies")? > 0);
+    Ok(())
+}
@@ -0,0 +1,331 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use rusqlite::Connection;
+use serde_json::{json, Map, Value};
+
+use super::ndjson::parse_pg_array;
+use crate::{file_error, rows::DependencyKind, Error};
+
+/// Writes a cargo sparse registry index from the loaded `crates`, `versions` and
+/// `dependencies`, e.g. to bootstrap an offline mirror.
+pub struct IndexExporter {
+    /// Where cargo downloads `.crate` files from, the `dl` of `config.json`.
+    pub dl: String,
+    /// The registry's web API, the `api` of `config.json`.
+    pub api: Option<String>,
+    /// Commit the index into a git repository, for the classic `crates.io-index` protocol
+    /// rather than the sparse one. Needs `git` on the `PATH`.
+    pub git: bool,
+}
+
+impl Default for IndexExporter {
+    fn default() -> Self {
+        Self {
+            dl: "https://static.crates.io/crates".to_string(),
+            api: Some("https://crates.io".to_string()),
+            git: false,
+        }
+    }
+}
+
+/// Path of `name`'s file in an index, e.g. `se/rd/serde`.
+pub fn index_path(name: &str) -> PathBuf {
+    let name = name.to_ascii_lowercase();
+    match name.len() {
+        1 => Path::new("1").join(&name),
+        2 => Path::new("2").join(&name),
+        3 => Path::new("3").join(&name[..1]).join(&name),
+        _ => Path::new(&name[..2]).join(&name[2..4]).join(&name),
+    }
+}
+
+/// Splits the features using `dep:` or `?/` syntax off into `features2`, like crates.io does
+/// so older cargos can still read the rest.
+fn split_features(features: Value) -> (Value, Option<Value>) {
+    let features = match features {
+        Value::Object(features) => features,
+        _ => return (json!({}), None),
+    };
+    let is_new = |values: &Value| {
+        values.as_array().is_some_and(|values| {
+            values
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|v| v.starts_with("dep:") || v.contains("?/"))
+        })
+    };
+    let (new, old): (Map<String, Value>, Map<String, Value>) =
+        features.into_iter().partition(|(_, values)| is_new(values));
+    match new.is_empty() {
+        true => (Value::Object(old), None),
+        false => (Value::Object(old), Some(Value::Object(new))),
+    }
+}
+
+fn non_empty(text: String) -> Value {
+    match text.is_empty() {
+        true => Value::Null,
+        false => Value::from(text),
+    }
+}
+
+impl IndexExporter {
+    pub fn dl(&mut self, dl: &str) -> &mut Self {
+        self.dl = dl.to_string();
+        self
+    }
+
+    pub fn api(&mut self, api: Option<&str>) -> &mut Self {
+        self.api = api.map(str::to_string);
+        self
+    }
+
+    pub fn git(&mut self, should: bool) -> &mut Self {
+        self.git = should;
+        self
+    }
+
+    /// Writes `config.json` and a file per crate with versions into `dir`, returning how many
+    /// crates were written.
+    pub fn export(&self, db: &Connection, dir: &Path) -> Result<u64, Error> {
+        fs::create_dir_all(dir).map_err(file_error(dir))?;
+        let config = dir.join("config.json");
+        let mut config_json = json!({ "dl": self.dl });
+        if let Some(api) = &self.api {
+            config_json["api"] = Value::from(api.as_str());
+        }
+        fs::write(&config, serde_json::to_vec_pretty(&config_json)?)
+            .map_err(file_error(&config))?;
+
+        // Both ordered by crate and version, so each version's dependencies are read alongside.
+        let mut versions = db.prepare(
+            r#"
+                SELECT c.name, CAST(v.id AS INTEGER), v.num, v.checksum, v.features, v.yanked,
+                    COALESCE(v.links, '')
+                FROM versions v JOIN crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+                ORDER BY c.name, CAST(v.id AS INTEGER)
+            "#,
+        )?;
+        let mut dependencies = db.prepare(
+            r#"
+                SELECT CAST(d.version_id AS INTEGER), dc.name, COALESCE(d.explicit_name, ''),
+                    d.req, d.features, d.optional, d.default_features, COALESCE(d.target, ''),
+                    d.kind
+                FROM dependencies d
+                JOIN versions v ON CAST(v.id AS INTEGER) = CAST(d.version_id AS INTEGER)
+                JOIN crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+                JOIN crates dc ON CAST(dc.id AS INTEGER) = CAST(d.crate_id AS INTEGER)
+                ORDER BY c.name, CAST(d.version_id AS INTEGER), dc.name
+            "#,
+        )?;
+        let mut dependencies = dependencies.query([])?;
+        let mut next_dependency = dependencies.next()?;
+
+        let mut written = 0;
+        let mut current: Option<(String, BufWriter<File>)> = None;
+        let mut rows = versions.query([])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let version_id: i64 = row.get(1)?;
+
+            let mut deps = Vec::new();
+            while let Some(dependency) = next_dependency {
+                // Dependencies of versions without a row of their own are skipped.
+                let dependency_version: i64 = dependency.get(0)?;
+                if dependency_version > version_id {
+                    break;
+                }
+                if dependency_version == version_id {
+                    let package: String = dependency.get(1)?;
+                    let explicit_name: String = dependency.get(2)?;
+                    let features = parse_pg_array(&dependency.get::<_, String>(4)?)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>();
+                    let kind = dependency
+                        .get::<_, DependencyKind>(8)
+                        .unwrap_or(DependencyKind::Normal);
+                    let mut dep = json!({
+                        "name": package,
+                        "req": dependency.get::<_, String>(3)?,
+                        "features": features,
+                        "optional": dependency.get::<_, String>(5)? == "t",
+                        "default_features": dependency.get::<_, String>(6)? == "t",
+                        "target": non_empty(dependency.get(7)?),
+                        "kind": kind.name(),
+                    });
+                    if !explicit_name.is_empty() {
+                        dep["name"] = Value::from(explicit_name);
+                        dep["package"] = Value::from(package);
+                    }
+                    deps.push(dep);
+                }
+                next_dependency = dependencies.next()?;
+            }
+
+            let features: String = row.get(4)?;
+            let (features, features2) =
+                split_features(serde_json::from_str(&features).unwrap_or_else(|_| json!({})));
+            let mut line = json!({
+                "name": name,
+                "vers": row.get::<_, String>(2)?,
+                "deps": deps,
+                "cksum": row.get::<_, String>(3)?,
+                "features": features,
+                "yanked": row.get::<_, String>(5)? == "t",
+                "links": non_empty(row.get(6)?),
+            });
+            if let Some(features2) = features2 {
+                line["features2"] = features2;
+                line["v"] = Value::from(2);
+            }
+
+            if current.as_ref().map(|(n, _)| n) != Some(&name) {
+                if let Some((_, mut file)) = current.take() {
+                    file.flush()?;
+                }
+                let path = dir.join(index_path(&name));
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(file_error(parent))?;
+                }
+                let file = File::create(&path).map_err(file_error(&path))?;
+                current = Some((name.clone(), BufWriter::new(file)));
+                written += 1;
+            }
+            if let Some((_, file)) = current.as_mut() {
+                serde_json::to_writer(&mut *file, &line)?;
+                file.write_all(b"\n")?;
+            }
+        }
+        if let Some((_, mut file)) = current {
+            file.flush()?;
+        }
+        if self.git {
+            commit(dir)?;
+        }
+        Ok(written)
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<(), Error> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(Error::GitFailed {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }),
+    }
+}
+
+/// Commits everything in `dir`, making it a git repository first if it isn't one.
+fn commit(dir: &Path) -> Result<(), Error> {
+    if !dir.join(".git").exists() {
+        git(dir, &["init", "-q"])?;
+    }
+    git(dir, &["add", "-A"])?;
+    git(
+        dir,
+        &[
+            "-c",
+            "user.name=cratesio-dbdump-csvtab",
+            "-c",
+            "user.email=cratesio-dbdump-csvtab@localhost",
+            "commit",
+            "-q",
+            "--allow-empty",
+            "-m",
+            "Import crates.io db dump",
+        ],
+    )
+}
+
+/// Writes a cargo sparse registry index into `dir` with [`IndexExporter`]'s defaults.
+pub fn export_index(db: &Connection, dir: &Path) -> Result<u64, Error> {
+    IndexExporter::default().export(db, dir)
+}
+
+/// Writes a git `crates.io-index` style repository into `dir` with [`IndexExporter`]'s
+/// defaults, committing on top of whatever the repository already has.
+pub fn export_git_index(db: &Connection, dir: &Path) -> Result<u64, Error> {
+    IndexExporter::default().git(true).export(db, dir)
+}
+
+#[test]
+fn test_export_index() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("serde_json", &["1.0.0"])
+        .add_crate("syn", &["2.0.0"])
+        .dependency("serde_json", "1.0.0", "serde", "^1.0")
+        .build()?;
+    db.execute_batch(
+        r#"
+            UPDATE versions SET yanked = 't' WHERE num = '1.0.0' AND crate_id = '1';
+            UPDATE versions SET features = '{"derive":["dep:serde_derive"],"std":[]}'
+                WHERE num = '1.0.1';
+            UPDATE dependencies SET explicit_name = 'serde1', kind = '1';
+        "#,
+    )?;
+
+    let dir = Path::new("testdata/extracted/index");
+    let _ = fs::remove_dir_all(dir);
+    assert_eq!(3, export_index(&db, dir)?);
+    assert_eq!(Path::new("3/s/syn"), index_path("syn"));
+    assert_eq!(Path::new("se/rd/serde"), index_path("Serde"));
+
+    let lines = |name: &str| -> Result<Vec<Value>, Error> {
+        let text = fs::read_to_string(dir.join(index_path(name)))?;
+        Ok(text
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?)
+    };
+    let serde = lines("serde")?;
+    assert_eq!(2, serde.len());
+    assert_eq!(
+        (json!("1.0.0"), json!(true)),
+        (serde[0]["vers"].clone(), serde[0]["yanked"].clone())
+    );
+    assert_eq!(json!({"std": []}), serde[1]["features"]);
+    assert_eq!(
+        json!({"derive": ["dep:serde_derive"]}),
+        serde[1]["features2"]
+    );
+    assert_eq!(json!(2), serde[1]["v"]);
+
+    let serde_json = lines("serde_json")?;
+    let dep = &serde_json[0]["deps"][0];
+    assert_eq!(
+        (
+            json!("serde1"),
+            json!("serde"),
+            json!("^1.0"),
+            json!("build")
+        ),
+        (
+            dep["name"].clone(),
+            dep["package"].clone(),
+            dep["req"].clone(),
+            dep["kind"].clone()
+        )
+    );
+    let config: Value = serde_json::from_slice(&fs::read(dir.join("config.json"))?)?;
+    assert_eq!(json!("https://static.crates.io/crates"), config["dl"]);
+    assert!(!dir.join(".git").exists());
+
+    let git_dir = Path::new("testdata/extracted/git-index");
+    let _ = fs::remove_dir_all(git_dir);
+    assert_eq!(3, export_git_index(&db, git_dir)?);
+    assert!(git_dir.join(".git").is_dir());
+    assert!(git_dir.join("3/s/syn").is_file());
+    // Exporting again adds a commit rather than failing on an unchanged tree.
+    export_git_index(&db, git_dir)?;
+    Ok(())
+}
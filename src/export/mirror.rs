@@ -0,0 +1,85 @@
+use rusqlite::Connection;
+
+use super::subset::{select_crates, CrateSelection};
+use crate::Error;
+
+/// Where crates.io serves `.crate` files from.
+pub const CRATES_DL: &str = "https://static.crates.io/crates";
+
+/// A `.crate` file to download when seeding a mirror.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorEntry {
+    pub name: String,
+    pub version: String,
+    /// `{CRATES_DL}/{name}/{name}-{version}.crate`
+    pub url: String,
+    /// Expected sha256 of the file, hex encoded.
+    pub checksum: String,
+    /// Expected size of the file in bytes, when the dump has it.
+    pub size: Option<u64>,
+    /// Yanked versions are listed too, since lockfiles may still refer to them.
+    pub yanked: bool,
+}
+
+/// Every version of the crates in `selection` to fetch for a mirror, by crate name and then
+/// publication order.
+pub fn mirror_manifest(
+    db: &Connection,
+    selection: &CrateSelection,
+) -> Result<Vec<MirrorEntry>, Error> {
+    select_crates(db, selection)?;
+    let entries = (|| {
+        let mut stmt = db.prepare(
+            r#"
+                SELECT c.name, v.num, v.checksum, CAST(NULLIF(v.crate_size, '') AS INTEGER), v.yanked
+                FROM main.versions v
+                JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+                WHERE CAST(c.id AS INTEGER) IN temp.subset_crates
+                ORDER BY c.name, CAST(v.id AS INTEGER)
+            "#,
+        )?;
+        let entries = stmt.query_map([], |row| {
+            let name: String = row.get(0)?;
+            let version: String = row.get(1)?;
+            Ok(MirrorEntry {
+                url: format!("{0}/{1}/{1}-{2}.crate", CRATES_DL, name, version),
+                name,
+                version,
+                checksum: row.get(2)?,
+                size: row.get::<_, Option<i64>>(3)?.map(|size| size as u64),
+                yanked: row.get::<_, String>(4)? == "t",
+            })
+        })?;
+        entries.collect::<Result<Vec<_>, _>>()
+    })();
+    db.execute_batch("DROP TABLE IF EXISTS temp.subset_crates;")?;
+    Ok(entries?)
+}
+
+#[test]
+fn test_mirror_manifest() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("tokio", &["1.0.0"])
+        .build()?;
+    db.execute_batch(
+        "UPDATE versions SET crate_size = '1234', checksum = 'abc' WHERE num = '1.0.1';",
+    )?;
+
+    let entries = mirror_manifest(&db, &CrateSelection::Names(vec!["serde".to_string()]))?;
+    assert_eq!(2, entries.len());
+    assert_eq!(
+        "https://static.crates.io/crates/serde/serde-1.0.1.crate",
+        entries[1].url
+    );
+    assert_eq!(
+        ("abc", Some(1234)),
+        (entries[1].checksum.as_str(), entries[1].size)
+    );
+    assert_eq!(
+        3,
+        mirror_manifest(&db, &CrateSelection::TopByDownloads(10))?.len()
+    );
+    assert!(mirror_manifest(&db, &CrateSelection::Names(Vec::new()))?.is_empty());
+    Ok(())
+}
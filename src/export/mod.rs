@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+
+use crate::sql;
+
+#[cfg(feature = "closure")]
+mod closure;
+mod csv;
+mod dot;
+mod fixture;
+mod index;
+mod mirror;
+mod ndjson;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod sample;
+#[cfg(feature = "sbom")]
+mod sbom;
+mod subset;
+#[cfg(feature = "closure")]
+mod vendor;
+
+pub use self::csv::export_csv;
+#[cfg(feature = "parquet")]
+pub use self::parquet::{export_parquet, ParquetExporter};
+#[cfg(feature = "closure")]
+pub use closure::{unresolved_dependencies, DependencyClosure, UnresolvedDependency};
+pub use dot::{export_dot, DotExporter};
+pub use fixture::FixtureBuilder;
+pub use index::{export_git_index, export_index, index_path, IndexExporter};
+pub use mirror::{mirror_manifest, MirrorEntry, CRATES_DL};
+pub use ndjson::{export_ndjson, export_ndjson_where};
+pub use sample::export_sample;
+#[cfg(feature = "sbom")]
+pub use sbom::{export_sbom, SbomExporter, SbomFormat};
+pub use subset::{export_subset, CrateSelection};
+#[cfg(feature = "closure")]
+pub use vendor::{export_vendor_manifest, vendor_manifest};
+
+/// Treats a bare identifier as "the whole table" and anything else as SQL to run.
+pub(crate) fn table_or_query(query_or_table: &str) -> Cow<'_, str> {
+    let query_or_table = query_or_table.trim();
+    match query_or_table
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        true => Cow::Owned(format!("SELECT * FROM {}", sql::identifier(query_or_table))),
+        false => Cow::Borrowed(query_or_table),
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Write;
+
+use rusqlite::{types::ValueRef, Connection};
+use serde_json::{Map, Value};
+
+use crate::{
+    schema::{self, ColumnType},
+    sql, Error,
+};
+
+/// Writes one JSON object per row of `query_or_table` to `writer`, returning the row count.
+///
+/// When given a dump table by name, its text columns are typed from the canonical schema, so
+/// `t`/`f` become booleans, JSON columns are embedded and Postgres arrays become JSON arrays.
+pub fn export_ndjson<W: Write>(
+    db: &Connection,
+    query_or_table: &str,
+    writer: W,
+) -> Result<u64, Error> {
+    let canonical = schema::table(query_or_table.trim());
+    write_rows(
+        db,
+        &super::table_or_query(query_or_table),
+        canonical,
+        writer,
+    )
+}
+
+/// Like [`export_ndjson`] for the rows of `table` matching the SQL `condition`.
+pub fn export_ndjson_where<W: Write>(
+    db: &Connection,
+    table: &str,
+    condition: &str,
+    writer: W,
+) -> Result<u64, Error> {
+    let query = format!(
+        "SELECT * FROM {} WHERE {}",
+        sql::identifier(table),
+        condition
+    );
+    write_rows(db, &query, schema::table(table), writer)
+}
+
+fn write_rows<W: Write>(
+    db: &Connection,
+    sql: &str,
+    canonical: Option<&schema::TableSchema>,
+    mut writer: W,
+) -> Result<u64, Error> {
+    let mut stmt = db.prepare(sql)?;
+    let columns: Vec<(String, Option<ColumnType>)> = stmt
+        .column_names()
+        .into_iter()
+        .map(|name| {
+            let ty = canonical.and_then(|t| t.column(name)).map(|c| c.ty);
+            (name.to_string(), ty)
+        })
+        .collect();
+
+    let mut count = 0;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut object = Map::with_capacity(columns.len());
+        for (i, (name, ty)) in columns.iter().enumerate() {
+            object.insert(name.clone(), to_json(row.get_ref(i)?, *ty));
+        }
+        serde_json::to_writer(&mut writer, &object)?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+fn to_json(value: ValueRef, ty: Option<ColumnType>) -> Value {
+    let text = match value {
+        ValueRef::Null => return Value::Null,
+        ValueRef::Integer(i) => return Value::from(i),
+        ValueRef::Real(f) => return Value::from(f),
+        ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t),
+    };
+
+    match ty {
+        None | Some(ColumnType::Text) => Value::from(text.into_owned()),
+        // The CSVs have no way to tell NULL apart from an empty string.
+        Some(_) if text.is_empty() => Value::Null,
+        Some(ColumnType::Boolean) => match text.as_ref() {
+            "t" | "true" => Value::Bool(true),
+            "f" | "false" => Value::Bool(false),
+            _ => Value::from(text.into_owned()),
+        },
+        Some(ColumnType::Integer) | Some(ColumnType::BigInt) => match text.parse::<i64>() {
+            Ok(i) => Value::from(i),
+            Err(_) => Value::from(text.into_owned()),
+        },
+        Some(ColumnType::Json) => {
+            serde_json::from_str(&text).unwrap_or_else(|_| Value::from(text.into_owned()))
+        }
+        Some(ColumnType::TextArray) => parse_pg_array(&text)
+            .map(|items| items.into_iter().map(Value::from).collect())
+            .unwrap_or_else(|| Value::from(text.into_owned())),
+        Some(ColumnType::Timestamp) | Some(ColumnType::Date) => Value::from(text.into_owned()),
+    }
+}
+
+/// Parses a one-dimensional Postgres array literal such as `{a,"b c",NULL}`.
+pub(crate) fn parse_pg_array(text: &str) -> Option<Vec<Option<String>>> {
+    let inner = text.strip_prefix('{')?.strip_suffix('}')?;
+    let mut items = Vec::new();
+    if inner.is_empty() {
+        return Some(items);
+    }
+
+    let mut chars = inner.chars();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut was_quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                was_quoted = true;
+            }
+            '\\' if quoted => current.extend(chars.next()),
+            ',' if !quoted => {
+                items.push(finish_item(&mut current, was_quoted));
+                was_quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    items.push(finish_item(&mut current, was_quoted));
+    Some(items)
+}
+
+fn finish_item(current: &mut String, was_quoted: bool) -> Option<String> {
+    let item = std::mem::take(current);
+    match !was_quoted && item == "NULL" {
+        true => None,
+        false => Some(item),
+    }
+}
+
+#[test]
+fn test_export_ndjson() -> Result<(), Error> {
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(
+        r#"
+            CREATE TABLE dependencies(id INT, optional TEXT, features TEXT, kind TEXT);
+            INSERT INTO dependencies VALUES (1, 't', '{std,"a b",NULL}', '');
+        "#,
+    )?;
+
+    let mut out = Vec::new();
+    assert_eq!(1, export_ndjson(&db, "dependencies", &mut out)?);
+    let row: Value = serde_json::from_slice(&out)?;
+    assert_eq!(Value::Bool(true), row["optional"]);
+    assert_eq!(serde_json::json!(["std", "a b", null]), row["features"]);
+    assert_eq!(Value::Null, row["kind"]);
+
+    out.clear();
+    export_ndjson(&db, "SELECT id + 1 AS next FROM dependencies", &mut out)?;
+    assert_eq!("{\"next\":2}\n", String::from_utf8_lossy(&out));
+    Ok(())
+}
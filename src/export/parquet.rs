@@ -0,0 +1,127 @@
+use std::{
+    fs::{create_dir_all, File},
+    path::{Path, PathBuf},
+};
+
+use arrow::record_batch::RecordBatchReader;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::properties::{WriterProperties, DEFAULT_MAX_ROW_GROUP_ROW_COUNT},
+};
+use rusqlite::Connection;
+
+use crate::{
+    record_batch::{RecordBatches, DEFAULT_BATCH_SIZE},
+    Error,
+};
+
+pub struct ParquetExporter {
+    pub compression: Compression,
+    pub row_group_size: usize,
+    pub batch_size: usize,
+}
+
+impl Default for ParquetExporter {
+    fn default() -> Self {
+        Self {
+            compression: Compression::SNAPPY,
+            row_group_size: DEFAULT_MAX_ROW_GROUP_ROW_COUNT,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+impl ParquetExporter {
+    pub fn compression(&mut self, compression: Compression) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn row_group_size(&mut self, rows: usize) -> &mut Self {
+        self.row_group_size = rows;
+        self
+    }
+
+    pub fn batch_size(&mut self, rows: usize) -> &mut Self {
+        self.batch_size = rows;
+        self
+    }
+
+    /// Writes the whole of `table` into a single parquet file at `path`.
+    pub fn export_table(&self, db: &Connection, table: &str, path: &Path) -> Result<(), Error> {
+        self.export_query(db, &super::table_or_query(table), path)
+    }
+
+    /// Writes every table in `tables` into `dir`, one `<table>.parquet` per table.
+    pub fn export_tables(
+        &self,
+        db: &Connection,
+        tables: &[&str],
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>, Error> {
+        create_dir_all(dir)?;
+        tables
+            .iter()
+            .map(|table| {
+                let path = dir.join(format!("{}.parquet", table));
+                self.export_table(db, table, &path)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    pub fn export_query(&self, db: &Connection, sql: &str, path: &Path) -> Result<(), Error> {
+        let mut stmt = db.prepare(sql)?;
+        let batches = RecordBatches::new(&mut stmt, self.batch_size)?;
+
+        let props = WriterProperties::builder()
+            .set_compression(self.compression)
+            .set_max_row_group_row_count(Some(self.row_group_size))
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batches.schema(), Some(props))?;
+        for batch in batches {
+            writer.write(&batch?)?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Exports `table` to `path` using the default [`ParquetExporter`] settings.
+pub fn export_parquet(db: &Connection, table: &str, path: &Path) -> Result<(), Error> {
+    ParquetExporter::default().export_table(db, table, path)
+}
+
+#[test]
+fn test_export_parquet() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use cached_path::Cache;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let cache = Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/parquet");
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .preload(true)
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .table_schema("test", "CREATE TABLE x(renamed_id INT, name TEXT);")
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let path = target.join("test.parquet");
+    ParquetExporter::default()
+        .compression(Compression::UNCOMPRESSED)
+        .row_group_size(2)
+        .export_table(&db, "test", &path)?;
+
+    let reader = SerializedFileReader::new(File::open(&path)?)?;
+    assert_eq!(3, reader.metadata().file_metadata().num_rows());
+    assert_eq!(2, reader.metadata().num_row_groups());
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Tiny dump archives for tests, in the same layout as the real one.
+
+use std::{fs::File, path::Path};
+
+use flate2::{write::GzEncoder, Compression};
+use rusqlite::Connection;
+
+use super::{csv::export_csv, subset::write_subset, CrateSelection};
+use crate::{DumpMetadata, Error, METADATA_FILE};
+
+/// Writes a `.tar.gz` at `path` holding the selected crates, usable as a `resource()`.
+///
+/// The crates the selection depends on are kept too, without their versions, so every
+/// dependency resolves. `metadata` names the snapshot; without it the archive has no
+/// `metadata.json`.
+pub fn export_sample(
+    db: &Connection,
+    selection: &CrateSelection,
+    metadata: Option<&DumpMetadata>,
+    path: &Path,
+) -> Result<(), Error> {
+    let scratch = path.with_extension("sqlite.tmp");
+    write_subset(db, selection, &scratch, true)?;
+    let written = write_archive(&scratch, metadata, path);
+    std::fs::remove_file(&scratch)?;
+    written
+}
+
+fn write_archive(subset: &Path, metadata: Option<&DumpMetadata>, path: &Path) -> Result<(), Error> {
+    let subset = Connection::open(subset)?;
+    let mut stmt =
+        subset.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut csvs = Vec::new();
+    for table in tables {
+        let mut csv = Vec::new();
+        export_csv(&subset, &table, &mut csv)?;
+        csvs.push((table, csv));
+    }
+    write_dump_archive(path, metadata, csvs)
+}
+
+/// Writes a `.tar.gz` at `path` laid out like the real dump, with `tables` as
+/// `(name, csv)` pairs.
+pub(crate) fn write_dump_archive(
+    path: &Path,
+    metadata: Option<&DumpMetadata>,
+    tables: Vec<(String, Vec<u8>)>,
+) -> Result<(), Error> {
+    // The real dumps are rooted at e.g. `2021-05-15-020000/`.
+    let root = match metadata {
+        Some(m) => format!(
+            "{}-{}",
+            m.date(),
+            m.timestamp
+                .get(11..19)
+                .unwrap_or("00:00:00")
+                .replace(':', "")
+        ),
+        None => "sample".to_string(),
+    };
+
+    let mut archive = tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::best()));
+    let mut append = |name: String, data: Vec<u8>| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, name, data.as_slice())
+    };
+
+    if let Some(metadata) = metadata {
+        let mut json = serde_json::Map::new();
+        json.insert("timestamp".into(), metadata.timestamp.clone().into());
+        if let Some(commit) = &metadata.crates_io_commit {
+            json.insert("crates_io_commit".into(), commit.clone().into());
+        }
+        let json = serde_json::to_vec_pretty(&json)?;
+        append(format!("{}/{}", root, METADATA_FILE), json)?;
+    }
+    for (table, csv) in tables {
+        append(format!("{}/data/{}.csv", root, table), csv)?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[test]
+fn test_export_sample() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/sample");
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .preload(true)
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let path = target.join("sample.tar.gz");
+    let selection = CrateSelection::Names(vec!["tokio".to_string()]);
+    export_sample(&db, &selection, loader.metadata()?.as_ref(), &path)?;
+
+    let sample = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&sample)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource(&path.to_string_lossy())
+        .target_path(&target.join("loaded"))
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&sample)?;
+
+    let count = |sql: &str| sample.query_row(sql, [], |row| row.get::<_, i64>(0));
+    assert_eq!(2, count("SELECT COUNT(*) FROM versions")?);
+    assert_eq!(5, count("SELECT COUNT(*) FROM dependencies")?);
+    // tokio plus the bytes, mio and serde it depends on.
+    assert_eq!(4, count("SELECT COUNT(*) FROM crates")?);
+    assert_eq!(
+        0,
+        count("SELECT COUNT(*) FROM dependencies WHERE crate_id NOT IN (SELECT id FROM crates)")?
+    );
+    assert_eq!("2021-05-15", loader.metadata()?.unwrap().date());
+    Ok(())
+}
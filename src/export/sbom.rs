@@ -0,0 +1,302 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use super::{
+    closure::{resolve_closure, DependencyClosure, Package},
+    mirror::CRATES_DL,
+};
+use crate::{file_error, time::format_date, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON.
+    CycloneDx,
+    /// SPDX 2.3 JSON.
+    Spdx,
+}
+
+/// Writes SBOMs of crates or lockfiles, with versions, licenses and checksums from the dump.
+pub struct SbomExporter {
+    pub format: SbomFormat,
+    /// When the document claims to have been created, e.g. `2024-01-01T00:00:00Z`. Defaults to
+    /// the time of export.
+    pub created: Option<String>,
+}
+
+impl Default for SbomExporter {
+    fn default() -> Self {
+        Self {
+            format: SbomFormat::CycloneDx,
+            created: None,
+        }
+    }
+}
+
+fn purl(p: &Package) -> String {
+    format!("pkg:cargo/{}@{}", p.name, p.version)
+}
+
+fn spdx_id(p: &Package) -> String {
+    let id: String = format!("{}-{}", p.name, p.version)
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                true => c,
+                false => '-',
+            },
+        )
+        .collect();
+    format!("SPDXRef-Package-{}", id)
+}
+
+fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        format_date(days as i32),
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+impl SbomExporter {
+    pub fn format(&mut self, format: SbomFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    pub fn created(&mut self, created: &str) -> &mut Self {
+        self.created = Some(created.to_string());
+        self
+    }
+
+    /// The SBOM of `source` as JSON. Dependencies the dump can't satisfy are left out, see
+    /// [`crate::unresolved_dependencies`].
+    pub fn document(&self, db: &Connection, source: &DependencyClosure) -> Result<Value, Error> {
+        let resolved = resolve_closure(db, source)?;
+        let (root, packages) = (resolved.root, resolved.packages);
+        let created = self.created.clone().unwrap_or_else(now);
+        let tool = format!("cratesio-dbdump-csvtab-{}", env!("CARGO_PKG_VERSION"));
+        Ok(match self.format {
+            SbomFormat::CycloneDx => {
+                let component = |p: &Package| {
+                    let mut component = json!({
+                        "type": "library",
+                        "bom-ref": purl(p),
+                        "name": p.name,
+                        "version": p.version,
+                        "purl": purl(p),
+                    });
+                    if !p.license.is_empty() {
+                        component["licenses"] = json!([{ "expression": p.license }]);
+                    }
+                    if !p.checksum.is_empty() {
+                        component["hashes"] = json!([{ "alg": "SHA-256", "content": p.checksum }]);
+                    }
+                    component
+                };
+                let mut metadata = json!({
+                    "timestamp": created,
+                    "tools": [{ "name": "cratesio-dbdump-csvtab", "version": env!("CARGO_PKG_VERSION") }],
+                });
+                if let Some(root) = root {
+                    metadata["component"] = component(&packages[root]);
+                }
+                let components: Vec<Value> = packages
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| Some(*i) != root)
+                    .map(|(_, p)| component(p))
+                    .collect();
+                let dependencies: Vec<Value> = packages
+                    .iter()
+                    .filter(|_| root.is_some())
+                    .map(|p| {
+                        let depends_on: Vec<String> =
+                            p.dependencies.iter().map(|j| purl(&packages[*j])).collect();
+                        json!({ "ref": purl(p), "dependsOn": depends_on })
+                    })
+                    .collect();
+                let mut bom = json!({
+                    "bomFormat": "CycloneDX",
+                    "specVersion": "1.5",
+                    "version": 1,
+                    "metadata": metadata,
+                    "components": components,
+                });
+                if root.is_some() {
+                    bom["dependencies"] = Value::from(dependencies);
+                }
+                bom
+            }
+            SbomFormat::Spdx => {
+                let name = match root {
+                    Some(root) => format!("{}-{}", packages[root].name, packages[root].version),
+                    None => "Cargo.lock".to_string(),
+                };
+                let spdx_packages: Vec<Value> = packages
+                    .iter()
+                    .map(|p| {
+                        let license = match p.license.is_empty() {
+                            true => "NOASSERTION",
+                            false => p.license.as_str(),
+                        };
+                        let mut package = json!({
+                            "SPDXID": spdx_id(p),
+                            "name": p.name,
+                            "versionInfo": p.version,
+                            "downloadLocation": format!("{0}/{1}/{1}-{2}.crate", CRATES_DL, p.name, p.version),
+                            "filesAnalyzed": false,
+                            "licenseConcluded": "NOASSERTION",
+                            "licenseDeclared": license,
+                            "externalRefs": [{
+                                "referenceCategory": "PACKAGE-MANAGER",
+                                "referenceType": "purl",
+                                "referenceLocator": purl(p),
+                            }],
+                        });
+                        if !p.checksum.is_empty() {
+                            package["checksums"] =
+                                json!([{ "algorithm": "SHA256", "checksumValue": p.checksum }]);
+                        }
+                        package
+                    })
+                    .collect();
+                let mut relationships = Vec::new();
+                for (i, p) in packages.iter().enumerate() {
+                    if root.is_none() || root == Some(i) {
+                        relationships.push(json!({
+                            "spdxElementId": "SPDXRef-DOCUMENT",
+                            "relationshipType": "DESCRIBES",
+                            "relatedSpdxElement": spdx_id(p),
+                        }));
+                    }
+                    for j in &p.dependencies {
+                        relationships.push(json!({
+                            "spdxElementId": spdx_id(p),
+                            "relationshipType": "DEPENDS_ON",
+                            "relatedSpdxElement": spdx_id(&packages[*j]),
+                        }));
+                    }
+                }
+                json!({
+                    "spdxVersion": "SPDX-2.3",
+                    "dataLicense": "CC0-1.0",
+                    "SPDXID": "SPDXRef-DOCUMENT",
+                    "name": name,
+                    "documentNamespace": format!("https://crates.io/spdx/{}-{}", name, created),
+                    "creationInfo": { "created": created, "creators": [format!("Tool: {}", tool)] },
+                    "packages": spdx_packages,
+                    "relationships": relationships,
+                })
+            }
+        })
+    }
+
+    /// Writes the SBOM of `source` to `path`.
+    pub fn export(
+        &self,
+        db: &Connection,
+        source: &DependencyClosure,
+        path: &Path,
+    ) -> Result<(), Error> {
+        let document = self.document(db, source)?;
+        fs::write(path, serde_json::to_vec_pretty(&document)?).map_err(file_error(path))
+    }
+}
+
+/// Writes the SBOM of `source` in `format` to `path`.
+pub fn export_sbom(
+    db: &Connection,
+    source: &DependencyClosure,
+    format: SbomFormat,
+    path: &Path,
+) -> Result<(), Error> {
+    SbomExporter::default()
+        .format(format)
+        .export(db, source, path)
+}
+
+#[test]
+fn test_sbom() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("app", &["0.1.0"])
+        .add_crate("serde", &["1.0.0", "1.0.1", "2.0.0"])
+        .add_crate("serde_derive", &["1.0.0"])
+        .add_crate("unused", &["1.0.0"])
+        .dependency("app", "0.1.0", "serde", "^1.0")
+        .dependency("app", "0.1.0", "unused", "^1.0")
+        .dependency("serde", "1.0.1", "serde_derive", "=1.0.0")
+        .build()?;
+    db.execute_batch(
+        r#"
+            UPDATE dependencies SET optional = 't' WHERE CAST(crate_id AS INTEGER) = 4;
+            UPDATE versions SET license = 'MIT OR Apache-2.0', checksum = 'abc' WHERE num = '1.0.1';
+        "#,
+    )?;
+    let source = DependencyClosure::krate("app", "0.1.0");
+
+    let mut exporter = SbomExporter::default();
+    exporter.created("2024-01-01T00:00:00Z");
+    let bom = exporter.document(&db, &source)?;
+    assert_eq!(
+        json!("pkg:cargo/app@0.1.0"),
+        bom["metadata"]["component"]["purl"]
+    );
+    let components: Vec<&str> = bom["components"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c["purl"].as_str())
+        .collect();
+    assert_eq!(
+        vec!["pkg:cargo/serde@1.0.1", "pkg:cargo/serde_derive@1.0.0"],
+        components
+    );
+    assert_eq!(
+        json!("MIT OR Apache-2.0"),
+        bom["components"][0]["licenses"][0]["expression"]
+    );
+    assert_eq!(json!("abc"), bom["components"][0]["hashes"][0]["content"]);
+    assert_eq!(
+        json!(["pkg:cargo/serde@1.0.1"]),
+        bom["dependencies"][0]["dependsOn"]
+    );
+
+    let spdx = exporter.format(SbomFormat::Spdx).document(&db, &source)?;
+    assert_eq!(3, spdx["packages"].as_array().map_or(0, Vec::len));
+    assert_eq!(3, spdx["relationships"].as_array().map_or(0, Vec::len));
+    assert_eq!(json!("NOASSERTION"), spdx["packages"][0]["licenseDeclared"]);
+
+    let lockfile = DependencyClosure::Lockfile(
+        r#"
+            [[package]]
+            name = "serde"
+            version = "2.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+        "#
+        .to_string(),
+    );
+    let bom = SbomExporter::default().document(&db, &lockfile)?;
+    assert_eq!(1, bom["components"].as_array().map_or(0, Vec::len));
+    assert!(bom.get("dependencies").is_none());
+    assert!(matches!(
+        SbomExporter::default().document(&db, &DependencyClosure::krate("app", "9.9.9")),
+        Err(Error::UnknownVersion { .. })
+    ));
+    Ok(())
+}
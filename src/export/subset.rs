@@ -0,0 +1,180 @@
+//! Standalone databases restricted to a handful of crates.
+
+use std::{fs::remove_file, path::Path};
+
+use rusqlite::Connection;
+
+use crate::{sql, stats::crate_downloads, table_exists, Error};
+
+pub enum CrateSelection {
+    /// Crates with exactly these names.
+    Names(Vec<String>),
+    /// The `n` most downloaded crates.
+    TopByDownloads(usize),
+}
+
+// Every known table, with the filter selecting its rows that belong to the subset and the
+// helper tables that filter needs.
+const TABLE_FILTERS: &[(&str, &str, &[&str])] = &[
+    ("crates", "CAST(id AS INTEGER) IN subset_crate_rows", &["crates"]),
+    ("versions", "CAST(id AS INTEGER) IN subset_versions", &["versions"]),
+    ("dependencies", "CAST(version_id AS INTEGER) IN subset_versions", &["versions"]),
+    ("version_downloads", "CAST(version_id AS INTEGER) IN subset_versions", &["versions"]),
+    ("version_authors", "CAST(version_id AS INTEGER) IN subset_versions", &["versions"]),
+    ("badges", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    ("crate_downloads", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    ("crate_owners", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    ("crates_categories", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    ("crates_keywords", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    ("default_versions", "CAST(crate_id AS INTEGER) IN subset_crates", &["crates"]),
+    (
+        "categories",
+        "CAST(id AS INTEGER) IN (SELECT CAST(category_id AS INTEGER) FROM main.crates_categories WHERE CAST(crate_id AS INTEGER) IN subset_crates)",
+        &["crates", "crates_categories"],
+    ),
+    (
+        "keywords",
+        "CAST(id AS INTEGER) IN (SELECT CAST(keyword_id AS INTEGER) FROM main.crates_keywords WHERE CAST(crate_id AS INTEGER) IN subset_crates)",
+        &["crates", "crates_keywords"],
+    ),
+    (
+        "users",
+        "CAST(id AS INTEGER) IN (SELECT CAST(owner_id AS INTEGER) FROM main.crate_owners WHERE CAST(owner_kind AS INTEGER) = 0 AND CAST(crate_id AS INTEGER) IN subset_crates)",
+        &["crates", "crate_owners"],
+    ),
+    (
+        "teams",
+        "CAST(id AS INTEGER) IN (SELECT CAST(owner_id AS INTEGER) FROM main.crate_owners WHERE CAST(owner_kind AS INTEGER) = 1 AND CAST(crate_id AS INTEGER) IN subset_crates)",
+        &["crates", "crate_owners"],
+    ),
+    ("metadata", "1", &[]),
+    ("reserved_crate_names", "1", &[]),
+];
+
+/// Writes a standalone database at `path` holding only the selected crates and every row of the
+/// other loaded tables that refers to them.
+pub fn export_subset(
+    db: &Connection,
+    selection: &CrateSelection,
+    path: &Path,
+) -> Result<(), Error> {
+    write_subset(db, selection, path, false)
+}
+
+/// (Re)creates `temp.subset_crates(id)` with the ids of the crates in `selection`.
+pub(super) fn select_crates(db: &Connection, selection: &CrateSelection) -> Result<(), Error> {
+    db.execute_batch("DROP TABLE IF EXISTS temp.subset_crates;")?;
+    match selection {
+        CrateSelection::Names(names) => {
+            db.execute_batch("CREATE TEMP TABLE subset_crates(id INTEGER PRIMARY KEY);")?;
+            let mut insert = db.prepare(
+                "INSERT OR IGNORE INTO subset_crates SELECT CAST(id AS INTEGER) FROM main.crates WHERE name = ?",
+            )?;
+            for name in names {
+                insert.execute([name])?;
+            }
+        }
+        CrateSelection::TopByDownloads(n) => {
+            db.execute(
+                &format!(
+                    r#"
+                        CREATE TEMP TABLE subset_crates AS SELECT CAST(c.id AS INTEGER) AS id FROM main.crates c
+                        LEFT JOIN {} d ON d.crate_id = CAST(c.id AS INTEGER)
+                        ORDER BY COALESCE(d.downloads, 0) DESC LIMIT ?
+                    "#,
+                    crate_downloads(db, "main")?
+                ),
+                [*n as i64],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Like [`export_subset`], optionally also keeping the `crates` rows of everything the selected
+/// versions depend on, so that every `dependencies.crate_id` resolves.
+pub(crate) fn write_subset(
+    db: &Connection,
+    selection: &CrateSelection,
+    path: &Path,
+    dependency_crates: bool,
+) -> Result<(), Error> {
+    if path.exists() {
+        remove_file(path)?;
+    }
+
+    db.execute_batch(
+        "DROP TABLE IF EXISTS temp.subset_versions; DROP TABLE IF EXISTS temp.subset_crate_rows;",
+    )?;
+    select_crates(db, selection)?;
+    let has_versions = table_exists(db, "versions")?;
+    if has_versions {
+        db.execute_batch(
+            "CREATE TEMP TABLE subset_versions AS SELECT CAST(id AS INTEGER) AS id FROM main.versions WHERE CAST(crate_id AS INTEGER) IN subset_crates;",
+        )?;
+    }
+    db.execute_batch("CREATE TEMP TABLE subset_crate_rows AS SELECT id FROM subset_crates;")?;
+    if dependency_crates && has_versions && table_exists(db, "dependencies")? {
+        db.execute_batch(
+            "INSERT INTO subset_crate_rows SELECT DISTINCT CAST(crate_id AS INTEGER) FROM main.dependencies WHERE CAST(version_id AS INTEGER) IN subset_versions AND CAST(crate_id AS INTEGER) NOT IN subset_crates;",
+        )?;
+    }
+
+    db.execute("ATTACH DATABASE ? AS subset", [path.to_string_lossy()])?;
+    let copied = (|| {
+        for (table, filter, requires) in TABLE_FILTERS {
+            let mut available = table_exists(db, table)?;
+            for required in requires.iter() {
+                available &= table_exists(db, required)?;
+            }
+            if !available {
+                continue;
+            }
+            db.execute_batch(&format!(
+                "CREATE TABLE subset.{0} AS SELECT * FROM main.{0} WHERE {1};",
+                sql::identifier(table),
+                filter
+            ))?;
+        }
+        Ok(())
+    })();
+    db.execute_batch(
+        "DETACH DATABASE subset; DROP TABLE IF EXISTS temp.subset_crates; DROP TABLE IF EXISTS temp.subset_versions; DROP TABLE IF EXISTS temp.subset_crate_rows;",
+    )?;
+    copied
+}
+
+#[test]
+fn test_export_subset() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/subset");
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .preload(true)
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let path = target.join("subset.sqlite");
+    export_subset(
+        &db,
+        &CrateSelection::Names(vec!["tokio".to_string()]),
+        &path,
+    )?;
+
+    let subset = Connection::open(&path)?;
+    let count = |sql: &str| subset.query_row(sql, [], |row| row.get::<_, i64>(0));
+    assert_eq!(1, count("SELECT COUNT(*) FROM crates")?);
+    assert_eq!(2, count("SELECT COUNT(*) FROM versions")?);
+    assert_eq!(5, count("SELECT COUNT(*) FROM dependencies")?);
+    assert_eq!(1, count("SELECT COUNT(*) FROM teams")?);
+    assert_eq!(2, count("SELECT COUNT(*) FROM keywords")?);
+    assert_eq!(3, count("SELECT COUNT(*) FROM reserved_crate_names")?);
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use std::{fs, path::Path};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::json;
+
+use super::{
+    closure::{resolve_closure, DependencyClosure, Package},
+    mirror::{MirrorEntry, CRATES_DL},
+};
+use crate::{file_error, Error};
+
+/// Every package of `closure` to fetch for an offline build, by name and version. Packages
+/// the dump doesn't have are listed with an empty checksum, so pipelines can refuse them.
+pub fn vendor_manifest(
+    db: &Connection,
+    closure: &DependencyClosure,
+) -> Result<Vec<MirrorEntry>, Error> {
+    entries(db, resolve_closure(db, closure)?.packages)
+}
+
+fn entries(db: &Connection, packages: Vec<Package>) -> Result<Vec<MirrorEntry>, Error> {
+    let mut stmt = db.prepare(
+        r#"
+            SELECT CAST(NULLIF(v.crate_size, '') AS INTEGER), v.yanked = 't'
+            FROM main.versions v JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ? AND v.num = ?
+        "#,
+    )?;
+    let mut entries = Vec::new();
+    for package in packages {
+        let (size, yanked) = stmt
+            .query_row([&package.name, &package.version], |row| {
+                Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, bool>(1)?))
+            })
+            .optional()?
+            .unwrap_or((None, false));
+        entries.push(MirrorEntry {
+            url: format!(
+                "{0}/{1}/{1}-{2}.crate",
+                CRATES_DL, package.name, package.version
+            ),
+            name: package.name,
+            version: package.version,
+            checksum: package.checksum,
+            size: size.map(|size| size as u64),
+            yanked,
+        });
+    }
+    entries.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(entries)
+}
+
+/// Writes [`vendor_manifest`] to `path` as JSON: `{"version": 1, "packages": [...]}` with the
+/// `name`, `version`, `checksum` (sha256), `url`, `size` and `yanked` of each package, and
+/// `"unresolved": [...]` with the `name`, `req` and `dependent` (`name version`) of the
+/// [`crate::unresolved_dependencies`], if any.
+pub fn export_vendor_manifest(
+    db: &Connection,
+    closure: &DependencyClosure,
+    path: &Path,
+) -> Result<usize, Error> {
+    let resolved = resolve_closure(db, closure)?;
+    let entries = entries(db, resolved.packages)?;
+    let packages: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            json!({
+                "name": e.name,
+                "version": e.version,
+                "checksum": e.checksum,
+                "url": e.url,
+                "size": e.size,
+                "yanked": e.yanked,
+            })
+        })
+        .collect();
+    let mut manifest = json!({ "version": 1, "packages": packages });
+    if !resolved.unresolved.is_empty() {
+        let unresolved: Vec<_> = resolved
+            .unresolved
+            .iter()
+            .map(|u| {
+                json!({
+                    "name": u.name,
+                    "req": u.req,
+                    "dependent": format!("{} {}", u.dependent.0, u.dependent.1),
+                })
+            })
+            .collect();
+        manifest["unresolved"] = json!(unresolved);
+    }
+    fs::write(path, serde_json::to_vec_pretty(&manifest)?).map_err(file_error(path))?;
+    Ok(entries.len())
+}
+
+#[test]
+fn test_vendor_manifest() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("app", &["0.1.0"])
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .dependency("app", "0.1.0", "serde", "^1.0")
+        .build()?;
+    db.execute_batch(
+        "UPDATE versions SET crate_size = '1234', checksum = 'abc' WHERE num = '1.0.1';",
+    )?;
+
+    let entries = vendor_manifest(&db, &DependencyClosure::krate("app", "0.1.0"))?;
+    assert_eq!(
+        vec![("app", "0.1.0"), ("serde", "1.0.1")],
+        entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.version.as_str()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        (
+            "abc",
+            Some(1234),
+            "https://static.crates.io/crates/serde/serde-1.0.1.crate"
+        ),
+        (
+            entries[1].checksum.as_str(),
+            entries[1].size,
+            entries[1].url.as_str()
+        )
+    );
+
+    let path = Path::new("testdata/extracted/vendor.json");
+    let lockfile = DependencyClosure::Lockfile(
+        r#"
+            [[package]]
+            name = "serde"
+            version = "9.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+        "#
+        .to_string(),
+    );
+    fs::create_dir_all("testdata/extracted")?;
+    assert_eq!(1, export_vendor_manifest(&db, &lockfile, path)?);
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(path)?)?;
+    assert_eq!(json!(""), manifest["packages"][0]["checksum"]);
+    assert!(manifest.get("unresolved").is_none());
+
+    db.execute_batch("UPDATE dependencies SET req = '^2'")?;
+    let app = DependencyClosure::krate("app", "0.1.0");
+    assert_eq!(1, export_vendor_manifest(&db, &app, path)?);
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(path)?)?;
+    assert_eq!(
+        json!([{ "name": "serde", "req": "^2", "dependent": "app 0.1.0" }]),
+        manifest["unresolved"]
+    );
+    Ok(())
+}
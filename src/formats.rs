@@ -0,0 +1,209 @@
+//! Layouts the dump had over time, relative to [`schema::TABLES`], so dumps of any age load
+//! into the same canonical columns. The layout of an extracted dump is picked by the date in
+//! its `metadata.json`, or pinned with `schema_version()`, see
+//! [`CratesIODumpLoader::dump_format`].
+//!
+//! Formats are versioned: when [`schema::TABLES`] changes, the change gets a new format and
+//! the older ones list the difference, so a loader pinned to one keeps loading the same
+//! tables and types.
+
+use crate::{schema, schema::Column, sql, CratesIODumpLoader, Error};
+
+/// A layout of the dump, from the first dump that had it until the next one's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DumpFormat {
+    pub name: &'static str,
+    /// Date (`YYYY-MM-DD`) of the first dump with this layout, empty for the oldest one.
+    pub since: &'static str,
+    /// Columns the dump names differently than [`schema::TABLES`], as
+    /// `(table, column in the dump, canonical column)`. They load under the canonical name.
+    pub renamed: &'static [(&'static str, &'static str, &'static str)],
+    /// Canonical tables the dump doesn't have.
+    pub missing_tables: &'static [&'static str],
+    /// Canonical columns the dump doesn't have, as `(table, column)`. Their absence isn't
+    /// reported as [`crate::SchemaDrift`].
+    pub missing_columns: &'static [(&'static str, &'static str)],
+}
+
+/// Every known layout, oldest first.
+pub const FORMATS: &[DumpFormat] = &[
+    DumpFormat {
+        name: "2021",
+        since: "",
+        renamed: &[],
+        missing_tables: &["crate_downloads"],
+        missing_columns: &[],
+    },
+    // Download counters moved from `crates` into `crate_downloads`.
+    DumpFormat {
+        name: "2024",
+        since: "2024-03-01",
+        renamed: &[],
+        missing_tables: &[],
+        missing_columns: &[("crates", "downloads")],
+    },
+];
+
+/// The layout of dumps taken on `date` (`YYYY-MM-DD`).
+pub fn for_date(date: &str) -> &'static DumpFormat {
+    FORMATS
+        .iter()
+        .rev()
+        .find(|format| format.since <= date)
+        .unwrap_or(&FORMATS[0])
+}
+
+/// The format called `name`.
+pub fn by_name(name: &str) -> Option<&'static DumpFormat> {
+    FORMATS.iter().find(|format| format.name == name)
+}
+
+/// The layout of current dumps.
+pub fn latest() -> &'static DumpFormat {
+    &FORMATS[FORMATS.len() - 1]
+}
+
+impl DumpFormat {
+    /// The canonical name of `column` of `table` in this layout.
+    pub fn canonical_column<'a>(&self, table: &str, column: &'a str) -> &'a str {
+        self.renamed
+            .iter()
+            .find(|(t, dumped, _)| *t == table && *dumped == column)
+            .map_or(column, |(_, _, canonical)| canonical)
+    }
+
+    /// Whether any column of `table` is renamed in this layout.
+    pub fn renames(&self, table: &str) -> bool {
+        self.renamed.iter().any(|(t, _, _)| *t == table)
+    }
+
+    /// Whether this layout lacks the canonical `table`.
+    pub fn lacks_table(&self, table: &str) -> bool {
+        self.missing_tables.contains(&table)
+    }
+
+    /// Whether this layout lacks the canonical `column` of `table`.
+    pub fn lacks(&self, table: &str, column: &str) -> bool {
+        self.missing_columns.contains(&(table, column))
+    }
+
+    /// The canonical columns of `table` in this layout, `None` for tables it doesn't have.
+    pub fn columns(&self, table: &str) -> Option<Vec<Column>> {
+        if self.lacks_table(table) {
+            return None;
+        }
+        let canonical = schema::table(table)?;
+        Some(
+            canonical
+                .columns
+                .iter()
+                .filter(|c| !self.lacks(table, c.name))
+                .copied()
+                .collect(),
+        )
+    }
+
+    /// The schema to load `table`'s csv with, given its `header`: every column under its
+    /// canonical name, typed from [`DumpFormat::columns`] when `typed`, otherwise TEXT.
+    pub(crate) fn csv_schema(
+        &self,
+        table: &str,
+        header: &csv::StringRecord,
+        typed: bool,
+    ) -> String {
+        let canonical = self.columns(table).filter(|_| typed).unwrap_or_default();
+        let columns: Vec<String> = header
+            .iter()
+            .map(|name| {
+                let name = self.canonical_column(table, name);
+                let ty = canonical
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map_or("TEXT", |c| c.ty.sqlite_type());
+                format!("{} {}", sql::identifier(name), ty)
+            })
+            .collect();
+        format!("CREATE TABLE x({});", columns.join(", "))
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Layout of the extracted dump: the one pinned with `schema_version()`, or else by the
+    /// date in its `metadata.json`, or the latest one for archives without it.
+    pub fn dump_format(&self) -> Result<&'static DumpFormat, Error> {
+        if let Some(format) = self.schema_version.as_deref().and_then(by_name) {
+            return Ok(format);
+        }
+        Ok(match self.metadata()? {
+            Some(metadata) => for_date(metadata.date()),
+            None => latest(),
+        })
+    }
+}
+
+#[test]
+fn test_formats() -> Result<(), Error> {
+    use std::path::Path;
+
+    assert_eq!("2021", for_date("2021-05-15").name);
+    assert_eq!("2024", for_date("2024-03-01").name);
+    assert_eq!(latest(), for_date("2099-01-01"));
+    assert!(FORMATS.windows(2).all(|w| w[0].since < w[1].since));
+
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/formats"))
+        .minimal()
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+    loader.update()?;
+    assert_eq!("2021", loader.dump_format()?.name);
+    let pinned = loader.into_builder().schema_version("2024").build()?;
+    assert_eq!("2024", pinned.dump_format()?.name);
+    assert!(pinned
+        .dump_format()?
+        .columns("crates")
+        .unwrap()
+        .iter()
+        .all(|c| c.name != "downloads"));
+    assert_eq!(None, for_date("2021-05-15").columns("crate_downloads"));
+    assert!(matches!(
+        CratesIODumpLoader::builder().schema_version("1999").build(),
+        Err(Error::InvalidOption {
+            option: "schema_version",
+            ..
+        })
+    ));
+
+    // Fixtures follow the format of their timestamp.
+    let has = |timestamp: &str, sql: &str| -> Result<bool, Error> {
+        let db = crate::MockDump::default()
+            .timestamp(timestamp)
+            .add_crate("serde", &["1.0.0"])
+            .build()?;
+        Ok(db.query_row(sql, [], |row| row.get(0))?)
+    };
+    let split = "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'crate_downloads'";
+    let downloads = "SELECT COUNT(*) > 0 FROM pragma_table_info('crates') WHERE name = 'downloads'";
+    assert!(!has("2021-05-15T02:00:00Z", split)? && has("2021-05-15T02:00:00Z", downloads)?);
+    assert!(has("2024-06-01T02:00:00Z", split)? && !has("2024-06-01T02:00:00Z", downloads)?);
+
+    let renaming = DumpFormat {
+        name: "test",
+        since: "",
+        renamed: &[("crates", "crate_name", "name")],
+        missing_tables: &[],
+        missing_columns: &[],
+    };
+    let header = csv::StringRecord::from(vec!["id", "crate_name", "extra"]);
+    assert_eq!("name", renaming.canonical_column("crates", "crate_name"));
+    assert_eq!(
+        "crate_name",
+        renaming.canonical_column("versions", "crate_name")
+    );
+    assert_eq!(
+        r#"CREATE TABLE x("id" INTEGER, "name" TEXT, "extra" TEXT);"#,
+        renaming.csv_schema("crates", &header, true)
+    );
+    Ok(())
+}
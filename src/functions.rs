@@ -0,0 +1,71 @@
+use rusqlite::{functions::FunctionFlags, types::ValueRef, Connection};
+use semver::{Version, VersionReq};
+
+use crate::{rows::DependencyKind, Error};
+
+/// Registers SQL helpers on `db`:
+///
+/// - `normalize_name(name)` folds a crate name the way crates.io compares them, so
+///   `normalize_name('Serde-JSON') = 'serde_json'`.
+/// - `semver_matches(req, version)` is 1 when `version` satisfies the cargo requirement `req`,
+///   and NULL when either fails to parse.
+/// - `kind_name(kind)` decodes `dependencies.kind` into `normal`, `build` or `dev`, and NULL
+///   for kinds it doesn't know, see [`DependencyKind`].
+pub fn register_functions(db: &Connection) -> Result<(), Error> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    db.create_scalar_function("normalize_name", 1, flags, |ctx| {
+        Ok(ctx
+            .get::<Option<String>>(0)?
+            .map(|name| normalize_name(&name)))
+    })?;
+    db.create_scalar_function("semver_matches", 2, flags, |ctx| {
+        let req = ctx.get::<Option<String>>(0)?;
+        let version = ctx.get::<Option<String>>(1)?;
+        Ok(req
+            .zip(version)
+            .and_then(|(req, version)| semver_matches(&req, &version)))
+    })?;
+    db.create_scalar_function("kind_name", 1, flags, |ctx| {
+        let raw = match ctx.get_raw(0) {
+            ValueRef::Integer(raw) => Some(raw),
+            ValueRef::Text(text) => std::str::from_utf8(text).ok().and_then(|t| t.parse().ok()),
+            _ => None,
+        };
+        Ok(raw
+            .and_then(DependencyKind::from_raw)
+            .map(DependencyKind::name))
+    })?;
+    Ok(())
+}
+
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+pub(crate) fn semver_matches(req: &str, version: &str) -> Option<bool> {
+    let req = VersionReq::parse(req).ok()?;
+    let version = Version::parse(version).ok()?;
+    Some(req.matches(&version))
+}
+
+#[test]
+fn test_register_functions() -> Result<(), Error> {
+    let db = Connection::open_in_memory()?;
+    register_functions(&db)?;
+
+    let row: (String, bool, bool, Option<bool>) = db.query_row(
+        "SELECT normalize_name('Serde-JSON'), semver_matches('^1.2', '1.4.0'),
+            semver_matches('~1.2', '1.4.0'), semver_matches('nope', '1.0.0')",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    assert_eq!(("serde_json".to_string(), true, false, None), row);
+
+    let kinds: (String, String, Option<String>) = db.query_row(
+        "SELECT kind_name(2), kind_name('1'), kind_name(7)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    assert_eq!(("dev".to_string(), "build".to_string(), None), kinds);
+    Ok(())
+}
@@ -0,0 +1,606 @@
+//! The `graphql` feature: the loaded tables as a read-only GraphQL schema, see [`SCHEMA`].
+//!
+//! Queries are a subset of GraphQL: fields with aliases and literal arguments, nested
+//! selections and `__typename`, without variables, fragments or directives.
+
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{Map, Value};
+
+use crate::{
+    rows::{Crate, Dependency, DependencyKind, DumpRow, Version},
+    table_exists, Error,
+};
+
+/// How deeply selections may nest, counting the operation's own.
+pub const MAX_DEPTH: usize = 8;
+
+/// The most crates `crates(first:)` returns.
+pub const MAX_FIRST: i64 = 100;
+
+/// The schema [`execute`] answers queries against.
+pub const SCHEMA: &str = r#"type Query {
+  crate(name: String!): Crate
+  "Most downloaded first, optionally only those with the keyword and in the category slug. At most 100."
+  crates(keyword: String, category: String, first: Int = 20): [Crate!]!
+}
+
+type Crate {
+  id: Int!
+  name: String!
+  description: String!
+  repository: String!
+  downloads: Int!
+  createdAt: String!
+  updatedAt: String!
+  "Oldest first, or the newest `last` ones."
+  versions(last: Int): [Version!]!
+  keywords: [String!]!
+  "Category slugs."
+  categories: [String!]!
+}
+
+type Version {
+  id: Int!
+  num: String!
+  license: String!
+  downloads: Int!
+  yanked: Boolean!
+  createdAt: String!
+  dependencies(kind: DependencyKind): [Dependency!]!
+}
+
+enum DependencyKind { normal build dev }
+
+type Dependency {
+  req: String!
+  kind: DependencyKind!
+  optional: Boolean!
+  defaultFeatures: Boolean!
+  crate: Crate
+}
+"#;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Arg {
+    Int(i64),
+    /// Strings and enum values.
+    Text(String),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Field {
+    alias: Option<String>,
+    name: String,
+    args: Vec<(String, Arg)>,
+    selection: Vec<Field>,
+}
+
+impl Field {
+    fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    fn arg(&self, name: &str) -> Option<&Arg> {
+        self.args
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, arg)| arg)
+            .filter(|arg| **arg != Arg::Null)
+    }
+
+    fn text_arg(&self, name: &str) -> Result<Option<&str>, Error> {
+        match self.arg(name) {
+            None => Ok(None),
+            Some(Arg::Text(text)) => Ok(Some(text)),
+            Some(_) => Err(invalid(format!(
+                "{} of {} must be a string",
+                name, self.name
+            ))),
+        }
+    }
+
+    fn int_arg(&self, name: &str) -> Result<Option<i64>, Error> {
+        match self.arg(name) {
+            None => Ok(None),
+            Some(Arg::Int(n)) => Ok(Some(*n)),
+            Some(_) => Err(invalid(format!("{} of {} must be an Int", name, self.name))),
+        }
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidGraphQL(message)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Text(String),
+    Int(i64),
+    Punct(char),
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() || c == ',' => {}
+            '#' => while chars.next_if(|c| *c != '\n').is_some() {},
+            '{' | '}' | '(' | ')' | ':' | '!' | '=' | '[' | ']' => tokens.push(Token::Punct(c)),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some(escaped) => text.push(escaped),
+                            None => return Err(invalid("unterminated string".to_string())),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err(invalid("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = c.to_string();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                let n = number
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid Int {}", number)))?;
+                tokens.push(Token::Int(n));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                tokens.push(Token::Name(name));
+            }
+            '$' => return Err(invalid("variables aren't supported".to_string())),
+            '.' => return Err(invalid("fragments aren't supported".to_string())),
+            '@' => return Err(invalid("directives aren't supported".to_string())),
+            c => return Err(invalid(format!("unexpected {:?}", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Selections currently open.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), Error> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(invalid(format!("expected {:?}", punct))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            _ => Err(invalid("expected a name".to_string())),
+        }
+    }
+
+    fn document(&mut self) -> Result<Vec<Field>, Error> {
+        if let Some(Token::Name(keyword)) = self.peek() {
+            if keyword != "query" {
+                return Err(invalid(format!("{} operations aren't supported", keyword)));
+            }
+            self.pos += 1;
+            if let Some(Token::Name(_)) = self.peek() {
+                self.pos += 1;
+            }
+        }
+        let selection = self.selection()?;
+        match self.peek() {
+            None => Ok(selection),
+            Some(_) => Err(invalid("only one operation is supported".to_string())),
+        }
+    }
+
+    fn selection(&mut self) -> Result<Vec<Field>, Error> {
+        self.expect('{')?;
+        if self.depth == MAX_DEPTH {
+            return Err(invalid(format!(
+                "selections nest deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            fields.push(self.field()?);
+        }
+        self.depth -= 1;
+        if fields.is_empty() {
+            return Err(invalid("empty selection".to_string()));
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<Field, Error> {
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        let mut args = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let arg = self.name()?;
+                self.expect(':')?;
+                let value = match self.next() {
+                    Some(Token::Int(n)) => Arg::Int(n),
+                    Some(Token::Text(text)) => Arg::Text(text),
+                    Some(Token::Name(name)) => match name.as_str() {
+                        "true" => Arg::Bool(true),
+                        "false" => Arg::Bool(false),
+                        "null" => Arg::Null,
+                        _ => Arg::Text(name),
+                    },
+                    _ => return Err(invalid(format!("expected a value for {}", arg))),
+                };
+                args.push((arg, value));
+            }
+        }
+        let selection = match self.peek() {
+            Some(Token::Punct('{')) => self.selection()?,
+            _ => Vec::new(),
+        };
+        Ok(Field {
+            alias,
+            name,
+            args,
+            selection,
+        })
+    }
+}
+
+/// Resolves the fields of an object of type `ty` with `resolve`, which is `None` for fields
+/// `ty` doesn't have. `objects` are the fields that need a selection.
+fn object<F>(ty: &str, objects: &[&str], fields: &[Field], mut resolve: F) -> Result<Value, Error>
+where
+    F: FnMut(&Field) -> Result<Option<Value>, Error>,
+{
+    let mut map = Map::new();
+    for field in fields {
+        let value = match field.name.as_str() {
+            "__typename" => Some(Value::from(ty)),
+            _ => resolve(field)?,
+        };
+        let value = value.ok_or_else(|| invalid(format!("{} has no field {}", ty, field.name)))?;
+        match (
+            objects.contains(&field.name.as_str()),
+            field.selection.is_empty(),
+        ) {
+            (true, true) => {
+                return Err(invalid(format!("{}.{} needs a selection", ty, field.name)))
+            }
+            (false, false) => return Err(invalid(format!("{}.{} has no fields", ty, field.name))),
+            _ => {}
+        }
+        map.insert(field.key().to_string(), value);
+    }
+    Ok(Value::Object(map))
+}
+
+fn crate_sql(db: &Connection, filter: &str) -> Result<String, Error> {
+    Ok(format!(
+        "SELECT * FROM ({}) WHERE {}",
+        Crate::query(db)?,
+        filter
+    ))
+}
+
+fn strings(db: &Connection, sql: &str, id: i64) -> Result<Value, Error> {
+    let mut stmt = db.prepare(sql)?;
+    let values = stmt.query_map([id], |row| row.get::<_, String>(0))?;
+    Ok(Value::from(values.collect::<Result<Vec<_>, _>>()?))
+}
+
+fn resolve_crate(db: &Connection, krate: &Crate, fields: &[Field]) -> Result<Value, Error> {
+    object("Crate", &["versions"], fields, |field| {
+        Ok(Some(match field.name.as_str() {
+            "id" => Value::from(krate.id),
+            "name" => Value::from(krate.name.as_str()),
+            "description" => Value::from(krate.description.as_str()),
+            "repository" => Value::from(krate.repository.as_str()),
+            "downloads" => Value::from(krate.downloads),
+            "createdAt" => Value::from(krate.created_at.as_str()),
+            "updatedAt" => Value::from(krate.updated_at.as_str()),
+            "keywords" if !table_exists(db, "keywords")? => Value::Array(Vec::new()),
+            "keywords" => strings(
+                db,
+                r#"
+                    SELECT k.keyword FROM main.crates_keywords ck
+                    JOIN main.keywords k ON CAST(k.id AS INTEGER) = CAST(ck.keyword_id AS INTEGER)
+                    WHERE CAST(ck.crate_id AS INTEGER) = ? ORDER BY k.keyword
+                "#,
+                krate.id,
+            )?,
+            "categories" if !table_exists(db, "categories")? => Value::Array(Vec::new()),
+            "categories" => strings(
+                db,
+                r#"
+                    SELECT cat.slug FROM main.crates_categories cc
+                    JOIN main.categories cat ON CAST(cat.id AS INTEGER) = CAST(cc.category_id AS INTEGER)
+                    WHERE CAST(cc.crate_id AS INTEGER) = ? ORDER BY cat.slug
+                "#,
+                krate.id,
+            )?,
+            "versions" => {
+                let sql = format!(
+                    "{} WHERE CAST(crate_id AS INTEGER) = ? ORDER BY CAST(id AS INTEGER)",
+                    Version::query(db)?
+                );
+                let mut stmt = db.prepare(&sql)?;
+                let mut versions = stmt
+                    .query_map([krate.id], Version::from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(last) = field.int_arg("last")? {
+                    let skip = versions.len().saturating_sub(last.max(0) as usize);
+                    versions.drain(..skip);
+                }
+                let versions = versions
+                    .iter()
+                    .map(|v| resolve_version(db, v, &field.selection))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::from(versions)
+            }
+            _ => return Ok(None),
+        }))
+    })
+}
+
+fn resolve_version(db: &Connection, version: &Version, fields: &[Field]) -> Result<Value, Error> {
+    object("Version", &["dependencies"], fields, |field| {
+        Ok(Some(match field.name.as_str() {
+            "id" => Value::from(version.id),
+            "num" => Value::from(version.num.as_str()),
+            "license" => Value::from(version.license.as_str()),
+            "downloads" => Value::from(version.downloads),
+            "yanked" => Value::from(version.yanked),
+            "createdAt" => Value::from(version.created_at.as_str()),
+            "dependencies" => {
+                let kind = match field.text_arg("kind")? {
+                    None => None,
+                    Some(kind) => Some(
+                        [
+                            DependencyKind::Normal,
+                            DependencyKind::Build,
+                            DependencyKind::Dev,
+                        ]
+                        .iter()
+                        .copied()
+                        .find(|k| k.name() == kind)
+                        .ok_or_else(|| invalid(format!("unknown DependencyKind {}", kind)))?,
+                    ),
+                };
+                let sql = format!(
+                    "{} WHERE CAST(version_id AS INTEGER) = ?1 AND (?2 IS NULL OR CAST(kind AS INTEGER) = ?2) ORDER BY CAST(id AS INTEGER)",
+                    Dependency::QUERY
+                );
+                let mut stmt = db.prepare(&sql)?;
+                let dependencies = stmt
+                    .query_map(
+                        rusqlite::params![version.id, kind.map(DependencyKind::raw)],
+                        Dependency::from_row,
+                    )?
+                    .collect::<Result<Vec<_>, _>>()?;
+                let dependencies = dependencies
+                    .iter()
+                    .map(|d| resolve_dependency(db, d, &field.selection))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::from(dependencies)
+            }
+            _ => return Ok(None),
+        }))
+    })
+}
+
+fn resolve_dependency(
+    db: &Connection,
+    dependency: &Dependency,
+    fields: &[Field],
+) -> Result<Value, Error> {
+    object("Dependency", &["crate"], fields, |field| {
+        Ok(Some(match field.name.as_str() {
+            "req" => Value::from(dependency.req.as_str()),
+            "kind" => Value::from(dependency.kind.name()),
+            "optional" => Value::from(dependency.optional),
+            "defaultFeatures" => Value::from(dependency.default_features),
+            "crate" => {
+                let sql = crate_sql(
+                    db,
+                    "name = (SELECT name FROM main.crates WHERE CAST(id AS INTEGER) = ?)",
+                )?;
+                let krate = db
+                    .query_row(&sql, [dependency.crate_id], Crate::from_row)
+                    .optional()?;
+                match krate {
+                    Some(krate) => resolve_crate(db, &krate, &field.selection)?,
+                    None => Value::Null,
+                }
+            }
+            _ => return Ok(None),
+        }))
+    })
+}
+
+fn crates(db: &Connection, field: &Field) -> Result<Vec<Crate>, Error> {
+    let first = field.int_arg("first")?.unwrap_or(20).clamp(0, MAX_FIRST);
+    let keyword = field.text_arg("keyword")?;
+    let category = field.text_arg("category")?;
+    let mut filters = Vec::new();
+    if keyword.is_some() {
+        filters.push(
+            r#"name IN (
+                SELECT c.name FROM main.crates c
+                JOIN main.crates_keywords ck ON CAST(ck.crate_id AS INTEGER) = CAST(c.id AS INTEGER)
+                JOIN main.keywords k ON CAST(k.id AS INTEGER) = CAST(ck.keyword_id AS INTEGER)
+                WHERE k.keyword = ?1
+            )"#,
+        );
+    }
+    if category.is_some() {
+        filters.push(
+            r#"name IN (
+                SELECT c.name FROM main.crates c
+                JOIN main.crates_categories cc ON CAST(cc.crate_id AS INTEGER) = CAST(c.id AS INTEGER)
+                JOIN main.categories cat ON CAST(cat.id AS INTEGER) = CAST(cc.category_id AS INTEGER)
+                WHERE cat.slug = ?2
+            )"#,
+        );
+    }
+    if filters.is_empty() {
+        filters.push("?1 IS NULL AND ?2 IS NULL");
+    }
+    let sql = format!(
+        "{} ORDER BY 5 DESC, name LIMIT ?3",
+        crate_sql(db, &filters.join(" AND "))?
+    );
+    let mut stmt = db.prepare(&sql)?;
+    let crates = stmt.query_map(rusqlite::params![keyword, category, first], Crate::from_row)?;
+    Ok(crates.collect::<Result<_, _>>()?)
+}
+
+/// Answers `query` from the tables loaded into `db`, as `{"data": ...}`. Queries that don't
+/// fit [`SCHEMA`] or the supported subset of GraphQL are [`Error::InvalidGraphQL`].
+pub fn execute(db: &Connection, query: &str) -> Result<Value, Error> {
+    let fields = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+        depth: 0,
+    }
+    .document()?;
+    let data = object("Query", &["crate", "crates"], &fields, |field| {
+        Ok(Some(match field.name.as_str() {
+            "crate" => {
+                let name = field
+                    .text_arg("name")?
+                    .ok_or_else(|| invalid("crate needs a name".to_string()))?;
+                let krate = db
+                    .query_row(&crate_sql(db, "name = ?")?, [name], Crate::from_row)
+                    .optional()?;
+                match krate {
+                    Some(krate) => resolve_crate(db, &krate, &field.selection)?,
+                    None => Value::Null,
+                }
+            }
+            "crates" => Value::from(
+                crates(db, field)?
+                    .iter()
+                    .map(|krate| resolve_crate(db, krate, &field.selection))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            _ => return Ok(None),
+        }))
+    })?;
+    let mut response = Map::new();
+    response.insert("data".to_string(), data);
+    Ok(Value::Object(response))
+}
+
+#[test]
+fn test_graphql() -> Result<(), Error> {
+    use serde_json::json;
+
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("serde_json", &["1.0.0"])
+        .add_crate("tokio", &["1.0.0"])
+        .dependency("serde_json", "1.0.0", "serde", "^1.0")
+        .row("keywords", &[("id", "1"), ("keyword", "json")])
+        .row("crates_keywords", &[("crate_id", "2"), ("keyword_id", "1")])
+        .build()?;
+
+    let response = execute(
+        &db,
+        r#"
+            query Deps {
+                serde_json: crate(name: "serde_json") {
+                    __typename
+                    keywords
+                    versions(last: 1) {
+                        num
+                        dependencies(kind: normal) { req kind crate { name versions { num } } }
+                    }
+                }
+                json: crates(keyword: "json") { name }
+                top: crates(first: 2) { name }
+                nope: crate(name: "nope") { name }
+            }
+        "#,
+    )?;
+    assert_eq!(
+        json!({
+            "data": {
+                "serde_json": {
+                    "__typename": "Crate",
+                    "keywords": ["json"],
+                    "versions": [{
+                        "num": "1.0.0",
+                        "dependencies": [{
+                            "req": "^1.0",
+                            "kind": "normal",
+                            "crate": {"name": "serde", "versions": [{"num": "1.0.0"}, {"num": "1.0.1"}]},
+                        }],
+                    }],
+                },
+                "json": [{"name": "serde_json"}],
+                "top": [{"name": "serde"}, {"name": "serde_json"}],
+                "nope": null,
+            }
+        }),
+        response
+    );
+    let rejected = |query: &str| matches!(execute(&db, query), Err(Error::InvalidGraphQL(_)));
+    assert!(rejected("{ crate(name: \"serde\") { nope } }"));
+    assert!(rejected("{ crate(name: \"serde\") }"));
+    assert!(rejected(
+        "query($name: String) { crate(name: $name) { id } }"
+    ));
+    assert!(rejected("mutation { crate }"));
+    assert!(rejected("{ crates(first: \"two\") { id } }"));
+    let nested = format!(
+        "{}{}",
+        "{ crate(name: \"serde\") ".repeat(100_000),
+        "}".repeat(100_000)
+    );
+    assert!(rejected(&nested));
+    let crates = execute(&db, "{ crates(first: 1000000) { name } }")?;
+    assert_eq!(3, crates["data"]["crates"].as_array().map_or(0, Vec::len));
+    Ok(())
+}
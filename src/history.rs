@@ -0,0 +1,254 @@
+//! Longitudinal tables built from a series of dated dump snapshots.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+
+use crate::{schema::NEWER_TABLES, stats::crate_downloads, time, CratesIODumpLoader, Error, Table};
+
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS history_snapshots(date TEXT PRIMARY KEY);
+    CREATE TABLE IF NOT EXISTS history_crates(
+        name TEXT PRIMARY KEY, created_at TEXT, first_seen TEXT, last_seen TEXT
+    );
+    CREATE TABLE IF NOT EXISTS history_versions(
+        crate_name TEXT, num TEXT, created_at TEXT, first_seen TEXT, yanked_seen TEXT,
+        PRIMARY KEY (crate_name, num)
+    );
+    CREATE TABLE IF NOT EXISTS history_downloads(
+        date TEXT, crate_name TEXT, downloads INTEGER,
+        PRIMARY KEY (crate_name, date)
+    );
+"#;
+
+/// Ingests dated snapshots into `history_*` tables:
+///
+/// - `history_snapshots(date)` lists the ingested snapshots.
+/// - `history_crates(name, created_at, first_seen, last_seen)` has the first and last snapshot
+///   each crate was present in.
+/// - `history_versions(crate_name, num, created_at, first_seen, yanked_seen)` has when each
+///   version first appeared and the first snapshot that had it yanked.
+/// - `history_downloads(date, crate_name, downloads)` has the all-time downloads of every crate
+///   in every snapshot.
+///
+/// Columns missing from older dumps are read as NULL, so snapshots of different vintages can
+/// be mixed. Ingesting into an existing database adds to what is already there.
+pub struct History {
+    pub snapshots: Vec<(String, CratesIODumpLoader)>,
+    pub target_path: PathBuf,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            snapshots: Vec::new(),
+            target_path: Path::new("data").join("history"),
+        }
+    }
+}
+
+impl History {
+    /// Adds the snapshot taken on `date` (`YYYY-MM-DD`), read through `loader`.
+    pub fn snapshot(&mut self, date: &str, loader: CratesIODumpLoader) -> &mut Self {
+        self.snapshots.push((date.to_string(), loader));
+        self
+    }
+
+    /// Directory the snapshots added by [`History::range`] are extracted to, one per date.
+    pub fn target_path(&mut self, path: &Path) -> &mut Self {
+        self.target_path = path.to_path_buf();
+        self
+    }
+
+    /// Adds a snapshot every `step_days` from `from` through `to`, fetched from `template` with
+    /// `{date}` replaced by the date. Dates that don't parse add nothing.
+    pub fn range(
+        &mut self,
+        template: &str,
+        from: &str,
+        to: &str,
+        step_days: u32,
+    ) -> Result<&mut Self, Error> {
+        let (from, to) = match (time::parse_date(from), time::parse_date(to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Ok(self),
+        };
+        for day in (from..=to).step_by(step_days.max(1) as usize) {
+            let date = time::format_date(day);
+            let loader = CratesIODumpLoader::builder()
+                .resource(&template.replace("{date}", &date))
+                .target_path(&self.target_path.join(&date))
+                .tables(&[Table::Crates, Table::Versions, Table::CrateDownloads])
+                .build()?;
+            self.snapshot(&date, loader);
+        }
+        Ok(self)
+    }
+
+    /// Updates every snapshot, oldest first, and ingests it into `db`.
+    pub fn build_into(&mut self, db: &Connection) -> Result<(), Error> {
+        db.execute_batch(SCHEMA)?;
+        rusqlite::vtab::csvtab::load_module(db)?;
+        self.snapshots.sort_by(|a, b| a.0.cmp(&b.0));
+        for (date, loader) in &mut self.snapshots {
+            let path = loader.update()?.sqlite_path();
+            // Dumps from before the newer tables were added don't have them to load.
+            let target = loader.target_path.clone();
+            loader.files.retain(|file| {
+                let table = file.file_stem().unwrap_or_default().to_string_lossy();
+                !NEWER_TABLES.contains(&table.as_ref()) || target.join(file).exists()
+            });
+            drop(loader.open_db()?);
+
+            db.execute("ATTACH DATABASE ? AS snapshot", [path.to_string_lossy()])?;
+            let ingested = ingest(db, date);
+            db.execute_batch("DETACH DATABASE snapshot")?;
+            ingested?;
+        }
+        Ok(())
+    }
+}
+
+fn snapshot_has(db: &Connection, table: &str) -> Result<bool, Error> {
+    let count: i64 = db.query_row(
+        "SELECT COUNT(*) FROM snapshot.sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn column_exists(db: &Connection, table: &str, column: &str) -> Result<bool, Error> {
+    let mut stmt = db.prepare(&format!("PRAGMA snapshot.table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// `table.column`, or NULL when this snapshot's dump doesn't have the column.
+fn column_or_null(
+    db: &Connection,
+    table: &str,
+    alias: &str,
+    column: &str,
+) -> Result<String, Error> {
+    Ok(match column_exists(db, table, column)? {
+        true => format!("{}.{}", alias, column),
+        false => "NULL".to_string(),
+    })
+}
+
+fn ingest(db: &Connection, date: &str) -> Result<(), Error> {
+    db.execute("INSERT OR IGNORE INTO history_snapshots VALUES (?)", [date])?;
+
+    let created_at = column_or_null(db, "crates", "c", "created_at")?;
+    db.execute(
+        &format!(
+            r#"
+                INSERT INTO history_crates SELECT c.name, {}, ?1, ?1 FROM snapshot.crates c WHERE true
+                ON CONFLICT (name) DO UPDATE SET
+                    created_at = COALESCE(excluded.created_at, created_at),
+                    first_seen = MIN(first_seen, excluded.first_seen),
+                    last_seen = MAX(last_seen, excluded.last_seen)
+            "#,
+            created_at
+        ),
+        [date],
+    )?;
+
+    if snapshot_has(db, "crate_downloads")? || column_exists(db, "crates", "downloads")? {
+        db.execute(
+            &format!(
+                r#"
+                    INSERT OR REPLACE INTO history_downloads
+                    SELECT ?1, c.name, d.downloads FROM snapshot.crates c
+                    JOIN {} d ON d.crate_id = CAST(c.id AS INTEGER)
+                "#,
+                crate_downloads(db, "snapshot")?
+            ),
+            [date],
+        )?;
+    }
+
+    if !snapshot_has(db, "versions")? {
+        return Ok(());
+    }
+    let created_at = column_or_null(db, "versions", "v", "created_at")?;
+    let yanked = match column_exists(db, "versions", "yanked")? {
+        true => "CASE v.yanked WHEN 't' THEN ?1 END",
+        false => "NULL",
+    };
+    db.execute(
+        &format!(
+            r#"
+                INSERT INTO history_versions
+                SELECT c.name, v.num, {}, ?1, {} FROM snapshot.versions v
+                JOIN snapshot.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+                WHERE true
+                ON CONFLICT (crate_name, num) DO UPDATE SET
+                    created_at = COALESCE(excluded.created_at, created_at),
+                    first_seen = MIN(first_seen, excluded.first_seen),
+                    yanked_seen = CASE
+                        WHEN yanked_seen IS NULL OR excluded.yanked_seen < yanked_seen
+                        THEN excluded.yanked_seen ELSE yanked_seen END
+            "#,
+            created_at, yanked
+        ),
+        [date],
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_history() -> Result<(), Error> {
+    use crate::mock::archive_loader;
+
+    let loader = |resource: &str, target: &str| -> Result<CratesIODumpLoader, Error> {
+        let mut loader = archive_loader(resource, target)?;
+        loader.tables(&[Table::Crates, Table::Versions, Table::CrateDownloads]);
+        Ok(loader)
+    };
+
+    let db = Connection::open_in_memory()?;
+    History::default()
+        .snapshot(
+            "2021-05-22",
+            loader(
+                "testdata/fixture-next.tar.gz",
+                "testdata/extracted/history-new",
+            )?,
+        )
+        .snapshot(
+            "2021-05-15",
+            loader("testdata/fixture.tar.gz", "testdata/extracted/history-old")?,
+        )
+        .build_into(&db)?;
+
+    let row = |sql: &str| {
+        db.query_row(sql, [], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+    };
+    assert_eq!(
+        ("2021-05-22".to_string(), Some("2021-05-22".to_string())),
+        row("SELECT first_seen, last_seen FROM history_crates WHERE name = 'eyre'")?
+    );
+    assert_eq!(
+        ("2021-05-15".to_string(), Some("2021-05-22".to_string())),
+        row("SELECT first_seen, yanked_seen FROM history_versions WHERE num = '0.7.11'")?
+    );
+    let downloads: Vec<i64> = db
+        .prepare("SELECT downloads FROM history_downloads WHERE crate_name = 'eyre' ORDER BY date")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    assert_eq!(vec![100], downloads);
+    let snapshots: i64 = db.query_row("SELECT COUNT(*) FROM history_snapshots", [], |row| {
+        row.get(0)
+    })?;
+    assert_eq!(2, snapshots);
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! The `index-check` feature: audits the dump against a crate's entries in the sparse index.
+
+use reqwest::{blocking::Client, header::USER_AGENT, StatusCode};
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::{index_path, Error};
+
+/// A difference between the dump and the index for one version of a crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexMismatch {
+    /// In the dump, but not in the index.
+    MissingFromIndex { version: String },
+    /// In the index, but not in the dump, e.g. published after it was taken.
+    MissingFromDump { version: String },
+    Checksum {
+        version: String,
+        dump: String,
+        index: String,
+    },
+    Yanked {
+        version: String,
+        dump: bool,
+        index: bool,
+    },
+}
+
+/// Fetches index files from a sparse registry, crates.io's by default.
+pub struct IndexChecker {
+    pub index: String,
+    pub user_agent: String,
+
+    client: Client,
+}
+
+impl Default for IndexChecker {
+    fn default() -> Self {
+        Self {
+            index: "https://index.crates.io".to_string(),
+            user_agent: format!(
+                "cratesio-dbdump-csvtab/{} (+{})",
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_REPOSITORY")
+            ),
+            client: Client::new(),
+        }
+    }
+}
+
+impl IndexChecker {
+    pub fn index(&mut self, url: &str) -> &mut Self {
+        self.index = url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub fn user_agent(&mut self, user_agent: &str) -> &mut Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    /// The index file of `name`, empty when the index doesn't have the crate.
+    pub fn fetch(&self, name: &str) -> Result<String, Error> {
+        let path = index_path(name);
+        let url = format!(
+            "{}/{}",
+            self.index,
+            path.to_string_lossy().replace('\\', "/")
+        );
+        let response = self
+            .client
+            .get(&url)
+            .header(USER_AGENT, &self.user_agent)
+            .send()?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(String::new()),
+            _ => Ok(response.error_for_status()?.text()?),
+        }
+    }
+
+    /// Compares `name`'s versions in the dump loaded into `db` with the index.
+    pub fn check_crate(&self, db: &Connection, name: &str) -> Result<Vec<IndexMismatch>, Error> {
+        compare_index_file(db, name, &self.fetch(name)?)
+    }
+}
+
+/// Compares `name`'s versions in the dump loaded into `db` with `index_file`, the crate's file
+/// of an index, e.g. one written by [`crate::IndexExporter`]. Mismatches are in the order of
+/// the dump, then the versions only the index has.
+pub fn compare_index_file(
+    db: &Connection,
+    name: &str,
+    index_file: &str,
+) -> Result<Vec<IndexMismatch>, Error> {
+    let mut indexed = Vec::new();
+    for line in index_file.lines().filter(|l| !l.trim().is_empty()) {
+        let entry: Value = serde_json::from_str(line)?;
+        indexed.push((
+            entry["vers"].as_str().unwrap_or_default().to_string(),
+            entry["cksum"].as_str().unwrap_or_default().to_string(),
+            entry["yanked"].as_bool().unwrap_or_default(),
+        ));
+    }
+
+    let mut stmt = db.prepare(
+        r#"
+            SELECT v.num, v.checksum, v.yanked = 't'
+            FROM main.versions v JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ? ORDER BY CAST(v.id AS INTEGER)
+        "#,
+    )?;
+    let dumped = stmt
+        .query_map([name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut mismatches = Vec::new();
+    for (version, checksum, yanked) in &dumped {
+        let (_, index_checksum, index_yanked) = match indexed.iter().find(|(v, _, _)| v == version)
+        {
+            Some(entry) => entry,
+            None => {
+                mismatches.push(IndexMismatch::MissingFromIndex {
+                    version: version.clone(),
+                });
+                continue;
+            }
+        };
+        if checksum != index_checksum {
+            mismatches.push(IndexMismatch::Checksum {
+                version: version.clone(),
+                dump: checksum.clone(),
+                index: index_checksum.clone(),
+            });
+        }
+        if yanked != index_yanked {
+            mismatches.push(IndexMismatch::Yanked {
+                version: version.clone(),
+                dump: *yanked,
+                index: *index_yanked,
+            });
+        }
+    }
+    for (version, _, _) in &indexed {
+        if !dumped.iter().any(|(v, _, _)| v == version) {
+            mismatches.push(IndexMismatch::MissingFromDump {
+                version: version.clone(),
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+#[test]
+fn test_compare_index_file() -> Result<(), Error> {
+    use std::{fs, path::Path};
+
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1", "1.0.2"])
+        .build()?;
+    let dir = Path::new("testdata/extracted/index-check");
+    let _ = fs::remove_dir_all(dir);
+    crate::export_index(&db, dir)?;
+    let exported = fs::read_to_string(dir.join(index_path("serde")))?;
+    assert!(compare_index_file(&db, "serde", &exported)?.is_empty());
+
+    db.execute_batch(
+        r#"
+            UPDATE versions SET checksum = 'abc' WHERE num = '1.0.0';
+            UPDATE versions SET yanked = 't' WHERE num = '1.0.1';
+            DELETE FROM versions WHERE num = '1.0.2';
+        "#,
+    )?;
+    let extra =
+        r#"{"name":"serde","vers":"2.0.0","deps":[],"cksum":"def","features":{},"yanked":false}"#;
+    let index_file = format!("{}{}\n", exported, extra);
+    let first: Value = serde_json::from_str(exported.lines().next().unwrap_or_default())?;
+    assert_eq!(
+        vec![
+            IndexMismatch::Checksum {
+                version: "1.0.0".to_string(),
+                dump: "abc".to_string(),
+                index: first["cksum"].as_str().unwrap_or_default().to_string(),
+            },
+            IndexMismatch::Yanked {
+                version: "1.0.1".to_string(),
+                dump: true,
+                index: false,
+            },
+            IndexMismatch::MissingFromDump {
+                version: "1.0.2".to_string(),
+            },
+            IndexMismatch::MissingFromDump {
+                version: "2.0.0".to_string(),
+            },
+        ],
+        compare_index_file(&db, "serde", &index_file)?
+    );
+    assert_eq!(
+        vec![IndexMismatch::MissingFromIndex {
+            version: "1.0.0".to_string()
+        }],
+        compare_index_file(&db, "serde", "")?[..1].to_vec()
+    );
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use crate::{resource, CratesIODumpLoader, TableWriter};
+
+impl CratesIODumpLoader {
+    /// Every table `load_dump_into()` loads, dump tables first, then custom ones.
+    pub fn table_names(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|f| f.file_stem().unwrap_or_default().to_string_lossy().into())
+            .chain(self.custom_tables.iter().map(|t| t.name.clone()))
+            .collect()
+    }
+
+    /// The `table_schema()` overrides, by table.
+    pub fn table_schemas(&self) -> &HashMap<String, String> {
+        &self.table_schema
+    }
+
+    /// The `columns()` selections, by table.
+    pub fn table_columns(&self) -> &HashMap<String, Vec<String>> {
+        &self.table_columns
+    }
+
+    pub fn custom_tables(&self) -> &[TableWriter] {
+        &self.custom_tables
+    }
+
+    /// What `update()` fetches: the resource with `file://` URLs and paths relative to
+    /// `base_path` resolved.
+    pub fn effective_resource(&self) -> String {
+        resource::resolve(&self.resource, self.base_path.as_deref())
+    }
+
+    /// The cache directory, if the cache has been set with `cache()` or built by
+    /// `cache_handle()`.
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache.get().map(|cache| cache.dir.as_path())
+    }
+}
+
+impl fmt::Debug for CratesIODumpLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let custom_tables: Vec<&str> = self.custom_tables.iter().map(|t| t.name.as_str()).collect();
+        f.debug_struct("CratesIODumpLoader")
+            .field("resource", &self.resource)
+            .field("base_path", &self.base_path)
+            .field("target_path", &self.target_path)
+            .field("files", &self.files)
+            .field("discover_tables", &self.discover_tables)
+            .field("custom_tables", &custom_tables)
+            .field("table_schema", &self.table_schema)
+            .field("table_columns", &self.table_columns)
+            .field("sha256", &self.sha256)
+            .field("preload", &self.preload)
+            .field("preload_engine", &self.preload_engine)
+            .field("typed_schemas", &self.typed_schemas)
+            .field("threads", &self.threads)
+            .field("lazy", &self.lazy)
+            .field("continue_on_error", &self.continue_on_error)
+            .field("sample_rows", &self.sample_rows)
+            .field("seed", &self.seed)
+            .field("downloads_since", &self.downloads_since)
+            .field("growth_stats", &self.growth_stats)
+            .field("category_stats", &self.category_stats)
+            .field("cadence_views", &self.cadence_views)
+            .field("tag_views", &self.tag_views)
+            .field("epoch_timestamps", &self.epoch_timestamps)
+            .field("schema_version", &self.schema_version)
+            .field("index_presets", &self.index_presets)
+            .field("memory_limit", &self.memory_limit)
+            .field("db_cache", &self.db_cache)
+            .field("busy_timeout", &self.busy_timeout)
+            .field("page_size", &self.page_size)
+            .field("mmap_size", &self.mmap_size)
+            .field("download_timeout", &self.download_timeout)
+            .field("extract_timeout", &self.extract_timeout)
+            .field("load_timeout", &self.load_timeout)
+            .field("cache_dir", &self.cache_dir())
+            .field("events", &self.events.is_some())
+            .finish()
+    }
+}
+
+#[test]
+fn test_introspection() -> Result<(), crate::Error> {
+    let mut favorites = TableWriter::new("favorites", &["name"]);
+    favorites.row(&["serde"]);
+    let loader = CratesIODumpLoader::builder()
+        .base_path(Path::new("/srv/dumps"))
+        .resource("db-dump.tar.gz")
+        .minimal()
+        .custom_table(favorites)
+        .table_schema("crates", "CREATE TABLE x(id INT);")
+        .columns("versions", &["id", "num"])
+        .build()?;
+
+    assert_eq!(
+        vec!["crates", "dependencies", "versions", "favorites"],
+        loader.table_names()
+    );
+    assert_eq!(
+        Some(&"CREATE TABLE x(id INT);".to_string()),
+        loader.table_schemas().get("crates")
+    );
+    assert_eq!(vec!["id", "num"], loader.table_columns()["versions"]);
+    assert_eq!(1, loader.custom_tables()[0].rows.len());
+    assert_eq!(
+        Path::new("/srv/dumps")
+            .join("db-dump.tar.gz")
+            .to_string_lossy(),
+        loader.effective_resource()
+    );
+    assert_eq!(None, loader.cache_dir());
+
+    let debug = format!("{:?}", loader.clone());
+    assert!(debug.contains("custom_tables: [\"favorites\"]"));
+    assert!(debug.contains("CREATE TABLE x(id INT);"));
+    assert!(!debug.contains("serde"));
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::path::PathBuf;
+
+use crate::{CratesIODumpLoader, Error};
+
+/// The table of a `no such table: <name>` failure.
+fn missing_table(error: &Error) -> Option<&str> {
+    let message = match error {
+        Error::RusqliteError(rusqlite::Error::SqliteFailure(_, Some(message))) => message,
+        _ => return None,
+    };
+    let table = message.strip_prefix("no such table: ")?;
+    Some(
+        table
+            .strip_prefix("main.")
+            .or_else(|| table.strip_prefix("temp."))
+            .unwrap_or(table),
+    )
+}
+
+impl CratesIODumpLoader {
+    /// Creates `table` in `db` unless it is already there, for `lazy()` loaders.
+    pub fn ensure_table(&self, db: &Connection, table: &str) -> Result<(), Error> {
+        let exists = db
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE name = ?1 UNION ALL SELECT 1 FROM sqlite_temp_master WHERE name = ?1",
+                [table],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if exists {
+            return Ok(());
+        }
+
+        if let Some(custom) = self.custom_tables.iter().find(|t| t.name == table) {
+            custom.write(&self.target_path)?;
+            return self.load_file(db, &custom.file_name(), table);
+        }
+        match self.dump_file(table) {
+            Some(file) => self.load_file(db, file, table),
+            None => Err(Error::UnknownTable(table.to_string())),
+        }
+    }
+
+    pub fn ensure_tables(&self, db: &Connection, tables: &[&str]) -> Result<(), Error> {
+        for table in tables {
+            self.ensure_table(db, table)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f`, e.g. a helper like [`crate::reverse_dependencies`], creating the tables it
+    /// fails to find with `ensure_table()` and running it again until it stops asking for more.
+    pub fn with_tables<T>(
+        &self,
+        db: &Connection,
+        mut f: impl FnMut(&Connection) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        loop {
+            let error = match f(db) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            match missing_table(&error) {
+                // Tables this loader doesn't know about are the caller's problem.
+                Some(table) if self.knows_table(table) => self.ensure_table(db, table)?,
+                _ => return Err(error),
+            }
+        }
+    }
+
+    fn dump_file(&self, table: &str) -> Option<&PathBuf> {
+        self.files
+            .iter()
+            .find(|f| f.file_stem() == Some(table.as_ref()))
+    }
+
+    fn knows_table(&self, table: &str) -> bool {
+        self.custom_tables.iter().any(|t| t.name == table) || self.dump_file(table).is_some()
+    }
+}
+
+#[test]
+fn test_lazy_tables() -> Result<(), Error> {
+    use crate::reverse_dependencies;
+    use std::path::Path;
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    let report = loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/lazy"))
+        .lazy(true)
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+    assert!(report.loaded.is_empty());
+
+    let tables = |db: &Connection| -> Result<Vec<String>, Error> {
+        let mut stmt = db.prepare("SELECT name FROM sqlite_master ORDER BY name")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        Ok(names.collect::<Result<_, _>>()?)
+    };
+    assert!(tables(&db)?.is_empty());
+
+    let rdeps = loader.with_tables(&db, |db| reverse_dependencies(db, "serde"))?;
+    assert_eq!(4, rdeps.len());
+    assert_eq!(vec!["crates", "dependencies", "versions"], tables(&db)?);
+
+    loader.ensure_tables(&db, &["crates", "keywords"])?;
+    assert_eq!(4, tables(&db)?.len());
+    assert!(matches!(
+        loader.ensure_table(&db, "nope"),
+        Err(Error::UnknownTable(_))
+    ));
+    assert!(loader
+        .with_tables(&db, |db| Ok(db.execute_batch("SELECT * FROM nope")?))
+        .is_err());
+    Ok(())
+}
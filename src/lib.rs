@@ -1,16 +1,168 @@
+// Tests keep exercising the deprecated `&mut self` setters.
+#![cfg_attr(test, allow(deprecated))]
+
 use flate2::read::GzDecoder;
 use std::{
     collections::HashMap,
-    fs::{create_dir_all, File},
-    io,
+    fs::File,
+    io::{self, BufReader},
     path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc, OnceLock},
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
 use cached_path::{Cache, CacheBuilder, Error as CachedError};
 use rusqlite::{Connection, Error as SqliteError};
+use timeouts::Deadline;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+mod builder;
+pub mod cadence;
+pub mod changelog;
+pub mod columns;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "polars")]
+mod dataframe;
+#[cfg(feature = "datafusion")]
+mod datafusion_tables;
+#[cfg(feature = "db-cache")]
+mod db_cache;
+pub mod delta;
+pub mod diff;
+mod direct;
+mod discover;
+pub mod downloads;
+mod downloads_since;
+mod drift;
+#[cfg(feature = "duckdb")]
+mod duckdb_backend;
+mod env;
+mod epoch;
+mod events;
+mod export;
+pub mod formats;
+#[cfg(feature = "functions")]
+mod functions;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod history;
+#[cfg(feature = "index-check")]
+pub mod index_check;
+mod introspect;
+mod lazy;
+#[cfg(feature = "live")]
+pub mod live;
+mod load_report;
+mod lock;
+pub mod lockfile;
+mod memory;
+mod metadata;
+mod mock;
+mod overrides;
+mod parallel;
+pub mod pgdump;
+mod pin;
+mod pipeline;
+#[cfg(feature = "postgres")]
+mod postgres_backend;
+mod presets;
+mod profiles;
+mod rdeps;
+mod read_ahead;
+#[cfg(feature = "arrow")]
+mod record_batch;
+pub mod registry_cache;
+#[cfg(feature = "reports")]
+pub mod reports;
+mod resource;
+pub mod rows;
+#[cfg(feature = "sample-data")]
+mod sample_data;
+mod sampling;
+pub mod schema;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod sql;
+#[cfg(feature = "static-db")]
+mod static_db;
+pub mod stats;
+mod steps;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+mod table_writer;
+pub mod tags;
+pub mod time;
+mod timeouts;
+mod validate;
+#[cfg(feature = "watch")]
+mod watcher;
+
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncConnection, RowStream};
+pub use builder::CratesIODumpLoaderBuilder;
+#[cfg(feature = "polars")]
+pub use dataframe::{query_to_dataframe, to_dataframe};
+pub use direct::PreloadEngine;
+pub use drift::SchemaDrift;
+pub use env::ENV_PREFIX;
+pub use epoch::EpochTimestamps;
+pub use events::LoadEvent;
+pub use export::{
+    export_csv, export_dot, export_git_index, export_index, export_ndjson, export_ndjson_where,
+    export_sample, export_subset, index_path, mirror_manifest, CrateSelection, DotExporter,
+    FixtureBuilder, IndexExporter, MirrorEntry, CRATES_DL,
+};
+#[cfg(feature = "parquet")]
+pub use export::{export_parquet, ParquetExporter};
+#[cfg(feature = "sbom")]
+pub use export::{export_sbom, SbomExporter, SbomFormat};
+#[cfg(feature = "closure")]
+pub use export::{
+    export_vendor_manifest, unresolved_dependencies, vendor_manifest, DependencyClosure,
+    UnresolvedDependency,
+};
+#[cfg(feature = "functions")]
+pub use functions::register_functions;
+pub use load_report::{LoadReport, TableStats, UpdateReport};
+pub use metadata::{DumpMetadata, METADATA_FILE};
+pub use mock::MockDump;
+pub use pin::Pin;
+pub use presets::Preset;
+pub use profiles::Profile;
+pub use rdeps::{reverse_dependencies, ReverseDependencies, ReverseDependency};
+#[cfg(feature = "arrow")]
+pub use record_batch::{record_batches, RecordBatches, DEFAULT_BATCH_SIZE};
+#[cfg(feature = "sample-data")]
+pub use sample_data::SAMPLE_DUMP;
+pub use schema::Table;
+#[cfg(feature = "static-db")]
+pub use static_db::static_db;
+pub use steps::ArchiveHandle;
+pub use table_writer::TableWriter;
+pub use timeouts::Phase;
+#[cfg(feature = "watch")]
+pub use watcher::{DumpVersion, DumpWatcher, WatchHandle};
 
+#[cfg(feature = "arrow")]
+pub use arrow;
 pub use cached_path;
+#[cfg(feature = "datafusion")]
+pub use datafusion;
+#[cfg(feature = "duckdb")]
+pub use duckdb;
+#[cfg(feature = "parquet")]
+pub use parquet;
+#[cfg(feature = "polars")]
+pub use polars;
+#[cfg(feature = "postgres")]
+pub use postgres;
+#[cfg(feature = "proptest")]
+pub use proptest;
+#[cfg(any(feature = "watch", feature = "live", feature = "index-check"))]
+pub use reqwest;
 pub use rusqlite;
 
 #[derive(Error, Debug)]
@@ -23,207 +175,1133 @@ pub enum Error {
 
     #[error("failed to unpack dump")]
     IOError(#[from] io::Error),
+
+    #[error("failed to access {}", path.display())]
+    FileError {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("no tables selected")]
+    EmptyTableList,
+
+    #[error("invalid table name {0:?}, expected letters, digits, '_' and '-'")]
+    InvalidTableName(String),
+
+    #[error("{0:?} contains both kinds of quotes, which csvtab can't take as an argument")]
+    UnquotableArgument(String),
+
+    #[error("bad entry in dump archive")]
+    BadArchiveEntry(#[source] io::Error),
+
+    #[error("dump archive {} is corrupt", path.display())]
+    CorruptArchive {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to load table {table} with {sql}")]
+    TableError {
+        table: String,
+        sql: String,
+        #[source]
+        source: SqliteError,
+    },
+
+    #[error("failed to convert dump to csv")]
+    CsvError(#[from] csv::Error),
+
+    #[error("failed to encode json")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("both snapshots use the target path {0}")]
+    SharedTargetPath(PathBuf),
+
+    #[error("{phase} timed out after {limit:?}")]
+    Timeout { phase: Phase, limit: Duration },
+
+    #[error("{0} hasn't been loaded yet, see open_db()")]
+    NotMaterialized(PathBuf),
+
+    #[error("readers of {0} kept its WAL from being checkpointed, it was not replaced")]
+    DatabaseBusy(PathBuf),
+
+    #[error("invalid delta: {0}")]
+    InvalidDelta(String),
+
+    #[error("invalid date {0}, expected YYYY-MM-DD")]
+    InvalidDate(String),
+
+    #[error("invalid pin file: {0}")]
+    InvalidPin(String),
+
+    #[error("invalid lockfile: {0}")]
+    InvalidLockfile(String),
+
+    #[error("dump archive has sha256 {actual}, but {expected} is pinned")]
+    PinMismatch { expected: String, actual: String },
+
+    #[error(
+        "schema override for {table} has columns {columns:?}, but the csv header is {header:?}"
+    )]
+    SchemaOverrideMismatch {
+        table: String,
+        header: Vec<String>,
+        columns: Vec<String>,
+    },
+
+    #[error("{table} has no column {column}")]
+    UnknownColumn { table: String, column: String },
+
+    #[cfg(feature = "graphql")]
+    #[error("invalid GraphQL query: {0}")]
+    InvalidGraphQL(String),
+
+    #[error("invalid {option}: {reason}")]
+    InvalidOption {
+        option: &'static str,
+        reason: String,
+    },
+
+    #[error("invalid value {value:?} for {name}")]
+    InvalidEnvVar { name: String, value: String },
+
+    #[error("{name} {version} is not in the dump")]
+    UnknownVersion { name: String, version: String },
+
+    #[error("{0} is not one of the loader's tables")]
+    UnknownTable(String),
+
+    #[error("schema override for {table} is not valid SQL: {schema}")]
+    InvalidTableSchema {
+        table: String,
+        schema: String,
+        #[source]
+        source: SqliteError,
+    },
+
+    #[error("`git {args}` failed: {stderr}")]
+    GitFailed { args: String, stderr: String },
+
+    #[cfg(feature = "async")]
+    #[error("the async connection's thread has stopped")]
+    ConnectionClosed,
+
+    #[cfg(feature = "config")]
+    #[error("invalid config {}", path.display())]
+    InvalidConfig {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[cfg(feature = "arrow")]
+    #[error("failed to convert to arrow")]
+    ArrowError(#[from] arrow::error::ArrowError),
+
+    #[cfg(feature = "parquet")]
+    #[error("failed to write parquet")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+
+    #[cfg(feature = "datafusion")]
+    #[error("failed to register datafusion table")]
+    DataFusionError(#[from] datafusion::error::DataFusionError),
+
+    #[cfg(feature = "duckdb")]
+    #[error("failed to load duckdb")]
+    DuckDBError(#[from] duckdb::Error),
+
+    #[cfg(feature = "polars")]
+    #[error("failed to build dataframe")]
+    PolarsError(#[from] polars::error::PolarsError),
+
+    #[cfg(feature = "postgres")]
+    #[error("failed to load postgres")]
+    PostgresError(#[from] postgres::Error),
+
+    #[cfg(any(feature = "watch", feature = "live", feature = "index-check"))]
+    #[error("http request to crates.io failed")]
+    HttpError(#[from] reqwest::Error),
 }
 
+/// Downloads, extracts and loads the crates.io db dump.
+///
+/// Loaders are `Send + Sync`: once `open_db()` has loaded the database, one loader can be
+/// shared across threads that each take their own connection with `open_connection()`.
+#[derive(Clone)]
 pub struct CratesIODumpLoader {
     pub resource: String,
     pub files: Vec<PathBuf>,
-    pub cache: Cache,
+    /// Take every table in the archive's `data/` directory rather than only `files`, see
+    /// `update()`. Picking tables with `tables()` or `files()` turns it off, `all_tables()` on.
+    pub discover_tables: bool,
     pub target_path: PathBuf,
     pub preload: bool,
+    /// SHA-256 the archive must have, checked on every `update()`.
+    pub sha256: Option<String>,
+    /// Materialize the `growth_monthly` summary table when loading.
+    pub growth_stats: bool,
+    /// Materialize the `category_stats` summary table when loading.
+    pub category_stats: bool,
+    /// Directory relative `resource` paths are resolved against, instead of the working
+    /// directory.
+    pub base_path: Option<PathBuf>,
+    /// Keep at most this many rows per table when loading, see `sample_rows()`.
+    pub sample_rows: Option<usize>,
+    /// Pick sampled rows pseudo-randomly from this seed instead of taking the first ones.
+    pub seed: Option<u64>,
+    /// How long `open_db()` connections wait on a locked database before failing with
+    /// `SQLITE_BUSY`.
+    pub busy_timeout: Duration,
+    /// Page size `open_db()` creates `db.sqlite` with, in bytes.
+    pub page_size: u32,
+    /// How much of `db.sqlite` `open_db()` connections memory-map, in bytes. 0 disables it.
+    pub mmap_size: u64,
+    /// Keep loading the remaining tables when one fails, see `continue_on_error()`.
+    pub continue_on_error: bool,
+    /// Only create tables when first asked for, see `lazy()`.
+    pub lazy: bool,
+    /// Preload tables on up to this many threads, see `threads()`.
+    pub threads: usize,
+    /// Rough cap on SQLite's memory use while loading, see `memory_limit()`.
+    pub memory_limit: Option<usize>,
+    /// Restore preloaded databases from a compressed copy in the cache, see `db_cache()`.
+    pub db_cache: bool,
+    /// Earliest `version_downloads` date to extract, see `downloads_since()`.
+    pub downloads_since: Option<String>,
+    /// Index sets to create after loading, see `index_preset()`.
+    pub index_presets: Vec<Preset>,
+    /// How `preload()` loads the csvs, see `preload_engine()`.
+    pub preload_engine: PreloadEngine,
+    /// Declare canonical column types for tables without an override, see `typed_schemas()`.
+    pub typed_schemas: bool,
+    /// Create the release cadence views after loading, see `cadence_views()`.
+    pub cadence_views: bool,
+    /// Create the keyword and category views after loading, see `tag_views()`.
+    pub tag_views: bool,
+    /// Store timestamps of preloaded tables as unix epoch seconds, see `epoch_timestamps()`.
+    pub epoch_timestamps: Option<EpochTimestamps>,
+    /// Name of the [`formats::DumpFormat`] to load with whatever the dump's date, see
+    /// `schema_version()`.
+    pub schema_version: Option<String>,
+    /// Give up on fetching a remote `resource` after this long, see [`Phase::Download`].
+    pub download_timeout: Option<Duration>,
+    /// Give up on extracting the tables after this long.
+    pub extract_timeout: Option<Duration>,
+    /// Give up on `load_dump_into()` after this long.
+    pub load_timeout: Option<Duration>,
 
+    cache: OnceLock<Cache>,
     table_schema: HashMap<String, String>,
+    table_columns: HashMap<String, Vec<String>>,
+    events: Option<Sender<LoadEvent>>,
+    update_report: UpdateReport,
+    custom_tables: Arc<Vec<TableWriter>>,
 }
 
 impl Default for CratesIODumpLoader {
     fn default() -> Self {
         Self {
             resource: "https://static.crates.io/db-dump.tar.gz".to_string(),
-            files: tables_to_files(&[
-                "badges",
-                "categories",
-                "crate_owners",
-                "crates",
-                "crates_categories",
-                "crates_keywords",
-                "dependencies",
-                "keywords",
-                "metadata",
-                "reserved_crate_names",
-                "teams",
-                "users",
-                "version_authors",
-                "version_downloads",
-                "versions",
-            ]),
-            cache: Cache::new().unwrap(), // TODO: Maybe just store the builder instead... idk...
+            files: tables_to_files(&Table::ALL),
+            discover_tables: true,
+            cache: OnceLock::new(),
             target_path: Path::new("data").to_path_buf(),
             table_schema: HashMap::new(),
+            table_columns: HashMap::new(),
+            events: None,
+            update_report: UpdateReport::default(),
+            custom_tables: Arc::new(Vec::new()),
             preload: false,
+            sha256: None,
+            growth_stats: false,
+            category_stats: false,
+            base_path: None,
+            sample_rows: None,
+            seed: None,
+            busy_timeout: Duration::from_secs(5),
+            page_size: 32 * 1024,
+            mmap_size: 1 << 30,
+            continue_on_error: false,
+            lazy: false,
+            threads: 1,
+            memory_limit: None,
+            db_cache: false,
+            downloads_since: None,
+            index_presets: Vec::new(),
+            typed_schemas: false,
+            cadence_views: false,
+            tag_views: false,
+            epoch_timestamps: None,
+            schema_version: None,
+            download_timeout: None,
+            extract_timeout: None,
+            load_timeout: None,
+            preload_engine: PreloadEngine::default(),
         }
     }
 }
 
 impl CratesIODumpLoader {
+    /// Like `default()`, but builds the cache right away so a cache directory that can't be
+    /// created fails here rather than on the first `update()`.
+    pub fn try_new() -> Result<Self, Error> {
+        let loader = Self::default();
+        loader.cache_handle()?;
+        Ok(loader)
+    }
+
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn resource(&mut self, path: &str) -> &mut Self {
         self.resource = path.to_owned();
         self
     }
 
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn files(&mut self, files: Vec<PathBuf>) -> &mut Self {
         self.files = files;
+        self.discover_tables = false;
         self
     }
 
-    pub fn tables(&mut self, tables: &[&str]) -> &mut Self {
-        self.files = tables_to_files(tables);
+    /// Extract and load `tables`, given as [`Table`]s or by name.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn tables<T: Into<Table> + Clone>(&mut self, tables: &[T]) -> &mut Self {
+        let tables: Vec<Table> = tables.iter().cloned().map(Into::into).collect();
+        self.files = tables_to_files(&tables);
+        self.discover_tables = false;
         self
     }
 
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn table_schema(&mut self, table: &str, schema: &str) -> &mut Self {
         self.table_schema
             .insert(table.to_string(), schema.to_string());
         self
     }
 
+    /// Resolve relative `resource` paths against `path`.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn base_path(&mut self, path: &Path) -> &mut Self {
+        self.base_path = Some(path.to_path_buf());
+        self
+    }
+
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn busy_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// Page size for newly created `db.sqlite` files, a power of two from 512 to 65536;
+    /// SQLite ignores anything else. Larger pages make the full scans of analytics queries
+    /// faster at the cost of more IO for point lookups. Defaults to 32 KiB.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn page_size(&mut self, bytes: u32) -> &mut Self {
+        self.page_size = bytes;
+        self
+    }
+
+    /// Memory-map up to `bytes` of `db.sqlite` in `open_db()` connections, 0 to read it
+    /// through SQLite's page cache only. Defaults to 1 GiB.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn mmap_size(&mut self, bytes: u64) -> &mut Self {
+        self.mmap_size = bytes;
+        self
+    }
+
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn target_path(&mut self, path: &Path) -> &mut Self {
         self.target_path = path.to_path_buf();
         self
     }
 
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn cache(&mut self, builder: CacheBuilder) -> Result<&mut Self, Error> {
-        self.cache = builder.build()?;
+        self.cache = OnceLock::from(builder.build()?);
         Ok(self)
     }
 
+    /// The cache set with `cache()`, or else cached-path's default cache, built on first use.
+    /// If the default cache directory can't be created, e.g. on a read-only home, `.cache`
+    /// under `target_path` is used instead.
+    pub fn cache_handle(&self) -> Result<&Cache, Error> {
+        if let Some(cache) = self.cache.get() {
+            return Ok(cache);
+        }
+        let cache = default_cache(Cache::builder(), &self.target_path.join(".cache"))?;
+        Ok(self.cache.get_or_init(|| cache))
+    }
+
+    /// Keep loading the remaining tables when one fails, collecting the failures in the
+    /// [`LoadReport`] of `load_dump_into()` instead of returning the first one.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn continue_on_error(&mut self, should: bool) -> &mut Self {
+        self.continue_on_error = should;
+        self
+    }
+
+    /// Don't create any tables on `load_dump_into()`, leaving them to `ensure_table()` or
+    /// `with_tables()` when they are first needed.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn lazy(&mut self, should: bool) -> &mut Self {
+        self.lazy = should;
+        self
+    }
+
+    /// Preload tables on up to `threads` threads, each into a database of its own under
+    /// `target_path` that is copied into the loaded one afterwards. Only used with `preload()`
+    /// and without `sample_rows()`, whose tables depend on each other.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
     pub fn preload(&mut self, should: bool) -> &mut Self {
         self.preload = should;
         self
     }
 
+    /// Build the `growth_monthly` table of [`stats::materialize_growth`] when loading.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn growth_stats(&mut self, should: bool) -> &mut Self {
+        self.growth_stats = should;
+        self
+    }
+
+    /// Load at most `rows` rows per table, for small but realistic test databases. Implies
+    /// `preload(true)`. The first crates are kept, or those picked by `seed()`, and other tables
+    /// only keep rows referring to kept rows, e.g. versions of kept crates and dependencies
+    /// between kept versions and crates.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn sample_rows(&mut self, rows: usize) -> &mut Self {
+        self.sample_rows = Some(rows);
+        self
+    }
+
+    /// Sample rows in an order derived from `seed`, so the same rows are picked on every run and
+    /// machine as long as the dump is the same.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    #[allow(deprecated)]
     pub fn minimal(&mut self) -> &mut Self {
-        self.tables(&["crates", "dependencies", "versions"])
+        self.tables(&[Table::Crates, Table::Dependencies, Table::Versions])
     }
 
+    /// `minimal()`, preloaded with only the columns dependency analysis and the helpers in
+    /// this crate use, leaving out the bulky ones like `versions.features` and `license`.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    #[allow(deprecated)]
+    pub fn lean(&mut self) -> &mut Self {
+        self.minimal()
+            .preload(true)
+            .columns(
+                "crates",
+                &[
+                    "id",
+                    "name",
+                    "description",
+                    "created_at",
+                    "updated_at",
+                    "downloads",
+                ],
+            )
+            .columns(
+                "dependencies",
+                &[
+                    "id",
+                    "version_id",
+                    "crate_id",
+                    "req",
+                    "kind",
+                    "optional",
+                    "default_features",
+                    "target",
+                ],
+            )
+            .columns(
+                "versions",
+                &[
+                    "id",
+                    "crate_id",
+                    "num",
+                    "created_at",
+                    "updated_at",
+                    "downloads",
+                    "yanked",
+                ],
+            )
+    }
+
+    /// Only preload these columns of `table`, in this order. Virtual tables always have
+    /// every column.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn columns(&mut self, table: &str, columns: &[&str]) -> &mut Self {
+        self.table_columns.insert(
+            table.to_string(),
+            columns.iter().map(|c| c.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Refuse archives whose SHA-256 isn't `hash`.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn sha256(&mut self, hash: Option<&str>) -> &mut Self {
+        self.sha256 = hash.map(str::to_ascii_lowercase);
+        self
+    }
+
+    /// Fetches the archive and extracts the selected tables into `target_path`, unless they are
+    /// up to date. With `discover_tables`, `files` becomes every table found there.
     pub fn update(&mut self) -> Result<&mut Self, Error> {
-        let path = self.cache.cached_path(&self.resource)?;
-
-        let first_local_file = self.target_path.join(self.files.first().unwrap());
-        if first_local_file.exists()
-            && path.metadata()?.created()? <= first_local_file.metadata()?.created()?
-        {
-            // TODO: Improve change-detection later, this is just to prevent re-extracting existing obsurdity.
-            return Ok(self);
+        let started = Instant::now();
+        self.update_report = UpdateReport::default();
+        let report = self.update_inner()?;
+        self.files = self.table_files()?;
+        self.update_report = UpdateReport {
+            duration: started.elapsed(),
+            ..report
+        };
+        Ok(self)
+    }
+
+    /// What the last `update()` did.
+    pub fn update_report(&self) -> &UpdateReport {
+        &self.update_report
+    }
+
+    fn update_inner(&self) -> Result<UpdateReport, Error> {
+        self.check_table_names()?;
+        let mut report = UpdateReport::default();
+        let path = self.fetch_archive(&mut report)?;
+        match self.extract_archive(&path, &mut report) {
+            // A download cut short, try once more before giving up.
+            Err(Error::CorruptArchive { .. }) if self.remote_resource() => {
+                resource::invalidate_cached(&path)?;
+                report.extracted.clear();
+                let path = self.fetch_archive(&mut report)?;
+                self.extract_archive(&path, &mut report)?;
+            }
+            result => result?,
+        }
+        Ok(report)
+    }
+
+    /// Extracts the tables from the archive at `path`, unless they are newer than it already.
+    fn extract_archive(&self, path: &Path, report: &mut UpdateReport) -> Result<(), Error> {
+        let _lock = self.lock_target()?;
+        if self.extracted_since(path)? {
+            return Ok(());
         }
 
+        resource::create_target_dir(&self.target_path)?;
+        if path.extension() == Some("sql".as_ref()) {
+            let started = Instant::now();
+            let deadline = Deadline::start(Phase::Extract, self.extract_timeout);
+            let written = pgdump::copy_blocks_to_csv(
+                BufReader::new(deadline.reader(File::open(path).map_err(file_error(path))?)),
+                &self.target_path,
+                &self.files,
+            )
+            .map_err(|e| deadline.or_timeout(e))
+            .or_else(|e| self.remove_extracted().and(Err(e)))?;
+            self.extracted_from_sql(&written)?;
+            report.extracted.push((
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into(),
+                started.elapsed(),
+            ));
+            return Ok(());
+        }
+        self.extract_tar(path, report, None)
+    }
+
+    /// Whether the tables in `target_path` were extracted after the archive at `path` was
+    /// fetched.
+    fn extracted_since(&self, path: &Path) -> Result<bool, Error> {
+        // TODO: Improve change-detection later, this is just to prevent re-extracting existing obsurdity.
+        let first_local_file = self.target_path.join(self.first_file()?);
+        Ok(first_local_file.exists()
+            && path.metadata()?.created()? <= first_local_file.metadata()?.created()?)
+    }
+
+    /// Reports and filters the csvs written from a pg_dump.
+    fn extracted_from_sql(&self, written: &[PathBuf]) -> Result<(), Error> {
+        for file in written {
+            self.emit(LoadEvent::Extracting {
+                table: file
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into(),
+            });
+        }
+        self.filter_written_downloads(written)
+    }
+
+    /// Local path of the archive, checked against `sha256()`.
+    fn fetch_archive(&self, report: &mut UpdateReport) -> Result<PathBuf, Error> {
+        let cached = match self.remote_resource() {
+            true => resource::modified_times(&self.cache_handle()?.dir),
+            false => HashMap::new(),
+        };
+        let path = match (&self.events, self.remote_resource()) {
+            (Some(events), true) => {
+                self.emit(LoadEvent::DownloadStarted {
+                    resource: self.resource.clone(),
+                });
+                let _watch =
+                    events::DownloadWatch::start(&self.cache_handle()?.dir, events.clone());
+                self.cached_resource_in_time()?
+            }
+            _ => self.cached_resource_in_time()?,
+        };
+        self.emit(LoadEvent::DownloadFinished { path: path.clone() });
+        // cached-path doesn't say whether it downloaded, but a download is a new or newer file.
+        let metadata = path.metadata().map_err(file_error(&path))?;
+        if self.remote_resource() && cached.get(&path) != Some(&metadata.modified().ok()) {
+            report.downloaded_bytes += metadata.len();
+        }
+        if let Some(expected) = &self.sha256 {
+            let actual = pin::sha256_file(&path)?;
+            if actual != *expected {
+                return Err(Error::PinMismatch {
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(path)
+    }
+
+    /// Extracts the selected tables from the `.tar.gz` at `path`, sending the name of each csv
+    /// to `extracted` once it is complete.
+    fn extract_tar(
+        &self,
+        path: &Path,
+        report: &mut UpdateReport,
+        extracted: Option<&Sender<PathBuf>>,
+    ) -> Result<(), Error> {
+        let deadline = Deadline::start(Phase::Extract, self.extract_timeout);
+        self.extract_until(path, report, extracted, deadline)
+            .map_err(|e| deadline.or_timeout(e))
+            .or_else(|e| self.remove_extracted().and(Err(e)))
+    }
+
+    /// Removes the selected tables and the metadata after a failed extraction, so that what
+    /// was written before the failure isn't taken for an up to date extraction next time.
+    fn remove_extracted(&self) -> Result<(), Error> {
+        let metadata = Path::new(METADATA_FILE);
+        for file in self.files.iter().map(PathBuf::as_path).chain([metadata]) {
+            let path = self.target_path.join(file);
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(file_error(&path)(e)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_until(
+        &self,
+        path: &Path,
+        report: &mut UpdateReport,
+        extracted: Option<&Sender<PathBuf>>,
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        // Whoever is waiting for tables may have stopped, e.g. after failing to load one.
+        let announce = |file: &Path| {
+            if let (Some(extracted), Some(name)) = (extracted, file.file_name()) {
+                let _ = extracted.send(PathBuf::from(name));
+            }
+        };
+        let corrupt = |source: io::Error| match source.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData => Error::CorruptArchive {
+                path: path.to_path_buf(),
+                source,
+            },
+            _ => Error::BadArchiveEntry(source),
+        };
+
         // Extract files manually instead of letting cached_path do it so we don't have to worry about {date} folder.
-        let tar_gz = File::open(path)?;
-        let tar = GzDecoder::new(tar_gz);
-        let mut archive = tar::Archive::new(tar);
-
-        create_dir_all(&self.target_path)?;
-        for file in archive.entries().unwrap() {
-            let mut f = file.unwrap();
-            let aname = match f.path().unwrap_or_default().file_name() {
+        let tar_gz = File::open(path).map_err(file_error(path))?;
+        let tar = read_ahead::read_ahead(GzDecoder::new(tar_gz));
+        let mut archive = tar::Archive::new(deadline.reader(tar));
+
+        for file in archive.entries().map_err(corrupt)? {
+            let mut f = file.map_err(corrupt)?;
+            let entry = f.path().map_err(Error::BadArchiveEntry)?.into_owned();
+            let aname = match entry.file_name() {
                 Some(p) => PathBuf::from(p),
                 None => PathBuf::default(),
             };
-            if self.files.contains(&aname) {
-                f.unpack(self.target_path.join(aname))?;
+            if self.wants_entry(&entry, &aname) || aname == Path::new(METADATA_FILE) {
+                if aname != Path::new(METADATA_FILE) {
+                    self.emit(LoadEvent::Extracting {
+                        table: aname
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into(),
+                    });
+                }
+                let destination = self.target_path.join(&aname);
+                let started = Instant::now();
+                let unpacked = match self.downloads_since_day() {
+                    Some(since) if aname == Path::new(downloads_since::DOWNLOADS_FILE) => {
+                        downloads_since::copy_downloads_since(f, &destination, since)
+                    }
+                    _ => f.unpack(&destination).map(|_| ()),
+                };
+                unpacked.map_err(|e| match corrupt(e) {
+                    Error::BadArchiveEntry(source) => file_error(&destination)(source),
+                    e => e,
+                })?;
+                report
+                    .extracted
+                    .push((aname.to_string_lossy().into(), started.elapsed()));
+                if aname != Path::new(METADATA_FILE) {
+                    announce(&aname);
+                }
+            } else if aname.extension() == Some("sql".as_ref()) {
+                let written =
+                    pgdump::copy_blocks_to_csv(BufReader::new(f), &self.target_path, &self.files)?;
+                self.extracted_from_sql(&written)?;
+                written.iter().for_each(|file| announce(file));
             }
         }
-        Ok(self)
+        Ok(())
+    }
+
+    fn check_table_names(&self) -> Result<(), Error> {
+        let files = self
+            .files
+            .iter()
+            .map(|f| f.file_stem().unwrap_or_default().to_string_lossy());
+        let custom = self.custom_tables.iter().map(|t| t.name.as_str().into());
+        for table in files.chain(custom) {
+            if !sql::valid_table_name(&table) {
+                return Err(Error::InvalidTableName(table.into()));
+            }
+        }
+        Ok(())
+    }
+
+    fn first_file(&self) -> Result<&PathBuf, Error> {
+        self.files.first().ok_or(Error::EmptyTableList)
     }
 
     pub fn sqlite_path(&self) -> PathBuf {
         self.target_path.join(Path::new("db.sqlite"))
     }
 
+    /// Opens `sqlite_path()` in WAL mode, loading the dump into it first if needed.
+    ///
+    /// Loads are built in `db.sqlite.new` and renamed over `db.sqlite` once complete, so other
+    /// processes reading the database never see half-loaded tables. Connections opened before a
+    /// reload keep reading the previous dump until they are reopened. Processes sharing
+    /// `target_path` load one at a time, so only the first loads and the rest wait for it.
+    /// A load that is missing tables is never swapped in, even with `continue_on_error()`; the
+    /// first table's error is returned instead.
     pub fn open_db(&mut self) -> Result<Connection, Error> {
         let path = self.sqlite_path();
+        // Checked under the lock, so a process waiting on another's load finds it done.
+        let lock = self.lock_target()?;
 
-        let mut should_load = false;
-        let first_local_file = self.target_path.join(self.files.first().unwrap());
-        if !path.exists() {
-            should_load = true;
-        } else if !first_local_file.exists()
-            && path.exists()
-            && path.metadata()?.created()? <= first_local_file.metadata()?.created()?
-        {
-            should_load = true;
-            std::fs::remove_file(&path)?;
+        let first_local_file = self.target_path.join(self.first_file()?);
+        let should_load = !path.exists()
+            || (!first_local_file.exists()
+                && path.metadata()?.created()? <= first_local_file.metadata()?.created()?);
+
+        if should_load {
+            self.rebuild_db()?;
         }
+        drop(lock);
+        self.open_connection()
+    }
+
+    /// Loads the dump into `sqlite_path()` even if it is already loaded, swapping it in like
+    /// `open_db()` does, and opens it.
+    pub fn reload(&mut self) -> Result<Connection, Error> {
+        let lock = self.lock_target()?;
+        self.rebuild_db()?;
+        drop(lock);
+        self.open_connection()
+    }
 
+    /// Opens `sqlite_path()` like `open_db()` does, without ever loading the dump, failing with
+    /// [`Error::NotMaterialized`] when it hasn't been loaded yet. It takes `&self`, so worker
+    /// threads can share one loader, e.g. in an `Arc`, and call it concurrently once `open_db()`
+    /// has run.
+    pub fn open_connection(&self) -> Result<Connection, Error> {
+        let path = self.sqlite_path();
+        if !path.exists() {
+            return Err(Error::NotMaterialized(path));
+        }
         let db = Connection::open(&path)?;
+        db.busy_timeout(self.busy_timeout)?;
+        db.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+        db.query_row(
+            &format!("PRAGMA mmap_size = {}", self.mmap_size),
+            [],
+            |_| Ok(()),
+        )?;
         rusqlite::vtab::csvtab::load_module(&db)?;
+        Ok(db)
+    }
 
-        if should_load {
-            self.load_dump_into(&db)?;
+    /// Loads the dump into `db.sqlite.new` and swaps it in for `sqlite_path()`.
+    fn rebuild_db(&mut self) -> Result<(), Error> {
+        let path = self.sqlite_path();
+        let staging = path.with_extension("sqlite.new");
+        for leftover in [staging.clone(), staging.with_extension("new-journal")] {
+            if leftover.exists() {
+                std::fs::remove_file(&leftover).map_err(file_error(&leftover))?;
+            }
         }
-        Ok(db)
+
+        #[cfg(feature = "db-cache")]
+        let cached = self.cached_db()?;
+        #[cfg(feature = "db-cache")]
+        let restored = db_cache::restore(cached.as_deref(), &staging)?;
+        #[cfg(not(feature = "db-cache"))]
+        let restored = false;
+        if !restored {
+            {
+                let db = Connection::open(&staging)?;
+                // Only takes effect before the first table is written.
+                db.execute_batch(&format!("PRAGMA page_size = {};", self.page_size))?;
+                rusqlite::vtab::csvtab::load_module(&db)?;
+                let mut report = self.load_dump_into(&db)?;
+                // Only whole dumps get swapped in or cached.
+                if !report.is_ok() {
+                    drop(db);
+                    std::fs::remove_file(&staging).map_err(file_error(&staging))?;
+                    return Err(report.failed.remove(0).1);
+                }
+            }
+            #[cfg(feature = "db-cache")]
+            if let Some(cached) = &cached {
+                db_cache::store(&staging, cached)?;
+            }
+        }
+
+        // Empty the old database's WAL, the new one would otherwise pick it up by name. A
+        // reader holding a snapshot keeps the WAL in place, so wait for it rather than swap.
+        if path.exists() {
+            let old = Connection::open(&path)?;
+            old.busy_timeout(self.busy_timeout)?;
+            let waiting = Instant::now();
+            loop {
+                let busy: i64 =
+                    old.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+                if busy == 0 {
+                    break;
+                }
+                if waiting.elapsed() >= self.busy_timeout {
+                    return Err(Error::DatabaseBusy(path));
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+        std::fs::rename(&staging, &path).map_err(file_error(&path))
     }
 
-    pub fn load_dump_into(&mut self, db: &Connection) -> Result<(), Error> {
-        let schema = self
-            .files
-            .iter()
-            .map(|f| self.file_to_query(f))
-            .fold(String::new(), |a, b| a + b.as_str() + "\n");
-        db.execute_batch(schema.as_str())?;
+    pub fn load_dump_into(&mut self, db: &Connection) -> Result<LoadReport, Error> {
+        let deadline = Deadline::start(Phase::Load, self.load_timeout);
+        let _interrupt = deadline.interrupt(db);
+        self.load_until(db, deadline)
+            .map_err(|e| deadline.or_timeout(e))
+    }
+
+    fn load_until(&mut self, db: &Connection, deadline: Deadline) -> Result<LoadReport, Error> {
+        self.check_table_names()?;
+        self.check_schema_sql()?;
+        self.check_table_schemas(false)?;
+        self.limit_memory(db, self.loading_connections())?;
+        let loading = Instant::now();
+        let mut report = LoadReport {
+            update: self.update_report.clone(),
+            ..LoadReport::default()
+        };
+        if self.lazy {
+            if self.growth_stats {
+                self.with_tables(db, stats::materialize_growth)?;
+                report.loaded.push("growth_monthly".into());
+            }
+            if self.category_stats {
+                self.with_tables(db, stats::materialize_category_stats)?;
+                report.loaded.push("category_stats".into());
+            }
+            return Ok(report);
+        }
+        let mut files: Vec<&PathBuf> = self.files.iter().collect();
+        if self.sample_rows.is_some() {
+            files.sort_by_key(|f| {
+                sampling::load_order(&f.file_stem().unwrap_or_default().to_string_lossy())
+            });
+        }
+        let custom_files = self.write_custom_tables(&mut report)?;
+        files.extend(custom_files.iter());
+        let mut parallel = match self.parallel_preload() {
+            true => Some(self.preload_parallel(db, &files)?.into_iter()),
+            false => None,
+        };
+        for file in files {
+            deadline.check()?;
+            let table: String = file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into();
+            let started = Instant::now();
+            let result = parallel
+                .as_mut()
+                .and_then(Iterator::next)
+                .unwrap_or_else(|| self.load_file(db, file, &table));
+            self.record_table(db, table, started, result, deadline, &mut report)?;
+        }
+        self.finish_load(db, report, loading)
+    }
+
+    fn write_custom_tables(&self, report: &mut LoadReport) -> Result<Vec<PathBuf>, Error> {
+        let mut custom_files = Vec::new();
+        for table in self.custom_tables.iter() {
+            match table.write(&self.target_path) {
+                Ok(_) => custom_files.push(table.file_name()),
+                Err(e) if self.continue_on_error => report.failed.push((table.name.clone(), e)),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(custom_files)
+    }
+
+    /// Reports how loading `table` went, failing unless `continue_on_error()` allows it.
+    fn record_table(
+        &self,
+        db: &Connection,
+        table: String,
+        started: Instant,
+        result: Result<(), Error>,
+        deadline: Deadline,
+        report: &mut LoadReport,
+    ) -> Result<(), Error> {
+        match result {
+            Ok(()) => {
+                if let Some(drift) = self.table_drift(&table)? {
+                    self.emit(LoadEvent::SchemaDrift(drift.clone()));
+                    report.drift.push(drift);
+                }
+                let stats = TableStats {
+                    table: table.clone(),
+                    rows: self.preloaded_rows(db, &table)?,
+                    duration: started.elapsed(),
+                };
+                self.emit(LoadEvent::TableLoaded {
+                    table: stats.table.clone(),
+                    rows: stats.rows,
+                    duration: stats.duration,
+                });
+                report.tables.push(stats);
+                report.loaded.push(table)
+            }
+            Err(e) if self.continue_on_error && deadline.check().is_ok() => {
+                // Don't leave the staging table of a failed preload behind.
+                let staging = sql::identifier(&format!("temp_{}", table));
+                let _ = db.execute_batch(&format!("DROP TABLE IF EXISTS {};", staging));
+                report.failed.push((table, e));
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Reports how materializing the summary table `table` went, failing unless
+    /// `continue_on_error()` allows it.
+    fn record_summary(
+        &self,
+        table: &str,
+        result: Result<(), Error>,
+        report: &mut LoadReport,
+    ) -> Result<(), Error> {
+        match result {
+            Ok(()) => report.loaded.push(table.into()),
+            Err(e) if self.continue_on_error => report.failed.push((table.into(), e)),
+            Err(e) => return Err(e),
+        }
         Ok(())
     }
 
-    fn file_to_query(&self, path: &PathBuf) -> String {
+    /// What's left after loading the tables: summary tables, index presets and views.
+    fn finish_load(
+        &self,
+        db: &Connection,
+        mut report: LoadReport,
+        loading: Instant,
+    ) -> Result<LoadReport, Error> {
+        if self.growth_stats {
+            let growth = stats::materialize_growth(db);
+            self.record_summary("growth_monthly", growth, &mut report)?;
+        }
+        if self.category_stats {
+            let categories = stats::materialize_category_stats(db);
+            self.record_summary("category_stats", categories, &mut report)?;
+        }
+        self.convert_timestamps(db, &report.loaded)?;
+        for preset in &self.index_presets {
+            preset.create(db)?;
+        }
+        self.create_views(db)?;
+        report.db_bytes = db.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? as u64;
+        report.duration = loading.elapsed();
+        Ok(report)
+    }
+
+    /// Rows in `table` if it was preloaded, see [`TableStats::rows`].
+    fn preloaded_rows(&self, db: &Connection, table: &str) -> Result<Option<u64>, Error> {
+        if !self.preload && self.sample_rows.is_none() {
+            return Ok(None);
+        }
+        let sql = format!("SELECT COUNT(*) FROM {}", sql::identifier(table));
+        let rows: i64 = db.query_row(&sql, [], |row| row.get(0))?;
+        Ok(Some(rows as u64))
+    }
+
+    fn load_file(&self, db: &Connection, file: &PathBuf, table: &str) -> Result<(), Error> {
+        if self.direct_preload() {
+            return self.load_direct(db, file, table);
+        }
+        let sql = self.file_to_query(file)?;
+        db.execute_batch(&sql).map_err(|source| Error::TableError {
+            table: table.to_string(),
+            sql: sql.trim().to_string(),
+            source,
+        })
+    }
+
+    fn file_to_query(&self, path: &PathBuf) -> Result<String, Error> {
         let actual_file = self.target_path.join(path);
         let table = path.file_stem().unwrap_or_default().to_string_lossy();
-        let vtable = match self.preload {
-            true => format!("temp_{}", table),
-            false => table.to_string(),
+        let preload = self.preload || self.sample_rows.is_some();
+        let vtable = match preload {
+            true => sql::identifier(&format!("temp_{}", table)),
+            false => sql::identifier(&table),
         };
+        let filename = sql::csvtab_argument(&sql::csvtab_path(&actual_file))?;
 
-        let vtab = match self.table_schema.get(&table.to_string()) {
+        let vtab = match self.schema_for(&table)? {
             Some(schema) => format!(
                 r#"
                     DROP TABLE IF EXISTS {0};
-                    CREATE VIRTUAL TABLE {0} USING csv(filename='{1}',header=yes,schema='{2}');
+                    CREATE VIRTUAL TABLE {0} USING csv(filename={1},header=yes,schema={2});
                 "#,
                 vtable,
-                actual_file.display(),
-                schema,
+                filename,
+                sql::csvtab_argument(&schema)?,
             ),
             None => format!(
                 r#"
                     DROP TABLE IF EXISTS {0};
-                    CREATE VIRTUAL TABLE {0} USING csv(filename='{1}',header=yes);
+                    CREATE VIRTUAL TABLE {0} USING csv(filename={1},header=yes);
                 "#,
-                vtable,
-                actual_file.display(),
+                vtable, filename,
             ),
         };
 
-        if self.preload {
+        if preload {
+            let filter = match self.sample_rows {
+                Some(rows) => {
+                    let loaded: Vec<String> = self
+                        .files
+                        .iter()
+                        .map(|f| f.file_stem().unwrap_or_default().to_string_lossy().into())
+                        .collect();
+                    let order = match self.seed {
+                        Some(seed) => format!(" ORDER BY {}", sampling::shuffle(seed)),
+                        None => String::new(),
+                    };
+                    format!(
+                        " WHERE {}{} LIMIT {}",
+                        sampling::filter(&table, &loaded),
+                        order,
+                        rows
+                    )
+                }
+                None => String::new(),
+            };
             let ptab = format!(
                 r#"
                     DROP TABLE IF EXISTS {0};
-                    CREATE TABLE {0} AS SELECT * FROM {1};
+                    CREATE TABLE {0} AS SELECT {3} FROM {1}{2};
                     DROP TABLE {1};
                 "#,
-                table, vtable,
+                sql::identifier(&table),
+                vtable,
+                filter,
+                match self.table_columns.get(&table.to_string()) {
+                    Some(columns) => columns
+                        .iter()
+                        .map(|c| sql::identifier(c))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    None => "*".to_string(),
+                },
             );
 
-            return format!("{}\n{}", vtab, ptab);
+            return Ok(format!("{}\n{}", vtab, ptab));
         }
 
-        vtab
+        Ok(vtab)
+    }
+}
+
+fn default_cache(builder: CacheBuilder, fallback: &Path) -> Result<Cache, Error> {
+    match builder.build() {
+        Ok(cache) => Ok(cache),
+        Err(_) => Ok(Cache::builder().dir(fallback.to_path_buf()).build()?),
+    }
+}
+
+/// Attaches `path` to IO errors.
+pub(crate) fn file_error(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::FileError {
+        path: path.to_path_buf(),
+        source,
     }
 }
 
-fn tables_to_files(tables: &[&str]) -> Vec<PathBuf> {
+pub(crate) fn table_exists(db: &Connection, table: &str) -> Result<bool, Error> {
+    let count: i64 = db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn tables_to_files(tables: &[Table]) -> Vec<PathBuf> {
     tables
         .iter()
         .map(|t| {
             let mut buf = PathBuf::new();
-            buf.set_file_name(t);
+            buf.set_file_name(t.name());
             buf.set_extension("csv");
             buf
         })
@@ -278,4 +1356,403 @@ fn test_basic_csvtab_open() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_open_db_partial_load() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/partial-open");
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test", "missing"])
+        .continue_on_error(true)
+        .preload(true)
+        .cache(Cache::builder().progress_bar(None))?
+        .build()?;
+    loader.update()?;
+
+    let result = loader.open_db();
+    assert!(matches!(result, Err(Error::TableError { ref table, .. }) if table == "missing"));
+    assert!(!loader.sqlite_path().exists());
+    assert!(!target.join("db.sqlite.new").exists());
+    Ok(())
+}
+
+#[test]
+fn test_reload() -> Result<(), Error> {
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/reload"))
+        .tables(&["test"])
+        .preload(true)
+        .cache(Cache::builder().progress_bar(None))?
+        .build()?;
+    let count =
+        |db: &Connection| db.query_row("SELECT COUNT(*) FROM test", [], |r| r.get::<_, i64>(0));
+    let reader = loader.update()?.open_db()?;
+    reader.execute("DELETE FROM test", [])?;
+    assert_eq!(0, count(&reader)?);
+
+    // Readers keep the database they opened, and a new connection sees the fresh load.
+    let db = loader.reload()?;
+    assert_eq!(0, count(&reader)?);
+    assert!(count(&db)? > 0);
+    assert!(!loader.target_path.join("db.sqlite.new").exists());
+    Ok(())
+}
+
+#[test]
+fn test_pgdump_resource() -> Result<(), Error> {
+    // Setup cache.
+    let cache = Cache::builder().progress_bar(None);
+
+    // Load dump from COPY blocks in a plain pg_dump file.
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .preload(true)
+        .resource("testdata/test.sql")
+        .target_path(Path::new("testdata/extracted/pgdump"))
+        .tables(&["test"])
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let mut s = db.prepare("SELECT id FROM test WHERE name = ?")?;
+    let dummy = s.query_row(["awooo"], |row| row.get::<_, String>(0))?;
+    assert_eq!("3", dummy);
+    let count: i64 = db.query_row("SELECT COUNT(*) FROM test", [], |row| row.get(0))?;
+    assert_eq!(4, count);
+    Ok(())
+}
+
+#[test]
+fn test_error_context() -> Result<(), Error> {
+    let cache = || Cache::builder().progress_bar(None);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let result = CratesIODumpLoader::default()
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/error-context"))
+        .tables(&["test", "missing"])
+        .cache(cache())?
+        .update()?
+        .load_dump_into(&db);
+    match result {
+        Err(Error::TableError { table, sql, .. }) => {
+            assert_eq!("missing", table);
+            assert!(sql.contains("missing.csv"));
+        }
+        other => panic!("expected a table error, got {:?}", other),
+    }
+
+    let report = CratesIODumpLoader::default()
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/error-context"))
+        .tables(&["missing", "test"])
+        .continue_on_error(true)
+        .preload(true)
+        .cache(cache())?
+        .load_dump_into(&db)?;
+    assert!(!report.is_ok());
+    assert_eq!(vec!["test"], report.loaded);
+    assert!(matches!(
+        report.error("missing"),
+        Some(Error::TableError { .. })
+    ));
+    let leftovers: i64 = db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE name = 'temp_missing'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(0, leftovers);
+
+    let result = CratesIODumpLoader::default()
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/test.csv/extracted"))
+        .cache(cache())?
+        .update()
+        .map(|_| ());
+    match result {
+        Err(Error::FileError { path, .. }) => {
+            assert_eq!(Path::new("testdata/test.csv/extracted"), path)
+        }
+        other => panic!("expected a file error, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_update_edge_cases() -> Result<(), Error> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let cache = || Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/edge-cases");
+
+    let mut empty = CratesIODumpLoader::default();
+    empty
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables::<Table>(&[])
+        .cache(cache())?;
+    assert!(matches!(empty.update(), Err(Error::EmptyTableList)));
+    assert!(matches!(empty.open_db(), Err(Error::EmptyTableList)));
+
+    std::fs::create_dir_all(target)?;
+    let garbage = target.join("garbage.tar.gz");
+    let mut encoder = GzEncoder::new(File::create(&garbage)?, Compression::fast());
+    encoder.write_all(&[b'x'; 1024])?;
+    encoder.finish()?;
+    let result = CratesIODumpLoader::default()
+        .resource(&garbage.to_string_lossy())
+        .target_path(&target.join("garbage"))
+        .tables(&["test"])
+        .cache(cache())?
+        .update()
+        .map(|_| ());
+    assert!(matches!(result, Err(Error::BadArchiveEntry(_))));
+    Ok(())
+}
+
+#[test]
+fn test_quoted_paths_and_names() -> Result<(), Error> {
+    let cache = || Cache::builder().progress_bar(None);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .preload(true)
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/it's quoted"))
+        .tables(&["test"])
+        .table_schema("test", "CREATE TABLE x(id INT, name TEXT DEFAULT 'none');")
+        .cache(cache())?
+        .update()?
+        .load_dump_into(&db)?;
+    let name: String = db.query_row("SELECT name FROM test WHERE id = 3", [], |row| row.get(0))?;
+    assert_eq!("awooo", name);
+
+    let result = CratesIODumpLoader::default()
+        .resource("testdata/test.tar.gz")
+        .target_path(Path::new("testdata/extracted/quoting"))
+        .tables(&["test; DROP TABLE crates"])
+        .cache(cache())?
+        .update()
+        .map(|_| ());
+    assert!(matches!(result, Err(Error::InvalidTableName(_))));
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_archive_refetch() -> Result<(), Error> {
+    use std::{
+        io::{BufRead, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    let cache = |dir: &Path| Cache::builder().dir(dir.to_path_buf()).progress_bar(None);
+    let target = Path::new("testdata/extracted/corrupt");
+    let _ = std::fs::remove_dir_all(target);
+    std::fs::create_dir_all(target)?;
+    let archive = std::fs::read("testdata/test.tar.gz")?;
+    let truncated = &archive[..archive.len() / 2];
 
+    let local = target.join("truncated.tar.gz");
+    std::fs::write(&local, truncated)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource(&local.to_string_lossy())
+        .target_path(&target.join("local"))
+        .tables(&["test"])
+        .cache(cache(&target.join("cache")))?;
+    let result = loader.update().map(|_| ());
+    assert!(matches!(result, Err(Error::CorruptArchive { .. })));
+    assert!(!target.join("local/test.csv").exists());
+    // Nothing left behind passes for an up to date extraction.
+    let result = loader.update().map(|_| ());
+    assert!(matches!(result, Err(Error::CorruptArchive { .. })));
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/db-dump.tar.gz", listener.local_addr()?);
+    let bodies = [truncated.to_vec(), archive.clone()];
+    let server = thread::spawn(move || -> std::io::Result<usize> {
+        let mut gets = 0;
+        for stream in listener.incoming() {
+            let mut stream = stream?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let mut request = String::new();
+            reader.read_line(&mut request)?;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            let body = &bodies[gets.min(1)];
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nETag: \"a\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )?;
+            if request.starts_with("GET") {
+                stream.write_all(body)?;
+                gets += 1;
+                if gets == bodies.len() {
+                    return Ok(gets);
+                }
+            }
+        }
+        Ok(gets)
+    });
+
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource(&url)
+        .target_path(&target.join("remote"))
+        .tables(&["test"])
+        .cache(cache(&target.join("cache")))?
+        .update()?;
+    assert!(target.join("remote/test.csv").exists());
+    assert_eq!(
+        (truncated.len() + archive.len()) as u64,
+        loader.update_report().downloaded_bytes
+    );
+    assert_eq!(2, server.join().unwrap()?);
+    Ok(())
+}
+
+#[test]
+fn test_reload_with_open_readers() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/reload");
+    let _ = std::fs::remove_dir_all(target);
+    let marker = |value: &str| {
+        let mut table = TableWriter::new("marker", &["value"]);
+        table.row(&[value]);
+        table
+    };
+
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .preload(true)
+        .custom_table(marker("old"))
+        .cache(Cache::builder().progress_bar(None))?
+        .update()?;
+    let db = loader.open_db()?;
+    let mode: String = db.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    assert_eq!("wal", mode);
+    let page_size: i64 = db.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    assert_eq!(32 * 1024, page_size);
+    let mmap_size: i64 = db.query_row("PRAGMA mmap_size", [], |row| row.get(0))?;
+    assert_eq!(1 << 30, mmap_size);
+
+    let reader = Connection::open(loader.sqlite_path())?;
+    reader.execute_batch("BEGIN")?;
+    let marker_value = |db: &Connection| -> Result<String, Error> {
+        Ok(db.query_row("SELECT value FROM marker", [], |row| row.get(0))?)
+    };
+    assert_eq!("old", marker_value(&reader)?);
+
+    loader
+        .custom_table(marker("new"))
+        .page_size(4096)
+        .rebuild_db()?;
+    assert_eq!("old", marker_value(&reader)?);
+    let db = loader.mmap_size(0).open_db()?;
+    assert_eq!("new", marker_value(&db)?);
+    let page_size: i64 = db.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    assert_eq!(4096, page_size);
+    assert!(!target.join("db.sqlite.new").exists());
+
+    // A snapshot older than the WAL's last frames keeps it from being truncated.
+    db.execute_batch("INSERT INTO marker VALUES ('first')")?;
+    let reader = loader.open_connection()?;
+    reader.execute_batch("BEGIN")?;
+    assert_eq!("new", marker_value(&reader)?);
+    db.execute_batch("INSERT INTO marker VALUES ('second')")?;
+    assert!(matches!(
+        loader.busy_timeout(Duration::from_millis(100)).rebuild_db(),
+        Err(Error::DatabaseBusy(_))
+    ));
+    assert_eq!(
+        3,
+        db.query_row("SELECT COUNT(*) FROM marker", [], |row| row
+            .get::<_, i64>(0))?
+    );
+    reader.execute_batch("COMMIT")?;
+    loader.rebuild_db()?;
+    let db = loader.open_connection()?;
+    assert_eq!(
+        1,
+        db.query_row("SELECT COUNT(*) FROM marker", [], |row| row
+            .get::<_, i64>(0))?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_shared_loader() -> Result<(), Error> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CratesIODumpLoader>();
+
+    let target = Path::new("testdata/extracted/shared");
+    let _ = std::fs::remove_dir_all(target);
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .preload(true)
+        .cache(Cache::builder().progress_bar(None))?
+        .update()?;
+    assert!(matches!(
+        loader.open_connection(),
+        Err(Error::NotMaterialized(_))
+    ));
+    drop(loader.open_db()?);
+
+    let loader = Arc::new(loader);
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let loader = Arc::clone(&loader);
+            std::thread::spawn(move || -> Result<i64, Error> {
+                let db = loader.open_connection()?;
+                Ok(db.query_row("SELECT COUNT(*) FROM test", [], |row| row.get(0))?)
+            })
+        })
+        .collect();
+    for worker in workers {
+        assert!(worker.join().unwrap()? > 0);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lazy_cache() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/lazy-cache");
+    let _ = std::fs::remove_dir_all(target);
+    std::fs::create_dir_all(target)?;
+
+    let blocker = target.join("not-a-dir");
+    File::create(&blocker)?;
+    let unwritable = Cache::builder().dir(blocker.join("cache"));
+    let cache = default_cache(unwritable, &target.join(".cache"))?;
+    assert_eq!(target.join(".cache"), cache.dir);
+    assert!(target.join(".cache").is_dir());
+
+    let mut loader = CratesIODumpLoader::try_new()?;
+    assert!(loader
+        .cache(Cache::builder().dir(blocker.join("cache")))
+        .is_err());
+    loader
+        .resource("testdata/test.tar.gz")
+        .target_path(&target.join("loaded"))
+        .tables(&["test"])
+        .update()?;
+    assert!(target.join("loaded/test.csv").exists());
+    Ok(())
+}
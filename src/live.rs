@@ -0,0 +1,246 @@
+//! The `live` feature: the snapshot's view of a crate, refreshed from the crates.io API.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    blocking::Client,
+    header::{RETRY_AFTER, USER_AGENT},
+    StatusCode,
+};
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::{
+    rows::{crate_by_name, Crate, Version},
+    Error,
+};
+
+/// A crate as of the dump, with whatever the API knew better when asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FreshCrate {
+    /// Live all-time downloads. The id is 0 for crates published after the dump.
+    pub krate: Crate,
+    /// Every version, oldest first, with live downloads and yanked flags. Versions published
+    /// after the dump have the ids crates.io gives them.
+    pub versions: Vec<Version>,
+    /// Whether the crate was found on crates.io, if not this is just the snapshot.
+    pub live: bool,
+}
+
+/// Asks the crates.io API about single crates, at most once per `min_interval` as its crawler
+/// policy asks.
+pub struct LiveClient {
+    pub api: String,
+    /// crates.io rejects requests without one, and wants it to say how to reach you.
+    pub user_agent: String,
+    pub min_interval: Duration,
+    /// How often a rate limited request is retried, after the `Retry-After` it was given.
+    pub retries: u32,
+
+    client: Client,
+    last_request: Option<Instant>,
+}
+
+impl Default for LiveClient {
+    fn default() -> Self {
+        Self {
+            api: "https://crates.io/api/v1".to_string(),
+            user_agent: format!(
+                "cratesio-dbdump-csvtab/{} (+{})",
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_REPOSITORY")
+            ),
+            min_interval: Duration::from_secs(1),
+            retries: 3,
+            client: Client::new(),
+            last_request: None,
+        }
+    }
+}
+
+/// An API timestamp like `2024-01-01T00:00:00.000000+00:00` in the dump's format.
+fn dump_timestamp(timestamp: &str) -> String {
+    timestamp
+        .trim_end_matches("+00:00")
+        .trim_end_matches('Z')
+        .replacen('T', " ", 1)
+}
+
+impl LiveClient {
+    pub fn api(&mut self, url: &str) -> &mut Self {
+        self.api = url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub fn user_agent(&mut self, user_agent: &str) -> &mut Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    pub fn min_interval(&mut self, interval: Duration) -> &mut Self {
+        self.min_interval = interval;
+        self
+    }
+
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The API's `crates/{name}` response, `None` when crates.io doesn't know the crate.
+    pub fn fetch_crate(&mut self, name: &str) -> Result<Option<Value>, Error> {
+        let url = format!("{}/crates/{}", self.api, name);
+        let mut retries = self.retries;
+        loop {
+            if let Some(elapsed) = self.last_request.map(|last| last.elapsed()) {
+                if elapsed < self.min_interval {
+                    thread::sleep(self.min_interval - elapsed);
+                }
+            }
+            self.last_request = Some(Instant::now());
+            let response = self
+                .client
+                .get(&url)
+                .header(USER_AGENT, &self.user_agent)
+                .send()?;
+            match response.status() {
+                StatusCode::NOT_FOUND => return Ok(None),
+                StatusCode::TOO_MANY_REQUESTS if retries > 0 => {
+                    retries -= 1;
+                    let wait = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok()?.parse().ok())
+                        .map_or(self.min_interval, Duration::from_secs);
+                    thread::sleep(wait);
+                }
+                _ => {
+                    return Ok(Some(serde_json::from_str(
+                        &response.error_for_status()?.text()?,
+                    )?))
+                }
+            }
+        }
+    }
+
+    /// `name` as of the dump loaded into `db`, with downloads, yanked flags and the versions
+    /// published since taken from the API. `None` when neither knows the crate.
+    pub fn crate_by_name_fresh(
+        &mut self,
+        db: &Connection,
+        name: &str,
+    ) -> Result<Option<FreshCrate>, Error> {
+        let snapshot = crate_by_name(db, name)?;
+        let live = self.fetch_crate(name)?;
+        Ok(overlay(snapshot, live.as_ref()))
+    }
+}
+
+fn overlay(snapshot: Option<(Crate, Vec<Version>)>, live: Option<&Value>) -> Option<FreshCrate> {
+    let (mut krate, mut versions) = match (snapshot, live) {
+        (Some(snapshot), _) => snapshot,
+        (None, None) => return None,
+        (None, Some(live)) => {
+            let text = |key: &str| live["crate"][key].as_str().unwrap_or_default().to_string();
+            let krate = Crate {
+                id: 0,
+                name: text("name"),
+                description: text("description"),
+                repository: text("repository"),
+                downloads: 0,
+                created_at: dump_timestamp(&text("created_at")),
+                updated_at: dump_timestamp(&text("updated_at")),
+            };
+            (krate, Vec::new())
+        }
+    };
+    let live = match live {
+        Some(live) => live,
+        None => {
+            return Some(FreshCrate {
+                krate,
+                versions,
+                live: false,
+            })
+        }
+    };
+
+    if let Some(downloads) = live["crate"]["downloads"].as_i64() {
+        krate.downloads = downloads;
+    }
+    if let Some(updated_at) = live["crate"]["updated_at"].as_str() {
+        krate.updated_at = dump_timestamp(updated_at);
+    }
+    for version in live["versions"].as_array().into_iter().flatten() {
+        let num = version["num"].as_str().unwrap_or_default();
+        let downloads = version["downloads"].as_i64().unwrap_or_default();
+        let yanked = version["yanked"].as_bool().unwrap_or_default();
+        match versions.iter_mut().find(|v| v.num == num) {
+            Some(known) => {
+                known.downloads = downloads;
+                known.yanked = yanked;
+            }
+            None => versions.push(Version {
+                id: version["id"].as_i64().unwrap_or_default(),
+                crate_id: krate.id,
+                num: num.to_string(),
+                license: version["license"].as_str().unwrap_or_default().to_string(),
+                downloads,
+                yanked,
+                created_at: dump_timestamp(version["created_at"].as_str().unwrap_or_default()),
+            }),
+        }
+    }
+    versions.sort_by_key(|v| v.id);
+    Some(FreshCrate {
+        krate,
+        versions,
+        live: true,
+    })
+}
+
+#[test]
+fn test_overlay() -> Result<(), Error> {
+    use serde_json::json;
+
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .build()?;
+    let live = json!({
+        "crate": {"name": "serde", "downloads": 1000, "updated_at": "2024-01-02T03:04:05.000000+00:00"},
+        "versions": [
+            {"id": 999, "num": "1.0.2", "downloads": 10, "yanked": false, "license": "MIT", "created_at": "2024-01-02T03:04:05.000000+00:00"},
+            {"id": 2, "num": "1.0.1", "downloads": 400, "yanked": true},
+        ],
+    });
+
+    let fresh = overlay(crate_by_name(&db, "serde")?, Some(&live)).unwrap();
+    assert!(fresh.live);
+    assert_eq!(1000, fresh.krate.downloads);
+    assert_eq!("2024-01-02 03:04:05.000000", fresh.krate.updated_at);
+    assert_eq!(
+        vec![("1.0.0", false), ("1.0.1", true), ("1.0.2", false)],
+        fresh
+            .versions
+            .iter()
+            .map(|v| (v.num.as_str(), v.yanked))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        (400, fresh.krate.id),
+        (fresh.versions[1].downloads, fresh.versions[2].crate_id)
+    );
+
+    let offline = overlay(crate_by_name(&db, "serde")?, None).unwrap();
+    assert!(!offline.live && offline.versions.len() == 2);
+    assert_eq!(None, overlay(crate_by_name(&db, "nope")?, None));
+    let published_since = overlay(None, Some(&live)).unwrap();
+    assert_eq!(
+        (0, 2),
+        (published_since.krate.id, published_since.versions.len())
+    );
+    Ok(())
+}
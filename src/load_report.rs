@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::{Error, SchemaDrift};
+
+/// What the last `update()` did, see [`CratesIODumpLoader::update_report`].
+///
+/// [`CratesIODumpLoader::update_report`]: crate::CratesIODumpLoader::update_report
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpdateReport {
+    /// Size of the archive if it was downloaded rather than found in the cache.
+    pub downloaded_bytes: u64,
+    /// Files extracted from the archive, with how long each took. A pg_dump is extracted in
+    /// one go, so it is listed once under its own file name.
+    pub extracted: Vec<(String, Duration)>,
+    pub duration: Duration,
+}
+
+/// A table loaded by `load_dump_into()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableStats {
+    pub table: String,
+    /// Only counted for preloaded tables, since counting a virtual table reads its whole csv.
+    pub rows: Option<u64>,
+    pub duration: Duration,
+}
+
+/// What `load_dump_into()` loaded. Only has failures with `continue_on_error(true)`, otherwise
+/// the first failure is returned as the error instead.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Tables that loaded, in load order.
+    pub loaded: Vec<String>,
+    /// Tables that didn't load, with why.
+    pub failed: Vec<(String, Error)>,
+    /// Rows and timings of the tables in `loaded`, in the same order.
+    pub tables: Vec<TableStats>,
+    /// Dump tables whose csv columns differ from [`crate::schema::TABLES`].
+    pub drift: Vec<SchemaDrift>,
+    /// The loader's last `update()`, if any.
+    pub update: UpdateReport,
+    /// Size of the database after loading.
+    pub db_bytes: u64,
+    pub duration: Duration,
+}
+
+impl LoadReport {
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// The error a table failed with, if it did.
+    pub fn error(&self, table: &str) -> Option<&Error> {
+        self.failed
+            .iter()
+            .find(|(t, _)| t == table)
+            .map(|(_, error)| error)
+    }
+}
+
+#[test]
+fn test_load_report_stats() -> Result<(), Error> {
+    use std::path::Path;
+
+    use rusqlite::Connection;
+
+    let target = Path::new("testdata/extracted/load-report");
+    let _ = std::fs::remove_dir_all(target);
+    let mut loader = crate::CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .minimal()
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?;
+    let update = loader.update_report().clone();
+    assert_eq!(0, update.downloaded_bytes);
+    assert!(update.extracted.iter().any(|(f, _)| f == "crates.csv"));
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let report = loader.load_dump_into(&db)?;
+    assert_eq!(update, report.update);
+    let crates = report.tables.iter().find(|t| t.table == "crates").unwrap();
+    assert_eq!(Some(6), crates.rows);
+    assert_eq!(report.loaded.len(), report.tables.len());
+    assert!(report.db_bytes > 0);
+
+    // Already extracted, so nothing is extracted again.
+    loader.update()?;
+    assert!(loader.update_report().extracted.is_empty());
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Coordination between processes sharing a `target_path`: only one extracts or loads the
+//! database at a time, and the others wait for it and then find the result up to date.
+//! Downloads into a shared cache are already locked by cached-path.
+
+use std::fs::{File, OpenOptions};
+
+use crate::{file_error, resource, CratesIODumpLoader, Error, LoadEvent};
+
+/// Name of the lock file in `target_path`.
+pub(crate) const LOCK_FILE: &str = ".dbdump.lock";
+
+/// Held while extracting or loading, released when dropped.
+pub(crate) struct TargetLock {
+    _file: File,
+}
+
+impl CratesIODumpLoader {
+    /// Locks `target_path`, creating it if needed, waiting for whoever holds the lock.
+    pub(crate) fn lock_target(&self) -> Result<TargetLock, Error> {
+        resource::create_target_dir(&self.target_path)?;
+        let path = self.target_path.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(file_error(&path))?;
+        if file.try_lock().is_err() {
+            self.emit(LoadEvent::WaitingForLock { path: path.clone() });
+            file.lock().map_err(file_error(&path))?;
+        }
+        Ok(TargetLock { _file: file })
+    }
+}
+
+#[test]
+fn test_target_lock() -> Result<(), Error> {
+    use std::{path::Path, sync::mpsc::RecvTimeoutError, thread, time::Duration};
+
+    let target = Path::new("testdata/extracted/lock");
+    let _ = std::fs::remove_dir_all(target);
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .minimal()
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+    let events = loader.events();
+
+    // Another process extracting.
+    let other = loader.clone().lock_target()?;
+    let waiting = loader.clone();
+    let (done, finished) = std::sync::mpsc::channel();
+    let worker = thread::spawn(move || {
+        let _ = done.send(
+            waiting
+                .fetch()
+                .and_then(|archive| waiting.extract(&archive)),
+        );
+    });
+    assert_eq!(
+        Err(RecvTimeoutError::Timeout),
+        finished
+            .recv_timeout(Duration::from_millis(300))
+            .map(|_| ())
+    );
+    assert!(events.try_iter().any(|e| e
+        == LoadEvent::WaitingForLock {
+            path: target.join(LOCK_FILE)
+        }));
+    drop(other);
+    assert_eq!(3, finished.recv().unwrap()?.len());
+    worker.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! The packages pinned in a `Cargo.lock`.
+
+use serde::Deserialize;
+
+use crate::Error;
+
+/// A package pinned in a `Cargo.lock`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+}
+
+impl LockedPackage {
+    pub fn is_crates_io(&self) -> bool {
+        match &self.source {
+            Some(source) => {
+                source == "registry+https://github.com/rust-lang/crates.io-index"
+                    || source == "sparse+https://index.crates.io/"
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+/// Reads the `[[package]]` tables of a lockfile.
+pub fn parse_lockfile(text: &str) -> Result<Vec<LockedPackage>, Error> {
+    let lockfile: Lockfile =
+        toml::from_str(text).map_err(|e| Error::InvalidLockfile(e.to_string()))?;
+    Ok(lockfile.package)
+}
+
+#[test]
+fn test_parse_lockfile() -> Result<(), Error> {
+    let packages = parse_lockfile(
+        r#"
+            version = 3
+
+            [[package]]
+            name = "local"
+            version = "0.1.0"
+            dependencies = [
+             "serde",
+            ]
+
+            [[package]]
+            name = "serde"
+            version = "1.0.1"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "abc"
+
+            [metadata]
+        "#,
+    )?;
+    assert_eq!(2, packages.len());
+    assert!(!packages[0].is_crates_io());
+    assert_eq!(
+        ("serde", "1.0.1", true),
+        (
+            packages[1].name.as_str(),
+            packages[1].version.as_str(),
+            packages[1].is_crates_io()
+        )
+    );
+
+    assert!(matches!(
+        parse_lockfile("[[package]]\nname = serde\n"),
+        Err(Error::InvalidLockfile(_))
+    ));
+    Ok(())
+}
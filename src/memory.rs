@@ -0,0 +1,86 @@
+use rusqlite::Connection;
+
+use crate::{CratesIODumpLoader, Error};
+
+/// The smallest page cache handed to a connection, below which SQLite thrashes for no gain.
+const MIN_CACHE_KIB: usize = 256;
+
+impl CratesIODumpLoader {
+    /// Keep SQLite's memory use while loading to roughly `bytes`.
+    ///
+    /// The loading connections get a page cache of at most `bytes` between them, split across
+    /// the `threads()` preloading in parallel and the one they are copied into, and sorts and
+    /// temporary tables spill to disk instead of memory. Loads get slower the tighter the
+    /// limit, as more of them goes through disk, and the connection passed to
+    /// `load_dump_into()` keeps both settings afterwards. A `:memory:` database still holds all
+    /// of its tables in memory whatever the limit, so use `open_db()` when memory is tight.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn memory_limit(&mut self, bytes: usize) -> &mut Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Applies `memory_limit()` to `db`, one of `connections` loading at once.
+    pub(crate) fn limit_memory(&self, db: &Connection, connections: usize) -> Result<(), Error> {
+        let bytes = match self.memory_limit {
+            Some(bytes) => bytes,
+            None => return Ok(()),
+        };
+        let cache_kib = (bytes / connections.max(1) / 1024).max(MIN_CACHE_KIB);
+        // A negative cache_size is in KiB rather than pages.
+        db.execute_batch(&format!(
+            "PRAGMA cache_size = -{}; PRAGMA temp_store = FILE;",
+            cache_kib
+        ))?;
+        Ok(())
+    }
+
+    /// How many connections load at once with the current settings.
+    pub(crate) fn loading_connections(&self) -> usize {
+        match self.parallel_preload() {
+            true => self.threads + 1,
+            false => 1,
+        }
+    }
+
+    pub(crate) fn parallel_preload(&self) -> bool {
+        self.threads > 1 && self.preload && self.sample_rows.is_none()
+    }
+}
+
+#[test]
+fn test_memory_limit() -> Result<(), Error> {
+    use std::path::Path;
+
+    let pragma = |db: &Connection, name: &str| -> Result<i64, Error> {
+        Ok(db.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?)
+    };
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/memory-limit"))
+        .preload(true)
+        .memory_limit(8 << 20)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+    assert_eq!(-8192, pragma(&db, "cache_size")?);
+    assert_eq!(1, pragma(&db, "temp_store")?);
+    assert_eq!(
+        6,
+        db.query_row("SELECT COUNT(*) FROM crates", [], |row| row
+            .get::<_, i64>(0))?
+    );
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.threads(3).load_dump_into(&db)?;
+    assert_eq!(-2048, pragma(&db, "cache_size")?);
+
+    loader.memory_limit(1024);
+    loader.limit_memory(&db, 1)?;
+    assert_eq!(-(MIN_CACHE_KIB as i64), pragma(&db, "cache_size")?);
+    Ok(())
+}
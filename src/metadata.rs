@@ -0,0 +1,39 @@
+use std::{fs::File, io::BufReader};
+
+use serde_json::Value;
+
+use crate::{CratesIODumpLoader, Error};
+
+/// Name of the file in the dump archive describing the snapshot; it is always extracted.
+pub const METADATA_FILE: &str = "metadata.json";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DumpMetadata {
+    /// When the dump was taken, e.g. `2021-05-15T02:00:00Z`.
+    pub timestamp: String,
+    pub crates_io_commit: Option<String>,
+}
+
+impl DumpMetadata {
+    /// The `YYYY-MM-DD` part of the timestamp.
+    pub fn date(&self) -> &str {
+        self.timestamp.get(..10).unwrap_or(&self.timestamp)
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Metadata of the extracted dump, if the archive shipped a `metadata.json`.
+    pub fn metadata(&self) -> Result<Option<DumpMetadata>, Error> {
+        let path = self.target_path.join(METADATA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let field = |name: &str| json.get(name).and_then(Value::as_str).map(str::to_string);
+        Ok(field("timestamp").map(|timestamp| DumpMetadata {
+            timestamp,
+            crates_io_commit: field("crates_io_commit"),
+        }))
+    }
+}
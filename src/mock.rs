@@ -0,0 +1,163 @@
+//! In-memory databases built from Rust, for tests that don't want to touch the filesystem.
+
+use rusqlite::Connection;
+
+use crate::{sql, stats, Error, FixtureBuilder};
+
+/// Builds the database [`crate::CratesIODumpLoader`] would load with `preload(true)`, straight
+/// from crates, versions and dependencies declared in Rust.
+///
+/// Rows are generated like [`FixtureBuilder`] does, and every column is `TEXT` just like the
+/// csv tables, so queries that work against the real dump work here too. The csv module is
+/// loaded and, with the `functions` feature, the SQL helpers are registered.
+#[derive(Default)]
+pub struct MockDump {
+    fixture: FixtureBuilder,
+    pub growth_stats: bool,
+    pub category_stats: bool,
+}
+
+impl From<FixtureBuilder> for MockDump {
+    fn from(fixture: FixtureBuilder) -> Self {
+        Self {
+            fixture,
+            growth_stats: false,
+            category_stats: false,
+        }
+    }
+}
+
+impl MockDump {
+    /// When the dump claims to have been taken, e.g. `2021-05-15T02:00:00Z`.
+    pub fn timestamp(&mut self, timestamp: &str) -> &mut Self {
+        self.fixture.timestamp(timestamp);
+        self
+    }
+
+    /// See [`FixtureBuilder::add_crate`].
+    pub fn add_crate(&mut self, name: &str, versions: &[&str]) -> &mut Self {
+        self.fixture.add_crate(name, versions);
+        self
+    }
+
+    /// See [`FixtureBuilder::dependency`].
+    pub fn dependency(
+        &mut self,
+        name: &str,
+        version: &str,
+        dependency: &str,
+        req: &str,
+    ) -> &mut Self {
+        self.fixture.dependency(name, version, dependency, req);
+        self
+    }
+
+    /// See [`FixtureBuilder::row`].
+    pub fn row(&mut self, table: &str, values: &[(&str, &str)]) -> &mut Self {
+        self.fixture.row(table, values);
+        self
+    }
+
+    /// Also materialize the `growth_monthly` table, like the loader option of the same name.
+    pub fn growth_stats(&mut self, should: bool) -> &mut Self {
+        self.growth_stats = should;
+        self
+    }
+
+    /// Also materialize the `category_stats` table, like the loader option of the same name.
+    pub fn category_stats(&mut self, should: bool) -> &mut Self {
+        self.category_stats = should;
+        self
+    }
+
+    /// A fresh in-memory database with every table filled in.
+    pub fn build(&self) -> Result<Connection, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        #[cfg(feature = "functions")]
+        crate::register_functions(&db)?;
+        self.build_into(&db)?;
+        Ok(db)
+    }
+
+    /// Creates every table in `db`, replacing tables of the same name.
+    pub fn build_into(&self, db: &Connection) -> Result<(), Error> {
+        let tx = db.unchecked_transaction()?;
+        for (table, columns, rows) in self.fixture.tables() {
+            let table = sql::identifier(&table);
+            let definitions: Vec<String> = columns
+                .iter()
+                .map(|c| format!("{} TEXT", sql::identifier(c)))
+                .collect();
+            tx.execute_batch(&format!(
+                "DROP TABLE IF EXISTS {0}; CREATE TABLE {0}({1});",
+                table,
+                definitions.join(", ")
+            ))?;
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            let mut insert =
+                tx.prepare(&format!("INSERT INTO {} VALUES ({})", table, placeholders))?;
+            for row in &rows {
+                insert.execute(rusqlite::params_from_iter(row))?;
+            }
+        }
+        tx.commit()?;
+        if self.growth_stats {
+            stats::materialize_growth(db)?;
+        }
+        if self.category_stats {
+            stats::materialize_category_stats(db)?;
+        }
+        Ok(())
+    }
+}
+
+/// A loader of the archive at `resource` extracting into `target`, without a progress bar, for
+/// tests that need real archives.
+#[cfg(test)]
+pub(crate) fn archive_loader(
+    resource: &str,
+    target: &str,
+) -> Result<crate::CratesIODumpLoader, Error> {
+    let mut loader = crate::CratesIODumpLoader::default();
+    loader
+        .resource(resource)
+        .target_path(std::path::Path::new(target))
+        .cache(cached_path::Cache::builder().progress_bar(None))?;
+    Ok(loader)
+}
+
+#[test]
+fn test_mock_dump() -> Result<(), Error> {
+    use crate::reverse_dependencies;
+
+    let db = MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("tokio", &["1.0.0"])
+        .dependency("tokio", "1.0.0", "serde", "^1.0")
+        .row("categories", &[("id", "1"), ("slug", "asynchronous")])
+        .growth_stats(true)
+        .build()?;
+
+    let count = |sql: &str| db.query_row(sql, [], |row| row.get::<_, i64>(0));
+    assert_eq!(2, count("SELECT COUNT(*) FROM crates")?);
+    assert_eq!(3, count("SELECT COUNT(*) FROM versions")?);
+    assert_eq!(0, count("SELECT COUNT(*) FROM badges")?);
+    assert_eq!(1, count("SELECT COUNT(*) FROM growth_monthly")?);
+    let rdeps = reverse_dependencies(&db, "serde")?;
+    assert_eq!(
+        vec!["tokio"],
+        rdeps
+            .iter()
+            .map(|d| d.crate_name.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    let column_type: String = db.query_row(
+        "SELECT type FROM pragma_table_info('crates') WHERE name = 'downloads'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!("TEXT", column_type);
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use rusqlite::Connection;
+
+use crate::{sql, CratesIODumpLoader, Error};
+
+impl CratesIODumpLoader {
+    /// Checks every `table_schema()` override against the header of its extracted csv, failing
+    /// with [`Error::SchemaOverrideMismatch`] when the column count or any column name differs.
+    ///
+    /// Names are compared case-insensitively. `load_dump_into()` only checks the column count,
+    /// so overrides that rename columns still load; call this to catch those too.
+    pub fn validate_table_schemas(&self) -> Result<(), Error> {
+        self.check_schema_sql()?;
+        self.check_table_schemas(true)
+    }
+
+    /// Prepares every override against a scratch connection, so a malformed one fails with
+    /// [`Error::InvalidTableSchema`] naming its table instead of somewhere in the load script.
+    pub(crate) fn check_schema_sql(&self) -> Result<(), Error> {
+        if self.table_schema.is_empty() {
+            return Ok(());
+        }
+        let scratch = Connection::open_in_memory()?;
+        let mut overrides: Vec<_> = self.table_schema.iter().collect();
+        overrides.sort();
+        for (table, override_schema) in overrides {
+            scratch
+                .prepare(override_schema)
+                .map_err(|source| Error::InvalidTableSchema {
+                    table: table.clone(),
+                    schema: override_schema.clone(),
+                    source,
+                })?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_table_schemas(&self, names: bool) -> Result<(), Error> {
+        let mut overrides: Vec<_> = self.table_schema.iter().collect();
+        overrides.sort();
+        for (table, override_schema) in overrides {
+            let path = self.target_path.join(format!("{}.csv", table));
+            if !path.exists() {
+                continue;
+            }
+            let header: Vec<String> = csv::Reader::from_path(&path)?
+                .headers()?
+                .iter()
+                .map(String::from)
+                .collect();
+            let columns = override_columns(table, override_schema)?;
+
+            let mismatch = header.len() != columns.len()
+                || (names
+                    && header
+                        .iter()
+                        .zip(&columns)
+                        .any(|(h, c)| !h.eq_ignore_ascii_case(c)));
+            if mismatch {
+                return Err(Error::SchemaOverrideMismatch {
+                    table: table.clone(),
+                    header,
+                    columns,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The columns SQLite gives the table `override_schema` creates on a scratch connection.
+fn override_columns(table: &str, override_schema: &str) -> Result<Vec<String>, Error> {
+    let scratch = Connection::open_in_memory()?;
+    scratch
+        .prepare(override_schema)
+        .and_then(|mut stmt| stmt.execute([]))
+        .map_err(|source| Error::InvalidTableSchema {
+            table: table.to_string(),
+            schema: override_schema.to_string(),
+            source,
+        })?;
+    let created: String = scratch.query_row(
+        "SELECT name FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut stmt = scratch.prepare(&format!("PRAGMA table_info({})", sql::identifier(&created)))?;
+    let names = stmt.query_map([], |row| row.get(1))?;
+    Ok(names.collect::<Result<_, _>>()?)
+}
+
+#[test]
+fn test_validate_table_schemas() -> Result<(), Error> {
+    use std::path::Path;
+
+    let cache = || cached_path::Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/overrides");
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .table_schema("test", "CREATE TABLE x(id INT, \"name\" TEXT);")
+        .cache(cache())?
+        .update()?;
+    loader.validate_table_schemas()?;
+
+    loader.table_schema("test", "CREATE TABLE x(renamed_id INT, name TEXT);");
+    match loader.validate_table_schemas() {
+        Err(Error::SchemaOverrideMismatch { table, header, .. }) => {
+            assert_eq!("test", table);
+            assert_eq!(vec!["ID", "NAME"], header);
+        }
+        other => panic!("expected a mismatch, got {:?}", other),
+    }
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.load_dump_into(&db)?;
+
+    // Commas inside a column's type don't split it.
+    loader.table_schema("test", "CREATE TABLE x(id DECIMAL(10,2), name TEXT);");
+    loader.load_dump_into(&db)?;
+
+    loader.table_schema("test", "CREATE TABLE x(id INT);");
+    assert!(matches!(
+        loader.load_dump_into(&db),
+        Err(Error::SchemaOverrideMismatch { .. })
+    ));
+
+    loader.table_schema("test", "CREATE TABLE x(id INT, name TEXT,);");
+    match loader.load_dump_into(&db) {
+        Err(Error::InvalidTableSchema { table, .. }) => assert_eq!("test", table),
+        other => panic!("expected an invalid schema, got {:?}", other),
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Preloading tables on several threads, each into its own database that is then copied over.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+    thread,
+};
+
+use rusqlite::Connection;
+
+use crate::{file_error, sql, CratesIODumpLoader, Error};
+
+const PART: &str = "csvtab_part";
+
+fn table_name(file: &Path) -> String {
+    file.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into()
+}
+
+impl CratesIODumpLoader {
+    /// Preloads `files` into `db`, returning how each went in the order of `files`.
+    pub(crate) fn preload_parallel(
+        &self,
+        db: &Connection,
+        files: &[&PathBuf],
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        let dir = self.target_path.join(".parallel");
+        fs::create_dir_all(&dir).map_err(file_error(&dir))?;
+
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = channel();
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(files.len()) {
+                let sender = sender.clone();
+                let (next, dir) = (&next, &dir);
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let file = match files.get(i) {
+                        Some(file) => file,
+                        None => break,
+                    };
+                    let part = dir.join(file).with_extension("sqlite");
+                    let _ = sender.send((i, self.build_part(file, &part).map(|_| part)));
+                });
+            }
+        });
+        drop(sender);
+
+        let mut parts: Vec<Option<Result<PathBuf, Error>>> = files.iter().map(|_| None).collect();
+        for (i, part) in receiver {
+            parts[i] = Some(part);
+        }
+        let results = files
+            .iter()
+            .zip(parts)
+            .map(|(file, part)| match part {
+                Some(Ok(part)) => {
+                    let result = copy_part(db, &part, &table_name(file));
+                    let _ = fs::remove_file(&part);
+                    result
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(Error::UnknownTable(table_name(file))),
+            })
+            .collect();
+        let _ = fs::remove_dir(&dir);
+        Ok(results)
+    }
+
+    fn build_part(&self, file: &PathBuf, part: &Path) -> Result<(), Error> {
+        if part.exists() {
+            fs::remove_file(part).map_err(file_error(part))?;
+        }
+        let db = Connection::open(part)?;
+        // Scratch databases, nothing to recover if this is interrupted.
+        db.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+        self.limit_memory(&db, self.loading_connections())?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        self.load_file(&db, file, &table_name(file))
+    }
+}
+
+fn copy_part(db: &Connection, part: &Path, table: &str) -> Result<(), Error> {
+    db.execute(
+        &format!("ATTACH DATABASE ?1 AS {}", PART),
+        [part.to_string_lossy()],
+    )?;
+    let sql = format!(
+        "DROP TABLE IF EXISTS main.{0}; CREATE TABLE main.{0} AS SELECT * FROM {1}.{0};",
+        sql::identifier(table),
+        PART
+    );
+    let result = db.execute_batch(&sql).map_err(|source| Error::TableError {
+        table: table.to_string(),
+        sql,
+        source,
+    });
+    db.execute_batch(&format!("DETACH DATABASE {}", PART))?;
+    result
+}
+
+#[test]
+fn test_parallel_preload() -> Result<(), Error> {
+    let cache = || cached_path::Cache::builder().progress_bar(None);
+    let load = |threads: usize| -> Result<Connection, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::default()
+            .resource("testdata/fixture.tar.gz")
+            .target_path(Path::new("testdata/extracted/parallel"))
+            .preload(true)
+            .threads(threads)
+            .cache(cache())?
+            .update()?
+            .load_dump_into(&db)?;
+        Ok(db)
+    };
+    let contents = |db: &Connection| -> Result<Vec<(String, i64)>, Error> {
+        let mut stmt = db.prepare("SELECT name FROM sqlite_master ORDER BY name")?;
+        let tables = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        tables
+            .into_iter()
+            .map(|table| {
+                let sql = format!("SELECT COUNT(*) FROM {}", sql::identifier(&table));
+                Ok((table, db.query_row(&sql, [], |row| row.get(0))?))
+            })
+            .collect()
+    };
+
+    let serial = contents(&load(1)?)?;
+    assert_eq!(15, serial.len());
+    assert_eq!(serial, contents(&load(4)?)?);
+    assert!(!Path::new("testdata/extracted/parallel/.parallel").exists());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Conversion of plain-format `pg_dump` data into the CSV layout the loader expects.
+
+use std::{
+    fs::File,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Parses the header of a `COPY public.crates (id, name) FROM stdin;` statement.
+fn parse_copy_statement(line: &str) -> Option<(String, Vec<String>)> {
+    let rest = line.strip_prefix("COPY ")?;
+    if !rest.trim_end().ends_with("FROM stdin;") {
+        return None;
+    }
+    let open = rest.find('(')?;
+    let close = rest.find(')')?;
+    let table = rest[..open].trim().rsplit('.').next()?.trim_matches('"');
+    let columns = rest[open + 1..close]
+        .split(',')
+        .map(|c| c.trim().trim_matches('"').to_string())
+        .collect();
+    Some((table.to_string(), columns))
+}
+
+/// Undoes the escaping of the COPY text format, with `\N` (NULL) becoming an empty field.
+fn unescape_field(field: &str) -> String {
+    if field == "\\N" {
+        return String::new();
+    }
+
+    let mut out = Vec::with_capacity(field.len());
+    let bytes = field.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match bytes[i] {
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'v' => out.push(0x0b),
+            b'x' => {
+                let end = (i + 3).min(bytes.len());
+                let digits = bytes[i + 1..end]
+                    .iter()
+                    .take_while(|b| b.is_ascii_hexdigit())
+                    .count();
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 1 + digits]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => out.push(b),
+                    Err(_) => out.push(b'x'),
+                }
+                i += digits;
+            }
+            b'0'..=b'7' => {
+                let end = (i + 3).min(bytes.len());
+                let digits = bytes[i..end]
+                    .iter()
+                    .take_while(|b| (b'0'..=b'7').contains(b))
+                    .count();
+                let octal = std::str::from_utf8(&bytes[i..i + digits]).unwrap_or_default();
+                out.push(u8::from_str_radix(octal, 8).unwrap_or_default());
+                i += digits - 1;
+            }
+            other => out.push(other),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Writes every `COPY ... FROM stdin` block whose table is listed in `files` to `<table>.csv`
+/// inside `target_dir`, returning the files that were written.
+pub fn copy_blocks_to_csv<R: BufRead>(
+    mut reader: R,
+    target_dir: &Path,
+    files: &[PathBuf],
+) -> Result<Vec<PathBuf>, Error> {
+    let mut written = Vec::new();
+    let mut writer: Option<csv::Writer<File>> = None;
+    let mut in_block = false;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let content = line.trim_end_matches(&['\n', '\r'][..]);
+
+        if in_block {
+            if content == "\\." {
+                if let Some(mut w) = writer.take() {
+                    w.flush()?;
+                }
+                in_block = false;
+            } else if let Some(w) = writer.as_mut() {
+                w.write_record(content.split('\t').map(unescape_field))?;
+            }
+            continue;
+        }
+
+        if let Some((table, columns)) = parse_copy_statement(content) {
+            in_block = true;
+            let file = PathBuf::from(format!("{}.csv", table));
+            if files.contains(&file) {
+                let mut w = csv::Writer::from_path(target_dir.join(&file))?;
+                w.write_record(&columns)?;
+                writer = Some(w);
+                written.push(file);
+            }
+        }
+    }
+    Ok(written)
+}
+
+#[test]
+fn test_unescape_field() {
+    assert_eq!("", unescape_field("\\N"));
+    assert_eq!("a\tb\\c", unescape_field("a\\tb\\\\c"));
+    assert_eq!("AB", unescape_field("\\101\\x42"));
+}
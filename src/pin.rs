@@ -0,0 +1,250 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{file_error, CratesIODumpLoader, EpochTimestamps, Error, Preset};
+
+/// Everything needed to rebuild a database from exactly the same archive: the archive and
+/// every option that changes what gets loaded. Custom tables are code, so they aren't pinned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pin {
+    pub resource: String,
+    pub sha256: String,
+    /// Dump timestamp from `metadata.json`, for reference only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    pub tables: Vec<String>,
+    pub preload: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rows: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downloads_since: Option<String>,
+    pub typed_schemas: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epoch_timestamps: Option<EpochTimestamps>,
+    pub index_presets: Vec<Preset>,
+    pub growth_stats: bool,
+    pub category_stats: bool,
+    pub cadence_views: bool,
+    pub tag_views: bool,
+    pub table_schema: BTreeMap<String, String>,
+    /// Preloaded columns of the tables `columns()` was set for.
+    pub columns: BTreeMap<String, Vec<String>>,
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    let mut file = File::open(path).map_err(file_error(path))?;
+    io::copy(&mut file, &mut hasher).map_err(file_error(path))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+impl Pin {
+    pub fn to_toml(&self) -> String {
+        let toml = toml::to_string(self).expect("pins are representable in TOML");
+        format!("# Rebuild with CratesIODumpLoader::from_pin.\n{}", toml)
+    }
+
+    /// Reads a pin file, e.g. one written by [`Pin::to_toml`].
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let pin: Pin = toml::from_str(text).map_err(|e| Error::InvalidPin(e.to_string()))?;
+        if pin.resource.is_empty() || pin.sha256.is_empty() {
+            return Err(Error::InvalidPin(
+                "resource and sha256 are required".to_string(),
+            ));
+        }
+        Ok(pin)
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Records the archive, its hash and the options that affect what is loaded in a TOML
+    /// file at `path`, from which [`CratesIODumpLoader::from_pin`] rebuilds the same database.
+    pub fn pin(&self, path: &Path) -> Result<Pin, Error> {
+        let archive = self.cached_resource()?;
+        let pin = Pin {
+            resource: self.resource.clone(),
+            sha256: sha256_file(&archive)?,
+            timestamp: self.metadata()?.map(|m| m.timestamp),
+            tables: self
+                .files
+                .iter()
+                .map(|f| f.file_stem().unwrap_or_default().to_string_lossy().into())
+                .collect(),
+            preload: self.preload,
+            sample_rows: self.sample_rows,
+            seed: self.seed,
+            downloads_since: self.downloads_since.clone(),
+            typed_schemas: self.typed_schemas,
+            schema_version: self.schema_version.clone(),
+            epoch_timestamps: self.epoch_timestamps,
+            index_presets: self.index_presets.clone(),
+            growth_stats: self.growth_stats,
+            category_stats: self.category_stats,
+            cadence_views: self.cadence_views,
+            tag_views: self.tag_views,
+            table_schema: self
+                .table_schema
+                .iter()
+                .map(|(t, s)| (t.clone(), s.clone()))
+                .collect(),
+            columns: self
+                .table_columns
+                .iter()
+                .map(|(t, c)| (t.clone(), c.clone()))
+                .collect(),
+        };
+        fs::write(path, pin.to_toml())?;
+        Ok(pin)
+    }
+
+    /// A loader configured from the pin file at `path`, whose `update()` fails unless the
+    /// archive still has the pinned hash.
+    #[allow(deprecated)]
+    pub fn from_pin(path: &Path) -> Result<Self, Error> {
+        let pin = Pin::from_toml(&fs::read_to_string(path)?)?;
+        let tables: Vec<&str> = pin.tables.iter().map(String::as_str).collect();
+        let mut loader = Self::default();
+        loader
+            .resource(&pin.resource)
+            .tables(&tables)
+            .preload(pin.preload)
+            .typed_schemas(pin.typed_schemas)
+            .growth_stats(pin.growth_stats)
+            .cadence_views(pin.cadence_views)
+            .sha256(Some(&pin.sha256));
+        if let Some(rows) = pin.sample_rows {
+            loader.sample_rows(rows);
+        }
+        if let Some(seed) = pin.seed {
+            loader.seed(seed);
+        }
+        if let Some(date) = &pin.downloads_since {
+            loader.downloads_since(date)?;
+        }
+        for preset in pin.index_presets {
+            loader.index_preset(preset);
+        }
+        loader.schema_version = pin.schema_version;
+        loader.epoch_timestamps = pin.epoch_timestamps;
+        loader.tag_views = pin.tag_views;
+        loader.category_stats = pin.category_stats;
+        for (table, schema) in &pin.table_schema {
+            loader.table_schema(table, schema);
+        }
+        for (table, columns) in &pin.columns {
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            loader.columns(table, &columns);
+        }
+        Ok(loader)
+    }
+}
+
+#[test]
+fn test_pin() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let cache = || cached_path::Cache::builder().progress_bar(None);
+    let target = Path::new("testdata/extracted/pin");
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .preload(true)
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .table_schema("test", "CREATE TABLE x(renamed_id INT, \"name\" TEXT);")
+        .cache(cache())?
+        .update()?;
+
+    let path = target.join("dump.pin.toml");
+    let pin = loader.pin(&path)?;
+    assert_eq!(pin, Pin::from_toml(&pin.to_toml())?);
+
+    let handwritten = Pin::from_toml(
+        r#"
+            resource = 'testdata\test.tar.gz' # a literal string
+            sha256 = "ab"
+            tables = [
+                "test",
+            ]
+
+            [table_schema]
+            "test" = """CREATE TABLE x(id INT);"""
+        "#,
+    )?;
+    assert_eq!(r"testdata\test.tar.gz", handwritten.resource);
+    assert_eq!(vec!["test"], handwritten.tables);
+    assert_eq!(
+        Some("CREATE TABLE x(id INT);"),
+        handwritten.table_schema.get("test").map(String::as_str)
+    );
+    assert!(matches!(
+        Pin::from_toml("resource = \"x\"\nsha256 = \"ab\"\nprelaod = true"),
+        Err(Error::InvalidPin(_))
+    ));
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::from_pin(&path)?
+        .target_path(target)
+        .cache(cache())?
+        .update()?
+        .load_dump_into(&db)?;
+    let id: i64 = db.query_row(
+        "SELECT renamed_id FROM test WHERE name = 'awooo'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(3, id);
+
+    let mut tampered = CratesIODumpLoader::from_pin(&path)?;
+    tampered
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .cache(cache())?;
+    assert!(matches!(tampered.update(), Err(Error::PinMismatch { .. })));
+
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/test.tar.gz")
+        .target_path(target)
+        .tables(&["test"])
+        .preload(true)
+        .sample_rows(2)
+        .seed(7)
+        .downloads_since("2021-01-01")?
+        .typed_schemas(true)
+        .schema_version("2021")
+        .epoch_timestamps(EpochTimestamps::Alongside)
+        .index_preset(Preset::Search)
+        .index_preset(Preset::Downloads)
+        .growth_stats(true)
+        .category_stats(true)
+        .cadence_views(true)
+        .tag_views(true)
+        .table_schema("test", "CREATE TABLE x(id INT, name TEXT);")
+        .columns("test", &["name", "id"])
+        .cache(cache())?
+        .build()?;
+    loader.update()?;
+    let pin = loader.pin(&path)?;
+    assert_eq!(pin, Pin::from_toml(&pin.to_toml())?);
+    let mut restored = CratesIODumpLoader::from_pin(&path)?;
+    restored.target_path(target).cache(cache())?;
+    assert_eq!(pin, restored.pin(&target.join("restored.pin.toml"))?);
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! `update()` and `load_dump_into()` overlapped, loading each table while the next one is
+//! still being extracted.
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Instant,
+};
+
+use rusqlite::Connection;
+
+use crate::{
+    resource, timeouts::Deadline, CratesIODumpLoader, Error, LoadReport, Phase, UpdateReport,
+};
+
+impl CratesIODumpLoader {
+    /// `update()` followed by `load_dump_into(db)`, overlapped: each table is loaded as soon as
+    /// it has been extracted, while the next one extracts. Tables load in the order the archive
+    /// has them, followed by custom tables.
+    ///
+    /// Only preloading benefits, and loaders that `lazy()`, `sample_rows()` or preload on
+    /// several `threads()` run the two one after another. The download still has to finish
+    /// before extracting starts, as cached-path only hands out complete files.
+    pub fn update_into(&mut self, db: &Connection) -> Result<LoadReport, Error> {
+        if !self.preload || self.lazy || self.sample_rows.is_some() || self.parallel_preload() {
+            self.update()?;
+            return self.load_dump_into(db);
+        }
+        let started = Instant::now();
+        self.update_report = UpdateReport::default();
+        self.check_table_names()?;
+        self.check_schema_sql()?;
+        self.check_table_schemas(false)?;
+
+        let mut update = UpdateReport::default();
+        let path = self.fetch_archive(&mut update)?;
+        let lock = self.lock_target()?;
+        if path.extension() == Some("sql".as_ref()) || self.extracted_since(&path)? {
+            // Nothing to overlap.
+            drop(lock);
+            self.extract_archive(&path, &mut update)?;
+            self.update_report = UpdateReport {
+                duration: started.elapsed(),
+                ..update
+            };
+            return self.load_dump_into(db);
+        }
+        resource::create_target_dir(&self.target_path)?;
+
+        let deadline = Deadline::start(Phase::Load, self.load_timeout);
+        let _interrupt = deadline.interrupt(db);
+        let this = &*self;
+        let archive = path.as_path();
+        let (extracted, loaded) = thread::scope(|scope| {
+            let (sender, files) = mpsc::channel();
+            let extractor = scope.spawn(move || {
+                let mut report = UpdateReport::default();
+                this.extract_tar(archive, &mut report, Some(&sender))
+                    .map(|()| report)
+            });
+            let loaded = this.load_extracted(db, files, deadline);
+            (extractor.join().expect("extracting panicked"), loaded)
+        });
+        drop(lock);
+
+        let extracted = match extracted {
+            Ok(extracted) => extracted,
+            // A download cut short, let update() fetch it again and start over.
+            Err(Error::CorruptArchive { .. }) if self.remote_resource() => {
+                resource::invalidate_cached(&path)?;
+                self.update()?;
+                return self.load_dump_into(db);
+            }
+            Err(e) => return Err(e),
+        };
+        update.extracted = extracted.extracted;
+        self.files = self.table_files()?;
+        self.update_report = UpdateReport {
+            duration: started.elapsed(),
+            ..update
+        };
+        let mut report = loaded.map_err(|e| deadline.or_timeout(e))?;
+        report.update = self.update_report.clone();
+        Ok(report)
+    }
+
+    /// Loads the csvs named by `files` as they come in, then the custom tables.
+    fn load_extracted(
+        &self,
+        db: &Connection,
+        files: Receiver<PathBuf>,
+        deadline: Deadline,
+    ) -> Result<LoadReport, Error> {
+        self.limit_memory(db, 1)?;
+        let loading = Instant::now();
+        let mut report = LoadReport::default();
+        let custom_files = self.write_custom_tables(&mut report)?;
+        for file in files.into_iter().chain(custom_files) {
+            deadline.check()?;
+            let table: String = file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into();
+            let started = Instant::now();
+            let result = self.load_file(db, &file, &table);
+            self.record_table(db, table, started, result, deadline, &mut report)?;
+        }
+        self.finish_load(db, report, loading)
+    }
+}
+
+#[test]
+fn test_update_into() -> Result<(), Error> {
+    use std::path::Path;
+
+    let target = Path::new("testdata/extracted/pipeline");
+    let _ = std::fs::remove_dir_all(target);
+    let mut favorites = crate::TableWriter::new("favorites", &["name"]);
+    favorites.row(&["serde"]);
+    let mut loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .minimal()
+        .preload(true)
+        .custom_table(favorites)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let report = loader.update_into(&db)?;
+    let mut loaded = report.loaded.clone();
+    loaded.sort();
+    assert_eq!(
+        vec!["crates", "dependencies", "favorites", "versions"],
+        loaded
+    );
+    let csvs = |report: &LoadReport| {
+        report
+            .update
+            .extracted
+            .iter()
+            .filter(|(file, _)| file.ends_with(".csv"))
+            .count()
+    };
+    assert_eq!(3, csvs(&report));
+    assert_eq!(&report.update, loader.update_report());
+    let crates: i64 = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?;
+    assert_eq!(6, crates);
+
+    // Already extracted, so loaded the usual way.
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let report = loader.update_into(&db)?;
+    assert_eq!(0, csvs(&report));
+    assert_eq!(4, report.loaded.len());
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use postgres::Client;
+
+use crate::{schema, sql, CratesIODumpLoader, Error};
+
+impl CratesIODumpLoader {
+    /// COPYs the extracted CSVs into `client`, recreating each table with the canonical schema.
+    ///
+    /// Everything happens in one transaction, so a failed load leaves the previous tables intact.
+    pub fn load_dump_into_postgres(&mut self, client: &mut Client) -> Result<(), Error> {
+        let mut tx = client.transaction()?;
+        for path in &self.files {
+            let table = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut reader = BufReader::new(File::open(self.target_path.join(path))?);
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            let header = schema::parse_header(&header)?;
+
+            let columns = self.postgres_columns(&table, &header);
+            let (create, copy) = statements(&table, &columns, header.len());
+            tx.batch_execute(&create)?;
+            let mut writer = tx.copy_in(copy.as_str())?;
+            io::copy(&mut reader, &mut writer)?;
+            writer.finish()?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Overrides rename columns positionally like csvtab does; otherwise the CSV header is typed
+    // from the canonical schema, with anything unknown kept as TEXT.
+    fn postgres_columns(&self, table: &str, header: &[String]) -> Vec<(String, String)> {
+        if let Some(schema) = self.table_schema.get(table) {
+            return schema::parse_columns(schema)
+                .into_iter()
+                .map(|(name, ty)| match ty.is_empty() {
+                    true => (name, "TEXT".to_string()),
+                    false => (name, ty),
+                })
+                .collect();
+        }
+
+        let canonical = schema::table(table);
+        header
+            .iter()
+            .map(|name| {
+                let ty = canonical
+                    .and_then(|t| t.column(name))
+                    .map(|c| c.ty.postgres_type())
+                    .unwrap_or("TEXT");
+                (name.clone(), ty.to_string())
+            })
+            .collect()
+    }
+}
+
+/// The statements recreating `table` with `columns` and copying a csv with `copied` of them
+/// into it.
+fn statements(table: &str, columns: &[(String, String)], copied: usize) -> (String, String) {
+    let table = sql::identifier(table);
+    let ddl = columns
+        .iter()
+        .map(|(name, ty)| format!("{} {}", sql::identifier(name), ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let names = columns
+        .iter()
+        .take(copied)
+        .map(|(name, _)| sql::identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    (
+        format!(
+            "DROP TABLE IF EXISTS {0}; CREATE TABLE {0}({1});",
+            table, ddl
+        ),
+        format!("COPY {} ({}) FROM STDIN WITH (FORMAT csv)", table, names),
+    )
+}
+
+#[test]
+fn test_postgres_statements() -> Result<(), Error> {
+    let header = schema::parse_header("id,\"odd, \"\"name\"\"\",downloads\r\n")?;
+    assert_eq!(vec!["id", "odd, \"name\"", "downloads"], header);
+
+    let loader = CratesIODumpLoader::default();
+    let columns = loader.postgres_columns("crates", &header);
+    assert_eq!(
+        (
+            r#"DROP TABLE IF EXISTS "crates"; CREATE TABLE "crates"("id" INTEGER, "odd, ""name""" TEXT, "downloads" INTEGER);"#.to_string(),
+            r#"COPY "crates" ("id", "odd, ""name""") FROM STDIN WITH (FORMAT csv)"#.to_string(),
+        ),
+        statements("crates", &columns, 2)
+    );
+    Ok(())
+}
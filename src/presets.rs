@@ -0,0 +1,149 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{sql, CratesIODumpLoader, Error, Table};
+
+/// Sets of indexes for common query patterns, matching the casts the helpers in this crate
+/// join on. Only preloaded tables can be indexed, virtual ones are skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// [`crate::reverse_dependencies`] and other joins from crates through dependencies to
+    /// versions.
+    ReverseDeps,
+    /// Looking crates up by name, keyword or category.
+    Search,
+    /// [`crate::downloads::DownloadHistory`] and other per-version or per-date download
+    /// queries.
+    Downloads,
+}
+
+impl Preset {
+    /// `(table, indexed expressions)` of every index in the preset.
+    pub fn indexes(self) -> &'static [(Table, &'static str)] {
+        match self {
+            Preset::ReverseDeps => &[
+                (Table::Crates, "name"),
+                (Table::Crates, "CAST(id AS INTEGER)"),
+                (
+                    Table::Dependencies,
+                    "CAST(crate_id AS INTEGER), CAST(version_id AS INTEGER)",
+                ),
+                (Table::Versions, "CAST(id AS INTEGER)"),
+                (Table::Versions, "crate_id, created_at"),
+            ],
+            Preset::Search => &[
+                (Table::Crates, "name COLLATE NOCASE"),
+                (Table::Keywords, "keyword"),
+                (
+                    Table::CratesKeywords,
+                    "CAST(keyword_id AS INTEGER), CAST(crate_id AS INTEGER)",
+                ),
+                (Table::Categories, "slug"),
+                (
+                    Table::CratesCategories,
+                    "CAST(category_id AS INTEGER), CAST(crate_id AS INTEGER)",
+                ),
+            ],
+            Preset::Downloads => &[
+                (Table::Crates, "name"),
+                (Table::Versions, "CAST(crate_id AS INTEGER)"),
+                (Table::VersionDownloads, "CAST(version_id AS INTEGER), date"),
+                (Table::VersionDownloads, "date"),
+            ],
+        }
+    }
+
+    /// Creates the preset's indexes on the tables of `db` that can have them, returning how
+    /// many it created.
+    pub fn create(self, db: &Connection) -> Result<usize, Error> {
+        let mut created = 0;
+        for (table, expressions) in self.indexes() {
+            let table = table.name();
+            let name = index_name(table, expressions);
+            let (indexable, exists): (i64, i64) = db.query_row(
+                r#"
+                    SELECT
+                        (SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1 AND sql NOT LIKE 'CREATE VIRTUAL%'),
+                        (SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?2)
+                "#,
+                [table, name.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            if indexable == 0 || exists > 0 {
+                continue;
+            }
+            db.execute_batch(&format!(
+                "CREATE INDEX {} ON {}({});",
+                sql::identifier(&name),
+                sql::identifier(table),
+                expressions
+            ))?;
+            created += 1;
+        }
+        Ok(created)
+    }
+}
+
+/// `preset_<table>_<columns>`, so presets sharing an index don't create it twice.
+fn index_name(table: &str, expressions: &str) -> String {
+    let columns: String = expressions
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+    let columns: Vec<&str> = columns.split('_').filter(|s| !s.is_empty()).collect();
+    format!("preset_{}_{}", table, columns.join("_"))
+}
+
+impl CratesIODumpLoader {
+    /// Create the indexes of `preset` after loading, see [`Preset`].
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn index_preset(&mut self, preset: Preset) -> &mut Self {
+        if !self.index_presets.contains(&preset) {
+            self.index_presets.push(preset);
+        }
+        self
+    }
+}
+
+#[test]
+fn test_presets() -> Result<(), Error> {
+    use crate::{FixtureBuilder, MockDump};
+
+    let db = MockDump::from(FixtureBuilder::synthetic(20, 2)).build()?;
+    assert_eq!(5, Preset::ReverseDeps.create(&db)?);
+    // crates(name) is shared with ReverseDeps.
+    assert_eq!(3, Preset::Downloads.create(&db)?);
+    let plan: String = db.query_row(
+        "EXPLAIN QUERY PLAN SELECT * FROM dependencies WHERE CAST(crate_id AS INTEGER) = 3",
+        [],
+        |row| row.get(3),
+    )?;
+    assert!(plan.contains("preset_dependencies_cast_crate_id_as_integer"));
+
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(std::path::Path::new("testdata/extracted/presets"))
+        .minimal()
+        .index_preset(Preset::ReverseDeps)
+        .index_preset(Preset::Search)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?;
+    let mut indexes = |preload: bool| -> Result<i64, Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        loader.preload(preload).load_dump_into(&db)?;
+        Ok(db.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index'",
+            [],
+            |row| row.get(0),
+        )?)
+    };
+    assert_eq!(0, indexes(false)?);
+    assert_eq!(6, indexes(true)?);
+    Ok(())
+}
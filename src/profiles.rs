@@ -0,0 +1,168 @@
+use rusqlite::Connection;
+
+use crate::{cadence, table_exists, tags, CratesIODumpLoader, Error, Preset, Table};
+
+/// Curated setups for common uses of the dump, see [`CratesIODumpLoader::profile`]. Every
+/// profile preloads its tables with [`CratesIODumpLoader::typed_schemas`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Dependency analysis: the `minimal()` tables, indexed for
+    /// [`crate::reverse_dependencies`], with the [`cadence`] views.
+    Minimal,
+    /// Download analytics: crates, versions and their downloads, indexed for
+    /// [`crate::downloads::DownloadHistory`], with the `growth_monthly` table.
+    Analytics,
+    /// Looking crates up by name, keyword or category, with the [`crate::tags`] views.
+    Search,
+    /// Every table in the archive, with all of the above and the `category_stats` table.
+    Full,
+}
+
+impl Profile {
+    pub fn tables(self) -> &'static [Table] {
+        match self {
+            Profile::Minimal => &[Table::Crates, Table::Dependencies, Table::Versions],
+            Profile::Analytics => &[Table::Crates, Table::VersionDownloads, Table::Versions],
+            Profile::Search => &[
+                Table::Categories,
+                Table::Crates,
+                Table::CratesCategories,
+                Table::CratesKeywords,
+                Table::Keywords,
+            ],
+            Profile::Full => &Table::ALL,
+        }
+    }
+
+    pub fn presets(self) -> &'static [Preset] {
+        match self {
+            Profile::Minimal => &[Preset::ReverseDeps],
+            Profile::Analytics => &[Preset::Downloads],
+            Profile::Search => &[Preset::Search],
+            Profile::Full => &[Preset::ReverseDeps, Preset::Downloads, Preset::Search],
+        }
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Select the tables of `profile` and load them the way it needs, see [`Profile`]. Settings
+    /// changed afterwards take precedence.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    #[allow(deprecated)]
+    pub fn profile(&mut self, profile: Profile) -> &mut Self {
+        self.tables(profile.tables());
+        // Whatever else the archive has too.
+        self.discover_tables = profile == Profile::Full;
+        for preset in profile.presets() {
+            self.index_preset(*preset);
+        }
+        if matches!(profile, Profile::Analytics | Profile::Full) {
+            self.growth_stats(true);
+        }
+        if profile == Profile::Full {
+            self.category_stats = true;
+        }
+        if matches!(profile, Profile::Minimal | Profile::Full) {
+            self.cadence_views(true);
+        }
+        if matches!(profile, Profile::Search | Profile::Full) {
+            self.tag_views = true;
+        }
+        self.typed_schemas(true).preload(true)
+    }
+
+    /// Declare the column types of [`schema::TABLES`] for dump tables without a
+    /// `table_schema()` override, so e.g. ids compare as numbers. Columns are matched to the
+    /// csv header by name, and ones the canonical schema doesn't know stay TEXT.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn typed_schemas(&mut self, should: bool) -> &mut Self {
+        self.typed_schemas = should;
+        self
+    }
+
+    /// Create the `release_intervals` and `release_cadence` views of [`cadence`] after
+    /// loading, when crates and versions are loaded.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn cadence_views(&mut self, should: bool) -> &mut Self {
+        self.cadence_views = should;
+        self
+    }
+
+    /// The schema to load `table` with: its override, or else its typed schema, or a schema
+    /// renaming the columns the dump's format names differently.
+    pub(crate) fn schema_for(&self, table: &str) -> Result<Option<String>, Error> {
+        if let Some(override_schema) = self.table_schema.get(table) {
+            return Ok(Some(override_schema.clone()));
+        }
+        let format = self.dump_format()?;
+        let typed = self.typed_schemas && format.columns(table).is_some();
+        if !typed && !format.renames(table) {
+            return Ok(None);
+        }
+        let path = self.target_path.join(format!("{}.csv", table));
+        let header = csv::Reader::from_path(&path)?.headers()?.clone();
+        Ok(Some(format.csv_schema(table, &header, typed)))
+    }
+
+    pub(crate) fn create_views(&self, db: &Connection) -> Result<(), Error> {
+        if self.cadence_views && table_exists(db, "crates")? && table_exists(db, "versions")? {
+            cadence::create_cadence_views(db)?;
+        }
+        if self.tag_views
+            && table_exists(db, "crates")?
+            && table_exists(db, "keywords")?
+            && table_exists(db, "crates_keywords")?
+            && table_exists(db, "categories")?
+            && table_exists(db, "crates_categories")?
+        {
+            tags::create_tag_views(db)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_profiles() -> Result<(), Error> {
+    use crate::schema;
+    use std::path::Path;
+
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/profiles"))
+        .profile(Profile::Minimal)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?;
+    assert_eq!(3, loader.files.len());
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    loader.load_dump_into(&db)?;
+    let types: String = db.query_row(
+        "SELECT typeof(id) || ' ' || typeof(name) || ' ' || typeof(downloads) FROM crates LIMIT 1",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!("integer text integer", types);
+    let objects = |kind: &str| -> Result<i64, Error> {
+        Ok(db.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = ?1",
+            [kind],
+            |row| row.get(0),
+        )?)
+    };
+    assert_eq!(5, objects("index")?);
+    assert_eq!(2, objects("view")?);
+    assert!(crate::cadence::release_cadence(&db, "serde")?.is_some());
+
+    let mut search = CratesIODumpLoader::default();
+    search.profile(Profile::Search);
+    assert!(search.preload && search.typed_schemas && !search.growth_stats && search.tag_views);
+    assert_eq!(vec![Preset::Search], search.index_presets);
+    let mut full = CratesIODumpLoader::default();
+    full.profile(Profile::Full);
+    assert_eq!(schema::TABLES.len(), full.files.len());
+    assert_eq!(3, full.index_presets.len());
+    assert!(full.growth_stats && full.category_stats && full.cadence_views && full.tag_views);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use rusqlite::Connection;
+
+use crate::{rows::DependencyKind, stats::crate_downloads, Error};
+
+/// A version of another crate that depends on the crate being queried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReverseDependency {
+    pub crate_name: String,
+    pub version: String,
+    pub req: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+    /// All-time downloads of the dependent crate.
+    pub downloads: i64,
+}
+
+#[derive(Default)]
+pub struct ReverseDependencies {
+    pub kind: Option<DependencyKind>,
+    pub latest_only: bool,
+}
+
+impl ReverseDependencies {
+    /// Only keep dependencies of this kind.
+    pub fn kind(&mut self, kind: Option<DependencyKind>) -> &mut Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Only look at the newest non-yanked version of each dependent.
+    pub fn latest_only(&mut self, latest_only: bool) -> &mut Self {
+        self.latest_only = latest_only;
+        self
+    }
+
+    /// Dependents of `name`, most downloaded first.
+    pub fn query(&self, db: &Connection, name: &str) -> Result<Vec<ReverseDependency>, Error> {
+        let versions = match self.latest_only {
+            true => {
+                r#"
+                    SELECT id, crate_id, num FROM (
+                        SELECT CAST(id AS INTEGER) AS id, CAST(crate_id AS INTEGER) AS crate_id, num,
+                            ROW_NUMBER() OVER (PARTITION BY crate_id ORDER BY created_at DESC) AS n
+                        FROM main.versions WHERE yanked <> 't'
+                    ) WHERE n = 1
+                "#
+            }
+            false => {
+                "SELECT CAST(id AS INTEGER) AS id, CAST(crate_id AS INTEGER) AS crate_id, num FROM main.versions"
+            }
+        };
+        let sql = format!(
+            r#"
+                SELECT dependent.name, v.num, d.req, CAST(d.kind AS INTEGER) AS kind, d.optional,
+                    COALESCE(dl.downloads, 0) AS downloads
+                FROM main.crates c
+                JOIN main.dependencies d ON CAST(d.crate_id AS INTEGER) = CAST(c.id AS INTEGER)
+                JOIN ({}) v ON v.id = CAST(d.version_id AS INTEGER)
+                JOIN main.crates dependent ON CAST(dependent.id AS INTEGER) = v.crate_id
+                LEFT JOIN {} dl ON dl.crate_id = v.crate_id
+                WHERE c.name = ?1 AND (?2 IS NULL OR CAST(d.kind AS INTEGER) = ?2)
+                ORDER BY downloads DESC, dependent.name, v.id DESC
+            "#,
+            versions,
+            crate_downloads(db, "main")?
+        );
+        let mut stmt = db.prepare(&sql)?;
+        let rows = stmt.query_map(
+            rusqlite::params![name, self.kind.map(DependencyKind::raw)],
+            |row| {
+                Ok(ReverseDependency {
+                    crate_name: row.get(0)?,
+                    version: row.get(1)?,
+                    req: row.get(2)?,
+                    kind: row.get(3)?,
+                    optional: row.get::<_, String>(4)? == "t",
+                    downloads: row.get(5)?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// Every version of every crate depending on `name`.
+pub fn reverse_dependencies(db: &Connection, name: &str) -> Result<Vec<ReverseDependency>, Error> {
+    ReverseDependencies::default().query(db, name)
+}
+
+#[test]
+fn test_reverse_dependencies() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/rdeps"))
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let all = reverse_dependencies(&db, "serde")?;
+    let dependents: Vec<_> = all.iter().map(|d| d.crate_name.as_str()).collect();
+    assert_eq!(vec!["serde_derive", "bytes", "tokio", "anyhow"], dependents);
+
+    let normal = ReverseDependencies::default()
+        .kind(Some(DependencyKind::Normal))
+        .latest_only(true)
+        .query(&db, "mio")?;
+    assert_eq!(1, normal.len());
+    assert_eq!(
+        ("tokio", true),
+        (normal[0].crate_name.as_str(), normal[0].optional)
+    );
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Decompressing on a thread of its own, so inflating the dump overlaps with untarring it and
+//! writing the csvs out.
+
+use std::{
+    io::{self, Read},
+    sync::mpsc::{sync_channel, Receiver},
+    thread,
+};
+
+const CHUNK: usize = 256 * 1024;
+/// Chunks decoded ahead of the reader, bounding memory use to about a megabyte.
+const AHEAD: usize = 4;
+
+pub(crate) struct ReadAhead {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+/// Reads `reader` on a background thread. Errors come out in the same place, with their kind.
+pub(crate) fn read_ahead<R: Read + Send + 'static>(mut reader: R) -> ReadAhead {
+    let (sender, chunks) = sync_channel(AHEAD);
+    thread::spawn(move || loop {
+        let mut chunk = vec![0; CHUNK];
+        let result = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                chunk.truncate(n);
+                Ok(chunk)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+        let failed = result.is_err();
+        // The reader went away, or there is nothing more to read after an error.
+        if sender.send(result).is_err() || failed {
+            break;
+        }
+    });
+    ReadAhead {
+        chunks,
+        chunk: Vec::new(),
+        pos: 0,
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                // The decoding thread is done.
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_read_ahead() -> io::Result<()> {
+    use flate2::read::GzDecoder;
+    use std::fs::{self, File};
+
+    let mut direct = Vec::new();
+    GzDecoder::new(File::open("testdata/fixture.tar.gz")?).read_to_end(&mut direct)?;
+    let mut ahead = Vec::new();
+    read_ahead(GzDecoder::new(File::open("testdata/fixture.tar.gz")?)).read_to_end(&mut ahead)?;
+    assert_eq!(direct, ahead);
+
+    let archive = fs::read("testdata/fixture.tar.gz")?;
+    let truncated = archive[..archive.len() / 2].to_vec();
+    let error = read_ahead(GzDecoder::new(io::Cursor::new(truncated)))
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, BinaryBuilder, Float64Builder, Int64Builder, StringBuilder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+    record_batch::{RecordBatch, RecordBatchReader},
+};
+use rusqlite::{types::ValueRef, Rows, Statement};
+
+use crate::Error;
+
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// Maps a declared SQLite column type to an arrow type using SQLite's affinity rules.
+fn decl_type_to_arrow(decl_type: Option<&str>) -> DataType {
+    let decl = decl_type.unwrap_or_default().to_ascii_uppercase();
+    if decl.contains("INT") {
+        DataType::Int64
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        DataType::Utf8
+    } else if decl.contains("BLOB") {
+        DataType::Binary
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    }
+}
+
+pub(crate) fn statement_schema(stmt: &Statement) -> SchemaRef {
+    let fields: Vec<Field> = stmt
+        .columns()
+        .iter()
+        .map(|c| Field::new(c.name(), decl_type_to_arrow(c.decl_type()), true))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+enum ColumnBuilder {
+    Int(Int64Builder),
+    Float(Float64Builder),
+    Text(StringBuilder),
+    Blob(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Int64 => Self::Int(Int64Builder::new()),
+            DataType::Float64 => Self::Float(Float64Builder::new()),
+            DataType::Binary => Self::Blob(BinaryBuilder::new()),
+            _ => Self::Text(StringBuilder::new()),
+        }
+    }
+
+    // SQLite is dynamically typed, so coerce whatever is stored into the column's declared type.
+    fn append(&mut self, value: ValueRef) {
+        match self {
+            Self::Int(b) => b.append_option(match value {
+                ValueRef::Integer(i) => Some(i),
+                ValueRef::Real(f) => Some(f as i64),
+                ValueRef::Text(t) => std::str::from_utf8(t).ok().and_then(|s| s.parse().ok()),
+                _ => None,
+            }),
+            Self::Float(b) => b.append_option(match value {
+                ValueRef::Integer(i) => Some(i as f64),
+                ValueRef::Real(f) => Some(f),
+                ValueRef::Text(t) => std::str::from_utf8(t).ok().and_then(|s| s.parse().ok()),
+                _ => None,
+            }),
+            Self::Text(b) => match value {
+                ValueRef::Null => b.append_null(),
+                ValueRef::Integer(i) => b.append_value(i.to_string()),
+                ValueRef::Real(f) => b.append_value(f.to_string()),
+                ValueRef::Text(t) | ValueRef::Blob(t) => b.append_value(String::from_utf8_lossy(t)),
+            },
+            Self::Blob(b) => match value {
+                ValueRef::Null => b.append_null(),
+                ValueRef::Integer(i) => b.append_value(i.to_string()),
+                ValueRef::Real(f) => b.append_value(f.to_string()),
+                ValueRef::Text(t) | ValueRef::Blob(t) => b.append_value(t),
+            },
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Int(b) => Arc::new(b.finish()),
+            Self::Float(b) => Arc::new(b.finish()),
+            Self::Text(b) => Arc::new(b.finish()),
+            Self::Blob(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Streams the rows of a prepared statement as arrow [`RecordBatch`]es.
+///
+/// Column types follow the declared SQLite types, so give dump tables a `table_schema()` with
+/// `INT`/`REAL` columns if you want numeric arrays instead of strings.
+pub struct RecordBatches<'stmt> {
+    rows: Rows<'stmt>,
+    schema: SchemaRef,
+    batch_size: usize,
+    done: bool,
+}
+
+impl<'stmt> RecordBatches<'stmt> {
+    pub fn new(stmt: &'stmt mut Statement, batch_size: usize) -> Result<Self, Error> {
+        let schema = statement_schema(stmt);
+        Ok(Self {
+            rows: stmt.query([])?,
+            schema,
+            batch_size: batch_size.max(1),
+            done: false,
+        })
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>, ArrowError> {
+        let mut builders: Vec<ColumnBuilder> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| ColumnBuilder::new(f.data_type()))
+            .collect();
+
+        let mut count = 0;
+        while count < self.batch_size {
+            let row = match self.rows.next().map_err(external)? {
+                Some(row) => row,
+                None => {
+                    self.done = true;
+                    break;
+                }
+            };
+            for (i, builder) in builders.iter_mut().enumerate() {
+                builder.append(row.get_ref(i).map_err(external)?);
+            }
+            count += 1;
+        }
+
+        if count == 0 {
+            return Ok(None);
+        }
+        let columns = builders.iter_mut().map(ColumnBuilder::finish).collect();
+        Ok(Some(RecordBatch::try_new(self.schema.clone(), columns)?))
+    }
+}
+
+fn external(err: rusqlite::Error) -> ArrowError {
+    ArrowError::ExternalError(Box::new(err))
+}
+
+impl Iterator for RecordBatches<'_> {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let batch = self.next_batch();
+        if batch.is_err() {
+            self.done = true;
+        }
+        batch.transpose()
+    }
+}
+
+impl RecordBatchReader for RecordBatches<'_> {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// Runs `stmt` and returns its rows as batches of at most `batch_size` rows.
+pub fn record_batches<'stmt>(
+    stmt: &'stmt mut Statement,
+    batch_size: usize,
+) -> Result<RecordBatches<'stmt>, Error> {
+    RecordBatches::new(stmt, batch_size)
+}
+
+#[test]
+fn test_record_batches() -> Result<(), Error> {
+    use arrow::array::{Array, Int64Array, StringArray};
+    use rusqlite::Connection;
+
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(
+        "CREATE TABLE test(id INT, name TEXT, score REAL);
+         INSERT INTO test VALUES (1, 'hello', 0.5), (2, 'world', NULL), ('3', 'awooo', 2);",
+    )?;
+
+    let mut stmt = db.prepare("SELECT * FROM test")?;
+    let batches =
+        record_batches(&mut stmt, 2)?.collect::<Result<Vec<RecordBatch>, ArrowError>>()?;
+    assert_eq!(2, batches.len());
+    assert_eq!(&DataType::Float64, batches[0].schema().field(2).data_type());
+
+    let ids = batches[1]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(3, ids.value(0));
+    let names = batches[1]
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!("awooo", names.value(0));
+    assert!(batches[0].column(2).is_null(1));
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! What the dump says about the crates a developer's cargo has cached, i.e. the `.crate` files
+//! and index entries under `~/.cargo/registry`.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+
+use crate::{file_error, Error};
+
+/// Where cargo found out about a cached version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CacheSource {
+    /// A downloaded `.crate` file under `registry/cache`.
+    Crate,
+    /// An entry of the index cache under `registry/index/*/.cache`, i.e. a version cargo has
+    /// seen while resolving, downloaded or not.
+    Index,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedVersion {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub source: CacheSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CachedStatus {
+    /// The newest non-yanked version of its crate.
+    Current,
+    Yanked,
+    /// Not in the dump, e.g. published after it was taken or from another registry.
+    Missing,
+    /// A newer non-yanked version of the crate exists.
+    Superseded {
+        newest: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedVersionStatus {
+    pub cached: CachedVersion,
+    pub status: CachedStatus,
+}
+
+/// `$CARGO_HOME/registry`, or else `~/.cargo/registry`.
+pub fn default_registry_path() -> Option<PathBuf> {
+    match env::var_os("CARGO_HOME") {
+        Some(home) => Some(PathBuf::from(home).join("registry")),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo/registry")),
+    }
+}
+
+/// Splits a `.crate` file stem like `serde_json-1.0.0-rc.1` into name and version, at the
+/// first `-` followed by something shaped like `major.minor.patch`.
+fn split_crate_file(stem: &str) -> Option<(&str, &str)> {
+    stem.match_indices('-').find_map(|(i, _)| {
+        let version = &stem[i + 1..];
+        let core = version.split(['-', '+']).next()?;
+        let parts: Vec<&str> = core.split('.').collect();
+        let numeric = parts.len() == 3
+            && parts
+                .iter()
+                .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()));
+        numeric.then(|| (&stem[..i], version))
+    })
+}
+
+/// The entries of a cargo index cache file: a format byte, on newer cargos the index format
+/// as a u32, then NUL terminated fields, the index revision and pairs of version and JSON.
+fn index_cache_entries(bytes: &[u8]) -> Vec<(String, String)> {
+    let header = match bytes.first() {
+        Some(version) if *version >= 3 => 5,
+        Some(_) => 1,
+        None => return Vec::new(),
+    };
+    let mut fields = bytes.get(header..).unwrap_or_default().split(|b| *b == 0);
+    // The revision.
+    fields.next();
+    let mut entries = Vec::new();
+    while let (Some(_), Some(json)) = (fields.next(), fields.next()) {
+        let entry: Value = match serde_json::from_slice(json) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if let (Some(name), Some(vers)) = (entry["name"].as_str(), entry["vers"].as_str()) {
+            entries.push((name.to_string(), vers.to_string()));
+        }
+    }
+    entries
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir).map_err(file_error(dir))? {
+        let path = entry?.path();
+        match path.is_dir() {
+            true => walk(&path, files)?,
+            false => files.push(path),
+        }
+    }
+    Ok(())
+}
+
+/// The versions cached in the cargo registry directory `registry`, see
+/// [`default_registry_path`].
+pub fn scan_registry(registry: &Path) -> Result<Vec<CachedVersion>, Error> {
+    let mut cached = Vec::new();
+
+    let mut files = Vec::new();
+    walk(&registry.join("cache"), &mut files)?;
+    for path in files {
+        if path.extension().is_none_or(|e| e != "crate") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some((name, version)) = split_crate_file(&stem) {
+            cached.push(CachedVersion {
+                name: name.to_string(),
+                version: version.to_string(),
+                path: path.clone(),
+                source: CacheSource::Crate,
+            });
+        }
+    }
+
+    let index = registry.join("index");
+    if index.is_dir() {
+        for entry in fs::read_dir(&index).map_err(file_error(&index))? {
+            let mut files = Vec::new();
+            walk(&entry?.path().join(".cache"), &mut files)?;
+            for path in files {
+                let bytes = fs::read(&path).map_err(file_error(&path))?;
+                for (name, version) in index_cache_entries(&bytes) {
+                    cached.push(CachedVersion {
+                        name,
+                        version,
+                        path: path.clone(),
+                        source: CacheSource::Index,
+                    });
+                }
+            }
+        }
+    }
+
+    cached.sort_by(|a, b| (&a.name, &a.version, &a.path).cmp(&(&b.name, &b.version, &b.path)));
+    Ok(cached)
+}
+
+/// Looks every version cached in `registry` up in the loaded `crates` and `versions`.
+pub fn compare_registry(
+    db: &Connection,
+    registry: &Path,
+) -> Result<Vec<CachedVersionStatus>, Error> {
+    let mut version = db.prepare(
+        r#"
+            SELECT v.yanked, CAST(v.crate_id AS INTEGER), v.created_at, CAST(v.id AS INTEGER)
+            FROM main.versions v JOIN main.crates c ON CAST(c.id AS INTEGER) = CAST(v.crate_id AS INTEGER)
+            WHERE c.name = ? AND v.num = ?
+        "#,
+    )?;
+    let mut newest = db.prepare(
+        r#"
+            SELECT num FROM main.versions
+            WHERE CAST(crate_id AS INTEGER) = ?1 AND yanked <> 't'
+                AND (created_at > ?2 OR (created_at = ?2 AND CAST(id AS INTEGER) > ?3))
+            ORDER BY created_at DESC, CAST(id AS INTEGER) DESC LIMIT 1
+        "#,
+    )?;
+
+    let mut statuses = Vec::new();
+    for cached in scan_registry(registry)? {
+        let found = version
+            .query_row([&cached.name, &cached.version], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .optional()?;
+        let status = match found {
+            None => CachedStatus::Missing,
+            Some((yanked, _, _, _)) if yanked == "t" => CachedStatus::Yanked,
+            Some((_, crate_id, created_at, id)) => match newest
+                .query_row(rusqlite::params![crate_id, created_at, id], |row| {
+                    row.get(0)
+                })
+                .optional()?
+            {
+                Some(newest) => CachedStatus::Superseded { newest },
+                None => CachedStatus::Current,
+            },
+        };
+        statuses.push(CachedVersionStatus { cached, status });
+    }
+    Ok(statuses)
+}
+
+#[test]
+fn test_compare_registry() -> Result<(), Error> {
+    assert_eq!(
+        Some(("serde-json", "1.0.0-rc.1")),
+        split_crate_file("serde-json-1.0.0-rc.1")
+    );
+    assert_eq!(Some(("gfx-2d", "0.1.0")), split_crate_file("gfx-2d-0.1.0"));
+    assert_eq!(None, split_crate_file("nope"));
+
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1", "1.0.2"])
+        .add_crate("tokio", &["1.0.0"])
+        .build()?;
+    db.execute_batch("UPDATE versions SET yanked = 't' WHERE num = '1.0.2';")?;
+
+    let registry = Path::new("testdata/extracted/registry");
+    let _ = fs::remove_dir_all(registry);
+    let cache = registry.join("cache/index.crates.io-6f17d22bba15001f");
+    fs::create_dir_all(&cache)?;
+    for file in [
+        "serde-1.0.0.crate",
+        "serde-1.0.2.crate",
+        "tokio-1.0.0.crate",
+        "rand-0.8.5.crate",
+    ] {
+        fs::write(cache.join(file), b"")?;
+    }
+    let index_cache = registry.join("index/index.crates.io-6f17d22bba15001f/.cache/to/ki");
+    fs::create_dir_all(&index_cache)?;
+    let mut bytes = vec![3, 2, 0, 0, 0];
+    bytes.extend_from_slice(b"etag\0");
+    bytes.extend_from_slice(b"1.0.1\0{\"name\":\"tokio\",\"vers\":\"1.0.1\"}\0");
+    fs::write(index_cache.join("tokio"), bytes)?;
+
+    let statuses: Vec<(String, CacheSource, CachedStatus)> = compare_registry(&db, registry)?
+        .into_iter()
+        .map(|s| {
+            let id = format!("{}-{}", s.cached.name, s.cached.version);
+            (id, s.cached.source, s.status)
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                "rand-0.8.5".to_string(),
+                CacheSource::Crate,
+                CachedStatus::Missing
+            ),
+            (
+                "serde-1.0.0".to_string(),
+                CacheSource::Crate,
+                CachedStatus::Superseded {
+                    newest: "1.0.1".to_string()
+                }
+            ),
+            (
+                "serde-1.0.2".to_string(),
+                CacheSource::Crate,
+                CachedStatus::Yanked
+            ),
+            (
+                "tokio-1.0.0".to_string(),
+                CacheSource::Crate,
+                CachedStatus::Current
+            ),
+            (
+                "tokio-1.0.1".to_string(),
+                CacheSource::Index,
+                CachedStatus::Missing
+            ),
+        ],
+        statuses
+    );
+    Ok(())
+}
@@ -0,0 +1,401 @@
+//! Health reports of a crate or of every crate in a category: how recently they were released,
+//! how many crates are exposed to them, whether they rely on yanked versions, their licenses and
+//! how fresh their dependencies are.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use rusqlite::Connection;
+use semver::{Version, VersionReq};
+use serde_json::{json, Value};
+
+use crate::{
+    cadence::release_cadence,
+    rows::{crate_by_name, DependencyKind},
+    tags::crates_in_category,
+    Error, ReverseDependencies,
+};
+
+/// Crates whose newest release is older than this, relative to the newest release in the dump,
+/// count as stale.
+pub const STALE_DAYS: f64 = 365.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReportSubject {
+    Crate(String),
+    /// A category slug.
+    Category(String),
+}
+
+/// A normal, non-optional dependency of the newest version of a crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub req: String,
+    /// The newest non-yanked version matching `req`.
+    pub resolved: Option<String>,
+    /// The newest non-yanked stable version.
+    pub newest: Option<String>,
+    /// Only yanked versions match `req`.
+    pub yanked_only: bool,
+}
+
+impl DependencyHealth {
+    /// `req` doesn't allow the newest version.
+    pub fn outdated(&self) -> bool {
+        self.newest.is_some() && self.resolved != self.newest
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrateHealth {
+    pub name: String,
+    /// The newest non-yanked version, or the newest one if all are yanked.
+    pub version: String,
+    pub license: String,
+    pub releases: i64,
+    pub yanked_versions: usize,
+    /// Days between its newest release and the newest release in the dump.
+    pub last_release_age_days: f64,
+    /// Crates whose newest version has a normal dependency on it.
+    pub dependents: usize,
+    /// All-time downloads of `dependents`.
+    pub dependent_downloads: i64,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+impl CrateHealth {
+    pub fn stale(&self) -> bool {
+        self.last_release_age_days > STALE_DAYS
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HealthReport {
+    pub subject: ReportSubject,
+    pub crates: Vec<CrateHealth>,
+    /// Licenses of the crates and of the versions their dependencies resolve to, as
+    /// `(license, packages)`, most common first. Versions without a license count as `unknown`.
+    pub licenses: Vec<(String, usize)>,
+}
+
+fn parse_versions<'a>(versions: impl Iterator<Item = &'a str>) -> Vec<Version> {
+    versions
+        .filter_map(|num| Version::parse(num).ok())
+        .collect()
+}
+
+fn dependency_health(
+    db: &Connection,
+    name: String,
+    req: String,
+    licenses: &mut BTreeMap<(String, String), String>,
+) -> Result<DependencyHealth, Error> {
+    let versions = crate_by_name(db, &name)?.map_or_else(Vec::new, |(_, versions)| versions);
+    let parsed_req = VersionReq::parse(&req).ok();
+    let matches = |v: &Version| parsed_req.as_ref().is_some_and(|req| req.matches(v));
+    let available = parse_versions(
+        versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .map(|v| v.num.as_str()),
+    );
+    let yanked = parse_versions(versions.iter().filter(|v| v.yanked).map(|v| v.num.as_str()));
+
+    let resolved = available.iter().filter(|v| matches(v)).max().cloned();
+    let newest = available.iter().filter(|v| v.pre.is_empty()).max();
+    if let Some(resolved) = &resolved {
+        let license = versions
+            .iter()
+            .find(|v| Version::parse(&v.num).ok().as_ref() == Some(resolved))
+            .map(|v| v.license.clone())
+            .unwrap_or_default();
+        licenses.insert((name.clone(), resolved.to_string()), license);
+    }
+    Ok(DependencyHealth {
+        yanked_only: resolved.is_none() && yanked.iter().any(matches),
+        resolved: resolved.map(|v| v.to_string()),
+        newest: newest.map(Version::to_string),
+        name,
+        req,
+    })
+}
+
+fn crate_health(
+    db: &Connection,
+    name: &str,
+    licenses: &mut BTreeMap<(String, String), String>,
+) -> Result<Option<CrateHealth>, Error> {
+    let versions = match crate_by_name(db, name)? {
+        Some((_, versions)) if !versions.is_empty() => versions,
+        _ => return Ok(None),
+    };
+    let newest = versions
+        .iter()
+        .rev()
+        .find(|v| !v.yanked)
+        .or_else(|| versions.last())
+        .expect("versions is not empty");
+    licenses.insert(
+        (name.to_string(), newest.num.clone()),
+        newest.license.clone(),
+    );
+    let cadence = release_cadence(db, name)?;
+
+    let mut dependents = BTreeMap::new();
+    for dependent in ReverseDependencies::default()
+        .kind(Some(DependencyKind::Normal))
+        .latest_only(true)
+        .query(db, name)?
+    {
+        dependents.insert(dependent.crate_name, dependent.downloads);
+    }
+
+    let mut stmt = db.prepare(
+        r#"
+            SELECT dc.name, d.req FROM main.dependencies d
+            JOIN main.crates dc ON CAST(dc.id AS INTEGER) = CAST(d.crate_id AS INTEGER)
+            WHERE CAST(d.version_id AS INTEGER) = ? AND CAST(d.kind AS INTEGER) = 0
+                AND d.optional <> 't'
+            ORDER BY dc.name
+        "#,
+    )?;
+    let requirements = stmt
+        .query_map([newest.id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+    let dependencies = requirements
+        .into_iter()
+        .map(|(name, req)| dependency_health(db, name, req, licenses))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Some(CrateHealth {
+        name: name.to_string(),
+        version: newest.num.clone(),
+        license: newest.license.clone(),
+        releases: cadence.as_ref().map_or(0, |c| c.releases),
+        yanked_versions: versions.iter().filter(|v| v.yanked).count(),
+        last_release_age_days: cadence.map_or(0.0, |c| c.last_release_age_days),
+        dependents: dependents.len(),
+        dependent_downloads: dependents.values().sum(),
+        dependencies,
+    }))
+}
+
+/// The health report of `subject`, or `None` for an unknown crate or an empty category.
+pub fn health_report(
+    db: &Connection,
+    subject: &ReportSubject,
+) -> Result<Option<HealthReport>, Error> {
+    let names = match subject {
+        ReportSubject::Crate(name) => vec![name.clone()],
+        ReportSubject::Category(slug) => crates_in_category(db, slug)?,
+    };
+    let mut licenses = BTreeMap::new();
+    let mut crates = Vec::new();
+    for name in &names {
+        crates.extend(crate_health(db, name, &mut licenses)?);
+    }
+    if crates.is_empty() {
+        return Ok(None);
+    }
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for license in licenses.into_values() {
+        let license = match license.is_empty() {
+            true => "unknown".to_string(),
+            false => license,
+        };
+        *counts.entry(license).or_default() += 1;
+    }
+    let mut licenses: Vec<(String, usize)> = counts.into_iter().collect();
+    licenses.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(Some(HealthReport {
+        subject: subject.clone(),
+        crates,
+        licenses,
+    }))
+}
+
+impl HealthReport {
+    fn dependencies(&self) -> impl Iterator<Item = &DependencyHealth> {
+        self.crates.iter().flat_map(|c| &c.dependencies)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = match &self.subject {
+            ReportSubject::Crate(name) => format!("# Health of {}\n\n", name),
+            ReportSubject::Category(slug) => format!("# Health of category {}\n\n", slug),
+        };
+        writeln!(
+            out,
+            "- {} crates, {} without a release in {} days",
+            self.crates.len(),
+            self.crates.iter().filter(|c| c.stale()).count(),
+            STALE_DAYS
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "- {} dependencies not allowing their newest version, {} only matching yanked versions",
+            self.dependencies().filter(|d| d.outdated()).count(),
+            self.dependencies().filter(|d| d.yanked_only).count()
+        )
+        .unwrap();
+
+        for c in &self.crates {
+            write!(out, "\n## {} {}\n\n", c.name, c.version).unwrap();
+            writeln!(
+                out,
+                "- {} releases, {} yanked, the newest {:.0} days before the dump's newest",
+                c.releases, c.yanked_versions, c.last_release_age_days
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "- {} dependents ({} downloads)",
+                c.dependents, c.dependent_downloads
+            )
+            .unwrap();
+            if !c.license.is_empty() {
+                writeln!(out, "- License: {}", c.license).unwrap();
+            }
+            for d in &c.dependencies {
+                let status = match (&d.resolved, &d.newest) {
+                    _ if d.yanked_only => "only yanked versions match".to_string(),
+                    (None, _) => "no version matches".to_string(),
+                    (Some(resolved), Some(newest)) if d.outdated() => {
+                        format!("{}, newest {}", resolved, newest)
+                    }
+                    (Some(resolved), _) => resolved.clone(),
+                };
+                writeln!(out, "- Depends on {} {} ({})", d.name, d.req, status).unwrap();
+            }
+        }
+
+        out.push_str("\n## Licenses\n\n");
+        for (license, packages) in &self.licenses {
+            writeln!(out, "- {}: {}", license, packages).unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let subject = match &self.subject {
+            ReportSubject::Crate(name) => json!({ "crate": name }),
+            ReportSubject::Category(slug) => json!({ "category": slug }),
+        };
+        json!({
+            "subject": subject,
+            "crates": self.crates.iter().map(|c| json!({
+                "name": c.name,
+                "version": c.version,
+                "license": c.license,
+                "releases": c.releases,
+                "yanked_versions": c.yanked_versions,
+                "last_release_age_days": c.last_release_age_days,
+                "stale": c.stale(),
+                "dependents": c.dependents,
+                "dependent_downloads": c.dependent_downloads,
+                "dependencies": c.dependencies.iter().map(|d| json!({
+                    "name": d.name,
+                    "req": d.req,
+                    "resolved": d.resolved,
+                    "newest": d.newest,
+                    "outdated": d.outdated(),
+                    "yanked_only": d.yanked_only,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "licenses": self.licenses.iter().map(|(license, packages)| json!({
+                "license": license,
+                "packages": packages,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[test]
+fn test_health_report() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("tokio", &["1.0.0", "1.1.0"])
+        .add_crate("mio", &["0.7.0", "0.8.0"])
+        .add_crate("bytes", &["1.0.0"])
+        .add_crate("hyper", &["0.14.0"])
+        .add_crate("async-std", &["1.0.0"])
+        .dependency("tokio", "1.1.0", "mio", "^0.7")
+        .dependency("tokio", "1.1.0", "bytes", "=1.0.0")
+        .dependency("hyper", "0.14.0", "tokio", "^1")
+        .row(
+            "categories",
+            &[
+                ("id", "1"),
+                ("slug", "asynchronous"),
+                ("category", "Asynchronous"),
+            ],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "1"), ("category_id", "1")],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "5"), ("category_id", "1")],
+        )
+        .build()?;
+    db.execute_batch(
+        r#"
+            UPDATE versions SET license = 'MIT';
+            UPDATE versions SET yanked = 't' WHERE num = '1.0.0' AND CAST(crate_id AS INTEGER) = 3;
+            UPDATE versions SET created_at = '2019-01-01 00:00:00'
+            WHERE CAST(crate_id AS INTEGER) = 5;
+        "#,
+    )?;
+
+    let report = health_report(&db, &ReportSubject::Crate("tokio".to_string()))?.unwrap();
+    let tokio = &report.crates[0];
+    assert_eq!(
+        ("1.1.0", 2, 1, false),
+        (
+            tokio.version.as_str(),
+            tokio.releases,
+            tokio.dependents,
+            tokio.stale()
+        )
+    );
+    let bytes = &tokio.dependencies[0];
+    assert_eq!(
+        ("bytes", None, true),
+        (
+            bytes.name.as_str(),
+            bytes.resolved.as_deref(),
+            bytes.yanked_only
+        )
+    );
+    let mio = &tokio.dependencies[1];
+    assert_eq!(
+        (Some("0.7.0".to_string()), Some("0.8.0".to_string()), true),
+        (mio.resolved.clone(), mio.newest.clone(), mio.outdated())
+    );
+    assert_eq!(vec![("MIT".to_string(), 2)], report.licenses);
+    assert_eq!(json!({ "crate": "tokio" }), report.to_json()["subject"]);
+    assert!(report.to_markdown().contains(
+        "- Depends on bytes =1.0.0 (only yanked versions match)\n- Depends on mio ^0.7 (0.7.0, newest 0.8.0)\n"
+    ));
+
+    let category =
+        health_report(&db, &ReportSubject::Category("asynchronous".to_string()))?.unwrap();
+    assert_eq!(
+        vec![("async-std", true), ("tokio", false)],
+        category
+            .crates
+            .iter()
+            .map(|c| (c.name.as_str(), c.stale()))
+            .collect::<Vec<_>>()
+    );
+    assert!(category
+        .to_markdown()
+        .starts_with("# Health of category asynchronous\n\n- 2 crates, 1 without a release"));
+    assert_eq!(
+        None,
+        health_report(&db, &ReportSubject::Crate("nope".to_string()))?
+    );
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{file_error, CratesIODumpLoader, Error};
+
+/// Decodes `%XX` escapes, leaving malformed ones as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The local path of a `file://` URL, with or without a `localhost` host.
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let path = percent_decode(rest);
+    // `file:///C:/dumps` on Windows.
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// What to hand to cached_path for `resource`: remote URLs as they are, `file://` URLs as
+/// paths, and relative paths joined onto `base`.
+pub(crate) fn resolve(resource: &str, base: Option<&Path>) -> String {
+    if let Some(path) = file_url_path(resource) {
+        return path.to_string_lossy().into_owned();
+    }
+    if resource.contains("://") {
+        return resource.to_string();
+    }
+    match base {
+        Some(base) if Path::new(resource).is_relative() => {
+            base.join(resource).to_string_lossy().into_owned()
+        }
+        _ => resource.to_string(),
+    }
+}
+
+/// Creates `path`, following it if it is a symlink whose target doesn't exist yet.
+pub(crate) fn create_target_dir(path: &Path) -> Result<(), Error> {
+    let dir = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() && !path.exists() => {
+            let link = fs::read_link(path).map_err(file_error(path))?;
+            path.parent().unwrap_or_else(|| Path::new("")).join(link)
+        }
+        _ => path.to_path_buf(),
+    };
+    fs::create_dir_all(&dir).map_err(file_error(&dir))
+}
+
+/// Removes a downloaded archive and the metadata cached_path keeps next to it, so the next
+/// `cached_path()` downloads it again.
+pub(crate) fn invalidate_cached(path: &Path) -> Result<(), Error> {
+    let mut meta = path.as_os_str().to_owned();
+    meta.push(".meta");
+    for file in [path, Path::new(&meta)] {
+        match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(file_error(file)(e)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// When each file in `dir` was last modified, empty if it can't be read.
+pub(crate) fn modified_times(dir: &Path) -> HashMap<PathBuf, Option<SystemTime>> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| {
+            (
+                entry.path(),
+                entry.metadata().and_then(|m| m.modified()).ok(),
+            )
+        })
+        .collect()
+}
+
+impl CratesIODumpLoader {
+    /// Local path of the archive, downloading it first if `resource` is remote.
+    pub(crate) fn cached_resource(&self) -> Result<PathBuf, Error> {
+        let resource = resolve(&self.resource, self.base_path.as_deref());
+        Ok(self.cache_handle()?.cached_path(&resource)?)
+    }
+
+    /// Whether cached_path downloads `resource` rather than reading it in place.
+    pub(crate) fn remote_resource(&self) -> bool {
+        resolve(&self.resource, self.base_path.as_deref()).starts_with("http")
+    }
+}
+
+#[test]
+fn test_resolve_resource() {
+    let base = Path::new("/srv/dumps");
+    assert_eq!(
+        "https://static.crates.io/db-dump.tar.gz",
+        resolve("https://static.crates.io/db-dump.tar.gz", Some(base))
+    );
+    assert_eq!(
+        "/srv/dumps/2021 05.tar.gz",
+        resolve("file:///srv/dumps/2021%2005.tar.gz", None)
+    );
+    assert_eq!(
+        "/srv/dumps/a.tar.gz",
+        resolve("file://localhost/srv/dumps/a.tar.gz", None)
+    );
+    assert_eq!(
+        Some(PathBuf::from("C:/dumps/a.tar.gz")),
+        file_url_path("file:///C:/dumps/a.tar.gz")
+    );
+    assert_eq!(
+        Path::new("/srv/dumps").join("a.tar.gz").to_string_lossy(),
+        resolve("a.tar.gz", Some(base))
+    );
+    #[cfg(unix)]
+    assert_eq!("/tmp/a.tar.gz", resolve("/tmp/a.tar.gz", Some(base)));
+    assert_eq!("a.tar.gz", resolve("a.tar.gz", None));
+}
+
+#[test]
+fn test_local_resources() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let cache = || cached_path::Cache::builder().progress_bar(None);
+    let absolute = crate::sql::csvtab_path(&fs::canonicalize("testdata/test.tar.gz")?);
+    let url = match absolute.starts_with('/') {
+        true => format!("file://{}", absolute.replace(' ', "%20")),
+        false => format!("file:///{}", absolute.replace(' ', "%20")),
+    };
+    let target = Path::new("testdata/extracted/resources");
+    let _ = fs::remove_dir_all(target);
+    fs::create_dir_all(target)?;
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource(&url)
+        .target_path(&target.join("from-url"))
+        .tables(&["test"])
+        .cache(cache())?
+        .update()?
+        .load_dump_into(&db)?;
+
+    CratesIODumpLoader::default()
+        .base_path(Path::new("testdata"))
+        .resource("test.tar.gz")
+        .target_path(&target.join("from-base"))
+        .tables(&["test"])
+        .cache(cache())?
+        .update()?;
+    assert!(target.join("from-base/test.csv").exists());
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("real", target.join("linked"))?;
+        CratesIODumpLoader::default()
+            .resource("testdata/test.tar.gz")
+            .target_path(&target.join("linked"))
+            .tables(&["test"])
+            .cache(cache())?
+            .update()?;
+        assert!(target.join("real/test.csv").exists());
+    }
+    Ok(())
+}
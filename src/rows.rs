@@ -0,0 +1,247 @@
+//! Typed rows of the main dump tables.
+
+use std::fmt;
+
+use rusqlite::{
+    types::{FromSql, FromSqlError, FromSqlResult, ValueRef},
+    Connection, OptionalExtension, Row,
+};
+
+use crate::{stats::crate_downloads, Error, Table};
+
+/// A row type of a dump table, read with [`read_rows`] or, with the `async` feature,
+/// [`crate::CratesIODumpLoader::stream_table`].
+pub trait DumpRow: Sized + Send + 'static {
+    const TABLE: Table;
+    /// Selects the row's columns, cast so they read the same with or without typed schemas.
+    const QUERY: &'static str;
+
+    /// `QUERY`, adapted to the layout of the dump loaded into `db`.
+    fn query(_db: &Connection) -> Result<String, Error> {
+        Ok(Self::QUERY.to_string())
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crate {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub repository: String,
+    /// All-time downloads.
+    pub downloads: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl DumpRow for Crate {
+    const TABLE: Table = Table::Crates;
+    const QUERY: &'static str = "SELECT CAST(id AS INTEGER), name, description, repository, \
+        CAST(downloads AS INTEGER), created_at, updated_at FROM main.crates";
+
+    /// Takes `downloads` from `crate_downloads` in dumps that moved it there.
+    fn query(db: &Connection) -> Result<String, Error> {
+        Ok(format!(
+            "SELECT CAST(c.id AS INTEGER), c.name, c.description, c.repository, \
+            COALESCE(d.downloads, 0), c.created_at, c.updated_at FROM main.crates c \
+            LEFT JOIN {} d ON d.crate_id = CAST(c.id AS INTEGER)",
+            crate_downloads(db, "main")?
+        ))
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Crate {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            repository: row.get(3)?,
+            downloads: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub id: i64,
+    pub crate_id: i64,
+    pub num: String,
+    pub license: String,
+    pub downloads: i64,
+    pub yanked: bool,
+    pub created_at: String,
+}
+
+impl DumpRow for Version {
+    const TABLE: Table = Table::Versions;
+    const QUERY: &'static str = "SELECT CAST(id AS INTEGER), CAST(crate_id AS INTEGER), num, \
+        license, CAST(downloads AS INTEGER), yanked = 't', created_at FROM main.versions";
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Version {
+            id: row.get(0)?,
+            crate_id: row.get(1)?,
+            num: row.get(2)?,
+            license: row.get(3)?,
+            downloads: row.get(4)?,
+            yanked: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+/// Decoded `dependencies.kind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DependencyKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl DependencyKind {
+    /// The kind stored as `raw` in the dump: 0 normal, 1 build, 2 dev.
+    pub fn from_raw(raw: i64) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Build),
+            2 => Some(Self::Dev),
+            _ => None,
+        }
+    }
+
+    pub fn raw(self) -> i64 {
+        match self {
+            Self::Normal => 0,
+            Self::Build => 1,
+            Self::Dev => 2,
+        }
+    }
+
+    /// `normal`, `build` or `dev`, as SQL's `kind_name()` returns it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Build => "build",
+            Self::Dev => "dev",
+        }
+    }
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Reads the raw kind, as an integer or as the text of a csv column.
+impl FromSql for DependencyKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let raw = match value {
+            ValueRef::Text(text) => std::str::from_utf8(text)
+                .ok()
+                .and_then(|text| text.parse().ok())
+                .ok_or(FromSqlError::InvalidType)?,
+            value => i64::column_result(value)?,
+        };
+        Self::from_raw(raw).ok_or(FromSqlError::OutOfRange(raw))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+    pub id: i64,
+    /// The depending version.
+    pub version_id: i64,
+    /// The crate depended on.
+    pub crate_id: i64,
+    pub req: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+    pub default_features: bool,
+}
+
+impl DumpRow for Dependency {
+    const TABLE: Table = Table::Dependencies;
+    const QUERY: &'static str = "SELECT CAST(id AS INTEGER), CAST(version_id AS INTEGER), \
+        CAST(crate_id AS INTEGER), req, CAST(kind AS INTEGER), optional = 't', \
+        default_features = 't' FROM main.dependencies";
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Dependency {
+            id: row.get(0)?,
+            version_id: row.get(1)?,
+            crate_id: row.get(2)?,
+            req: row.get(3)?,
+            kind: row.get(4)?,
+            optional: row.get(5)?,
+            default_features: row.get(6)?,
+        })
+    }
+}
+
+/// Every row of `T`'s table.
+pub fn read_rows<T: DumpRow>(db: &Connection) -> Result<Vec<T>, Error> {
+    let mut stmt = db.prepare(&T::query(db)?)?;
+    let rows = stmt.query_map([], T::from_row)?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// The crate called `name` and its versions, oldest first.
+pub fn crate_by_name(db: &Connection, name: &str) -> Result<Option<(Crate, Vec<Version>)>, Error> {
+    let krate = db
+        .query_row(
+            &format!("SELECT * FROM ({}) WHERE name = ?", Crate::query(db)?),
+            [name],
+            Crate::from_row,
+        )
+        .optional()?;
+    let krate = match krate {
+        Some(krate) => krate,
+        None => return Ok(None),
+    };
+    let mut stmt = db.prepare(&format!(
+        "{} WHERE CAST(crate_id AS INTEGER) = ? ORDER BY CAST(id AS INTEGER)",
+        Version::query(db)?
+    ))?;
+    let versions = stmt
+        .query_map([krate.id], Version::from_row)?
+        .collect::<Result<_, _>>()?;
+    Ok(Some((krate, versions)))
+}
+
+#[test]
+fn test_read_rows() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("serde_derive", &["1.0.0"])
+        .dependency("serde_derive", "1.0.0", "serde", "^1.0")
+        .build()?;
+
+    let crates: Vec<Crate> = read_rows(&db)?;
+    assert_eq!(
+        vec!["serde", "serde_derive"],
+        crates.iter().map(|c| c.name.as_str()).collect::<Vec<_>>()
+    );
+    let versions: Vec<Version> = read_rows(&db)?;
+    assert_eq!(3, versions.len());
+    assert!(versions.iter().all(|v| !v.yanked && v.crate_id > 0));
+    let dependencies: Vec<Dependency> = read_rows(&db)?;
+    assert_eq!("^1.0", dependencies[0].req);
+    assert_eq!(crates[0].id, dependencies[0].crate_id);
+    assert_eq!(DependencyKind::Normal, dependencies[0].kind);
+
+    // Dumps with crate_downloads count downloads there.
+    db.execute_batch(
+        "CREATE TABLE crate_downloads(crate_id TEXT, downloads TEXT);
+        INSERT INTO crate_downloads SELECT id, '42' FROM crates WHERE name = 'serde';",
+    )?;
+    let crates: Vec<Crate> = read_rows(&db)?;
+    assert_eq!(
+        vec![42, 0],
+        crates.iter().map(|c| c.downloads).collect::<Vec<_>>()
+    );
+    Ok(())
+}
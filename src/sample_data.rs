@@ -0,0 +1,48 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::{CratesIODumpLoader, Error};
+
+/// A dump of 40 well known crates as of 2021-06-01, with every table filled in.
+pub const SAMPLE_DUMP: &[u8] = include_bytes!("../sample/db-dump.tar.gz");
+
+impl CratesIODumpLoader {
+    /// A loader for [`SAMPLE_DUMP`], for examples and CI that shouldn't download anything.
+    ///
+    /// The archive is written to, and extracted in, a directory under the system temp dir.
+    pub fn sample() -> Result<Self, Error> {
+        let dir: PathBuf = env::temp_dir().join(format!(
+            "cratesio-dbdump-sample-{}",
+            env!("CARGO_PKG_VERSION")
+        ));
+        let archive = dir.join("db-dump.tar.gz");
+        if !archive.exists() {
+            fs::create_dir_all(&dir)?;
+            // Written under another name first so concurrent callers never see half a file.
+            let partial = dir.join(format!("db-dump.tar.gz.{}", std::process::id()));
+            fs::write(&partial, SAMPLE_DUMP)?;
+            fs::rename(&partial, &archive)?;
+        }
+
+        Self::builder()
+            .resource(&archive.to_string_lossy())
+            .target_path(&dir.join("data"))
+            .build()
+    }
+}
+
+#[test]
+fn test_sample() -> Result<(), Error> {
+    let db = CratesIODumpLoader::sample()?
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .open_db()?;
+    let crates: i64 = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?;
+    assert_eq!(40, crates);
+    let rdeps = crate::reverse_dependencies(&db, "serde")?;
+    assert!(rdeps.iter().any(|d| d.crate_name == "serde_json"));
+    assert_eq!(
+        crate::schema::SchemaReport::default(),
+        crate::schema::validate_schema(&db)?
+    );
+    Ok(())
+}
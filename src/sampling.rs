@@ -0,0 +1,207 @@
+//! Row filters that keep a sampled database referentially consistent.
+
+// Tables in load order, each with the conditions tying its rows to tables loaded before it. A
+// condition only applies when the table it refers to is loaded too.
+const SAMPLE_FILTERS: &[(&str, &[(&str, &str)])] = &[
+    ("crates", &[]),
+    (
+        "versions",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "dependencies",
+        &[
+            ("versions", "CAST(version_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.versions)"),
+            ("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)"),
+        ],
+    ),
+    (
+        "version_downloads",
+        &[("versions", "CAST(version_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.versions)")],
+    ),
+    (
+        "version_authors",
+        &[("versions", "CAST(version_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.versions)")],
+    ),
+    (
+        "default_versions",
+        &[
+            ("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)"),
+            ("versions", "CAST(version_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.versions)"),
+        ],
+    ),
+    (
+        "badges",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "crate_downloads",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "crate_owners",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "crates_categories",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "crates_keywords",
+        &[("crates", "CAST(crate_id AS INTEGER) IN (SELECT CAST(id AS INTEGER) FROM main.crates)")],
+    ),
+    (
+        "categories",
+        &[(
+            "crates_categories",
+            "CAST(id AS INTEGER) IN (SELECT CAST(category_id AS INTEGER) FROM main.crates_categories)",
+        )],
+    ),
+    (
+        "keywords",
+        &[(
+            "crates_keywords",
+            "CAST(id AS INTEGER) IN (SELECT CAST(keyword_id AS INTEGER) FROM main.crates_keywords)",
+        )],
+    ),
+    (
+        "users",
+        &[(
+            "crate_owners",
+            "CAST(id AS INTEGER) IN (SELECT CAST(owner_id AS INTEGER) FROM main.crate_owners WHERE CAST(owner_kind AS INTEGER) = 0)",
+        )],
+    ),
+    (
+        "teams",
+        &[(
+            "crate_owners",
+            "CAST(id AS INTEGER) IN (SELECT CAST(owner_id AS INTEGER) FROM main.crate_owners WHERE CAST(owner_kind AS INTEGER) = 1)",
+        )],
+    ),
+];
+
+/// Where `table` has to be loaded so the tables its filter refers to are already there. Tables
+/// without a filter go last.
+pub(crate) fn load_order(table: &str) -> usize {
+    SAMPLE_FILTERS
+        .iter()
+        .position(|(t, _)| *t == table)
+        .unwrap_or(SAMPLE_FILTERS.len())
+}
+
+/// `WHERE` condition keeping the rows of `table` that refer to rows already sampled from the
+/// `loaded` tables.
+pub(crate) fn filter(table: &str, loaded: &[String]) -> String {
+    let conditions: Vec<&str> = SAMPLE_FILTERS
+        .iter()
+        .filter(|(t, _)| *t == table)
+        .flat_map(|(_, conditions)| conditions.iter())
+        .filter(|(requires, _)| loaded.iter().any(|l| l == requires))
+        .map(|(_, condition)| *condition)
+        .collect();
+    match conditions.is_empty() {
+        true => "1".to_string(),
+        false => conditions.join(" AND "),
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// SQL expression hashing `rowid` with constants derived from `seed`, to order rows by.
+///
+/// SQLite has no seedable random function, so this is a multiply-xorshift-multiply hash kept
+/// within 32 bits, where none of the products can overflow a 64-bit integer for rowids below
+/// 2^32.
+pub(crate) fn shuffle(seed: u64) -> String {
+    let mut state = seed;
+    let mut constant = || (splitmix64(&mut state) & 0x7fff_ffff) | 1;
+    let (a, b, c) = (constant(), constant(), constant());
+    let h = format!("((rowid * {} + {}) & 4294967295)", a, b);
+    // SQLite has no XOR either, so `x ^ y` is `(x | y) - (x & y)`.
+    let x = format!("(({0} | ({0} >> 16)) - ({0} & ({0} >> 16)))", h);
+    format!("(({} * {}) & 4294967295)", x, c)
+}
+
+#[test]
+fn test_sample_rows() -> Result<(), crate::Error> {
+    use crate::CratesIODumpLoader;
+    use rusqlite::Connection;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/sampling"))
+        .sample_rows(3)
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let ids = |sql: &str| -> Result<Vec<i64>, crate::Error> {
+        let mut stmt = db.prepare(sql)?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        Ok(ids.collect::<Result<_, _>>()?)
+    };
+    assert_eq!(
+        vec![1, 2, 3],
+        ids("SELECT CAST(id AS INTEGER) FROM crates ORDER BY 1")?
+    );
+    assert_eq!(
+        vec![10, 11, 20],
+        ids("SELECT CAST(id AS INTEGER) FROM versions ORDER BY 1")?
+    );
+    assert_eq!(
+        vec![100, 101, 102],
+        ids("SELECT CAST(id AS INTEGER) FROM dependencies ORDER BY 1")?
+    );
+    assert_eq!(
+        Vec::<i64>::new(),
+        ids("SELECT CAST(category_id AS INTEGER) FROM crates_categories LEFT JOIN categories ON CAST(categories.id AS INTEGER) = CAST(category_id AS INTEGER) WHERE categories.id IS NULL")?
+    );
+    assert_eq!(
+        vec![1, 2],
+        ids("SELECT CAST(id AS INTEGER) FROM users ORDER BY 1")?
+    );
+    let virtual_tables: i64 = db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE sql LIKE 'CREATE VIRTUAL%'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert_eq!(0, virtual_tables);
+
+    let seeded = |seed: u64| -> Result<Vec<i64>, crate::Error> {
+        let db = Connection::open_in_memory()?;
+        rusqlite::vtab::csvtab::load_module(&db)?;
+        CratesIODumpLoader::default()
+            .resource("testdata/fixture.tar.gz")
+            .target_path(Path::new("testdata/extracted/sampling"))
+            .minimal()
+            .sample_rows(3)
+            .seed(seed)
+            .cache(cached_path::Cache::builder().progress_bar(None))?
+            .update()?
+            .load_dump_into(&db)?;
+        let orphans: i64 = db.query_row(
+            "SELECT COUNT(*) FROM versions WHERE CAST(crate_id AS INTEGER) NOT IN (SELECT CAST(id AS INTEGER) FROM crates)",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(0, orphans);
+        let mut stmt = db.prepare("SELECT CAST(id AS INTEGER) FROM crates ORDER BY 1")?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        Ok(ids.collect::<Result<_, _>>()?)
+    };
+    assert_eq!(seeded(42)?, seeded(42)?);
+    assert_eq!(3, seeded(42)?.len());
+    let samples: Vec<_> = (0..8).map(seeded).collect::<Result<_, _>>()?;
+    assert!(samples.iter().any(|s| *s != samples[0]));
+    Ok(())
+}
@@ -0,0 +1,592 @@
+//! Canonical layout of the crates.io database dump tables.
+
+use rusqlite::Connection;
+
+use crate::{table_exists, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    BigInt,
+    Text,
+    Boolean,
+    Timestamp,
+    Date,
+    Json,
+    TextArray,
+}
+
+impl ColumnType {
+    pub fn postgres_type(&self) -> &'static str {
+        match self {
+            Self::Integer => "INTEGER",
+            Self::BigInt => "BIGINT",
+            Self::Text => "TEXT",
+            Self::Boolean => "BOOLEAN",
+            Self::Timestamp => "TIMESTAMP",
+            Self::Date => "DATE",
+            Self::Json => "JSONB",
+            Self::TextArray => "TEXT[]",
+        }
+    }
+
+    pub fn sqlite_type(&self) -> &'static str {
+        match self {
+            Self::Integer | Self::BigInt => "INTEGER",
+            _ => "TEXT",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+}
+
+const fn col(name: &'static str, ty: ColumnType) -> Column {
+    Column { name, ty }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableSchema {
+    pub name: &'static str,
+    pub columns: &'static [Column],
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn postgres_ddl(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.ty.postgres_type()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("CREATE TABLE {}({});", self.name, columns)
+    }
+
+    /// Schema in the form expected by `table_schema()`.
+    pub fn sqlite_schema(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.ty.sqlite_type()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("CREATE TABLE x({});", columns)
+    }
+}
+
+use ColumnType::*;
+
+/// Every table in the dump, with columns in the order they appear in the CSV headers.
+pub const TABLES: &[TableSchema] = &[
+    TableSchema {
+        name: "badges",
+        columns: &[
+            col("attributes", Json),
+            col("badge_type", Text),
+            col("crate_id", Integer),
+        ],
+    },
+    TableSchema {
+        name: "categories",
+        columns: &[
+            col("category", Text),
+            col("crates_cnt", Integer),
+            col("created_at", Timestamp),
+            col("description", Text),
+            col("id", Integer),
+            col("path", Text),
+            col("slug", Text),
+        ],
+    },
+    TableSchema {
+        name: "crate_downloads",
+        columns: &[col("crate_id", Integer), col("downloads", Integer)],
+    },
+    TableSchema {
+        name: "crate_owners",
+        columns: &[
+            col("crate_id", Integer),
+            col("created_at", Timestamp),
+            col("created_by", Integer),
+            col("owner_id", Integer),
+            col("owner_kind", Integer),
+        ],
+    },
+    TableSchema {
+        name: "crates",
+        columns: &[
+            col("created_at", Timestamp),
+            col("description", Text),
+            col("documentation", Text),
+            col("downloads", Integer),
+            col("homepage", Text),
+            col("id", Integer),
+            col("max_upload_size", Integer),
+            col("name", Text),
+            col("readme", Text),
+            col("repository", Text),
+            col("updated_at", Timestamp),
+        ],
+    },
+    TableSchema {
+        name: "crates_categories",
+        columns: &[col("category_id", Integer), col("crate_id", Integer)],
+    },
+    TableSchema {
+        name: "crates_keywords",
+        columns: &[col("crate_id", Integer), col("keyword_id", Integer)],
+    },
+    TableSchema {
+        name: "default_versions",
+        columns: &[
+            col("crate_id", Integer),
+            col("num_versions", Integer),
+            col("version_id", Integer),
+        ],
+    },
+    TableSchema {
+        name: "dependencies",
+        columns: &[
+            col("crate_id", Integer),
+            col("default_features", Boolean),
+            col("explicit_name", Text),
+            col("features", TextArray),
+            col("id", Integer),
+            col("kind", Integer),
+            col("optional", Boolean),
+            col("req", Text),
+            col("target", Text),
+            col("version_id", Integer),
+        ],
+    },
+    TableSchema {
+        name: "keywords",
+        columns: &[
+            col("crates_cnt", Integer),
+            col("created_at", Timestamp),
+            col("id", Integer),
+            col("keyword", Text),
+        ],
+    },
+    TableSchema {
+        name: "metadata",
+        columns: &[col("total_downloads", BigInt)],
+    },
+    TableSchema {
+        name: "reserved_crate_names",
+        columns: &[col("name", Text)],
+    },
+    TableSchema {
+        name: "teams",
+        columns: &[
+            col("avatar", Text),
+            col("github_id", Integer),
+            col("id", Integer),
+            col("login", Text),
+            col("name", Text),
+        ],
+    },
+    TableSchema {
+        name: "users",
+        columns: &[
+            col("gh_avatar", Text),
+            col("gh_id", Integer),
+            col("gh_login", Text),
+            col("id", Integer),
+            col("name", Text),
+        ],
+    },
+    TableSchema {
+        name: "version_authors",
+        columns: &[col("name", Text), col("version_id", Integer)],
+    },
+    TableSchema {
+        name: "version_downloads",
+        columns: &[
+            col("date", Date),
+            col("downloads", Integer),
+            col("version_id", Integer),
+        ],
+    },
+    TableSchema {
+        name: "versions",
+        columns: &[
+            col("checksum", Text),
+            col("crate_id", Integer),
+            col("crate_size", Integer),
+            col("created_at", Timestamp),
+            col("downloads", Integer),
+            col("features", Json),
+            col("id", Integer),
+            col("license", Text),
+            col("links", Text),
+            col("num", Text),
+            col("published_by", Integer),
+            col("updated_at", Timestamp),
+            col("yanked", Boolean),
+        ],
+    },
+];
+
+/// Tables crates.io added later, which older dumps don't have.
+pub const NEWER_TABLES: &[&str] = &["crate_downloads", "default_versions"];
+
+pub fn table(name: &str) -> Option<&'static TableSchema> {
+    TABLES.iter().find(|t| t.name == name)
+}
+
+/// A table to load, one of the dump's or a [`Table::Custom`] one, e.g. from
+/// [`crate::TableWriter`]. Converts from table names, so `tables()` takes either.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Table {
+    Badges,
+    Categories,
+    CrateDownloads,
+    CrateOwners,
+    Crates,
+    CratesCategories,
+    CratesKeywords,
+    DefaultVersions,
+    Dependencies,
+    Keywords,
+    Metadata,
+    ReservedCrateNames,
+    Teams,
+    Users,
+    VersionAuthors,
+    VersionDownloads,
+    Versions,
+    Custom(String),
+}
+
+impl Table {
+    /// Every dump table, in the order of [`TABLES`].
+    pub const ALL: [Table; 17] = [
+        Table::Badges,
+        Table::Categories,
+        Table::CrateDownloads,
+        Table::CrateOwners,
+        Table::Crates,
+        Table::CratesCategories,
+        Table::CratesKeywords,
+        Table::DefaultVersions,
+        Table::Dependencies,
+        Table::Keywords,
+        Table::Metadata,
+        Table::ReservedCrateNames,
+        Table::Teams,
+        Table::Users,
+        Table::VersionAuthors,
+        Table::VersionDownloads,
+        Table::Versions,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Table::Badges => "badges",
+            Table::Categories => "categories",
+            Table::CrateDownloads => "crate_downloads",
+            Table::CrateOwners => "crate_owners",
+            Table::Crates => "crates",
+            Table::CratesCategories => "crates_categories",
+            Table::CratesKeywords => "crates_keywords",
+            Table::DefaultVersions => "default_versions",
+            Table::Dependencies => "dependencies",
+            Table::Keywords => "keywords",
+            Table::Metadata => "metadata",
+            Table::ReservedCrateNames => "reserved_crate_names",
+            Table::Teams => "teams",
+            Table::Users => "users",
+            Table::VersionAuthors => "version_authors",
+            Table::VersionDownloads => "version_downloads",
+            Table::Versions => "versions",
+            Table::Custom(name) => name,
+        }
+    }
+
+    /// The canonical layout, `None` for custom tables.
+    pub fn schema(&self) -> Option<&'static TableSchema> {
+        match self {
+            Table::Custom(_) => None,
+            known => table(known.name()),
+        }
+    }
+}
+
+impl From<&str> for Table {
+    fn from(name: &str) -> Self {
+        Table::ALL
+            .iter()
+            .find(|t| t.name() == name)
+            .cloned()
+            .unwrap_or_else(|| Table::Custom(name.to_string()))
+    }
+}
+
+impl From<String> for Table {
+    fn from(name: String) -> Self {
+        Table::from(name.as_str())
+    }
+}
+
+impl From<&Table> for Table {
+    fn from(table: &Table) -> Self {
+        table.clone()
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Splits a `CREATE TABLE x(a INT, b TEXT)` schema override into `(name, type)` pairs.
+pub fn parse_columns(schema: &str) -> Vec<(String, String)> {
+    let start = schema.find('(').map(|i| i + 1).unwrap_or(0);
+    let end = schema.rfind(')').unwrap_or(schema.len());
+    schema[start..end.max(start)]
+        .split(',')
+        .filter_map(|column| {
+            let mut parts = column.split_whitespace();
+            let name = parts.next()?.trim_matches(|c| c == '"' || c == '`');
+            let ty = parts.collect::<Vec<_>>().join(" ");
+            Some((name.to_string(), ty))
+        })
+        .collect()
+}
+
+/// The column names in the first line of a csv.
+#[cfg(any(feature = "postgres", feature = "datafusion"))]
+pub(crate) fn parse_header(line: &str) -> Result<Vec<String>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+    Ok(match reader.records().next() {
+        Some(record) => record?.iter().map(str::to_string).collect(),
+        None => Vec::new(),
+    })
+}
+
+/// A column whose declared type or values don't fit its canonical type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub table: String,
+    pub column: String,
+    pub expected: ColumnType,
+    /// Declared SQLite type, `TEXT` for csv tables without a schema override.
+    pub declared: String,
+    /// A value from the first rows that doesn't parse as `expected`, if any.
+    pub sample: Option<String>,
+}
+
+/// Differences between the loaded tables and [`TABLES`], see [`validate_schema`]. Missing
+/// [`NEWER_TABLES`] aren't reported.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    pub missing_tables: Vec<String>,
+    /// `(table, column)` pairs.
+    pub missing_columns: Vec<(String, String)>,
+    /// `(table, column)` pairs.
+    pub extra_columns: Vec<(String, String)>,
+    pub type_mismatches: Vec<TypeMismatch>,
+}
+
+impl SchemaReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing_tables.is_empty()
+            && self.missing_columns.is_empty()
+            && self.extra_columns.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+}
+
+/// How many rows per table [`validate_schema`] checks values of.
+const SAMPLED_ROWS: usize = 100;
+
+/// Condition true for `v` when it is a valid value of `ty`.
+fn value_check(ty: ColumnType) -> Option<&'static str> {
+    match ty {
+        Integer | BigInt => Some("CAST(CAST(v AS INTEGER) AS TEXT) = v"),
+        Boolean => Some("v IN ('t', 'f')"),
+        Timestamp => Some("datetime(v) IS NOT NULL"),
+        Date => Some("date(v) IS NOT NULL"),
+        Json => Some("json_valid(v)"),
+        Text | TextArray => None,
+    }
+}
+
+/// Declared types a column of type `ty` may have: `TEXT` like every csv column, or its own
+/// SQLite type from a schema override.
+fn declared_fits(ty: ColumnType, declared: &str) -> bool {
+    let declared = declared.to_ascii_uppercase();
+    declared.is_empty()
+        || declared == "TEXT"
+        || declared.contains(ty.sqlite_type())
+        || (ty.sqlite_type() == "INTEGER" && declared.contains("INT"))
+}
+
+/// Compares the tables loaded into `db` against [`TABLES`], as an early warning that crates.io
+/// changed the dump format.
+///
+/// Canonical tables that weren't loaded are reported missing. Values are only checked in the
+/// first rows of each table, so this stays quick on the full dump.
+pub fn validate_schema(db: &Connection) -> Result<SchemaReport, Error> {
+    let mut report = SchemaReport::default();
+    for table in TABLES {
+        if !table_exists(db, table.name)? {
+            if !NEWER_TABLES.contains(&table.name) {
+                report.missing_tables.push(table.name.to_string());
+            }
+            continue;
+        }
+        let mut stmt = db.prepare("SELECT name, type FROM pragma_table_info(?)")?;
+        let loaded = stmt
+            .query_map([table.name], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for column in table.columns {
+            let declared = match loaded.iter().find(|(name, _)| name == column.name) {
+                Some((_, declared)) => declared,
+                None => {
+                    report
+                        .missing_columns
+                        .push((table.name.to_string(), column.name.to_string()));
+                    continue;
+                }
+            };
+            let sample = match value_check(column.ty) {
+                Some(check) => db.query_row(
+                    &format!(
+                        "SELECT (SELECT v FROM (SELECT CAST(\"{0}\" AS TEXT) AS v FROM {1} LIMIT {2}) WHERE v != '' AND NOT ({3}) LIMIT 1)",
+                        column.name, table.name, SAMPLED_ROWS, check
+                    ),
+                    [],
+                    |row| row.get::<_, Option<String>>(0),
+                )?,
+                None => None,
+            };
+            if sample.is_some() || !declared_fits(column.ty, declared) {
+                report.type_mismatches.push(TypeMismatch {
+                    table: table.name.to_string(),
+                    column: column.name.to_string(),
+                    expected: column.ty,
+                    declared: declared.clone(),
+                    sample,
+                });
+            }
+        }
+        for (name, _) in &loaded {
+            if table.column(name).is_none() {
+                report
+                    .extra_columns
+                    .push((table.name.to_string(), name.clone()));
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[test]
+fn test_table_enum() {
+    assert_eq!(TABLES.len(), Table::ALL.len());
+    for (table, schema) in Table::ALL.iter().zip(TABLES) {
+        assert_eq!(schema.name, table.name());
+        assert_eq!(Some(schema), table.schema());
+    }
+    assert_eq!(Table::VersionDownloads, Table::from("version_downloads"));
+    assert_eq!(Table::Custom("favorites".into()), Table::from("favorites"));
+    assert_eq!(None, Table::from("favorites").schema());
+
+    let mut loader = crate::CratesIODumpLoader::default();
+    loader.tables(&[Table::Crates, Table::Custom("favorites".into())]);
+    assert_eq!(
+        vec![
+            std::path::PathBuf::from("crates.csv"),
+            std::path::PathBuf::from("favorites.csv")
+        ],
+        loader.files
+    );
+}
+
+#[test]
+fn test_canonical_schema() {
+    let dependencies = table("dependencies").unwrap();
+    assert_eq!(
+        Some(ColumnType::Boolean),
+        dependencies.column("optional").map(|c| c.ty)
+    );
+    assert!(table("metadata")
+        .unwrap()
+        .postgres_ddl()
+        .contains("total_downloads BIGINT"));
+    assert_eq!(
+        vec![
+            ("renamed_id".to_string(), "INT".to_string()),
+            ("name".to_string(), "TEXT".to_string())
+        ],
+        parse_columns("CREATE TABLE x(renamed_id INT, name TEXT);")
+    );
+}
+
+#[test]
+fn test_validate_schema() -> Result<(), Error> {
+    use crate::{CratesIODumpLoader, MockDump};
+
+    let fixture = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&fixture)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(std::path::Path::new("testdata/extracted/validate"))
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&fixture)?;
+    assert_eq!(SchemaReport::default(), validate_schema(&fixture)?);
+
+    let db = MockDump::default().add_crate("serde", &["1.0.0"]).build()?;
+    assert_eq!(SchemaReport::default(), validate_schema(&db)?);
+
+    db.execute_batch(
+        r#"
+            DROP TABLE badges;
+            ALTER TABLE crates ADD COLUMN stars TEXT;
+            ALTER TABLE teams DROP COLUMN avatar;
+            UPDATE versions SET yanked = 'maybe';
+            DROP TABLE keywords;
+            CREATE TABLE keywords(crates_cnt INTEGER, created_at TEXT, id TEXT, keyword INTEGER);
+        "#,
+    )?;
+    let report = validate_schema(&db)?;
+    assert!(!report.is_ok());
+    assert_eq!(vec!["badges".to_string()], report.missing_tables);
+    assert_eq!(
+        vec![("teams".to_string(), "avatar".to_string())],
+        report.missing_columns
+    );
+    assert_eq!(
+        vec![("crates".to_string(), "stars".to_string())],
+        report.extra_columns
+    );
+    let mismatches: Vec<_> = report
+        .type_mismatches
+        .iter()
+        .map(|m| (m.table.as_str(), m.column.as_str(), m.sample.as_deref()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("keywords", "keyword", None),
+            ("versions", "yanked", Some("maybe"))
+        ],
+        mismatches
+    );
+    Ok(())
+}
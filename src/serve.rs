@@ -0,0 +1,355 @@
+//! The `serve` feature: a small read-only HTTP JSON API over a loaded database, built on axum.
+//!
+//! - `GET /crates/{name}`: the crate and its versions.
+//! - `GET /crates/{name}/reverse_dependencies`: see [`crate::reverse_dependencies`].
+//! - `GET /search?q=`: crates with `q` in their name or as a keyword, most downloaded first.
+//! - `GET /graphql?query=`: with the `graphql` feature, see [`crate::graphql`].
+
+use std::{
+    collections::HashMap,
+    net::{TcpListener, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use hyper::server::conn::http1;
+use hyper_util::{
+    rt::{TokioIo, TokioTimer},
+    service::TowerToHyperService,
+};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+use crate::{
+    reverse_dependencies, rows::crate_by_name, stats::crate_downloads, table_exists, Error,
+};
+
+/// How many crates `/search` returns at most.
+pub const SEARCH_LIMIT: i64 = 100;
+
+/// How long clients get to send the headers of a request, and requests get to be answered.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Answers requests concurrently, running their queries one at a time on a single connection,
+/// which it makes read-only.
+pub struct Server {
+    db: Mutex<Connection>,
+}
+
+type Params = Query<HashMap<String, String>>;
+
+fn not_found() -> (u16, Value) {
+    (404, json!({ "error": "not found" }))
+}
+
+/// Runs `answer` off the async workers and turns its result into a response, errors into 500s.
+async fn respond<F>(server: Arc<Server>, answer: F) -> Response
+where
+    F: FnOnce(&Server) -> Result<(u16, Value), Error> + Send + 'static,
+{
+    let answered = tokio::time::timeout(
+        TIMEOUT,
+        tokio::task::spawn_blocking(move || answer(&server)),
+    )
+    .await;
+    let (status, body) = match answered {
+        Ok(Ok(Ok(answer))) => answer,
+        Ok(Ok(Err(e))) => (500, json!({ "error": e.to_string() })),
+        Ok(Err(_)) => (500, json!({ "error": "the request handler panicked" })),
+        Err(_) => (503, json!({ "error": "the request timed out" })),
+    };
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    (status, Json(body)).into_response()
+}
+
+impl Server {
+    pub fn new(db: Connection) -> Result<Self, Error> {
+        db.pragma_update(None, "query_only", &true)?;
+        Ok(Self { db: Mutex::new(db) })
+    }
+
+    fn db(&self) -> MutexGuard<'_, Connection> {
+        self.db.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The routes of the API. Requests for anything else are 404s, and requests with methods
+    /// other than `GET` 405s.
+    pub fn router(self) -> Router {
+        let router = Router::new()
+            .route(
+                "/crates/{name}",
+                get(|State(server), Path(name): Path<String>| {
+                    respond(server, move |server| server.krate(&name))
+                }),
+            )
+            .route(
+                "/crates/{name}/reverse_dependencies",
+                get(|State(server), Path(name): Path<String>| {
+                    respond(server, move |server| server.reverse_dependencies(&name))
+                }),
+            )
+            .route(
+                "/search",
+                get(|State(server), Query(params): Params| {
+                    respond(server, move |server| {
+                        server.search(params.get("q").map_or("", String::as_str))
+                    })
+                }),
+            );
+        #[cfg(feature = "graphql")]
+        let router = router.route(
+            "/graphql",
+            get(|State(server), Query(params): Params| {
+                respond(server, move |server| {
+                    server.graphql(params.get("query").map_or("", String::as_str))
+                })
+            }),
+        );
+        router
+            .fallback(|| async {
+                let (_, body) = not_found();
+                (StatusCode::NOT_FOUND, Json(body))
+            })
+            .with_state(Arc::new(self))
+    }
+
+    fn krate(&self, name: &str) -> Result<(u16, Value), Error> {
+        let (krate, versions) = match crate_by_name(&self.db(), name)? {
+            Some(found) => found,
+            None => return Ok(not_found()),
+        };
+        let versions: Vec<Value> = versions
+            .iter()
+            .map(|v| {
+                json!({
+                    "num": v.num,
+                    "license": v.license,
+                    "downloads": v.downloads,
+                    "yanked": v.yanked,
+                    "created_at": v.created_at,
+                })
+            })
+            .collect();
+        Ok((
+            200,
+            json!({
+                "name": krate.name,
+                "description": krate.description,
+                "repository": krate.repository,
+                "downloads": krate.downloads,
+                "created_at": krate.created_at,
+                "updated_at": krate.updated_at,
+                "versions": versions,
+            }),
+        ))
+    }
+
+    fn reverse_dependencies(&self, name: &str) -> Result<(u16, Value), Error> {
+        let db = self.db();
+        if crate_by_name(&db, name)?.is_none() {
+            return Ok(not_found());
+        }
+        let dependents: Vec<Value> = reverse_dependencies(&db, name)?
+            .iter()
+            .map(|d| {
+                json!({
+                    "crate": d.crate_name,
+                    "version": d.version,
+                    "req": d.req,
+                    "kind": d.kind.name(),
+                    "optional": d.optional,
+                    "downloads": d.downloads,
+                })
+            })
+            .collect();
+        Ok((200, Value::from(dependents)))
+    }
+
+    fn search(&self, q: &str) -> Result<(u16, Value), Error> {
+        let q = q.trim();
+        if q.is_empty() {
+            return Ok((400, json!({ "error": "missing q" })));
+        }
+        let db = self.db();
+        let keywords = match table_exists(&db, "keywords")? && table_exists(&db, "crates_keywords")?
+        {
+            true => {
+                r#"
+                    OR CAST(c.id AS INTEGER) IN (
+                        SELECT CAST(ck.crate_id AS INTEGER) FROM main.crates_keywords ck
+                        JOIN main.keywords k ON CAST(k.id AS INTEGER) = CAST(ck.keyword_id AS INTEGER)
+                        WHERE k.keyword = lower(?1)
+                    )
+                "#
+            }
+            false => "",
+        };
+        let sql = format!(
+            r#"
+                SELECT c.name, c.description, COALESCE(d.downloads, 0) AS downloads
+                FROM main.crates c LEFT JOIN {} d ON d.crate_id = CAST(c.id AS INTEGER)
+                WHERE instr(lower(c.name), lower(?1)) > 0 {}
+                ORDER BY downloads DESC, c.name LIMIT ?2
+            "#,
+            crate_downloads(&db, "main")?,
+            keywords
+        );
+        let mut stmt = db.prepare(&sql)?;
+        let crates = stmt
+            .query_map(rusqlite::params![q, SEARCH_LIMIT], |row| {
+                Ok(json!({
+                    "name": row.get::<_, String>(0)?,
+                    "description": row.get::<_, String>(1)?,
+                    "downloads": row.get::<_, i64>(2)?,
+                }))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((200, Value::from(crates)))
+    }
+
+    #[cfg(feature = "graphql")]
+    fn graphql(&self, query: &str) -> Result<(u16, Value), Error> {
+        match crate::graphql::execute(&self.db(), query) {
+            Err(Error::InvalidGraphQL(message)) => {
+                Ok((400, json!({ "errors": [{ "message": message }] })))
+            }
+            response => Ok((200, response?)),
+        }
+    }
+
+    /// Answers requests on `listener` until accepting a connection fails. Errors of single
+    /// connections, and clients that take longer than [`TIMEOUT`] to send a request, only end
+    /// their connection.
+    pub fn serve_listener(self, listener: TcpListener) -> Result<(), Error> {
+        listener.set_nonblocking(true)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let router = self.router();
+        runtime.block_on(async move {
+            accept(tokio::net::TcpListener::from_std(listener)?, router).await
+        })
+    }
+
+    /// Binds `addr`, e.g. `127.0.0.1:8080`, and serves on it.
+    pub fn serve(self, addr: impl ToSocketAddrs) -> Result<(), Error> {
+        self.serve_listener(TcpListener::bind(addr)?)
+    }
+}
+
+async fn accept(listener: tokio::net::TcpListener, router: Router) -> Result<(), Error> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            let _ = http1::Builder::new()
+                .timer(TokioTimer::new())
+                .header_read_timeout(TIMEOUT)
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+#[cfg(test)]
+fn request(addr: std::net::SocketAddr, request: &str) -> Result<(u16, Value), Error> {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    write!(
+        stream,
+        "{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        request
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response
+        .get(9..12)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_default();
+    let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    Ok((status, serde_json::from_str(body).unwrap_or(Value::Null)))
+}
+
+#[cfg(test)]
+fn spawn(server: Server) -> Result<std::net::SocketAddr, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    std::thread::spawn(move || server.serve_listener(listener));
+    Ok(addr)
+}
+
+#[test]
+fn test_serve() -> Result<(), Error> {
+    use std::net::TcpStream;
+
+    let db = crate::MockDump::default()
+        .add_crate("serde", &["1.0.0", "1.0.1"])
+        .add_crate("serde_json", &["1.0.0"])
+        .add_crate("tokio", &["1.0.0"])
+        .dependency("serde_json", "1.0.0", "serde", "^1.0")
+        .row("keywords", &[("id", "1"), ("keyword", "json")])
+        .row("crates_keywords", &[("crate_id", "2"), ("keyword_id", "1")])
+        .build()?;
+    let server = Server::new(db)?;
+    assert!(server.db().execute_batch("DELETE FROM crates").is_err());
+    let addr = spawn(server)?;
+    // An idle client doesn't hold up the others.
+    let _idle = TcpStream::connect(addr)?;
+
+    let (status, body) = request(addr, "GET /crates/serde")?;
+    assert_eq!(200, status);
+    assert_eq!(2, body["versions"].as_array().map_or(0, Vec::len));
+    let (_, body) = request(addr, "GET /crates/serde/reverse_dependencies")?;
+    assert_eq!(json!("serde_json"), body[0]["crate"]);
+    let names = |target: &str| -> Result<Vec<Value>, Error> {
+        let (_, body) = request(addr, &format!("GET {}", target))?;
+        Ok(body
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|c| c["name"].clone())
+            .collect())
+    };
+    assert_eq!(
+        vec![json!("serde"), json!("serde_json")],
+        names("/search?q=SER")?
+    );
+    assert_eq!(vec![json!("serde_json")], names("/search?q=js%6Fn")?);
+    assert_eq!(404, request(addr, "GET /crates/nope")?.0);
+    assert_eq!(404, request(addr, "GET /nope")?.0);
+    assert_eq!(400, request(addr, "GET /search")?.0);
+    assert_eq!(405, request(addr, "POST /crates/serde")?.0);
+    #[cfg(feature = "graphql")]
+    {
+        let (status, body) = request(
+            addr,
+            "GET /graphql?query=%7B+crate(name:+%22tokio%22)+%7B+name+%7D+%7D",
+        )?;
+        assert_eq!(
+            (200, json!({"data": {"crate": {"name": "tokio"}}})),
+            (status, body)
+        );
+        assert_eq!(400, request(addr, "GET /graphql?query=%7B+nope+%7D")?.0);
+    }
+
+    // Failing queries are answered with a 500.
+    let db = Connection::open_in_memory()?;
+    db.execute_batch("CREATE TABLE crates(id, name, description, repository, downloads, created_at, updated_at); INSERT INTO crates VALUES (1, 'serde', '', '', 0, '', '');")?;
+    let addr = spawn(Server::new(db)?)?;
+    let (status, body) = request(addr, "GET /crates/serde")?;
+    assert_eq!(500, status);
+    assert!(body["error"].is_string());
+    Ok(())
+}
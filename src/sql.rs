@@ -0,0 +1,71 @@
+//! Quoting for the SQL generated from table names, schemas and paths.
+
+use std::path::Path;
+
+use crate::Error;
+
+/// Quotes `name` as an SQLite identifier, e.g. `my "table"` becomes `"my ""table"""`.
+pub fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes `value` as an SQL string literal, e.g. `it's` becomes `'it''s'`.
+pub fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes `value` as a csvtab argument. csvtab strips the outer quotes but doesn't unescape
+/// doubled ones, so the value is wrapped in whichever quote it doesn't contain.
+pub(crate) fn csvtab_argument(value: &str) -> Result<String, Error> {
+    match (value.contains('\''), value.contains('"')) {
+        (false, _) => Ok(format!("'{}'", value)),
+        (true, false) => Ok(format!("\"{}\"", value)),
+        (true, true) => Err(Error::UnquotableArgument(value.to_string())),
+    }
+}
+
+/// `path` as a csvtab `filename` argument. On Windows, backslashes become forward slashes,
+/// which the Windows file APIs accept too, and the `\\?\` prefix of canonicalized paths is
+/// dropped since it disables that.
+pub(crate) fn csvtab_path(path: &Path) -> String {
+    normalize_path(&path.to_string_lossy(), cfg!(windows))
+}
+
+fn normalize_path(path: &str, windows: bool) -> String {
+    if !windows {
+        return path.to_string();
+    }
+    let path = path.strip_prefix(r"\\?\").unwrap_or(path);
+    path.replace('\\', "/")
+}
+
+/// Table names double as csv file names, so they are limited to what the dump itself uses.
+pub(crate) fn valid_table_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[test]
+fn test_quoting() {
+    assert_eq!("\"crates\"", identifier("crates"));
+    assert_eq!("\"a\"\"b\"", identifier("a\"b"));
+    assert_eq!("'it''s'", literal("it's"));
+    assert_eq!("\"it's\"", csvtab_argument("it's").unwrap());
+    assert_eq!("'say \"hi\"'", csvtab_argument("say \"hi\"").unwrap());
+    assert!(csvtab_argument("it's \"both\"").is_err());
+    assert_eq!(
+        "C:/Users/me/data/crates.csv",
+        normalize_path(r"\\?\C:\Users\me\data\crates.csv", true)
+    );
+    assert_eq!(
+        "//server/share/crates.csv",
+        normalize_path(r"\\server\share\crates.csv", true)
+    );
+    assert_eq!(r"odd\name.csv", normalize_path(r"odd\name.csv", false));
+    assert!(valid_table_name("version_downloads"));
+    assert!(!valid_table_name("../etc/passwd"));
+    assert!(!valid_table_name("x; DROP TABLE crates"));
+    assert!(!valid_table_name(""));
+}
@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+
+use crate::{CratesIODumpLoader, Error};
+
+/// The loader behind `static_db()`, once it has loaded the database.
+static LOADED: Mutex<Option<CratesIODumpLoader>> = Mutex::new(None);
+
+/// A connection to a database loaded once per process, for test suites and examples.
+///
+/// The first call loads [`crate::SAMPLE_DUMP`], preloaded, unless `CRATESIO_DBDUMP_*`
+/// variables say otherwise, see [`CratesIODumpLoader::apply_env`]. Every call returns a
+/// connection of its own to that database, so tests running in parallel don't contend over
+/// one. A failed load is retried by the next call.
+pub fn static_db() -> Result<Connection, Error> {
+    let mut loaded = LOADED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(loader) = &*loaded {
+        return loader.open_connection();
+    }
+    let mut loader = CratesIODumpLoader::sample()?
+        .into_builder()
+        .preload(true)
+        .build()?;
+    loader.apply_env()?;
+    drop(loader.update()?.open_db()?);
+    let db = loader.open_connection()?;
+    *loaded = Some(loader);
+    Ok(db)
+}
+
+#[test]
+fn test_static_db() -> Result<(), Error> {
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| -> Result<i64, Error> {
+                let db = static_db()?;
+                Ok(db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?)
+            })
+        })
+        .collect();
+    for worker in workers {
+        assert_eq!(40, worker.join().unwrap()?);
+    }
+    assert!(LOADED.lock().unwrap().as_ref().is_some_and(|l| l.preload));
+    Ok(())
+}
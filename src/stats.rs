@@ -0,0 +1,364 @@
+//! Headline statistics over a loaded database.
+
+use rusqlite::Connection;
+
+use crate::{table_exists, Error};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistryStats {
+    pub crates: i64,
+    pub versions: Option<i64>,
+    pub total_downloads: i64,
+    /// Categories with the most crates, as `(category, crates)`.
+    pub top_categories: Vec<(String, i64)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MonthlyGrowth {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub crates: i64,
+    pub versions: i64,
+    pub total_crates: i64,
+    pub total_versions: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CategoryStats {
+    pub slug: String,
+    pub category: String,
+    pub crates: i64,
+    /// All-time downloads of its crates.
+    pub total_downloads: i64,
+    pub median_downloads: f64,
+    /// Versions of its crates released in the 365 days before the newest release in the dump.
+    pub releases_last_year: i64,
+}
+
+/// (Re)creates `growth_monthly(month, crates, versions, total_crates, total_versions)`, holding
+/// the crates and versions created each month and the running totals.
+///
+/// Versions count as 0 when the `versions` table isn't loaded.
+pub fn materialize_growth(db: &Connection) -> Result<(), Error> {
+    let versions = match table_exists(db, "versions")? {
+        true => "SELECT substr(created_at, 1, 7) AS month, COUNT(*) AS n FROM versions GROUP BY 1",
+        false => "SELECT NULL AS month, 0 AS n WHERE 0",
+    };
+    db.execute_batch(&format!(
+        r#"
+            DROP TABLE IF EXISTS growth_monthly;
+            CREATE TABLE growth_monthly AS
+            WITH c AS (
+                SELECT substr(created_at, 1, 7) AS month, COUNT(*) AS n FROM crates GROUP BY 1
+            ), v AS ({}),
+            months AS (SELECT month FROM c UNION SELECT month FROM v)
+            SELECT m.month AS month, COALESCE(c.n, 0) AS crates, COALESCE(v.n, 0) AS versions,
+                SUM(COALESCE(c.n, 0)) OVER (ORDER BY m.month) AS total_crates,
+                SUM(COALESCE(v.n, 0)) OVER (ORDER BY m.month) AS total_versions
+            FROM months m LEFT JOIN c ON c.month = m.month LEFT JOIN v ON v.month = m.month
+            ORDER BY m.month;
+        "#,
+        versions
+    ))?;
+    Ok(())
+}
+
+/// Reads `growth_monthly`, materializing it first if needed.
+pub fn growth(db: &Connection) -> Result<Vec<MonthlyGrowth>, Error> {
+    if !table_exists(db, "growth_monthly")? {
+        materialize_growth(db)?;
+    }
+    let mut stmt = db.prepare(
+        "SELECT month, crates, versions, total_crates, total_versions FROM growth_monthly ORDER BY month",
+    )?;
+    let months = stmt.query_map([], |row| {
+        Ok(MonthlyGrowth {
+            month: row.get(0)?,
+            crates: row.get(1)?,
+            versions: row.get(2)?,
+            total_crates: row.get(3)?,
+            total_versions: row.get(4)?,
+        })
+    })?;
+    Ok(months.collect::<Result<_, _>>()?)
+}
+
+/// (Re)creates `category_stats(slug, category, crates, total_downloads, median_downloads,
+/// releases_last_year)`, with the fields of [`CategoryStats`] for every category and an index on
+/// `slug`.
+///
+/// Needs `categories` and `crates_categories`. Releases count as 0 when the `versions` table
+/// isn't loaded.
+pub fn materialize_category_stats(db: &Connection) -> Result<(), Error> {
+    let releases = match table_exists(db, "versions")? {
+        true => {
+            r#"
+                SELECT CAST(cc.category_id AS INTEGER) AS category_id, COUNT(*) AS n
+                FROM crates_categories cc
+                JOIN versions v ON CAST(v.crate_id AS INTEGER) = CAST(cc.crate_id AS INTEGER)
+                WHERE julianday(v.created_at) > (SELECT julianday(MAX(created_at)) FROM versions) - 365
+                GROUP BY 1
+            "#
+        }
+        false => "SELECT NULL AS category_id, 0 AS n WHERE 0",
+    };
+    db.execute_batch(&format!(
+        r#"
+            DROP TABLE IF EXISTS category_stats;
+            CREATE TABLE category_stats AS
+            WITH members AS (
+                SELECT CAST(cc.category_id AS INTEGER) AS category_id, COALESCE(d.downloads, 0) AS downloads
+                FROM crates_categories cc LEFT JOIN {} d ON d.crate_id = CAST(cc.crate_id AS INTEGER)
+            ), ranked AS (
+                SELECT category_id, downloads,
+                    ROW_NUMBER() OVER (PARTITION BY category_id ORDER BY downloads) AS n,
+                    COUNT(*) OVER (PARTITION BY category_id) AS total
+                FROM members
+            ), medians AS (
+                SELECT category_id, AVG(downloads) AS median FROM ranked
+                WHERE n IN ((total + 1) / 2, (total + 2) / 2) GROUP BY category_id
+            ), releases AS ({}),
+            totals AS (
+                SELECT category_id, COUNT(*) AS crates, SUM(downloads) AS downloads
+                FROM members GROUP BY category_id
+            )
+            SELECT c.slug AS slug, c.category AS category, COALESCE(t.crates, 0) AS crates,
+                COALESCE(t.downloads, 0) AS total_downloads,
+                COALESCE(m.median, 0.0) AS median_downloads,
+                COALESCE(r.n, 0) AS releases_last_year
+            FROM categories c
+            LEFT JOIN totals t ON t.category_id = CAST(c.id AS INTEGER)
+            LEFT JOIN medians m ON m.category_id = CAST(c.id AS INTEGER)
+            LEFT JOIN releases r ON r.category_id = CAST(c.id AS INTEGER)
+            ORDER BY c.slug;
+            CREATE INDEX category_stats_slug ON category_stats(slug);
+        "#,
+        crate_downloads(db, "main")?,
+        releases
+    ))?;
+    Ok(())
+}
+
+/// Reads `category_stats`, materializing it first if needed.
+pub fn category_stats(db: &Connection) -> Result<Vec<CategoryStats>, Error> {
+    if !table_exists(db, "category_stats")? {
+        materialize_category_stats(db)?;
+    }
+    let mut stmt = db.prepare(
+        r#"
+            SELECT slug, category, crates, total_downloads, median_downloads, releases_last_year
+            FROM category_stats ORDER BY slug
+        "#,
+    )?;
+    let categories = stmt.query_map([], |row| {
+        Ok(CategoryStats {
+            slug: row.get(0)?,
+            category: row.get(1)?,
+            crates: row.get(2)?,
+            total_downloads: row.get(3)?,
+            median_downloads: row.get(4)?,
+            releases_last_year: row.get(5)?,
+        })
+    })?;
+    Ok(categories.collect::<Result<_, _>>()?)
+}
+
+/// `(crate_id, downloads)` of every crate in `schema`, as a subquery: from `crate_downloads`
+/// in newer dumps, which moved the counters there, otherwise from `crates.downloads`.
+pub(crate) fn crate_downloads(db: &Connection, schema: &str) -> Result<String, Error> {
+    let split: bool = db.query_row(
+        &format!(
+            "SELECT COUNT(*) > 0 FROM {}.sqlite_master WHERE type IN ('table', 'view') AND name = 'crate_downloads'",
+            schema
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(match split {
+        true => format!(
+            "(SELECT CAST(crate_id AS INTEGER) AS crate_id, CAST(downloads AS INTEGER) AS downloads FROM {}.crate_downloads)",
+            schema
+        ),
+        false => format!(
+            "(SELECT CAST(id AS INTEGER) AS crate_id, CAST(downloads AS INTEGER) AS downloads FROM {}.crates)",
+            schema
+        ),
+    })
+}
+
+/// Counts crates, versions and downloads, and ranks the `top` largest categories.
+pub fn registry_stats(db: &Connection, top: usize) -> Result<RegistryStats, Error> {
+    let count = |sql: &str| db.query_row(sql, [], |row| row.get::<_, Option<i64>>(0));
+
+    let mut stats = RegistryStats {
+        crates: count("SELECT COUNT(*) FROM crates")?.unwrap_or_default(),
+        ..RegistryStats::default()
+    };
+    if table_exists(db, "versions")? {
+        stats.versions = count("SELECT COUNT(*) FROM versions")?;
+    }
+    stats.total_downloads = match table_exists(db, "metadata")? {
+        true => count("SELECT CAST(total_downloads AS INTEGER) FROM metadata")?,
+        false => count(&format!(
+            "SELECT SUM(downloads) FROM {}",
+            crate_downloads(db, "main")?
+        ))?,
+    }
+    .unwrap_or_default();
+
+    if table_exists(db, "categories")? {
+        let sql = match table_exists(db, "crates_categories")? {
+            true => {
+                r#"
+                    SELECT c.category, COUNT(cc.crate_id) AS n FROM categories c
+                    LEFT JOIN crates_categories cc ON CAST(cc.category_id AS INTEGER) = CAST(c.id AS INTEGER)
+                    GROUP BY c.id ORDER BY n DESC, c.category LIMIT ?
+                "#
+            }
+            false => {
+                "SELECT category, CAST(crates_cnt AS INTEGER) AS n FROM categories ORDER BY n DESC, category LIMIT ?"
+            }
+        };
+        let mut stmt = db.prepare(sql)?;
+        let categories = stmt.query_map([top as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        stats.top_categories = categories.collect::<Result<_, _>>()?;
+    }
+    Ok(stats)
+}
+
+#[test]
+fn test_registry_stats() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let mut loader = CratesIODumpLoader::default();
+    loader
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/stats"))
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let stats = registry_stats(&db, 2)?;
+    assert_eq!(6, stats.crates);
+    assert_eq!(Some(9), stats.versions);
+    assert_eq!(495_000_000, stats.total_downloads);
+    assert_eq!(
+        vec![("Asynchronous".to_string(), 2), ("Encoding".to_string(), 2)],
+        stats.top_categories
+    );
+    assert_eq!("2021-05-15", loader.metadata()?.unwrap().date());
+    Ok(())
+}
+
+#[test]
+fn test_growth() -> Result<(), Error> {
+    use crate::CratesIODumpLoader;
+    use std::path::Path;
+
+    let cache = cached_path::Cache::builder().progress_bar(None);
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/growth"))
+        .minimal()
+        .growth_stats(true)
+        .cache(cache)?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let months = growth(&db)?;
+    let last = months.last().unwrap();
+    assert_eq!((6, 9), (last.total_crates, last.total_versions));
+    assert_eq!(9, months.iter().map(|m| m.versions).sum::<i64>());
+    assert!(months.windows(2).all(|w| w[0].month < w[1].month));
+    Ok(())
+}
+
+#[test]
+fn test_category_stats() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("tokio", &["1.0.0", "1.1.0"])
+        .add_crate("async-std", &["1.0.0"])
+        .add_crate("smol", &["1.0.0"])
+        .row(
+            "categories",
+            &[
+                ("id", "1"),
+                ("slug", "asynchronous"),
+                ("category", "Asynchronous"),
+            ],
+        )
+        .row(
+            "categories",
+            &[("id", "2"), ("slug", "empty"), ("category", "Empty")],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "1"), ("category_id", "1")],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "2"), ("category_id", "1")],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "3"), ("category_id", "1")],
+        )
+        .category_stats(true)
+        .build()?;
+    db.execute_batch(
+        r#"
+            UPDATE crates SET downloads = CASE name WHEN 'tokio' THEN '100' WHEN 'smol' THEN '5' ELSE '20' END;
+            UPDATE versions SET created_at = '2019-01-01 00:00:00' WHERE CAST(crate_id AS INTEGER) = 2;
+        "#,
+    )?;
+    materialize_category_stats(&db)?;
+
+    let stats = category_stats(&db)?;
+    assert_eq!(
+        CategoryStats {
+            slug: "asynchronous".into(),
+            category: "Asynchronous".into(),
+            crates: 3,
+            total_downloads: 125,
+            median_downloads: 20.0,
+            releases_last_year: 3,
+        },
+        stats[0]
+    );
+    assert_eq!((0, 0.0), (stats[1].crates, stats[1].median_downloads));
+    let indexed: bool = db.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'index' AND name = 'category_stats_slug'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(indexed);
+    Ok(())
+}
+
+#[test]
+fn test_crate_downloads() -> Result<(), Error> {
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(
+        r#"
+            CREATE TABLE crates(id TEXT, name TEXT, downloads TEXT);
+            INSERT INTO crates VALUES ('1', 'serde', '10'), ('2', 'eyre', '5');
+        "#,
+    )?;
+    assert_eq!(15, registry_stats(&db, 0)?.total_downloads);
+
+    // Newer dumps count downloads in crate_downloads instead.
+    db.execute_batch(
+        r#"
+            CREATE TABLE crate_downloads(crate_id TEXT, downloads TEXT);
+            INSERT INTO crate_downloads VALUES ('1', '300'), ('2', '200');
+        "#,
+    )?;
+    assert_eq!(500, registry_stats(&db, 0)?.total_downloads);
+    Ok(())
+}
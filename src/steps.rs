@@ -0,0 +1,92 @@
+//! `update()` and `load_dump_into()` as separate steps, e.g. to run each on tokio's blocking
+//! pool with `spawn_blocking`.
+
+use std::path::PathBuf;
+
+use rusqlite::Connection;
+
+use crate::{CratesIODumpLoader, Error, LoadReport, UpdateReport};
+
+/// An archive from [`CratesIODumpLoader::fetch`], ready for `extract()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveHandle {
+    /// The archive in the cache, or the local `resource` itself.
+    pub path: PathBuf,
+    /// Bytes downloaded to fetch it, 0 when it was cached or local.
+    pub downloaded_bytes: u64,
+}
+
+impl CratesIODumpLoader {
+    /// The first step of `update()`: download the archive unless it is cached, and check it
+    /// against `sha256()`.
+    pub fn fetch(&self) -> Result<ArchiveHandle, Error> {
+        self.check_table_names()?;
+        let mut report = UpdateReport::default();
+        let path = self.fetch_archive(&mut report)?;
+        Ok(ArchiveHandle {
+            path,
+            downloaded_bytes: report.downloaded_bytes,
+        })
+    }
+
+    /// The second step of `update()`: unpack the selected tables into `target_path`, unless
+    /// they are newer than `archive` already. Returns the paths of their csvs.
+    ///
+    /// Unlike `update()`, a corrupt archive isn't downloaded again, see
+    /// [`Error::CorruptArchive`].
+    pub fn extract(&self, archive: &ArchiveHandle) -> Result<Vec<PathBuf>, Error> {
+        self.extract_archive(&archive.path, &mut UpdateReport::default())?;
+        Ok(self
+            .table_files()?
+            .iter()
+            .map(|f| self.target_path.join(f))
+            .filter(|path| path.exists())
+            .collect())
+    }
+
+    /// `load_dump_into()` for just the csvs at `paths` in `target_path`, as returned by
+    /// `extract()`, along with any custom tables.
+    pub fn load(&self, paths: &[PathBuf], db: &Connection) -> Result<LoadReport, Error> {
+        let mut loader = self.clone();
+        loader.files = paths
+            .iter()
+            .filter_map(|path| path.file_name().map(PathBuf::from))
+            .collect();
+        loader.load_dump_into(db)
+    }
+}
+
+#[test]
+fn test_steps() -> Result<(), Error> {
+    use std::path::Path;
+
+    let target = Path::new("testdata/extracted/steps");
+    let _ = std::fs::remove_dir_all(target);
+    let loader = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(target)
+        .minimal()
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .build()?;
+
+    let archive = loader.fetch()?;
+    assert_eq!(0, archive.downloaded_bytes);
+    let paths = loader.extract(&archive)?;
+    assert_eq!(
+        vec![
+            target.join("crates.csv"),
+            target.join("dependencies.csv"),
+            target.join("versions.csv")
+        ],
+        paths
+    );
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    let report = loader.load(&paths[..1], &db)?;
+    assert_eq!(vec!["crates"], report.loaded);
+    let crates: i64 = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0))?;
+    assert_eq!(6, crates);
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! [`proptest`] strategies generating consistent crate graphs.
+
+use std::{collections::BTreeSet, path::Path};
+
+use proptest::{collection, prelude::*, sample::Index};
+use rusqlite::Connection;
+
+use crate::{CratesIODumpLoader, Error, FixtureBuilder, MockDump};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedCrate {
+    pub name: String,
+    /// Distinct, oldest first.
+    pub versions: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedDependency {
+    pub crate_name: String,
+    pub version: String,
+    pub dependency: String,
+    /// A caret requirement matching at least one version of `dependency`.
+    pub req: String,
+}
+
+/// Crates with versions and dependency edges between existing versions and crates, without
+/// self-dependencies or duplicate edges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrateGraph {
+    pub crates: Vec<GeneratedCrate>,
+    pub dependencies: Vec<GeneratedDependency>,
+}
+
+impl CrateGraph {
+    pub fn fixture(&self) -> FixtureBuilder {
+        let mut fixture = FixtureBuilder::default();
+        for krate in &self.crates {
+            let versions: Vec<&str> = krate.versions.iter().map(String::as_str).collect();
+            fixture.add_crate(&krate.name, &versions);
+        }
+        for dep in &self.dependencies {
+            fixture.dependency(&dep.crate_name, &dep.version, &dep.dependency, &dep.req);
+        }
+        fixture
+    }
+
+    pub fn mock(&self) -> MockDump {
+        let mut mock = MockDump::default();
+        for krate in &self.crates {
+            let versions: Vec<&str> = krate.versions.iter().map(String::as_str).collect();
+            mock.add_crate(&krate.name, &versions);
+        }
+        for dep in &self.dependencies {
+            mock.dependency(&dep.crate_name, &dep.version, &dep.dependency, &dep.req);
+        }
+        mock
+    }
+
+    /// Writes the graph as a dump archive under `target` and loads it into `db` with
+    /// [`CratesIODumpLoader`], exactly like a real dump.
+    pub fn load_into(&self, db: &Connection, target: &Path) -> Result<(), Error> {
+        std::fs::create_dir_all(target)?;
+        let archive = target.join("graph.tar.gz");
+        self.fixture().write(&archive)?;
+        CratesIODumpLoader::builder()
+            .resource(&archive.to_string_lossy())
+            .target_path(&target.join("data"))
+            .preload(true)
+            .cache(cached_path::Cache::builder().progress_bar(None))?
+            .build()?
+            .update()?
+            .load_dump_into(db)?;
+        Ok(())
+    }
+}
+
+fn versions(max_versions: usize) -> impl Strategy<Value = Vec<String>> {
+    collection::btree_set((0u8..3, 0u8..10, 0u8..10), 1..=max_versions.max(1)).prop_map(|set| {
+        set.into_iter()
+            .map(|(major, minor, patch)| format!("{}.{}.{}", major, minor, patch))
+            .collect()
+    })
+}
+
+/// Graphs of up to `max_crates` crates with up to `max_versions` versions each and up to
+/// `max_dependencies` dependency edges.
+pub fn crate_graph(
+    max_crates: usize,
+    max_versions: usize,
+    max_dependencies: usize,
+) -> impl Strategy<Value = CrateGraph> {
+    let names = collection::btree_set("[a-z][a-z0-9_]{0,11}", 1..=max_crates.max(1));
+    let crates = names.prop_flat_map(move |names| {
+        let count = names.len();
+        (Just(names), collection::vec(versions(max_versions), count))
+    });
+    let edges = collection::vec(
+        (
+            any::<Index>(),
+            any::<Index>(),
+            any::<Index>(),
+            any::<Index>(),
+        ),
+        0..=max_dependencies,
+    );
+    (crates, edges).prop_map(|((names, versions), edges)| {
+        let crates: Vec<GeneratedCrate> = names
+            .into_iter()
+            .zip(versions)
+            .map(|(name, versions)| GeneratedCrate { name, versions })
+            .collect();
+
+        let mut seen = BTreeSet::new();
+        let mut dependencies = Vec::new();
+        for (from, version, to, matched) in edges {
+            let (from, to) = (
+                &crates[from.index(crates.len())],
+                &crates[to.index(crates.len())],
+            );
+            let version = &from.versions[version.index(from.versions.len())];
+            if from.name == to.name
+                || !seen.insert((from.name.clone(), version.clone(), to.name.clone()))
+            {
+                continue;
+            }
+            let matched = &to.versions[matched.index(to.versions.len())];
+            dependencies.push(GeneratedDependency {
+                crate_name: from.name.clone(),
+                version: version.clone(),
+                dependency: to.name.clone(),
+                req: format!("^{}", matched),
+            });
+        }
+        CrateGraph {
+            crates,
+            dependencies,
+        }
+    })
+}
+
+impl Arbitrary for CrateGraph {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        crate_graph(8, 4, 16).boxed()
+    }
+}
+
+#[cfg(test)]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_crate_graph(graph in any::<CrateGraph>()) {
+        let db = graph.mock().build().unwrap();
+        let crates: i64 = db.query_row("SELECT COUNT(*) FROM crates", [], |row| row.get(0)).unwrap();
+        prop_assert_eq!(graph.crates.len() as i64, crates);
+        for krate in &graph.crates {
+            let expected = graph.dependencies.iter().filter(|d| d.dependency == krate.name).count();
+            let rdeps = crate::reverse_dependencies(&db, &krate.name).unwrap();
+            prop_assert_eq!(expected, rdeps.len());
+        }
+    }
+}
+
+#[test]
+fn test_crate_graph_load_into() -> Result<(), Error> {
+    use proptest::{strategy::ValueTree, test_runner::TestRunner};
+
+    let graph = crate_graph(4, 3, 6)
+        .new_tree(&mut TestRunner::deterministic())
+        .unwrap()
+        .current();
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    graph.load_into(&db, Path::new("testdata/extracted/strategies"))?;
+    let versions: i64 = db.query_row("SELECT COUNT(*) FROM versions", [], |row| row.get(0))?;
+    let expected: usize = graph.crates.iter().map(|c| c.versions.len()).sum();
+    assert_eq!(expected as i64, versions);
+    Ok(())
+}
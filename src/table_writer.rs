@@ -0,0 +1,104 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{CratesIODumpLoader, Error};
+
+/// A table of your own, loaded next to the dump tables by
+/// [`CratesIODumpLoader::custom_table`], e.g. a curated list of crates to join against.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableWriter {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl TableWriter {
+    pub fn new(name: &str, columns: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row, with one value per column.
+    pub fn row(&mut self, values: &[&str]) -> &mut Self {
+        self.rows
+            .push(values.iter().map(|v| v.to_string()).collect());
+        self
+    }
+
+    pub fn file_name(&self) -> PathBuf {
+        PathBuf::from(format!("{}.csv", self.name))
+    }
+
+    /// Writes `<name>.csv` into `dir`, returning its path. Fails on rows with the wrong number
+    /// of values.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Error> {
+        let path = dir.join(self.file_name());
+        let mut writer = csv::Writer::from_path(&path)?;
+        writer.write_record(&self.columns)?;
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+impl CratesIODumpLoader {
+    /// Also load `table` on `load_dump_into()`, written as a csv next to the dump tables and
+    /// loaded the same way, including `preload()` and `table_schema()` overrides.
+    #[deprecated(note = "use `CratesIODumpLoader::builder()` instead")]
+    pub fn custom_table(&mut self, table: TableWriter) -> &mut Self {
+        let tables = Arc::make_mut(&mut self.custom_tables);
+        tables.retain(|t| t.name != table.name);
+        tables.push(table);
+        self
+    }
+}
+
+#[test]
+fn test_custom_table() -> Result<(), Error> {
+    use rusqlite::Connection;
+
+    let mut favorites = TableWriter::new("favorites", &["name", "reason"]);
+    favorites
+        .row(&["serde", "serializes everything"])
+        .row(&["tokio", "async, with a comma"]);
+
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    CratesIODumpLoader::default()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/custom-table"))
+        .minimal()
+        .custom_table(favorites)
+        .table_schema("favorites", "CREATE TABLE x(name TEXT, why TEXT);")
+        .cache(cached_path::Cache::builder().progress_bar(None))?
+        .update()?
+        .load_dump_into(&db)?;
+
+    let mut stmt = db.prepare(
+        "SELECT f.name, f.why, c.id FROM favorites f JOIN crates c ON c.name = f.name ORDER BY f.name",
+    )?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(String, String, String)>, _>>()?;
+    assert_eq!(
+        vec![
+            ("serde".into(), "serializes everything".into(), "1".into()),
+            ("tokio".into(), "async, with a comma".into(), "3".into())
+        ],
+        rows
+    );
+
+    let mut broken = TableWriter::new("broken", &["a", "b"]);
+    broken.row(&["only one"]);
+    assert!(broken
+        .write(Path::new("testdata/extracted/custom-table"))
+        .is_err());
+    Ok(())
+}
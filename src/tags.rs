@@ -0,0 +1,136 @@
+//! Keywords and categories of crates, without going through the `crates_keywords` and
+//! `crates_categories` join tables.
+
+use rusqlite::{Connection, ToSql};
+
+use crate::Error;
+
+const VIEWS: &str = r#"
+    DROP VIEW IF EXISTS keyword_crates;
+    DROP VIEW IF EXISTS category_crates;
+    CREATE VIEW keyword_crates AS
+    SELECT k.keyword AS keyword, c.name AS crate_name
+    FROM crates_keywords ck
+    JOIN keywords k ON CAST(k.id AS INTEGER) = CAST(ck.keyword_id AS INTEGER)
+    JOIN crates c ON CAST(c.id AS INTEGER) = CAST(ck.crate_id AS INTEGER);
+
+    CREATE VIEW category_crates AS
+    SELECT cat.slug AS slug, cat.category AS category, c.name AS crate_name
+    FROM crates_categories cc
+    JOIN categories cat ON CAST(cat.id AS INTEGER) = CAST(cc.category_id AS INTEGER)
+    JOIN crates c ON CAST(c.id AS INTEGER) = CAST(cc.crate_id AS INTEGER);
+"#;
+
+/// (Re)creates the `keyword_crates(keyword, crate_name)` and
+/// `category_crates(slug, category, crate_name)` views, one row per crate and keyword or
+/// category, so either side can be looked up by the other.
+pub fn create_tag_views(db: &Connection) -> Result<(), Error> {
+    db.execute_batch(VIEWS)?;
+    Ok(())
+}
+
+fn names(db: &Connection, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<String>, Error> {
+    let mut stmt = db.prepare(sql)?;
+    let names = stmt.query_map(params, |row| row.get(0))?;
+    Ok(names.collect::<Result<_, _>>()?)
+}
+
+/// Keywords of `name`, alphabetically.
+pub fn keywords_of(db: &Connection, name: &str) -> Result<Vec<String>, Error> {
+    create_tag_views(db)?;
+    names(
+        db,
+        "SELECT keyword FROM keyword_crates WHERE crate_name = ? ORDER BY keyword",
+        &[&name],
+    )
+}
+
+/// Crates that have every one of `keywords`, alphabetically. No keywords match no crates.
+pub fn crates_with_all_keywords(db: &Connection, keywords: &[&str]) -> Result<Vec<String>, Error> {
+    if keywords.is_empty() {
+        return Ok(Vec::new());
+    }
+    create_tag_views(db)?;
+    let sql = format!(
+        r#"
+            SELECT crate_name FROM keyword_crates WHERE keyword IN ({})
+            GROUP BY crate_name HAVING COUNT(DISTINCT keyword) = ?
+            ORDER BY crate_name
+        "#,
+        vec!["?"; keywords.len()].join(", ")
+    );
+    let count = keywords.len() as i64;
+    let mut params: Vec<&dyn ToSql> = keywords.iter().map(|k| k as &dyn ToSql).collect();
+    params.push(&count);
+    names(db, &sql, &params)
+}
+
+/// Category slugs of `name`, alphabetically.
+pub fn categories_of(db: &Connection, name: &str) -> Result<Vec<String>, Error> {
+    create_tag_views(db)?;
+    names(
+        db,
+        "SELECT slug FROM category_crates WHERE crate_name = ? ORDER BY slug",
+        &[&name],
+    )
+}
+
+/// Crates in the category `slug`, alphabetically.
+pub fn crates_in_category(db: &Connection, slug: &str) -> Result<Vec<String>, Error> {
+    create_tag_views(db)?;
+    names(
+        db,
+        "SELECT crate_name FROM category_crates WHERE slug = ? ORDER BY crate_name",
+        &[&slug],
+    )
+}
+
+#[test]
+fn test_tags() -> Result<(), Error> {
+    let db = crate::MockDump::default()
+        .add_crate("tokio", &["1.0.0"])
+        .add_crate("async-std", &["1.0.0"])
+        .add_crate("serde", &["1.0.0"])
+        .row("keywords", &[("id", "1"), ("keyword", "async")])
+        .row("keywords", &[("id", "2"), ("keyword", "io")])
+        .row("keywords", &[("id", "3"), ("keyword", "serialization")])
+        .row("crates_keywords", &[("crate_id", "1"), ("keyword_id", "1")])
+        .row("crates_keywords", &[("crate_id", "1"), ("keyword_id", "2")])
+        .row("crates_keywords", &[("crate_id", "2"), ("keyword_id", "1")])
+        .row("crates_keywords", &[("crate_id", "3"), ("keyword_id", "3")])
+        .row(
+            "categories",
+            &[
+                ("id", "1"),
+                ("slug", "asynchronous"),
+                ("category", "Asynchronous"),
+            ],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "1"), ("category_id", "1")],
+        )
+        .row(
+            "crates_categories",
+            &[("crate_id", "2"), ("category_id", "1")],
+        )
+        .build()?;
+
+    assert_eq!(vec!["async", "io"], keywords_of(&db, "tokio")?);
+    assert_eq!(
+        vec!["async-std", "tokio"],
+        crates_with_all_keywords(&db, &["async"])?
+    );
+    assert_eq!(
+        vec!["tokio"],
+        crates_with_all_keywords(&db, &["async", "io"])?
+    );
+    assert!(crates_with_all_keywords(&db, &[])?.is_empty());
+    assert_eq!(vec!["asynchronous"], categories_of(&db, "tokio")?);
+    assert!(categories_of(&db, "serde")?.is_empty());
+    assert_eq!(
+        vec!["async-std", "tokio"],
+        crates_in_category(&db, "asynchronous")?
+    );
+    Ok(())
+}
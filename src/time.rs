@@ -0,0 +1,116 @@
+//! Parsing of the Postgres `date`/`timestamp` text found in the dump.
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn number(s: &str) -> Option<i64> {
+    match !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        true => s.parse().ok(),
+        false => None,
+    }
+}
+
+/// Days since the unix epoch for a `YYYY-MM-DD` date.
+pub fn parse_date(s: &str) -> Option<i32> {
+    let mut parts = s.get(..10)?.splitn(3, '-');
+    let year = number(parts.next()?)?;
+    let month = number(parts.next()?)?;
+    let day = number(parts.next()?)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) as i32)
+}
+
+/// `YYYY-MM-DD` for a number of days since the unix epoch, the inverse of [`parse_date`].
+pub fn format_date(days: i32) -> String {
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Microseconds since the unix epoch for a UTC `YYYY-MM-DD HH:MM:SS[.ffffff]` timestamp.
+pub fn parse_timestamp_micros(s: &str) -> Option<i64> {
+    let days = parse_date(s)? as i64;
+    let time = match s.get(10..) {
+        Some("") | None => return Some(days * 86_400_000_000),
+        Some(rest) => rest.strip_prefix(' ').or_else(|| rest.strip_prefix('T'))?,
+    };
+    let time = time.trim_end_matches('Z').trim_end_matches("+00");
+
+    let (hms, fraction) = match time.split_once('.') {
+        Some((hms, fraction)) => (hms, fraction),
+        None => (time, ""),
+    };
+    let mut parts = hms.splitn(3, ':');
+    let hours = number(parts.next()?)?;
+    let minutes = number(parts.next()?)?;
+    let seconds = number(parts.next().unwrap_or("0"))?;
+    let micros = match fraction.is_empty() {
+        true => 0,
+        false => number(&format!("{:0<6}", &fraction[..fraction.len().min(6)]))?,
+    };
+    Some((((days * 24 + hours) * 60 + minutes) * 60 + seconds) * 1_000_000 + micros)
+}
+
+/// A dump timestamp as a `chrono` datetime, see [`parse_timestamp_micros`].
+#[cfg(feature = "chrono")]
+pub fn parse_datetime(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp_micros(parse_timestamp_micros(s)?)
+}
+
+/// `at` as seconds since the unix epoch, to compare with the columns of
+/// [`crate::EpochTimestamps`].
+#[cfg(feature = "chrono")]
+pub fn epoch_seconds<Tz: chrono::TimeZone>(at: &chrono::DateTime<Tz>) -> i64 {
+    at.timestamp()
+}
+
+/// `at` in UTC as the dump writes timestamps, `YYYY-MM-DD HH:MM:SS.ffffff`, to compare with
+/// timestamp text.
+#[cfg(feature = "chrono")]
+pub fn format_datetime<Tz: chrono::TimeZone>(at: &chrono::DateTime<Tz>) -> String {
+    at.with_timezone(&chrono::Utc)
+        .format("%Y-%m-%d %H:%M:%S%.6f")
+        .to_string()
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn test_chrono() {
+    let at = parse_datetime("2021-05-15 12:00:00.5").unwrap();
+    assert_eq!(1_621_080_000, epoch_seconds(&at));
+    assert_eq!("2021-05-15 12:00:00.500000", format_datetime(&at));
+}
+
+#[test]
+fn test_parse_timestamp() {
+    assert_eq!(Some(0), parse_date("1970-01-01"));
+    assert_eq!(Some(18_762), parse_date("2021-05-15"));
+    assert_eq!("2021-05-15", format_date(18_762));
+    assert_eq!("2020-02-29", format_date(parse_date("2020-02-29").unwrap()));
+    assert_eq!(
+        Some(1_621_080_000_500_000),
+        parse_timestamp_micros("2021-05-15 12:00:00.5")
+    );
+    assert_eq!(None, parse_timestamp_micros("not a date"));
+}
@@ -0,0 +1,179 @@
+use std::{
+    fmt,
+    io::{self, Read},
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+
+use crate::{CratesIODumpLoader, Error};
+
+/// A stage of `update()` and `load_dump_into()` that can be given a timeout, see
+/// [`crate::CratesIODumpLoaderBuilder::phase_timeout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Fetching a remote `resource`. Local ones aren't limited.
+    Download,
+    /// Unpacking the tables from the archive or pg_dump.
+    Extract,
+    /// `load_dump_into()`, including presets and views.
+    Load,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Download => "download",
+            Phase::Extract => "extraction",
+            Phase::Load => "load",
+        })
+    }
+}
+
+/// When a phase has to be done by, if it has a timeout.
+#[derive(Clone, Copy)]
+pub(crate) struct Deadline {
+    phase: Phase,
+    limit: Option<Duration>,
+    started: Instant,
+}
+
+impl Deadline {
+    pub(crate) fn start(phase: Phase, limit: Option<Duration>) -> Self {
+        Self {
+            phase,
+            limit,
+            started: Instant::now(),
+        }
+    }
+
+    fn expired(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.started.elapsed() >= limit)
+    }
+
+    fn error(&self) -> Error {
+        Error::Timeout {
+            phase: self.phase,
+            limit: self.limit.unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self.expired() {
+            true => Err(self.error()),
+            false => Ok(()),
+        }
+    }
+
+    /// `e`, or the timeout if it is the reason `e` happened.
+    pub(crate) fn or_timeout(&self, e: Error) -> Error {
+        match self.expired() {
+            true => self.error(),
+            false => e,
+        }
+    }
+
+    /// `reader`, failing once the deadline has passed.
+    pub(crate) fn reader<R: Read>(self, reader: R) -> DeadlineReader<R> {
+        DeadlineReader {
+            deadline: self,
+            reader,
+        }
+    }
+
+    /// Interrupts whatever `db` is running once the deadline passes, until the returned guard
+    /// is dropped.
+    pub(crate) fn interrupt(&self, db: &Connection) -> Option<Sender<()>> {
+        let remaining = self.limit?.saturating_sub(self.started.elapsed());
+        let handle = db.get_interrupt_handle();
+        let (done, finished) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(remaining) {
+                handle.interrupt();
+            }
+        });
+        Some(done)
+    }
+}
+
+pub(crate) struct DeadlineReader<R> {
+    deadline: Deadline,
+    reader: R,
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.deadline.expired() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} timed out", self.deadline.phase),
+            ));
+        }
+        self.reader.read(buf)
+    }
+}
+
+impl CratesIODumpLoader {
+    /// `cached_resource()`, given up on after `download_timeout`. The download itself carries
+    /// on in the background, cached-path can't be cancelled.
+    pub(crate) fn cached_resource_in_time(&self) -> Result<PathBuf, Error> {
+        let limit = match self.download_timeout {
+            Some(limit) if self.remote_resource() => limit,
+            _ => return self.cached_resource(),
+        };
+        let loader = self.clone();
+        let (sender, result) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(loader.cached_resource());
+        });
+        result.recv_timeout(limit).unwrap_or(Err(Error::Timeout {
+            phase: Phase::Download,
+            limit,
+        }))
+    }
+}
+
+#[test]
+fn test_phase_timeouts() -> Result<(), Error> {
+    use std::path::Path;
+
+    let builder = CratesIODumpLoader::builder()
+        .resource("testdata/fixture.tar.gz")
+        .target_path(Path::new("testdata/extracted/timeouts"))
+        .minimal()
+        .preload(true)
+        .cache(cached_path::Cache::builder().progress_bar(None))?;
+    let _ = std::fs::remove_dir_all("testdata/extracted/timeouts");
+
+    let mut loader = builder
+        .clone()
+        .phase_timeout(Phase::Extract, Duration::ZERO)
+        .build()?;
+    match loader.update() {
+        Err(Error::Timeout { phase, .. }) => assert_eq!(Phase::Extract, phase),
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+
+    let mut loader = builder
+        .phase_timeout(Phase::Extract, Duration::from_secs(60))
+        .phase_timeout(Phase::Load, Duration::ZERO)
+        .continue_on_error(true)
+        .build()?;
+    let db = Connection::open_in_memory()?;
+    rusqlite::vtab::csvtab::load_module(&db)?;
+    match loader.update()?.load_dump_into(&db) {
+        Err(Error::Timeout { phase, limit }) => {
+            assert_eq!(Phase::Load, phase);
+            assert_eq!(Duration::ZERO, limit);
+        }
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+
+    loader.load_timeout = Some(Duration::from_secs(60));
+    loader.load_dump_into(&db)?;
+    Ok(())
+}
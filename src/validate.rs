@@ -0,0 +1,143 @@
+use std::{fs, path::Path};
+
+use crate::{formats, resource, CratesIODumpLoader, Error};
+
+/// Creates `dir` if needed and writes a file into it.
+fn check_writable(dir: &Path) -> Result<(), Error> {
+    let unwritable = |e: std::io::Error| {
+        invalid(
+            "target_path",
+            format!("{} is not writable: {}", dir.display(), e),
+        )
+    };
+    resource::create_target_dir(dir).map_err(|e| match e {
+        Error::FileError { source, .. } => unwritable(source),
+        e => e,
+    })?;
+    let probe = dir.join(".write-check");
+    fs::write(&probe, b"").map_err(unwritable)?;
+    fs::remove_file(&probe).map_err(unwritable)
+}
+
+fn invalid(option: &'static str, reason: String) -> Error {
+    Error::InvalidOption { option, reason }
+}
+
+impl CratesIODumpLoader {
+    /// Checks the configuration for mistakes that would otherwise only surface halfway through
+    /// `update()` or `load_dump_into()`, or not at all: no tables, invalid table names or
+    /// schema overrides, overrides or column selections for tables that aren't loaded, options
+    /// that don't work together, and a `target_path` that can't be written to, which is
+    /// created if missing.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.check_options()?;
+        check_writable(&self.target_path)
+    }
+
+    /// `validate()` without touching the file system.
+    pub(crate) fn check_options(&self) -> Result<(), Error> {
+        if self.files.is_empty() && self.custom_tables.is_empty() {
+            return Err(Error::EmptyTableList);
+        }
+        self.check_table_names()?;
+        self.check_schema_sql()?;
+
+        let selected = |table: &str| {
+            self.files
+                .iter()
+                .any(|f| f.file_stem() == Some(table.as_ref()))
+                || self.custom_tables.iter().any(|t| t.name == table)
+        };
+        let mut overridden: Vec<&String> = self.table_schema.keys().collect();
+        overridden.sort();
+        if let Some(table) = overridden.into_iter().find(|t| !selected(t)) {
+            return Err(invalid(
+                "table_schema",
+                format!("{} is overridden but not one of the selected tables", table),
+            ));
+        }
+        let mut trimmed: Vec<&String> = self.table_columns.keys().collect();
+        trimmed.sort();
+        if let Some(table) = trimmed.into_iter().find(|t| !selected(t)) {
+            return Err(invalid(
+                "columns",
+                format!("{} has columns selected but isn't loaded", table),
+            ));
+        }
+
+        if let Some(version) = &self.schema_version {
+            if formats::by_name(version).is_none() {
+                let known: Vec<&str> = formats::FORMATS.iter().map(|f| f.name).collect();
+                return Err(invalid(
+                    "schema_version",
+                    format!("{} is not one of {}", version, known.join(", ")),
+                ));
+            }
+        }
+
+        if self.lazy && self.sample_rows.is_some() {
+            return Err(invalid(
+                "lazy",
+                "sample_rows() filters tables by each other, so they can't be loaded one at a time"
+                    .into(),
+            ));
+        }
+        if !(512..=65536).contains(&self.page_size) || !self.page_size.is_power_of_two() {
+            return Err(invalid(
+                "page_size",
+                format!("{} is not a power of two from 512 to 65536", self.page_size),
+            ));
+        }
+        if let Some(hash) = &self.sha256 {
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid(
+                    "sha256",
+                    format!("{:?} is not 64 hex digits", hash),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_validate() -> Result<(), Error> {
+    let target = Path::new("testdata/extracted/validate");
+    let _ = fs::remove_dir_all(target);
+    let option = |loader: &CratesIODumpLoader| match loader.validate() {
+        Err(Error::InvalidOption { option, .. }) => Some(option),
+        Err(e) => panic!("expected an invalid option, got {:?}", e),
+        Ok(()) => None,
+    };
+
+    let mut loader = CratesIODumpLoader::default();
+    loader.target_path(target).minimal();
+    assert_eq!(None, option(&loader));
+    assert!(target.is_dir());
+
+    loader.table_schema("users", "CREATE TABLE x(id INT);");
+    assert_eq!(Some("table_schema"), option(&loader));
+    loader
+        .tables(&["crates", "users"])
+        .columns("versions", &["id"]);
+    assert_eq!(Some("columns"), option(&loader));
+    loader.tables(&["crates", "users", "versions"]);
+    assert_eq!(None, option(&loader));
+
+    loader.lazy(true).sample_rows(10);
+    assert_eq!(Some("lazy"), option(&loader));
+    loader.lazy(false).page_size(3000);
+    assert_eq!(Some("page_size"), option(&loader));
+    loader.page_size(4096).sha256(Some("abc"));
+    assert_eq!(Some("sha256"), option(&loader));
+    loader.sha256(None);
+
+    let blocker = target.join("not-a-dir");
+    fs::write(&blocker, "")?;
+    loader.target_path(&blocker.join("data"));
+    assert_eq!(Some("target_path"), option(&loader));
+
+    loader.tables::<&str>(&[]);
+    assert!(matches!(loader.validate(), Err(Error::EmptyTableList)));
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::{
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use reqwest::{
+    blocking::Client,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+
+use crate::Error;
+
+/// Validators identifying one published dump.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DumpVersion {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Polls the dump URL with conditional `HEAD` requests and reports when a new dump is published.
+pub struct DumpWatcher {
+    pub resource: String,
+    pub interval: Duration,
+    /// The dump seen last; `None` until the first poll, which always reports.
+    pub current: Option<DumpVersion>,
+
+    client: Client,
+}
+
+impl Default for DumpWatcher {
+    fn default() -> Self {
+        Self {
+            resource: "https://static.crates.io/db-dump.tar.gz".to_string(),
+            interval: Duration::from_secs(60 * 60),
+            current: None,
+            client: Client::new(),
+        }
+    }
+}
+
+impl DumpWatcher {
+    pub fn resource(&mut self, url: &str) -> &mut Self {
+        self.resource = url.to_owned();
+        self
+    }
+
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Treats `version` as already seen, e.g. one persisted by a previous run.
+    pub fn current(&mut self, version: DumpVersion) -> &mut Self {
+        self.current = Some(version);
+        self
+    }
+
+    /// Polls once, returning the new dump's validators if it changed since the last poll.
+    pub fn check(&mut self) -> Result<Option<DumpVersion>, Error> {
+        let mut request = self.client.head(&self.resource);
+        if let Some(current) = &self.current {
+            if let Some(etag) = &current.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &current.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.send()?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let version = DumpVersion {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        // Servers that ignore the conditional headers still give the same validators back.
+        if self.current.as_ref() == Some(&version) {
+            return Ok(None);
+        }
+        self.current = Some(version.clone());
+        Ok(Some(version))
+    }
+
+    /// Polls every `interval`, calling `on_new` for each new dump until it returns `false`.
+    ///
+    /// A failed poll ends the watch with its error.
+    pub fn watch<F>(&mut self, on_new: F) -> Result<(), Error>
+    where
+        F: FnMut(&DumpVersion) -> bool,
+    {
+        self.watch_until(on_new, |interval| {
+            thread::sleep(interval);
+            true
+        })
+    }
+
+    /// Like [`DumpWatcher::watch`], sending each new dump on `sender` until the receiver is
+    /// dropped. A dropped receiver is only noticed when the next dump is published, see
+    /// [`DumpWatcher::spawn`] for a watch that can be stopped in between.
+    pub fn watch_channel(&mut self, sender: Sender<DumpVersion>) -> Result<(), Error> {
+        self.watch(|version| sender.send(version.clone()).is_ok())
+    }
+
+    /// Watches on a background thread, returning the receiving end of its channel and a handle
+    /// that stops the watch when stopped or dropped, rather than at the next publish.
+    pub fn spawn(mut self) -> (Receiver<DumpVersion>, WatchHandle) {
+        let (sender, receiver) = channel();
+        let (stop, stopped) = channel::<()>();
+        let thread = thread::spawn(move || {
+            self.watch_until(
+                |version| sender.send(version.clone()).is_ok(),
+                |interval| {
+                    matches!(
+                        stopped.recv_timeout(interval),
+                        Err(RecvTimeoutError::Timeout)
+                    )
+                },
+            )
+        });
+        (receiver, WatchHandle { stop, thread })
+    }
+
+    /// Polls, then calls `wait` with the interval, until `on_new` or `wait` return `false`.
+    fn watch_until<F, W>(&mut self, mut on_new: F, mut wait: W) -> Result<(), Error>
+    where
+        F: FnMut(&DumpVersion) -> bool,
+        W: FnMut(Duration) -> bool,
+    {
+        loop {
+            if let Some(version) = self.check()? {
+                if !on_new(&version) {
+                    return Ok(());
+                }
+            }
+            if !wait(self.interval) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A [`DumpWatcher::spawn`]ed watch. Dropping it stops the watch without waiting for it.
+pub struct WatchHandle {
+    stop: Sender<()>,
+    thread: JoinHandle<Result<(), Error>>,
+}
+
+impl WatchHandle {
+    /// Stops the watch, waiting for a poll in progress, and returns how it ended.
+    pub fn stop(self) -> Result<(), Error> {
+        drop(self.stop);
+        self.thread.join().expect("the watcher thread panicked")
+    }
+}
+
+#[test]
+fn test_dump_watcher() -> Result<(), Error> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/db-dump.tar.gz", listener.local_addr()?);
+    let server = thread::spawn(move || -> std::io::Result<Vec<bool>> {
+        let mut conditional = Vec::new();
+        for (etag, stream) in ["\"a\"", "\"a\"", "\"b\""].iter().zip(listener.incoming()) {
+            let mut stream = stream?;
+            let mut matched = false;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                if line.trim().is_empty() {
+                    break;
+                }
+                matched |= line.to_ascii_lowercase() == format!("if-none-match: {}\r\n", etag);
+            }
+            conditional.push(matched);
+            let status = match matched {
+                true => "304 Not Modified",
+                false => "200 OK",
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nETag: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status, etag
+            )?;
+        }
+        Ok(conditional)
+    });
+
+    let mut watcher = DumpWatcher::default();
+    watcher.resource(&url);
+    let first = watcher.check()?;
+    assert_eq!(
+        Some("\"a\""),
+        first.as_ref().and_then(|v| v.etag.as_deref())
+    );
+    assert_eq!(None, watcher.check()?);
+    watcher.interval(Duration::from_secs(60 * 60));
+    let (receiver, handle) = watcher.spawn();
+    assert_eq!(Some("\"b\"".to_string()), receiver.recv().unwrap().etag);
+    // Stops right away rather than after the interval.
+    handle.stop()?;
+
+    assert_eq!(vec![false, true, false], server.join().unwrap()?);
+    Ok(())
+}
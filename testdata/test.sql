@@ -0,0 +1,16 @@
+--
+-- PostgreSQL database dump
+--
+
+SET client_encoding = 'UTF8';
+
+COPY public.test (id, name) FROM stdin;
+1	hello
+2	world
+3	awooo
+4	\N
+\.
+
+COPY public.other (id) FROM stdin;
+1
+\.